@@ -0,0 +1,447 @@
+use anyhow::{anyhow, Context, Result};
+use clap::{ArgEnum, Parser, Subcommand};
+use lox_core::coverage::Coverage;
+use lox_core::environment::Enviornment;
+use lox_core::error_codes::ErrorCode;
+use lox_core::intern::Symbol;
+use lox_core::lox::Lox;
+use lox_core::lox::LoxExit;
+use lox_core::lox::LoxOptions;
+use lox_core::lox::LoxRuntimeError;
+use lox_core::lox::{CancelToken, LoxErrorKind, LoxInterrupted};
+use lox_core::parser::Stmt;
+use lox_core::paths;
+use lox_core::recorder;
+use lox_core::scanner;
+use lox_core::stdlib::{self, INSPECT_DEFAULT_DEPTH};
+use lox_tools::ast_graph;
+use lox_tools::formatter;
+use lox_tools::transpile;
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Simple program to greet a person
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Args {
+    /// Script to run, or `-` to read it from stdin
+    #[clap()]
+    script: Option<String>,
+    /// Run this code instead of a script
+    #[clap(short, long, value_name = "CODE", conflicts_with = "script")]
+    eval: Option<String>,
+    /// Arguments for the script, given by `args()` and to the --entry
+    /// function; put `--` before any starting with `-`
+    #[clap(requires = "script")]
+    script_args: Vec<String>,
+    #[clap(flatten)]
+    lox_options: LoxOptions,
+    /// In the REPL, show a spinner once an evaluation runs this long
+    #[clap(long, value_name = "MS", default_value = "500")]
+    spinner_after_ms: u64,
+    /// When the script ends, write how many times each line ran to FILE:
+    /// an lcov tracefile if it ends in `.info`, or else the script with the
+    /// counts in the margin
+    #[clap(long, value_name = "FILE")]
+    coverage: Option<String>,
+    /// Print the script's syntax tree in FORMAT instead of running it
+    #[clap(long, arg_enum, value_name = "FORMAT", conflicts_with = "coverage")]
+    emit_ast: Option<AstFormat>,
+    /// Run the syntax tree in FILE, as printed by --emit-ast=json, instead
+    /// of a script; `-` reads it from stdin
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = &["script", "eval", "coverage", "emit-ast"]
+    )]
+    from_ast: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum AstFormat {
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Step through a log written by --record-execution
+    Replay {
+        #[clap()]
+        log: String,
+    },
+    /// Print the parse tree of a file or expression as Graphviz DOT
+    GraphAst {
+        #[clap(required_unless_present = "expr")]
+        file: Option<String>,
+        /// Graph this expression instead of a file
+        #[clap(short, long, conflicts_with = "file")]
+        expr: Option<String>,
+    },
+    /// Describe an error code, such as E1003, with an example
+    Explain {
+        #[clap()]
+        code: String,
+    },
+    /// Reformat scripts in place, or `-` from stdin to stdout
+    Fmt {
+        #[clap(required = true)]
+        files: Vec<String>,
+        /// Change nothing; list the files that need formatting and fail if any do
+        #[clap(long)]
+        check: bool,
+    },
+    /// Lower a script to a standalone Rust program, for `rustc -O`
+    Transpile {
+        #[clap()]
+        file: String,
+        /// Write the program here instead of to stdout
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+}
+
+fn explain(code: &str) -> Result<()> {
+    let code = ErrorCode::parse(code).ok_or_else(|| anyhow!("Unknown error code '{}'", code))?;
+    println!("{}: {}\n\n{}", code, code.summary(), code.explanation());
+    Ok(())
+}
+
+/// Format `files` as `lox-tools` lays Lox out. With `check`, exits with
+/// status 1 if any would change.
+fn fmt(files: &[String], check: bool) -> Result<()> {
+    let mut unformatted = 0;
+    for file in files {
+        let source = match file.as_str() {
+            "-" => io::read_to_string(io::stdin()).context("Could not read stdin")?,
+            path => paths::read_source(path)?,
+        };
+        let formatted = formatter::format(&source).map_err(|diagnostics| {
+            for d in &diagnostics {
+                eprintln!("{}", d);
+            }
+            anyhow!("Could not format {}: it has syntax errors", file)
+        })?;
+        match (check, file.as_str()) {
+            (true, _) if formatted != source => {
+                println!("Would reformat {}", file);
+                unformatted += 1;
+            }
+            (true, _) => {}
+            (false, "-") => print!("{}", formatted),
+            (false, path) if formatted != source => fs::write(paths::normalize(path), formatted)
+                .with_context(|| format!("Could not write {}", file))?,
+            (false, _) => {}
+        }
+    }
+    if unformatted > 0 {
+        ::std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn graph_ast(file: Option<String>, expr: Option<String>) -> Result<()> {
+    let source = match (file, expr) {
+        (_, Some(expr)) => format!("{};", expr),
+        (Some(file), None) => paths::read_source(&file)?,
+        (None, None) => unreachable!(),
+    };
+    let ast = Lox::new(LoxOptions::default()).parse(&source)?;
+    print!("{}", ast_graph::to_dot(&ast));
+    Ok(())
+}
+
+fn transpile(file: &str, output: Option<&str>) -> Result<()> {
+    let ast = Lox::new(LoxOptions::default()).parse(&read_script(file)?)?;
+    let rust = transpile::to_rust(&ast, file).map_err(|diagnostic| {
+        eprintln!("{}", diagnostic);
+        anyhow!("Could not transpile {}", file)
+    })?;
+    match output {
+        Some(path) => fs::write(paths::normalize(path), rust)
+            .with_context(|| format!("Could not write {}", path)),
+        None => {
+            print!("{}", rust);
+            Ok(())
+        }
+    }
+}
+
+/// The text of the file at `path`, or of stdin for `-`.
+fn read_script(path: &str) -> Result<String> {
+    match path {
+        "-" => io::read_to_string(io::stdin()).context("Could not read stdin"),
+        path => paths::read_source(path),
+    }
+}
+
+fn run_file(
+    script_path: &str,
+    args: Vec<String>,
+    opts: LoxOptions,
+    coverage: Option<&str>,
+) -> Result<()> {
+    run_source(read_script(script_path)?, script_path, args, opts, coverage)
+}
+
+/// Print the resolved syntax tree of `source` for other tools.
+fn emit_ast(source: &str, format: AstFormat, opts: LoxOptions) -> Result<()> {
+    let ast = Lox::new(opts).parse(source)?;
+    match format {
+        AstFormat::Json => println!("{}", serde_json::to_string(&ast)?),
+    }
+    Ok(())
+}
+
+/// Run the syntax tree `--emit-ast=json` wrote to `path`.
+fn run_ast_file(path: &str, opts: LoxOptions) -> Result<()> {
+    let json = read_script(path)?;
+    let mut reader = serde_json::Deserializer::from_str(&json);
+    // Each `+` of a long sum nests one level deeper than the last, which
+    // would soon pass serde_json's limit.
+    reader.disable_recursion_limit();
+    let ast = Vec::<Stmt>::deserialize(&mut reader)
+        .and_then(|ast| reader.end().map(|_| ast))
+        .with_context(|| format!("Could not read a syntax tree from {}", path))?;
+    Lox::new(opts).run_ast(ast)
+}
+
+/// Run `source`, which came from `name`. With `coverage`, also write the
+/// lines it ran there, even if it failed.
+fn run_source(
+    source: String,
+    name: &str,
+    args: Vec<String>,
+    opts: LoxOptions,
+    coverage: Option<&str>,
+) -> Result<()> {
+    let mut l = Lox::new(opts);
+    l.set_args(args);
+    let Some(out) = coverage else {
+        return l.run(source);
+    };
+    let mut report = Coverage::new(&source);
+    let rv = l.run_observed(source, |e| report.observe(e));
+    let text = if out.ends_with(".info") {
+        report.lcov(name)
+    } else {
+        report.listing()
+    };
+    let written = fs::write(out, text).with_context(|| format!("Could not write {}", out));
+    rv.and(written)
+}
+
+/// Shows elapsed time on stderr while an evaluation runs past `after`, until dropped.
+struct Spinner {
+    done: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Spinner {
+    fn start(after: Duration) -> Self {
+        let done = Arc::new(AtomicBool::new(false));
+        let thread_done = done.clone();
+        let handle = thread::spawn(move || {
+            const FRAMES: [char; 4] = ['|', '/', '-', '\\'];
+            let start = Instant::now();
+            let mut shown = false;
+            let mut frame = 0;
+            while !thread_done.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(100));
+                if start.elapsed() >= after {
+                    eprint!(
+                        "\r{} {:.1}s (Ctrl-C to abort)",
+                        FRAMES[frame % FRAMES.len()],
+                        start.elapsed().as_secs_f64()
+                    );
+                    shown = true;
+                    frame += 1;
+                }
+            }
+            if shown {
+                eprint!("\r\x1b[K");
+            }
+        });
+        Self {
+            done,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Handle `:load <file> [as <name>]`, running the file into the session or,
+/// with `as`, into a namespace object bound to `name`.
+fn load_command(l: &mut Lox, env: &mut Enviornment, args: &str) -> Result<()> {
+    let args: Vec<&str> = args.split_whitespace().collect();
+    match args[..] {
+        [path] => l.run_with_env(paths::read_source(path)?, env),
+        [path, "as", name] => {
+            let ns = l.run_as_namespace(paths::read_source(path)?, name)?;
+            env.define(Symbol::intern(name), ns);
+            Ok(())
+        }
+        _ => {
+            eprintln!("Usage: :load <file> [as <name>]");
+            Ok(())
+        }
+    }
+}
+
+fn run_prompt(opts: LoxOptions, spinner_after: Duration) -> Result<()> {
+    let mut l = Lox::new(opts);
+    let mut env = Enviornment::new();
+    let mut results = 0;
+    // Set when the program calls `exit()`, which ends the session.
+    let mut exited = None;
+
+    // rustyline sees Ctrl-C itself while reading a line, so the handler only
+    // fires while a program is running.
+    let cancel = CancelToken::new();
+    let handler_token = cancel.clone();
+    ctrlc::set_handler(move || handler_token.cancel())?;
+    l.set_cancel_token(cancel.clone());
+    const HISTORY_FILE: &str = "history.txt";
+
+    let mut rl = Editor::<()>::new()?;
+    if rl.load_history(HISTORY_FILE).is_err() {
+        println!("No privious history");
+    }
+
+    loop {
+        let readline = rl.readline("> ");
+        match readline {
+            Ok(mut line) => {
+                while scanner::is_incomplete(&line) {
+                    match rl.readline("..> ") {
+                        Ok(more) => {
+                            line.push('\n');
+                            line.push_str(&more);
+                        }
+                        // Abandon the unfinished input and start over.
+                        Err(_) => {
+                            line.clear();
+                            break;
+                        }
+                    }
+                }
+                cancel.reset();
+                let spinner = Spinner::start(spinner_after);
+                let rv = match line.trim().strip_prefix(":load") {
+                    Some(args) => load_command(&mut l, &mut env, args),
+                    None => l.run_expr_or_stmts(line, &mut env).map(|value| {
+                        if let Some(value) = value {
+                            println!("{}", stdlib::inspect(&value, INSPECT_DEFAULT_DEPTH));
+                            // `_` is the last result, `_N` the Nth of the session.
+                            results += 1;
+                            env.define(Symbol::intern(&format!("_{}", results)), value.clone());
+                            env.define(Symbol::intern("_"), value);
+                        }
+                    }),
+                };
+                drop(spinner);
+                match rv {
+                    Ok(()) => {}
+                    Err(e) if e.downcast_ref::<LoxExit>().is_some() => {
+                        exited = Some(e);
+                        break;
+                    }
+                    Err(e) if e.downcast_ref::<LoxInterrupted>().is_some() => {
+                        eprintln!("{}", e)
+                    }
+                    // Scan, parse and runtime errors have already been reported.
+                    Err(_) if l.has_error => {}
+                    Err(e) if e.downcast_ref::<LoxRuntimeError>().is_some() => {}
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+                // Keep the session going with its environment intact.
+                l.has_error = false;
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("CTRL-C");
+                break;
+            }
+            Err(ReadlineError::Eof) => {
+                println!("CTRL-D");
+                break;
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break;
+            }
+        }
+    }
+    rl.save_history(HISTORY_FILE)?;
+    let status = exited
+        .as_ref()
+        .and_then(|e| e.downcast_ref::<LoxExit>())
+        .map_or(0, |e| e.0);
+    l.exit(&mut env, status)?;
+
+    exited.map_or(Ok(()), Err)
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    // println!("Hello, world! {:?}", args);
+    let rv = match (args.command, args.eval, args.script) {
+        (Some(Command::Replay { log }), _, _) => {
+            recorder::replay(&log, std::io::stdin().lock(), std::io::stdout())
+        }
+        (Some(Command::GraphAst { file, expr }), _, _) => graph_ast(file, expr),
+        (Some(Command::Explain { code }), _, _) => explain(&code),
+        (Some(Command::Fmt { files, check }), _, _) => fmt(&files, check),
+        (Some(Command::Transpile { file, output }), _, _) => transpile(&file, output.as_deref()),
+        (None, _, _) if args.from_ast.is_some() => {
+            run_ast_file(args.from_ast.as_deref().unwrap(), args.lox_options)
+        }
+        (None, code, script) if args.emit_ast.is_some() => {
+            let source = match (code, script) {
+                (Some(code), _) => code,
+                (None, script) => read_script(script.as_deref().unwrap_or("-"))?,
+            };
+            emit_ast(&source, args.emit_ast.unwrap(), args.lox_options)
+        }
+        (None, Some(code), _) => run_source(
+            code,
+            "-",
+            args.script_args,
+            args.lox_options,
+            args.coverage.as_deref(),
+        ),
+        (None, None, None) => run_prompt(
+            args.lox_options,
+            Duration::from_millis(args.spinner_after_ms),
+        ),
+        (None, None, Some(script)) => run_file(
+            &script,
+            args.script_args,
+            args.lox_options,
+            args.coverage.as_deref(),
+        ),
+    };
+    // Lox errors have been reported; others are printed by returning them.
+    match rv.as_ref().err().and_then(LoxErrorKind::of) {
+        // Whoever closed the pipe, like `head`, has all the output it wanted.
+        Some(LoxErrorKind::OutputClosed) => Ok(()),
+        Some(kind) => ::std::process::exit(kind.exit_code()),
+        None => rv,
+    }
+}
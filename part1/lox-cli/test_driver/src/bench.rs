@@ -0,0 +1,60 @@
+use anyhow::{anyhow, Result};
+use lox_core::paths;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Where the benchmark programs live. `bench` runs all of them by default.
+pub const BENCHMARK_DIR: &str = "test_lox_files/benchmark";
+
+/// Benchmarks only check that they run and take too long for correctness
+/// runs, which skip them.
+pub fn is_benchmark(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str() == "benchmark")
+}
+
+fn benchmark_files() -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(paths::normalize(BENCHMARK_DIR))?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    files.retain(|f| f.extension().is_some_and(|e| e == "lox"));
+    files.sort();
+    Ok(files)
+}
+
+fn time_run(interpreter: &str, file: &Path) -> Result<Duration> {
+    let start = Instant::now();
+    let status = Command::new(interpreter)
+        .arg(file)
+        .stdout(Stdio::null())
+        .status()?;
+    let elapsed = start.elapsed();
+    match status.success() {
+        true => Ok(elapsed),
+        false => Err(anyhow!("{} failed: {}", paths::display(file), status)),
+    }
+}
+
+/// Run each of `files` (or every benchmark) `runs` times with `interpreter`
+/// and print the fastest time for each.
+pub fn run(interpreter: &str, files: &[String], runs: usize) -> Result<()> {
+    let files = match files {
+        [] => benchmark_files()?,
+        files => files.iter().map(|f| paths::normalize(f)).collect(),
+    };
+    for file in &files {
+        let best = (0..runs.max(1))
+            .map(|_| time_run(interpreter, file))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .min()
+            .unwrap_or_default();
+        println!(
+            "{:<40} {:>10.1} ms",
+            paths::display(file),
+            best.as_secs_f64() * 1000.0
+        );
+    }
+    Ok(())
+}
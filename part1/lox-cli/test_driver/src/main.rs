@@ -0,0 +1,711 @@
+use anyhow::anyhow;
+use anyhow::{Context, Result};
+use clap::{ArgEnum, Parser, Subcommand};
+use colored::*;
+use lazy_static::lazy_static;
+use lox_core::paths;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::iter::zip;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+mod bench;
+mod pool;
+mod report;
+mod suites;
+mod update;
+use report::TestReport;
+use suites::{Language, Suite};
+
+lazy_static! {
+    static ref EXPECTED_OUTPUT_PATTERN: Regex = Regex::new(r"// expect: ?(.*)").unwrap();
+    static ref EXPECTED_ERROR_PATTERN: Regex = Regex::new(r"// (Error.*)").unwrap();
+    static ref ERROR_LINE_PATTERN: Regex =
+        Regex::new(r"// \[((java|c) )?line (\d+)\] (Error.*)").unwrap();
+    static ref WARNING_LINE_PATTERN: Regex = Regex::new(r"// \[line (\d+)\] (Warning.*)").unwrap();
+    static ref WARNING_PATTERN: Regex = Regex::new(r"^\[line \d+\] Warning: ").unwrap();
+    static ref EXPECTED_RUNTIME_ERROR_PATTERN: Regex =
+        Regex::new(r"// expect runtime error: (.+)").unwrap();
+    static ref SYNTAX_ERROR_PATTERN: Regex = Regex::new(r"\[.*line (\d+)\] (Error.+)").unwrap();
+    static ref STACK_TRACE_PATTERN: Regex = Regex::new(r"\[line (\d+)\]").unwrap();
+    static ref NON_TEST_PATTERN: Regex = Regex::new(r"// nontest").unwrap();
+}
+
+/// Unexpected stderr lines shown for a failing test before the rest are
+/// just counted.
+const MAX_UNEXPECTED: usize = 10;
+
+#[derive(Debug)]
+struct ExpectedOutput {
+    line: i32,
+    output: String,
+}
+
+#[derive(Debug)]
+struct Test {
+    test_file: PathBuf,
+    expected_output: Vec<ExpectedOutput>,
+    /// The set of expected compile error messages.
+    expected_errors: Vec<String>,
+    /// Warnings that must be reported. Others are ignored, since they don't
+    /// change how a program runs.
+    expected_warnings: Vec<String>,
+    /// The expected runtime error message or `None` if there should not be one.
+    expected_runtime_error: Option<ExpectedOutput>,
+    expected_exit_code: i32,
+}
+
+impl Test {
+    fn try_parse(test_input_path: &PathBuf, language: Language) -> Option<Self> {
+        // let mut expected_output: Vec<ExpectedOutput> = vec![];
+        // let mut expected_errors: Vec<String> = vec![];
+        // let mut expected_exit_code: i32 = 0;
+        // let mut expected_runtime_error: Option<ExpectedOutput> = None;
+        let mut test = Test {
+            test_file: test_input_path.clone(),
+            expected_output: vec![],
+            expected_errors: vec![],
+            expected_warnings: vec![],
+            expected_exit_code: 0,
+            expected_runtime_error: None,
+        };
+        let file = File::open(test_input_path).ok()?;
+        let lines = io::BufReader::new(file).lines();
+        lines.enumerate().for_each(|(lineno, line)| {
+            let lineno = lineno + 1;
+            let line = line.unwrap(); // FIXME
+            if let Some(eo) = EXPECTED_OUTPUT_PATTERN.captures(&line) {
+                test.expected_output.push(ExpectedOutput {
+                    line: lineno as i32,
+                    output: eo[1].to_string(),
+                });
+            }
+
+            if let Some(ee) = EXPECTED_ERROR_PATTERN.captures(&line) {
+                test.expected_errors
+                    .push(format!("[line {}] {}", lineno, &ee[1]));
+                // If we expect a compile error, it should exit with EX_DATAERR.
+                test.expected_exit_code = 65;
+            }
+
+            if let Some(ee) = ERROR_LINE_PATTERN.captures(&line) {
+                // The two interpreters are slightly different in terms of which
+                // cascaded errors may appear after an initial compile error because
+                // their panic mode recovery is a little different. To handle that,
+                // the tests can indicate if an error line should only appear for a
+                // certain interpreter.
+                if ee.get(2).is_none_or(|l| l.as_str() == language.tag()) {
+                    test.expected_errors
+                        .push(format!("[line {}] {}", &ee[3], &ee[4]));
+                    test.expected_exit_code = 65;
+                }
+                //     // If we expect a compile error, it should exit with EX_DATAERR.
+                //     _expected_exit_code = 65;
+                //     _expectations++;
+                //   }
+                //   continue;
+            }
+            if let Some(w) = WARNING_LINE_PATTERN.captures(&line) {
+                test.expected_warnings
+                    .push(format!("[line {}] {}", &w[1], &w[2]));
+            }
+            if let Some(rte) = EXPECTED_RUNTIME_ERROR_PATTERN.captures(&line) {
+                test.expected_runtime_error = Some(ExpectedOutput {
+                    line: lineno as i32,
+                    output: rte[1].to_owned(),
+                });
+                // If we expect a runtime error, it should exit with EX_SOFTWARE.
+                test.expected_exit_code = 70;
+            }
+        });
+
+        if !test.expected_errors.is_empty() && test.expected_runtime_error.is_some() {
+            eprintln!(
+                "{} {} Cannot expect both compile and runtime errors.",
+                "TEST ERROR".magenta(),
+                paths::display(&test.test_file)
+            );
+            None
+        } else {
+            Some(test)
+        }
+    }
+    fn validate_runtime_error(&self, std_err: &[String]) -> Result<()> {
+        if let Some(expected_runtime_error) = &self.expected_runtime_error {
+            let Some(first) = std_err.first() else {
+                return Err(anyhow!(
+                    "Expected runtime error '{}' and got none.",
+                    expected_runtime_error.output
+                ));
+            };
+            if *first != expected_runtime_error.output {
+                return Err(anyhow!(
+                    "Expected runtime error '{}' and got:\n{}",
+                    expected_runtime_error.output,
+                    first
+                ));
+            }
+            // Make sure the stack trace has the right line.
+            let matching = std_err[1..]
+                .iter()
+                .find(|line| STACK_TRACE_PATTERN.is_match(line));
+            if let Some(stack) = matching {
+                let captured = STACK_TRACE_PATTERN.captures(stack);
+                let stack_line = captured.unwrap()[1].parse::<i32>().unwrap();
+                if stack_line != expected_runtime_error.line {
+                    return Err(anyhow!(
+                        "Expected runtime error on line {} but was on line {}",
+                        expected_runtime_error.line,
+                        stack_line
+                    ));
+                }
+            } else {
+                //     fail("Expected stack trace and got:", stackLines);
+                return Err(anyhow!("Expected stack trace and got: {:?}", &std_err[1..]));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check every line of `std_err` is an expected compile error, and that
+    /// every expected error was seen. Tests expecting a runtime error have
+    /// their stderr checked by `validate_runtime_error` instead.
+    fn validate_compile_errors(&self, std_err: &[String]) -> Result<()> {
+        if self.expected_runtime_error.is_some() {
+            return Ok(());
+        }
+        let mut failures = vec![];
+        let mut found = HashSet::new();
+        let mut unexpected = 0;
+        for line in std_err {
+            let failure = match SYNTAX_ERROR_PATTERN.captures(line) {
+                Some(m) => {
+                    let error = format!("[line {}] {}", &m[1], &m[2]);
+                    if self.expected_errors.contains(&error) {
+                        found.insert(error);
+                        continue;
+                    }
+                    "Unexpected error:"
+                }
+                None if line.is_empty() => continue,
+                None => "Unexpected output on stderr:",
+            };
+            if unexpected < MAX_UNEXPECTED {
+                failures.push(failure.to_owned());
+                failures.push(line.clone());
+            }
+            unexpected += 1;
+        }
+        if unexpected > MAX_UNEXPECTED {
+            failures.push(format!(
+                "(truncated {} more...)",
+                unexpected - MAX_UNEXPECTED
+            ));
+        }
+        for error in &self.expected_errors {
+            if !found.contains(error) {
+                failures.push(format!("Missing expected error: {}", error));
+            }
+        }
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(failures.join("\n"))),
+        }
+    }
+
+    fn validate_warnings(&self, warnings: &[String]) -> Result<()> {
+        let missing: Vec<String> = self
+            .expected_warnings
+            .iter()
+            .filter(|w| !warnings.contains(w))
+            .map(|w| format!("Missing expected warning: {}", w))
+            .collect();
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(missing.join("\n"))),
+        }
+    }
+
+    fn validate_exit_code(&self, exit_code: i32) -> Result<()> {
+        if exit_code == self.expected_exit_code {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "Expected return code {} and got {}",
+                self.expected_exit_code,
+                exit_code
+            ))
+        }
+    }
+    /// Compare `std_out` line by line with the `// expect:` comments, in order.
+    fn validate_output(&self, std_out: &[String]) -> Result<()> {
+        let mut failures = vec![];
+        for (index, line) in std_out.iter().enumerate() {
+            match self.expected_output.get(index) {
+                None => failures.push(format!("Got output '{}' when none was expected.", line)),
+                Some(expected) if &expected.output != line => failures.push(format!(
+                    "Expected output '{}' on line {} and got '{}'.",
+                    expected.output, expected.line, line
+                )),
+                Some(_) => {}
+            }
+        }
+        for expected in self.expected_output.iter().skip(std_out.len()) {
+            failures.push(format!(
+                "Missing expected output '{}' on line {}.",
+                expected.output, expected.line
+            ));
+        }
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!(failures.join("\n"))),
+        }
+    }
+}
+
+struct RunOutput {
+    exit_code: i32,
+    stdout: Vec<String>,
+    stderr: Vec<String>,
+}
+
+/// The interpreter was killed for running longer than `--timeout`.
+#[derive(Debug)]
+struct TimedOut(Duration);
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timed out after {} s", self.0.as_secs_f64())
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Collect the lines written to `pipe` on a thread of their own.
+fn read_lines(pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<String>> {
+    thread::spawn(move || BufReader::new(pipe).lines().map_while(Result::ok).collect())
+}
+
+fn run_interpreter(
+    prog: &str,
+    extra_args: &[String],
+    test_input_path: &PathBuf,
+    timeout: Duration,
+) -> Result<RunOutput> {
+    let mut process = Command::new(prog)
+        .args(extra_args)
+        .arg(test_input_path)
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // Drain both pipes while the test runs, so a full one can't stall it.
+    let stdout = read_lines(process.stdout.take().unwrap());
+    let stderr = read_lines(process.stderr.take().unwrap());
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = process.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            process.kill()?;
+            process.wait()?;
+            return Err(TimedOut(timeout).into());
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    Ok(RunOutput {
+        // Killed by a signal, as on a crash.
+        exit_code: status.code().unwrap_or(-1),
+        stdout: stdout.join().unwrap(),
+        stderr: stderr.join().unwrap(),
+    })
+}
+
+/// Run the test a second time with `flags` added to the interpreter command
+/// line and fail if anything observable differs from the plain run.
+fn run_differential(test: &Test, prog: &str, flags: &[String], timeout: Duration) -> Result<()> {
+    let test_input_path = &test.test_file;
+    let baseline = run_interpreter(prog, &[], test_input_path, timeout)?;
+    let variant = run_interpreter(prog, flags, test_input_path, timeout)?;
+
+    if baseline.exit_code != variant.exit_code {
+        return Err(anyhow!(
+            "Exit code differs with {:?}: {} vs {}",
+            flags,
+            baseline.exit_code,
+            variant.exit_code
+        ));
+    }
+    for (name, a, b) in [
+        ("stdout", &baseline.stdout, &variant.stdout),
+        ("stderr", &baseline.stderr, &variant.stderr),
+    ] {
+        if let Some((lineno, (a, b))) = zip(a, b).enumerate().find(|(_, (a, b))| a != b) {
+            return Err(anyhow!(
+                "{} differs with {:?} on line {}: '{}' vs '{}'",
+                name,
+                flags,
+                lineno + 1,
+                a,
+                b
+            ));
+        }
+        if a.len() != b.len() {
+            return Err(anyhow!(
+                "{} differs with {:?}: {} lines vs {} lines",
+                name,
+                flags,
+                a.len(),
+                b.len()
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn run_test(test: &Test, prog: &str, timeout: Duration) -> Result<RunOutput> {
+    // Keep the path as given, relative to the working directory, rather than
+    // canonicalizing it: on Windows that adds a `\\?\` prefix that would
+    // show up in messages.
+    let test_input_path = &test.test_file;
+
+    let output = run_interpreter(prog, &[], test_input_path, timeout)?;
+
+    // // Display the results.
+    // if (failures.isEmpty) {
+    //   _passed++;
+    // } else {
+    //   _failed++;
+    //   term.writeLine("${term.red("FAIL")} $path");
+    //   print("");
+    //   for (var failure in failures) {
+    //     print("     ${term.pink(failure)}");
+    //   }
+    //   print("");
+    // }
+    // assert_eq!(exit_code, test.expected_exit_code);
+    // assert_eq!(output_lines, test.expected_output);
+    // println!("{:?}", zip(output_lines, test.expected_output));
+
+    Ok(output)
+}
+
+fn check_test(test: &Test, output: &RunOutput) -> Result<()> {
+    let (warnings, std_err): (Vec<String>, Vec<String>) = output
+        .stderr
+        .iter()
+        .cloned()
+        .partition(|line| WARNING_PATTERN.is_match(line));
+    test.validate_warnings(&warnings)?;
+    test.validate_runtime_error(&std_err)?;
+    test.validate_compile_errors(&std_err)?;
+    test.validate_exit_code(output.exit_code)?;
+    test.validate_output(&output.stdout)
+}
+
+/// Where tests are looked for when none are named.
+const TEST_DIR: &str = "test_lox_files";
+
+/// Add the `.lox` files under `dir`, at any depth, to `files`.
+fn lox_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in
+        fs::read_dir(dir).with_context(|| format!("Could not read {}", paths::display(dir)))?
+    {
+        let path = entry?.path();
+        if path.is_dir() {
+            lox_files(&path, files)?;
+        } else if path.extension().is_some_and(|e| e == "lox") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The files named in `inputs`, with each directory replaced by the `.lox`
+/// files in it, in path order: those to run, then those `suite` leaves out.
+/// Files named on their own always run.
+fn discover(inputs: &[String], suite: &Suite) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut files = vec![];
+    let mut excluded = vec![];
+    for input in inputs {
+        let path = paths::normalize(input);
+        if path.is_dir() {
+            let mut found = vec![];
+            lox_files(&path, &mut found)?;
+            found.sort();
+            for file in found {
+                let relative: Vec<_> = file
+                    .strip_prefix(&path)
+                    .unwrap_or(&file)
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect();
+                match suite.includes(&relative.join("/")) {
+                    true => files.push(file),
+                    false => excluded.push(file),
+                }
+            }
+        } else if path.is_file() {
+            files.push(path);
+        } else {
+            return Err(anyhow!("No such test file or directory: {}", input));
+        }
+    }
+    Ok((files, excluded))
+}
+
+/// Files marked `// nontest` are helpers for other tests, not tests.
+fn is_nontest(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|source| NON_TEST_PATTERN.is_match(&source))
+}
+
+/// Run one test file, printing its result, and describe the run for the report.
+fn run_test_file(
+    test_input: &Path,
+    test_binary: &str,
+    differential_flags: &[String],
+    timeout: Duration,
+    language: Language,
+    pretty: bool,
+) -> TestReport {
+    let start = Instant::now();
+    let test = Test::try_parse(&test_input.to_path_buf(), language);
+    let test_input = paths::display(test_input);
+    let (output, e) = match test.as_ref() {
+        None => (
+            None,
+            Err(anyhow!("Cannot expect both compile and runtime errors.")),
+        ),
+        Some(test) => {
+            let e = if differential_flags.is_empty() {
+                Ok(())
+            } else {
+                run_differential(test, test_binary, differential_flags, timeout)
+            };
+            match e.and_then(|_| run_test(test, test_binary, timeout)) {
+                Ok(output) => {
+                    let e = check_test(test, &output);
+                    (Some(output), e)
+                }
+                Err(e) => (None, Err(e)),
+            }
+        }
+    };
+    // One print, so results from tests running alongside don't interleave.
+    match &e {
+        _ if !pretty => {}
+        Ok(_) => println!("[{}] ({})", "PASSED".green(), test_input),
+        Err(e) => {
+            let mut text = format!("[{}] ({})", "FAILED".red(), test_input);
+            for line in e.to_string().lines() {
+                text += &format!("\n     {}", line.magenta());
+            }
+            println!("{}", text);
+        }
+    };
+    let output = output.unwrap_or(RunOutput {
+        exit_code: -1,
+        stdout: vec![],
+        stderr: vec![],
+    });
+    TestReport {
+        path: test_input,
+        timed_out: e.as_ref().is_err_and(|e| e.is::<TimedOut>()),
+        failure: e.err().map(|e| format!("{:#}", e)),
+        stdout: output.stdout,
+        stderr: output.stderr,
+        expected_stdout: test
+            .iter()
+            .flat_map(|t| &t.expected_output)
+            .map(|o| o.output.clone())
+            .collect(),
+        expected_stderr: test
+            .iter()
+            .flat_map(|t| {
+                t.expected_errors
+                    .iter()
+                    .cloned()
+                    .chain(t.expected_runtime_error.iter().map(|o| o.output.clone()))
+            })
+            .collect(),
+        duration: start.elapsed(),
+    }
+}
+
+/// Run each of `tests` and rewrite its `// expect:` comments to the output,
+/// or with `dry_run` only print what would change.
+fn update_expectations(
+    tests: &[PathBuf],
+    prog: &str,
+    timeout: Duration,
+    jobs: usize,
+    dry_run: bool,
+) -> Result<()> {
+    let runs = pool::run(tests, jobs, |test| {
+        run_interpreter(prog, &[], test, timeout)
+    });
+    let mut updated = 0;
+    let mut failed = 0;
+    for (test, run) in zip(tests, runs) {
+        match run.and_then(|output| update::update_file(test, &output.stdout, dry_run)) {
+            Ok(changed) => updated += changed as usize,
+            Err(e) => {
+                failed += 1;
+                eprintln!(
+                    "[{}] ({}) {:#}",
+                    "NOT UPDATED".yellow(),
+                    paths::display(test),
+                    e
+                );
+            }
+        }
+    }
+    let verb = if dry_run { "Would update" } else { "Updated" };
+    println!("{} {} of {} tests", verb, updated, tests.len());
+    match failed {
+        0 => Ok(()),
+        failed => Err(anyhow!("{} tests could not be updated", failed)),
+    }
+}
+
+/// Run Lox scripts through an interpreter and check their output against
+/// the `// expect` comments in them
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Args {
+    #[clap(subcommand)]
+    command: Option<DriverCommand>,
+    /// Test files, or directories to search for them; test_lox_files by default
+    input_files: Vec<String>,
+    /// Also run the test with this interpreter flag (e.g. `--check-math`) and
+    /// fail if the output differs from the plain run. May be repeated.
+    #[clap(long = "differential", allow_hyphen_values = true)]
+    differential_flags: Vec<String>,
+    /// Write a static HTML report of the run into this directory
+    #[clap(long, value_name = "DIR")]
+    report_html: Option<PathBuf>,
+    /// Run this many tests at once; one per core by default
+    #[clap(short, long)]
+    jobs: Option<usize>,
+    /// Interpreter to test
+    #[clap(long, value_name = "PATH", default_value = "target/debug/interpreter")]
+    interpreter: String,
+    /// Which tests to run and expect to pass, such as jlox, clox or
+    /// chap08_statements; also picks which `[java line N]` or `[c line N]`
+    /// errors to expect
+    #[clap(long, default_value = "jlox")]
+    suite: String,
+    /// Fail a test whose interpreter runs longer than this
+    #[clap(long, value_name = "SECONDS", default_value_t = 10.0)]
+    timeout: f64,
+    /// How to print results: a line per test for people, or a JUnit XML or
+    /// JSON document for CI, with nothing else on stdout
+    #[clap(long, arg_enum, default_value = "pretty")]
+    format: Format,
+    /// Instead of checking the tests, rewrite their `// expect:` comments
+    /// to match what the interpreter prints
+    #[clap(long)]
+    update_expectations: bool,
+    /// With --update-expectations, show the changes without writing them
+    #[clap(long, requires = "update-expectations")]
+    dry_run: bool,
+}
+
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Pretty,
+    Junit,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum DriverCommand {
+    /// Time the benchmark programs instead of checking test expectations
+    Bench {
+        /// Benchmarks to run; all of test_lox_files/benchmark by default
+        files: Vec<String>,
+        /// Run each benchmark this many times and report the fastest
+        #[clap(long, default_value_t = 1)]
+        runs: usize,
+        /// Interpreter to time
+        #[clap(long, default_value = "target/release/interpreter")]
+        interpreter: String,
+    },
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let pretty = args.format == Format::Pretty;
+    if let Some(DriverCommand::Bench {
+        files,
+        runs,
+        interpreter,
+    }) = &args.command
+    {
+        return bench::run(interpreter, files, *runs);
+    }
+    let suite = suites::find(&args.suite)?;
+    if args.timeout.is_nan() || args.timeout <= 0.0 {
+        return Err(anyhow!("--timeout must be a positive number of seconds"));
+    }
+    let timeout = Duration::from_secs_f64(args.timeout);
+    let inputs = match args.input_files.as_slice() {
+        [] => vec![TEST_DIR.to_owned()],
+        inputs => inputs.to_vec(),
+    };
+    let (found, excluded) = discover(&inputs, &suite)?;
+    let (skipped, tests): (Vec<PathBuf>, Vec<PathBuf>) = found
+        .into_iter()
+        .partition(|f| bench::is_benchmark(f) || is_nontest(f));
+    let skipped: Vec<String> = skipped
+        .iter()
+        .chain(&excluded)
+        .map(|p| paths::display(p))
+        .collect();
+    for skip in skipped.iter().filter(|_| pretty) {
+        println!("[{}] ({})", "SKIPPED".yellow(), skip);
+    }
+    let jobs = args.jobs.unwrap_or_else(pool::default_jobs);
+    if args.update_expectations {
+        return update_expectations(&tests, &args.interpreter, timeout, jobs, args.dry_run);
+    }
+    let reports: Vec<TestReport> = pool::run(&tests, jobs, |test_input| {
+        run_test_file(
+            test_input,
+            &args.interpreter,
+            &args.differential_flags,
+            timeout,
+            suite.language,
+            pretty,
+        )
+    });
+    match args.format {
+        Format::Pretty => report::print_summary(&reports, &skipped),
+        Format::Junit => print!("{}", report::junit(suite.name, &reports, &skipped)),
+        Format::Json => print!("{}", report::json(&reports, &skipped)),
+    }
+    let failed = reports.iter().filter(|r| r.failure.is_some()).count();
+    if let Some(dir) = &args.report_html {
+        report::write_html(dir, &reports)?;
+        if pretty {
+            println!("Wrote report to {}", dir.join("index.html").display());
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        failed => Err(anyhow!("{} of {} tests failed", failed, reports.len())),
+    }
+}
@@ -0,0 +1,319 @@
+use colored::*;
+use lox_core::tokens::json_string;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// What happened when one test file ran, for the summary and reports.
+pub struct TestReport {
+    pub path: String,
+    /// Why the test failed, or `None` if it passed.
+    pub failure: Option<String>,
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+    /// What the test's comments say should be printed, to diff against the run.
+    pub expected_stdout: Vec<String>,
+    pub expected_stderr: Vec<String>,
+    pub duration: Duration,
+    /// Killed by the driver rather than finishing.
+    pub timed_out: bool,
+}
+
+impl TestReport {
+    fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// `passed`, `failed` or `timeout`, as the structured reports say it.
+    fn outcome(&self) -> &'static str {
+        match (self.passed(), self.timed_out) {
+            (true, _) => "passed",
+            (false, true) => "timeout",
+            (false, false) => "failed",
+        }
+    }
+
+    /// Name of this test's page in the report directory.
+    fn page(&self) -> String {
+        format!("{}.html", self.path.replace(['/', '\\', '.'], "_"))
+    }
+}
+
+/// Print a line per test, in path order, and the totals.
+pub fn print_summary(reports: &[TestReport], skipped: &[String]) {
+    let mut rows: Vec<(&str, String, &str)> = reports
+        .iter()
+        .map(|r| {
+            let status = match r.outcome() {
+                "passed" => "PASSED",
+                "timeout" => "TIMEOUT",
+                _ => "FAILED",
+            };
+            let ms = format!("{:.1}", r.duration.as_secs_f64() * 1000.0);
+            (status, ms, r.path.as_str())
+        })
+        .chain(
+            skipped
+                .iter()
+                .map(|p| ("SKIPPED", "-".to_owned(), p.as_str())),
+        )
+        .collect();
+    rows.sort_by(|a, b| a.2.cmp(b.2));
+    println!("{:<8} {:>10}  Test", "Result", "Time (ms)");
+    for (status, ms, path) in rows {
+        let status = format!("{:<8}", status);
+        let status = match status.trim_end() {
+            "PASSED" => status.green(),
+            "FAILED" | "TIMEOUT" => status.red(),
+            _ => status.yellow(),
+        };
+        println!("{} {:>10}  {}", status, ms, path);
+    }
+    let failed = reports.iter().filter(|r| !r.passed()).count();
+    println!(
+        "Passed: {} Failed: {} Skipped: {}",
+        (reports.len() - failed).to_string().green(),
+        failed.to_string().red(),
+        skipped.len().to_string().yellow(),
+    );
+}
+
+fn json_lines(lines: &[String]) -> String {
+    let lines: Vec<String> = lines.iter().map(|l| json_string(l)).collect();
+    format!("[{}]", lines.join(","))
+}
+
+/// The run as one JSON object: totals, then a record per test in path
+/// order, with skipped tests included under status `skipped`.
+pub fn json(reports: &[TestReport], skipped: &[String]) -> String {
+    let mut tests: Vec<(&str, String)> = reports
+        .iter()
+        .map(|r| {
+            let record = format!(
+                "{{\"name\":{},\"status\":\"{}\",\"duration_ms\":{:.1},\"failure\":{},\"stdout\":{},\"stderr\":{}}}",
+                json_string(&r.path),
+                r.outcome(),
+                r.duration.as_secs_f64() * 1000.0,
+                r.failure.as_deref().map_or("null".to_owned(), json_string),
+                json_lines(&r.stdout),
+                json_lines(&r.stderr)
+            );
+            (r.path.as_str(), record)
+        })
+        .chain(skipped.iter().map(|p| {
+            let record = format!("{{\"name\":{},\"status\":\"skipped\"}}", json_string(p));
+            (p.as_str(), record)
+        }))
+        .collect();
+    tests.sort_by(|a, b| a.0.cmp(b.0));
+    let failed = reports.iter().filter(|r| !r.passed()).count();
+    let tests: Vec<String> = tests.into_iter().map(|(_, record)| record).collect();
+    format!(
+        "{{\"passed\":{},\"failed\":{},\"skipped\":{},\"tests\":[\n{}\n]}}\n",
+        reports.len() - failed,
+        failed,
+        skipped.len(),
+        tests.join(",\n")
+    )
+}
+
+/// The run as a JUnit XML document, with the suite's name, for CI systems
+/// that chart test results.
+pub fn junit(suite: &str, reports: &[TestReport], skipped: &[String]) -> String {
+    let mut cases: Vec<(&str, String)> = reports
+        .iter()
+        .map(|r| {
+            let mut case = format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\">\n",
+                escape(suite),
+                escape(&r.path),
+                r.duration.as_secs_f64()
+            );
+            if let Some(failure) = &r.failure {
+                writeln!(
+                    case,
+                    "      <failure type=\"{}\" message=\"{}\">{}</failure>",
+                    r.outcome(),
+                    escape(failure.lines().next().unwrap_or_default()),
+                    escape(failure)
+                )
+                .unwrap();
+            }
+            for (tag, lines) in [("system-out", &r.stdout), ("system-err", &r.stderr)] {
+                if !lines.is_empty() {
+                    writeln!(case, "      <{0}>{1}</{0}>", tag, escape(&lines.join("\n"))).unwrap();
+                }
+            }
+            case += "    </testcase>\n";
+            (r.path.as_str(), case)
+        })
+        .chain(skipped.iter().map(|p| {
+            let case = format!(
+                "    <testcase classname=\"{}\" name=\"{}\">\n      <skipped/>\n    </testcase>\n",
+                escape(suite),
+                escape(p)
+            );
+            (p.as_str(), case)
+        }))
+        .collect();
+    cases.sort_by(|a, b| a.0.cmp(b.0));
+    let failed = reports.iter().filter(|r| !r.passed()).count();
+    let total: Duration = reports.iter().map(|r| r.duration).sum();
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n  \
+         <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+        escape(suite),
+        reports.len() + skipped.len(),
+        failed,
+        skipped.len(),
+        total.as_secs_f64()
+    );
+    for (_, case) in cases {
+        out += &case;
+    }
+    out += "  </testsuite>\n</testsuites>\n";
+    out
+}
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+td, th { padding: 0.2em 0.8em; text-align: left; }
+.passed { color: #2a2; } .failed { color: #c22; }
+.bar { background: #69c; height: 0.8em; }
+pre { background: #f4f4f4; padding: 0.5em; }
+.del { color: #c22; } .ins { color: #2a2; }";
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title>\
+         <style>{}</style></head>\n<body>\n{}</body></html>\n",
+        escape(title),
+        STYLE,
+        body
+    )
+}
+
+fn status(report: &TestReport) -> &'static str {
+    match report.passed() {
+        true => "<span class=\"passed\">PASSED</span>",
+        false => "<span class=\"failed\">FAILED</span>",
+    }
+}
+
+fn lines_block(summary: &str, lines: &[String], open: bool) -> String {
+    format!(
+        "<details{}><summary>{} ({} lines)</summary><pre>{}</pre></details>\n",
+        if open { " open" } else { "" },
+        summary,
+        lines.len(),
+        escape(&lines.join("\n"))
+    )
+}
+
+/// Line-by-line diff of `expected` against `actual`, marking lines that only
+/// one side has or that differ at the same position.
+fn diff(expected: &[String], actual: &[String]) -> String {
+    let mut out = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        match (expected.get(i), actual.get(i)) {
+            (Some(e), Some(a)) if e == a => writeln!(out, "  {}", escape(e)),
+            (e, a) => {
+                if let Some(e) = e {
+                    writeln!(out, "<span class=\"del\">- {}</span>", escape(e)).unwrap();
+                }
+                match a {
+                    Some(a) => writeln!(out, "<span class=\"ins\">+ {}</span>", escape(a)),
+                    None => Ok(()),
+                }
+            }
+        }
+        .unwrap();
+    }
+    out
+}
+
+fn test_page(report: &TestReport) -> String {
+    let mut body = format!(
+        "<p><a href=\"index.html\">&larr; Summary</a></p>\n<h1>{} {}</h1>\n<p>{:.1} ms</p>\n",
+        escape(&report.path),
+        status(report),
+        report.duration.as_secs_f64() * 1000.0
+    );
+    if let Some(failure) = &report.failure {
+        writeln!(body, "<pre class=\"failed\">{}</pre>", escape(failure)).unwrap();
+    }
+    body += &lines_block("stdout", &report.stdout, false);
+    body += &lines_block("stderr", &report.stderr, false);
+    for (name, expected, actual) in [
+        ("stdout", &report.expected_stdout, &report.stdout),
+        ("stderr", &report.expected_stderr, &report.stderr),
+    ] {
+        if !expected.is_empty() {
+            writeln!(
+                body,
+                "<details{}><summary>{} diff</summary><pre>{}</pre></details>",
+                if report.passed() { "" } else { " open" },
+                name,
+                diff(expected, actual)
+            )
+            .unwrap();
+        }
+    }
+    page(&report.path, &body)
+}
+
+fn summary_page(reports: &[TestReport]) -> String {
+    let passed = reports.iter().filter(|r| r.passed()).count();
+    let total: Duration = reports.iter().map(|r| r.duration).sum();
+    let slowest = reports
+        .iter()
+        .map(|r| r.duration)
+        .max()
+        .unwrap_or_default()
+        .as_secs_f64()
+        .max(f64::EPSILON);
+
+    let mut body = format!(
+        "<h1>Test report</h1>\n<p><span class=\"passed\">{} passed</span>, \
+         <span class=\"failed\">{} failed</span> of {} in {:.1} ms</p>\n\
+         <table>\n<tr><th>Test</th><th>Status</th><th>Duration</th><th></th></tr>\n",
+        passed,
+        reports.len() - passed,
+        reports.len(),
+        total.as_secs_f64() * 1000.0
+    );
+    for report in reports {
+        writeln!(
+            body,
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{:.1} ms</td>\
+             <td><div class=\"bar\" style=\"width: {:.0}px\"></div></td></tr>",
+            report.page(),
+            escape(&report.path),
+            status(report),
+            report.duration.as_secs_f64() * 1000.0,
+            report.duration.as_secs_f64() / slowest * 300.0
+        )
+        .unwrap();
+    }
+    body += "</table>\n";
+    page("Test report", &body)
+}
+
+/// Write `index.html` summarizing `reports`, plus a page per test, into `dir`.
+pub fn write_html(dir: &Path, reports: &[TestReport]) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join("index.html"), summary_page(reports))?;
+    for report in reports {
+        fs::write(dir.join(report.page()), test_page(report))?;
+    }
+    Ok(())
+}
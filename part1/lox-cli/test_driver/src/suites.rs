@@ -0,0 +1,273 @@
+use anyhow::{anyhow, Result};
+
+/// Which book interpreter a suite holds an implementation to, for the
+/// `// [java line N]` and `// [c line N]` expectations only one of them meets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    Java,
+    C,
+}
+
+impl Language {
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::Java => "java",
+            Self::C => "c",
+        }
+    }
+}
+
+/// A named set of tests, as in the upstream test runner: the tests a
+/// complete interpreter passes, or those an interpreter built up to some
+/// chapter does.
+pub struct Suite {
+    pub name: &'static str,
+    pub language: Language,
+    /// Paths under the test directory and whether their tests run. The
+    /// longest matching path decides; `""` matches everything.
+    rules: Vec<(&'static str, bool)>,
+}
+
+impl Suite {
+    fn new(name: &'static str, language: Language, rules: &[&[(&'static str, bool)]]) -> Self {
+        Self {
+            name,
+            language,
+            rules: rules.concat(),
+        }
+    }
+
+    /// Whether the test at `path`, relative to the test directory with `/`
+    /// between components, is in the suite.
+    pub fn includes(&self, path: &str) -> bool {
+        let matches = |prefix: &str| {
+            prefix.is_empty()
+                || path == prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        };
+        self.rules
+            .iter()
+            .filter(|(prefix, _)| matches(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .is_some_and(|(_, run)| *run)
+    }
+}
+
+const ALL: &[(&str, bool)] = &[("", true)];
+const NONE: &[(&str, bool)] = &[("", false)];
+
+/// Tests of the scanner and parser alone, before there are statements.
+const EARLY_CHAPTERS: &[(&str, bool)] = &[("scanning", false), ("expressions", false)];
+
+const JAVA_NAN_EQUALITY: &[(&str, bool)] = &[("number/nan_equality.lox", false)];
+
+/// Limits only clox has, from its fixed-size bytecode and stacks.
+const NO_C_LIMITS: &[(&str, bool)] = &[
+    ("limit/loop_too_large.lox", false),
+    ("limit/no_reuse_constants.lox", false),
+    ("limit/too_many_constants.lox", false),
+    ("limit/too_many_locals.lox", false),
+    ("limit/too_many_upvalues.lox", false),
+    ("limit/stack_overflow.lox", false),
+];
+
+const NO_CONTROL_FLOW: &[(&str, bool)] = &[
+    ("block/empty.lox", false),
+    ("for", false),
+    ("if", false),
+    ("logical_operator", false),
+    ("variable/unreached_undefined.lox", false),
+    ("while", false),
+];
+
+const NO_FUNCTIONS: &[(&str, bool)] = &[
+    ("call", false),
+    ("closure", false),
+    ("for/closure_in_body.lox", false),
+    ("for/return_closure.lox", false),
+    ("for/return_inside.lox", false),
+    ("for/syntax.lox", false),
+    ("function", false),
+    ("limit/too_many_arguments.lox", false),
+    ("limit/too_many_parameters.lox", false),
+    ("operator/not.lox", false),
+    ("regression/40.lox", false),
+    ("return", false),
+    ("unexpected_character.lox", false),
+    ("while/closure_in_body.lox", false),
+    ("while/return_closure.lox", false),
+    ("while/return_inside.lox", false),
+];
+
+const NO_RESOLUTION: &[(&str, bool)] = &[
+    ("closure/assign_to_shadowed_later.lox", false),
+    ("function/local_mutual_recursion.lox", false),
+    ("variable/collide_with_parameter.lox", false),
+    ("variable/duplicate_local.lox", false),
+    ("variable/duplicate_parameter.lox", false),
+    ("variable/early_bound.lox", false),
+    ("return/at_top_level.lox", false),
+    ("variable/use_local_in_initializer.lox", false),
+];
+
+const NO_CLASSES: &[(&str, bool)] = &[
+    ("assignment/to_this.lox", false),
+    ("call/object.lox", false),
+    ("class", false),
+    ("closure/close_over_method_parameter.lox", false),
+    ("constructor", false),
+    ("field", false),
+    ("inheritance", false),
+    ("method", false),
+    ("number/decimal_point_at_eof.lox", false),
+    ("number/trailing_dot.lox", false),
+    ("operator/equals_class.lox", false),
+    ("operator/equals_method.lox", false),
+    ("operator/not_class.lox", false),
+    ("regression/394.lox", false),
+    ("return/in_method.lox", false),
+    ("super", false),
+    ("this", false),
+    ("variable/local_from_method.lox", false),
+];
+
+const NO_INHERITANCE: &[(&str, bool)] = &[
+    ("class/local_inherit_other.lox", false),
+    ("class/local_inherit_self.lox", false),
+    ("class/inherit_self.lox", false),
+    ("class/inherited_method.lox", false),
+    ("inheritance", false),
+    ("regression/394.lox", false),
+    ("super", false),
+];
+
+/// Local variables arrive in clox a chapter after globals.
+const NO_C_LOCALS: &[(&str, bool)] = &[
+    ("block/scope.lox", false),
+    ("variable/in_middle_of_block.lox", false),
+    ("variable/in_nested_block.lox", false),
+    ("variable/scope_reuse_in_different_blocks.lox", false),
+    ("variable/shadow_and_local.lox", false),
+    ("variable/undefined_local.lox", false),
+    ("variable/use_local_in_initializer.lox", false),
+    ("variable/duplicate_local.lox", false),
+];
+
+pub fn all() -> Vec<Suite> {
+    use Language::{Java, C};
+    let java_statements = [
+        ALL,
+        EARLY_CHAPTERS,
+        JAVA_NAN_EQUALITY,
+        NO_C_LIMITS,
+        NO_FUNCTIONS,
+        NO_RESOLUTION,
+        NO_CLASSES,
+    ];
+    let evaluate = [NONE, &[("expressions/evaluate.lox", true)][..]];
+    vec![
+        Suite::new(
+            "jlox",
+            Java,
+            &[ALL, EARLY_CHAPTERS, JAVA_NAN_EQUALITY, NO_C_LIMITS],
+        ),
+        Suite::new("clox", C, &[ALL, EARLY_CHAPTERS]),
+        Suite::new("chap04_scanning", Java, &[NONE, &[("scanning", true)]]),
+        Suite::new(
+            "chap06_parsing",
+            Java,
+            &[NONE, &[("expressions/parse.lox", true)]],
+        ),
+        Suite::new("chap07_evaluating", Java, &evaluate),
+        Suite::new(
+            "chap08_statements",
+            Java,
+            &[&java_statements[..], &[NO_CONTROL_FLOW]].concat(),
+        ),
+        Suite::new("chap09_control", Java, &java_statements),
+        Suite::new(
+            "chap10_functions",
+            Java,
+            &[
+                ALL,
+                EARLY_CHAPTERS,
+                JAVA_NAN_EQUALITY,
+                NO_C_LIMITS,
+                NO_RESOLUTION,
+                NO_CLASSES,
+            ],
+        ),
+        Suite::new(
+            "chap11_resolving",
+            Java,
+            &[
+                ALL,
+                EARLY_CHAPTERS,
+                JAVA_NAN_EQUALITY,
+                NO_C_LIMITS,
+                NO_CLASSES,
+            ],
+        ),
+        Suite::new(
+            "chap12_classes",
+            Java,
+            &[
+                ALL,
+                EARLY_CHAPTERS,
+                JAVA_NAN_EQUALITY,
+                NO_C_LIMITS,
+                NO_INHERITANCE,
+            ],
+        ),
+        Suite::new(
+            "chap13_inheritance",
+            Java,
+            &[ALL, EARLY_CHAPTERS, JAVA_NAN_EQUALITY, NO_C_LIMITS],
+        ),
+        Suite::new("chap17_compiling", C, &evaluate),
+        Suite::new("chap18_types", C, &evaluate),
+        Suite::new("chap19_strings", C, &evaluate),
+        Suite::new("chap20_hash_tables", C, &evaluate),
+        Suite::new(
+            "chap21_global_variables",
+            C,
+            &[
+                ALL,
+                EARLY_CHAPTERS,
+                NO_CONTROL_FLOW,
+                NO_FUNCTIONS,
+                NO_CLASSES,
+                NO_C_LOCALS,
+            ],
+        ),
+        Suite::new(
+            "chap22_local_variables",
+            C,
+            &[
+                ALL,
+                EARLY_CHAPTERS,
+                NO_CONTROL_FLOW,
+                NO_FUNCTIONS,
+                NO_CLASSES,
+            ],
+        ),
+        Suite::new(
+            "chap23_jumping",
+            C,
+            &[ALL, EARLY_CHAPTERS, NO_FUNCTIONS, NO_CLASSES],
+        ),
+    ]
+}
+
+/// The suite called `name`.
+pub fn find(name: &str) -> Result<Suite> {
+    let suites = all();
+    let names: Vec<&str> = suites.iter().map(|s| s.name).collect();
+    let names = names.join(", ");
+    suites
+        .into_iter()
+        .find(|s| s.name == name)
+        .ok_or_else(|| anyhow!("Unknown suite '{}'; the suites are {}", name, names))
+}
@@ -0,0 +1,84 @@
+use crate::EXPECTED_OUTPUT_PATTERN;
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use lox_core::paths;
+use std::fs;
+use std::path::Path;
+
+/// One `// expect:` comment whose text changes.
+pub struct Change {
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+}
+
+/// `source` with its `// expect:` comments, in order, given the lines of
+/// `stdout`, and the comments that changed. The code and everything else
+/// in the file is kept byte for byte.
+///
+/// There must be as many comments as lines of output: where a missing
+/// expectation belongs, or which one an output line no longer meets, is
+/// for a person to decide.
+pub fn rewrite(source: &str, stdout: &[String]) -> Result<(String, Vec<Change>)> {
+    let mut rewritten = String::with_capacity(source.len());
+    let mut changes = vec![];
+    let mut expectations = 0;
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[content.len()..];
+        let expected = EXPECTED_OUTPUT_PATTERN
+            .captures(content)
+            .and_then(|c| c.get(1));
+        let (expected, actual) = match (expected, stdout.get(expectations)) {
+            (Some(expected), Some(actual)) => (expected, actual),
+            (expected, _) => {
+                expectations += expected.is_some() as usize;
+                rewritten += line;
+                continue;
+            }
+        };
+        expectations += 1;
+        if expected.as_str() != actual {
+            changes.push(Change {
+                line: index + 1,
+                old: expected.as_str().to_owned(),
+                new: actual.clone(),
+            });
+        }
+        rewritten += &content[..expected.start()];
+        rewritten += actual;
+        rewritten += ending;
+    }
+    if expectations != stdout.len() {
+        return Err(anyhow!(
+            "{} expectations but {} lines of output; update it by hand",
+            expectations,
+            stdout.len()
+        ));
+    }
+    Ok((rewritten, changes))
+}
+
+/// Bring the `// expect:` comments in `path` in line with `stdout`,
+/// printing each change. With `dry_run` the file is left alone. Returns
+/// whether anything changed.
+pub fn update_file(path: &Path, stdout: &[String], dry_run: bool) -> Result<bool> {
+    let source = fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}", paths::display(path)))?;
+    let (rewritten, changes) = rewrite(&source, stdout)?;
+    if changes.is_empty() {
+        return Ok(false);
+    }
+    let mut text = format!("--- {}", paths::display(path));
+    for change in &changes {
+        text += &format!("\n@@ line {} @@", change.line);
+        text += &format!("\n{}", format!("-// expect: {}", change.old).red());
+        text += &format!("\n{}", format!("+// expect: {}", change.new).green());
+    }
+    println!("{}", text);
+    if !dry_run {
+        fs::write(path, rewritten)
+            .with_context(|| format!("Could not write {}", paths::display(path)))?;
+    }
+    Ok(true)
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn interpreter(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_fixtures_run_the_same_from_their_ast() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_lox_files");
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let mut checked = 0;
+    for entry in std::fs::read_dir(fixtures).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "lox") {
+            continue;
+        }
+        let script = path.to_str().unwrap();
+        let emitted = interpreter(&["--emit-ast=json", script]);
+        // Those with scan or parse errors have no tree to write.
+        if !emitted.status.success() {
+            continue;
+        }
+        let json = dir.join(path.with_extension("json").file_name().unwrap());
+        std::fs::write(&json, &emitted.stdout).unwrap();
+        let from_source = interpreter(&[script]);
+        let from_ast = interpreter(&["--from-ast", json.to_str().unwrap()]);
+        assert_eq!(
+            String::from_utf8_lossy(&from_ast.stdout),
+            String::from_utf8_lossy(&from_source.stdout),
+            "{}",
+            script
+        );
+        assert_eq!(
+            from_ast.status.code(),
+            from_source.status.code(),
+            "{}",
+            script
+        );
+        checked += 1;
+    }
+    assert!(checked > 20);
+}
+
+#[test]
+fn test_handwritten_ast() {
+    // Slots may be left out; the tree is resolved before it runs.
+    let span = r#"{"start":0,"end":0,"line":1,"column":1}"#;
+    let x = format!(r#"{{"name":"x","span":{}}}"#, span);
+    let json = format!(
+        r#"[{{"Var":[{x},{{"Literal":[{{"Number":2}},{span}]}}]}},
+            {{"Block":[{{"Var":[{x},{{"Literal":[{{"String":"local"}},{span}]}}]}},
+                        {{"Print":{{"Variable":{x}}}}}]}},
+            {{"Print":{{"Binary":[{{"Variable":{x}}},{{"kind":"Multiply","span":{span}}},{{"Variable":{x}}}]}}}}]"#
+    );
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("handwritten.json");
+    std::fs::write(&path, json).unwrap();
+    let output = interpreter(&["--from-ast", path.to_str().unwrap()]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "local\n4\n");
+
+    std::fs::write(&path, r#"[{"Print":{"Shout":[]}}]"#).unwrap();
+    let output = interpreter(&["--from-ast", path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Could not read a syntax tree from"));
+}
@@ -0,0 +1,58 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Run `source` with its stdout closed after the first line, as
+/// `interpreter script.lox | head -1` would, giving the exit code and stderr.
+fn run_into_closed_pipe(name: &str, source: &str) -> (Option<i32>, String) {
+    let script = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&script, source).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .arg(&script)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut first = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut first)
+        .unwrap();
+    assert_eq!(first, "y\n");
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("{} kept running after its output closed", name);
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    (status.code(), stderr)
+}
+
+#[test]
+fn test_print_loop_stops_quietly() {
+    let (code, stderr) = run_into_closed_pipe("print_loop.lox", "while (true) print \"y\";\n");
+    assert_eq!(code, Some(0));
+    assert_eq!(stderr, "");
+}
+
+#[test]
+fn test_print_in_call_stops_quietly() {
+    // Calls swallow the error from the failed print; the loop must not.
+    let source = "fun f() { print \"y\"; }\nwhile (true) f();\n";
+    let (code, stderr) = run_into_closed_pipe("print_in_call.lox", source);
+    assert_eq!(code, Some(0));
+    assert_eq!(stderr, "");
+}
@@ -0,0 +1,43 @@
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_coverage_listing_and_lcov() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let script = dir.join("cover_me.lox");
+    let path = script.to_str().unwrap();
+    std::fs::write(
+        &script,
+        "var n = 0;\nwhile (n < 3)\n  n = n + 1;\nif (n > 5)\n  print n;\n",
+    )
+    .unwrap();
+
+    let listing = dir.join("cover_me.lox.cov");
+    let status = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(["--coverage", listing.to_str().unwrap(), path])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        std::fs::read_to_string(&listing).unwrap(),
+        "        1:    1:var n = 0;\n\
+         \x20       1:    2:while (n < 3)\n\
+         \x20       3:    3:  n = n + 1;\n\
+         \x20       1:    4:if (n > 5)\n\
+         \x20   #####:    5:  print n;\n"
+    );
+
+    let lcov = dir.join("cover_me.info");
+    let status = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(["--coverage", lcov.to_str().unwrap(), path])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        std::fs::read_to_string(&lcov).unwrap(),
+        format!(
+            "TN:\nSF:{}\nDA:1,1\nDA:2,1\nDA:3,3\nDA:4,1\nDA:5,0\nLF:5\nLH:4\nend_of_record\n",
+            path
+        )
+    );
+}
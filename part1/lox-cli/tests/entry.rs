@@ -0,0 +1,45 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run_entry(name: &str) -> Output {
+    let script = Path::new(env!("CARGO_TARGET_TMPDIR")).join("entry.lox");
+    std::fs::write(
+        &script,
+        "onError(fun (message, line) { print \"handled \" + message; });\n\
+         fun main() {\n\
+         \x20 print \"in main\";\n\
+         \x20 print -nil;\n\
+         }\n",
+    )
+    .unwrap();
+    Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(["--entry", name])
+        .arg(&script)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_entry_runtime_error_is_reported() {
+    let output = run_entry("main");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "in main\nhandled Operand must be a number.\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Operand must be a number.\n[line 4] in main\n[line 2] in script\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn test_missing_entry_is_a_usage_error() {
+    let output = run_entry("nope");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "No function 'nope' to use as the entry point.\n"
+    );
+    assert_eq!(output.status.code(), Some(64));
+}
@@ -0,0 +1,35 @@
+use std::process::Command;
+
+fn fmt(args: &[&str]) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .arg("fmt")
+        .args(args)
+        .output()
+        .unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn test_check_then_rewrite() {
+    let script = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("fmt_me.lox");
+    let path = script.to_str().unwrap();
+    std::fs::write(&script, "print 1+2; // sum\n").unwrap();
+
+    let (code, stdout) = fmt(&["--check", path]);
+    assert_eq!(code, Some(1));
+    assert_eq!(stdout, format!("Would reformat {}\n", path));
+    assert_eq!(
+        std::fs::read_to_string(&script).unwrap(),
+        "print 1+2; // sum\n"
+    );
+
+    assert_eq!(fmt(&[path]).0, Some(0));
+    assert_eq!(
+        std::fs::read_to_string(&script).unwrap(),
+        "print 1 + 2; // sum\n"
+    );
+    assert_eq!(fmt(&["--check", path]), (Some(0), String::new()));
+}
@@ -0,0 +1,55 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn run(name: &str, source: &str, args: &[&str]) -> Output {
+    let script = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&script, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(args)
+        .arg(&script)
+        .output()
+        .unwrap()
+}
+
+fn assert_too_deep(output: Output) {
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[line 1] Error: Can't nest more than 256 levels deep.\n"
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn test_nested_parentheses() {
+    let source = format!("print {}1{};", "(".repeat(20000), ")".repeat(20000));
+    assert_too_deep(run("parentheses.lox", &source, &[]));
+
+    let source = format!("print {}1{};", "(".repeat(100), ")".repeat(100));
+    let output = run("parentheses.lox", &source, &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+}
+
+#[test]
+fn test_nested_negation() {
+    let source = format!("print {}1;", "-".repeat(50000));
+    assert_too_deep(run("negation.lox", &source, &[]));
+}
+
+#[test]
+fn test_nested_blocks() {
+    let source = format!("{}{}", "{".repeat(20000), "}".repeat(20000));
+    assert_too_deep(run("blocks.lox", &source, &[]));
+}
+
+#[test]
+fn test_debug_ast_of_long_chain() {
+    let source = format!("print {};", vec!["1"; 100000].join(" +\n"));
+    let output = run("chain.lox", &source, &["--debug-ast"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("AST: Print [line 99999]\n  Binary +\n    Binary +\n"));
+    let marker = format!("\n{}...\n", " ".repeat(2 * 101));
+    assert!(stdout.contains(&marker));
+    assert!(stdout.ends_with("\n100000\n"));
+}
@@ -0,0 +1,79 @@
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn test_echoes_results_as_inspect_shows_them() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .current_dir(env!("CARGO_TARGET_TMPDIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"\"a\" + \"b\";\n[1, \"c\", [[2]]];\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("\"ab\"\n"), "{}", stdout);
+    assert!(stdout.contains("[1, \"c\", [[...]]]\n"), "{}", stdout);
+}
+
+#[test]
+fn test_ctrl_c_aborts_the_running_statement() {
+    // The REPL keeps its history in the working directory.
+    let mut child = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .current_dir(env!("CARGO_TARGET_TMPDIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    // Once something has run, the Ctrl-C handler is in place.
+    stdin.write_all(b"print \"ready\";\n").unwrap();
+    let mut line = String::new();
+    while line != "ready\n" {
+        line.clear();
+        assert!(stdout.read_line(&mut line).unwrap() > 0);
+    }
+
+    stdin
+        .write_all(b"fun spin() { while (true) {} }\nspin();\n")
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let kill = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(kill.success());
+    stdin.write_all(b"print \"back\";\n").unwrap();
+    drop(stdin);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while child.try_wait().unwrap().is_none() {
+        if Instant::now() > deadline {
+            child.kill().unwrap();
+            panic!("the REPL kept running after Ctrl-C");
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert!(rest.contains("back\n"), "{}", rest);
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    assert!(stderr.contains("Interrupted."), "{}", stderr);
+}
@@ -0,0 +1,18 @@
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_nested_runtime_error_trace() {
+    let script = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../test_lox_files/0043_nested_runtime_error.lox");
+    let output = Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .arg(&script)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Operand must be a number.\n[line 4] in inner\n[line 8] in outer\n[line 13] in script\n"
+    );
+    assert_eq!(output.status.code(), Some(70));
+}
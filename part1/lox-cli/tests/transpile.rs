@@ -0,0 +1,116 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn interpreter(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Transpile `script` and build it, giving the program, or `None` if it
+/// uses what can't be transpiled.
+fn build(script: &Path) -> Option<PathBuf> {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("transpiled");
+    std::fs::create_dir_all(&dir).unwrap();
+    let name = script.file_stem().unwrap();
+    let rust = dir.join(name).with_extension("rs");
+    let transpiled = interpreter(&[
+        "transpile",
+        script.to_str().unwrap(),
+        "-o",
+        rust.to_str().unwrap(),
+    ]);
+    if !transpiled.status.success() {
+        return None;
+    }
+    let program = dir.join(name);
+    let rustc = Command::new("rustc")
+        .args(["--edition", "2021", "-o"])
+        .arg(&program)
+        .arg(&rust)
+        .output()
+        .unwrap();
+    assert!(
+        rustc.status.success(),
+        "{}",
+        String::from_utf8_lossy(&rustc.stderr)
+    );
+    Some(program)
+}
+
+#[test]
+fn test_fixtures_run_the_same_transpiled() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("../test_lox_files");
+    let mut checked = 0;
+    for entry in std::fs::read_dir(fixtures).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "lox") {
+            continue;
+        }
+        let Some(program) = build(&path) else {
+            continue;
+        };
+        let interpreted = interpreter(&[path.to_str().unwrap()]);
+        let compiled = Command::new(program).output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&compiled.stdout),
+            String::from_utf8_lossy(&interpreted.stdout),
+            "{}",
+            path.display()
+        );
+        assert_eq!(
+            compiled.status.code(),
+            interpreted.status.code(),
+            "{}",
+            path.display()
+        );
+        checked += 1;
+    }
+    assert!(checked > 15);
+}
+
+#[test]
+fn test_transpiled_runtime_errors() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let script = dir.join("transpile_errors.lox");
+    std::fs::write(
+        &script,
+        "var fs = [];\n\
+         for (var x in \"ab\") push(fs, fun () { return x + 1; });\n\
+         for (var f in fs) print f();\n\
+         fun bad() { print \"before\"; return [1, 2][2]; }\n\
+         fun outer() { return bad(); }\n\
+         print outer();\n",
+    )
+    .unwrap();
+    let output = Command::new(build(&script).unwrap()).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a1\nb1\nbefore\n");
+    // The same trace as the interpreter's.
+    let interpreted = interpreter(&[script.to_str().unwrap()]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Index out of range.\n[line 4] in bad\n[line 5] in outer\n[line 6] in script\n"
+    );
+    assert_eq!(output.stderr, interpreted.stderr);
+    assert_eq!(output.status.code(), Some(70));
+
+    // Natives reject bad arguments as the interpreter's do.
+    std::fs::write(&script, "print len(\"ab\");\npush(1, 2);\n").unwrap();
+    let output = Command::new(build(&script).unwrap()).output().unwrap();
+    let interpreted = interpreter(&[script.to_str().unwrap()]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Expected argument 1 of push to be a list, got number.\n[line 2] in script\n"
+    );
+    assert_eq!(output.stderr, interpreted.stderr);
+    assert_eq!(output.status.code(), Some(70));
+
+    std::fs::write(&script, "print inspect;\n").unwrap();
+    let output = interpreter(&["transpile", script.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Can't transpile the builtin 'inspect'.")
+    );
+}
@@ -0,0 +1,153 @@
+use crate::ast_printer::{self, INDENT};
+use crate::parser::Stmt;
+
+/// Limits on how much of a parse tree `--debug-ast` shows.
+#[derive(Debug, Clone, Default)]
+pub struct DumpLimits {
+    /// Nesting levels shown; anything deeper becomes `...`.
+    pub depth: Option<usize>,
+    /// Entries shown per node or list; the rest become `... N more`.
+    pub width: Option<usize>,
+}
+
+/// Nesting levels `--debug-ast` shows unless told otherwise, as every level
+/// indents its lines further and a long `+` chain nests a level per term.
+pub const DEFAULT_DEPTH: usize = 100;
+
+/// `stmts` as an indented tree, cut down to `limits`.
+pub fn dump(stmts: &[&Stmt], limits: &DumpLimits) -> String {
+    // Keep a level past the limit, for `elide` to mark where lines went.
+    let max_depth = limits.depth.map_or(usize::MAX, |d| d + 1);
+    elide(&ast_printer::tree(stmts, max_depth), limits)
+}
+
+/// The declarations of functions and variables called `name`, however
+/// deeply nested in blocks or function bodies.
+pub fn declarations<'a>(stmts: &'a [Stmt], name: &str) -> Vec<&'a Stmt> {
+    let mut found = vec![];
+    for s in stmts {
+        find(s, name, &mut found);
+    }
+    found
+}
+
+fn find<'a>(s: &'a Stmt, name: &str, found: &mut Vec<&'a Stmt>) {
+    match s {
+        Stmt::Var(n, _) if n.name.as_str() == name => found.push(s),
+        Stmt::Function(n, _, _) if n.name.as_str() == name => found.push(s),
+        Stmt::Function(_, _, body) | Stmt::While(_, body) | Stmt::ForIn(_, _, body) => {
+            find(body, name, found)
+        }
+        Stmt::Block(stmts) => stmts.iter().for_each(|s| find(s, name, found)),
+        Stmt::If(_, then, otherwise) => {
+            find(then, name, found);
+            if let Some(otherwise) = otherwise {
+                find(otherwise, name, found);
+            }
+        }
+        Stmt::Try(_, body, _, handler) => {
+            find(body, name, found);
+            find(handler, name, found);
+        }
+        _ => {}
+    }
+}
+
+fn depth_of(line: &str) -> usize {
+    (line.len() - line.trim_start().len()) / INDENT
+}
+
+/// Drop the lines of an indented dump nested deeper than the depth limit, and
+/// the entries past the width limit, leaving a marker where lines went.
+fn elide(pretty: &str, limits: &DumpLimits) -> String {
+    let max_depth = limits.depth.unwrap_or(usize::MAX);
+    let max_width = limits.width.unwrap_or(usize::MAX);
+    let mut out = String::new();
+    // Entries seen so far at each level of the node being printed.
+    let mut entries: Vec<usize> = vec![];
+    // The level whose later entries are being dropped, and how many.
+    let mut dropping: Option<(usize, usize)> = None;
+    let mut too_deep = false;
+    let marker = |out: &mut String, depth: usize, text: &str| {
+        out.push_str(&" ".repeat(depth * INDENT));
+        out.push_str(text);
+        out.push('\n');
+    };
+    for line in pretty.lines() {
+        let depth = depth_of(line);
+        if let Some((level, count)) = dropping {
+            if depth >= level {
+                if depth == level {
+                    dropping = Some((level, count + 1));
+                }
+                continue;
+            }
+            marker(&mut out, level, &format!("... {} more", count));
+            dropping = None;
+        }
+        if depth > max_depth {
+            if !too_deep {
+                marker(&mut out, max_depth + 1, "...");
+                too_deep = true;
+            }
+            continue;
+        }
+        too_deep = false;
+        entries.truncate(depth + 1);
+        entries.resize(depth + 1, 0);
+        entries[depth] += 1;
+        if entries[depth] > max_width {
+            dropping = Some((depth, 1));
+            continue;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    if let Some((level, count)) = dropping {
+        marker(&mut out, level, &format!("... {} more", count));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "Block\n  A\n  B\n    C\n  D\nE\n";
+
+    #[test]
+    fn test_depth() {
+        let limits = DumpLimits {
+            depth: Some(1),
+            width: None,
+        };
+        assert_eq!(elide(DUMP, &limits), "Block\n  A\n  B\n    ...\n  D\nE\n");
+    }
+
+    #[test]
+    fn test_width() {
+        let limits = DumpLimits {
+            depth: None,
+            width: Some(1),
+        };
+        assert_eq!(
+            elide(DUMP, &limits),
+            "Block\n  A\n  ... 2 more\n... 1 more\n"
+        );
+    }
+
+    #[test]
+    fn test_declarations() {
+        let source = "var f = 1; { fun f() {} } fun g() { var f = 2; }";
+        let ast = crate::lox::Lox::default().parse(source).unwrap();
+        let found: Vec<String> = declarations(&ast, "f")
+            .iter()
+            .map(|s| match s {
+                Stmt::Var(..) => "var".to_owned(),
+                Stmt::Function(..) => "fun".to_owned(),
+                s => format!("{:?}", s),
+            })
+            .collect();
+        assert_eq!(found, ["var", "fun", "var"]);
+    }
+}
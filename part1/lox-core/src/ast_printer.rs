@@ -0,0 +1,552 @@
+use crate::parser::{Expr, ExprVisitor, Ident, Literal, OpKind, Operator, Stmt, StmtVisitor};
+use crate::tokens::{json_string, Span};
+
+/// Spaces per level in `tree` output.
+pub const INDENT: usize = 2;
+
+fn op_name(kind: OpKind) -> String {
+    match kind {
+        OpKind::Or => "or".to_owned(),
+        OpKind::And => "and".to_owned(),
+        kind => kind.to_string(),
+    }
+}
+
+/// Strings are quoted, so `"1"` and `1` print differently.
+fn literal(l: &Literal) -> String {
+    match l {
+        Literal::String(s) => json_string(s),
+        l => l.to_string(),
+    }
+}
+
+fn names(idents: &[Ident]) -> Vec<String> {
+    idents.iter().map(Ident::to_string).collect()
+}
+
+fn params_list(params: &[Ident]) -> String {
+    format!("({})", names(params).join(" "))
+}
+
+fn parens(name: &str, parts: &[String]) -> String {
+    match parts {
+        [] => format!("({})", name),
+        parts => format!("({} {})", name, parts.join(" ")),
+    }
+}
+
+/// Prints the book's prefix form, as its `AstPrinter` does:
+/// `(* (- 123) (group 45.67))` for expressions, `(var a (+ 1 2))` for
+/// statements. This is how `Expr` and `Stmt` display.
+pub struct AstPrinter;
+
+impl AstPrinter {
+    fn exprs(&mut self, es: &[Expr]) -> Vec<String> {
+        es.iter().map(|e| e.accept(self)).collect()
+    }
+}
+
+impl ExprVisitor<String> for AstPrinter {
+    fn visit_binary(&mut self, left: &Expr, op: &Operator, right: &Expr) -> String {
+        parens(&op_name(op.kind), &[left.accept(self), right.accept(self)])
+    }
+
+    fn visit_unary(&mut self, op: &Operator, operand: &Expr) -> String {
+        parens(&op.kind.to_string(), &[operand.accept(self)])
+    }
+
+    fn visit_literal(&mut self, value: &Literal, _: Span) -> String {
+        literal(value)
+    }
+
+    fn visit_grouping(&mut self, inner: &Expr) -> String {
+        parens("group", &[inner.accept(self)])
+    }
+
+    fn visit_variable(&mut self, name: &Ident) -> String {
+        name.to_string()
+    }
+
+    fn visit_assign(&mut self, name: &Ident, value: &Expr) -> String {
+        parens("=", &[name.to_string(), value.accept(self)])
+    }
+
+    fn visit_logical(&mut self, left: &Expr, op: &Operator, right: &Expr) -> String {
+        self.visit_binary(left, op, right)
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _: Span, args: &[Expr]) -> String {
+        parens(
+            "call",
+            &[vec![callee.accept(self)], self.exprs(args)].concat(),
+        )
+    }
+
+    fn visit_ternary(&mut self, cond: &Expr, then: &Expr, otherwise: &Expr) -> String {
+        let parts = [cond.accept(self), then.accept(self), otherwise.accept(self)];
+        parens("?:", &parts)
+    }
+
+    fn visit_list(&mut self, _: Span, items: &[Expr]) -> String {
+        parens("list", &self.exprs(items))
+    }
+
+    fn visit_index(&mut self, object: &Expr, _: Span, index: &Expr) -> String {
+        parens("[]", &[object.accept(self), index.accept(self)])
+    }
+
+    fn visit_index_set(&mut self, object: &Expr, _: Span, index: &Expr, value: &Expr) -> String {
+        let parts = [object.accept(self), index.accept(self), value.accept(self)];
+        parens("[]=", &parts)
+    }
+
+    /// A missing bound is `_`.
+    fn visit_slice(
+        &mut self,
+        object: &Expr,
+        _: Span,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> String {
+        let object = object.accept(self);
+        let mut bound = |b: Option<&Expr>| b.map_or("_".to_owned(), |b| b.accept(self));
+        let parts = [object, bound(start), bound(end)];
+        parens("[:]", &parts)
+    }
+
+    fn visit_lambda(&mut self, _: Span, params: &[Ident], body: &Stmt) -> String {
+        parens("fun", &[params_list(params), body.accept(self)])
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Ident) -> String {
+        parens(".", &[object.accept(self), name.to_string()])
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_expr_stmt(&mut self, expr: &Expr) -> String {
+        parens("expr", &[expr.accept(self)])
+    }
+
+    fn visit_print(&mut self, expr: &Expr) -> String {
+        parens("print", &[expr.accept(self)])
+    }
+
+    fn visit_var(&mut self, name: &Ident, init: Option<&Expr>) -> String {
+        let init = init.map(|e| e.accept(self));
+        parens(
+            "var",
+            &[vec![name.to_string()], init.into_iter().collect()].concat(),
+        )
+    }
+
+    fn visit_block(&mut self, stmts: &[Stmt]) -> String {
+        let stmts: Vec<String> = stmts.iter().map(|s| s.accept(self)).collect();
+        parens("block", &stmts)
+    }
+
+    fn visit_if(&mut self, cond: &Expr, then: &Stmt, otherwise: Option<&Stmt>) -> String {
+        let mut parts = vec![cond.accept(self), then.accept(self)];
+        parts.extend(otherwise.map(|s| s.accept(self)));
+        parens("if", &parts)
+    }
+
+    fn visit_while(&mut self, cond: &Expr, body: &Stmt) -> String {
+        parens("while", &[cond.accept(self), body.accept(self)])
+    }
+
+    fn visit_for_in(&mut self, name: &Ident, iterable: &Expr, body: &Stmt) -> String {
+        let parts = [name.to_string(), iterable.accept(self), body.accept(self)];
+        parens("for", &parts)
+    }
+
+    fn visit_function(&mut self, name: &Ident, params: &[Ident], body: &Stmt) -> String {
+        let parts = [name.to_string(), params_list(params), body.accept(self)];
+        parens("fun", &parts)
+    }
+
+    fn visit_return(&mut self, _: Span, value: Option<&Expr>) -> String {
+        let value: Vec<String> = value.map(|e| e.accept(self)).into_iter().collect();
+        parens("return", &value)
+    }
+
+    fn visit_throw(&mut self, _: Span, value: &Expr) -> String {
+        parens("throw", &[value.accept(self)])
+    }
+
+    fn visit_try(&mut self, body: &Stmt, name: &Ident, handler: &Stmt) -> String {
+        let parts = [body.accept(self), name.to_string(), handler.accept(self)];
+        parens("try", &parts)
+    }
+}
+
+/// `e` in the prefix form, from `AstPrinter`.
+pub fn expr(e: &Expr) -> String {
+    e.accept(&mut AstPrinter)
+}
+
+/// `s` in the prefix form, from `AstPrinter`.
+pub fn stmt(s: &Stmt) -> String {
+    s.accept(&mut AstPrinter)
+}
+
+/// `stmts` as an indented tree, a node per line with its children below
+/// it, for `--debug-ast`. Nodes more than `max_depth` levels down are left
+/// out.
+pub fn tree(stmts: &[&Stmt], max_depth: usize) -> String {
+    let mut out = String::new();
+    // From a stack rather than by recursion, as a long `+` chain nests deep.
+    let mut work: Vec<(Node, usize)> = stmts.iter().rev().map(|s| (Node::Stmt(s), 0)).collect();
+    while let Some((n, depth)) = work.pop() {
+        if depth > max_depth {
+            continue;
+        }
+        let (text, below) = match n {
+            Node::Stmt(s) => stmt_node(s),
+            Node::Expr(e) => expr_node(e),
+            Node::Label(text, below) => (text, below),
+        };
+        out.push_str(&" ".repeat(depth * INDENT));
+        out.push_str(&text);
+        out.push('\n');
+        // The first below is printed first.
+        work.extend(below.into_iter().rev().map(|n| (n, depth + 1)));
+    }
+    out
+}
+
+/// A line of `tree` output.
+enum Node<'a> {
+    Stmt(&'a Stmt),
+    Expr(&'a Expr),
+    /// A line of its own, as an `If`'s `Then`, and the nodes below it.
+    Label(String, Vec<Node<'a>>),
+}
+
+fn label<'a>(text: &str, below: Vec<Node<'a>>) -> Node<'a> {
+    Node::Label(text.to_owned(), below)
+}
+
+/// The line for `s`, and the nodes below it.
+fn stmt_node(s: &Stmt) -> (String, Vec<Node<'_>>) {
+    let at = s.line().map_or(String::new(), |l| format!(" [line {}]", l));
+    match s {
+        Stmt::Expr(e) => (format!("Expression{}", at), vec![Node::Expr(e)]),
+        Stmt::Print(e) => (format!("Print{}", at), vec![Node::Expr(e)]),
+        Stmt::Var(n, init) => (
+            format!("Var {}{}", n, at),
+            init.iter().map(Node::Expr).collect(),
+        ),
+        Stmt::Block(stmts) => (
+            format!("Block{}", at),
+            stmts.iter().map(Node::Stmt).collect(),
+        ),
+        Stmt::If(c, t, e) => {
+            let mut below = vec![Node::Expr(c), label("Then", vec![Node::Stmt(t)])];
+            below.extend(e.as_deref().map(|e| label("Else", vec![Node::Stmt(e)])));
+            (format!("If{}", at), below)
+        }
+        Stmt::While(c, body) => (
+            format!("While{}", at),
+            vec![Node::Expr(c), Node::Stmt(body)],
+        ),
+        Stmt::ForIn(n, iterable, body) => (
+            format!("ForIn {}{}", n, at),
+            vec![Node::Expr(iterable), Node::Stmt(body)],
+        ),
+        Stmt::Function(n, params, body) => {
+            let params = names(params).join(", ");
+            (
+                format!("Function {}({}){}", n, params, at),
+                vec![Node::Stmt(body)],
+            )
+        }
+        Stmt::Return(_, v) => (format!("Return{}", at), v.iter().map(Node::Expr).collect()),
+        Stmt::Throw(_, v) => (format!("Throw{}", at), vec![Node::Expr(v)]),
+        Stmt::Try(_, body, n, handler) => (
+            format!("Try{}", at),
+            vec![
+                Node::Stmt(body),
+                label(&format!("Catch {}", n), vec![Node::Stmt(handler)]),
+            ],
+        ),
+    }
+}
+
+fn exprs<'a>(es: &[&'a Expr]) -> Vec<Node<'a>> {
+    es.iter().map(|e| Node::Expr(e)).collect()
+}
+
+/// The line for `e`, and the nodes below it.
+fn expr_node(e: &Expr) -> (String, Vec<Node<'_>>) {
+    match e {
+        Expr::Binary(l, op, r) => (format!("Binary {}", op_name(op.kind)), exprs(&[l, r])),
+        Expr::Logical(l, op, r) => (format!("Logical {}", op_name(op.kind)), exprs(&[l, r])),
+        Expr::Unary(op, e) => (format!("Unary {}", op.kind), exprs(&[e])),
+        Expr::Literal(l, _) => (format!("Literal {}", literal(l)), vec![]),
+        Expr::Grouping(e) => ("Grouping".to_owned(), exprs(&[e])),
+        Expr::Variable(n) => (format!("Variable {}", n), vec![]),
+        Expr::Assign(n, v) => (format!("Assign {}", n), exprs(&[v])),
+        Expr::Call(callee, _, args) => (
+            "Call".to_owned(),
+            std::iter::once(&**callee)
+                .chain(args)
+                .map(Node::Expr)
+                .collect(),
+        ),
+        Expr::Ternary(c, t, e) => ("Ternary".to_owned(), exprs(&[c, t, e])),
+        Expr::List(_, items) => ("List".to_owned(), items.iter().map(Node::Expr).collect()),
+        Expr::Index(o, _, i) => ("Index".to_owned(), exprs(&[o, i])),
+        Expr::IndexSet(o, _, i, v) => ("IndexSet".to_owned(), exprs(&[o, i, v])),
+        Expr::Slice(o, _, a, b) => {
+            let mut below = vec![Node::Expr(o)];
+            for bound in [a, b] {
+                below.push(match bound {
+                    Some(bound) => Node::Expr(bound),
+                    None => label("(none)", vec![]),
+                });
+            }
+            ("Slice".to_owned(), below)
+        }
+        Expr::Lambda(_, params, body) => (
+            format!("Lambda({})", names(params).join(", ")),
+            vec![Node::Stmt(body)],
+        ),
+        Expr::Get(o, n) => (format!("Get {}", n), exprs(&[o])),
+    }
+}
+
+/// Prints expressions in reverse Polish notation, operands before their
+/// operator, as in the book's chapter 5 challenge: `(1 + 2) * (4 - 3)` is
+/// `1 2 + 4 3 - *`. Operators that take a varying number of operands carry
+/// the count, as in `f 1 2 call/2`.
+pub struct RpnPrinter;
+
+impl RpnPrinter {
+    fn postfix(&mut self, operands: &[Option<&Expr>], op: &str) -> String {
+        let mut parts: Vec<String> = operands
+            .iter()
+            .map(|e| e.map_or("_".to_owned(), |e| e.accept(self)))
+            .collect();
+        parts.push(op.to_owned());
+        parts.join(" ")
+    }
+}
+
+impl ExprVisitor<String> for RpnPrinter {
+    fn visit_binary(&mut self, left: &Expr, op: &Operator, right: &Expr) -> String {
+        self.postfix(&[Some(left), Some(right)], &op_name(op.kind))
+    }
+
+    /// Negation is `neg`, as `-` alone would read as subtraction.
+    fn visit_unary(&mut self, op: &Operator, operand: &Expr) -> String {
+        let name = match op.kind {
+            OpKind::Negate => "neg".to_owned(),
+            kind => kind.to_string(),
+        };
+        self.postfix(&[Some(operand)], &name)
+    }
+
+    fn visit_literal(&mut self, value: &Literal, _: Span) -> String {
+        literal(value)
+    }
+
+    fn visit_grouping(&mut self, inner: &Expr) -> String {
+        inner.accept(self)
+    }
+
+    fn visit_variable(&mut self, name: &Ident) -> String {
+        name.to_string()
+    }
+
+    fn visit_assign(&mut self, name: &Ident, value: &Expr) -> String {
+        format!("{} {} =", value.accept(self), name)
+    }
+
+    fn visit_logical(&mut self, left: &Expr, op: &Operator, right: &Expr) -> String {
+        self.postfix(&[Some(left), Some(right)], &op_name(op.kind))
+    }
+
+    fn visit_call(&mut self, callee: &Expr, _: Span, args: &[Expr]) -> String {
+        let operands: Vec<_> = std::iter::once(callee).chain(args).map(Some).collect();
+        self.postfix(&operands, &format!("call/{}", args.len()))
+    }
+
+    fn visit_ternary(&mut self, cond: &Expr, then: &Expr, otherwise: &Expr) -> String {
+        self.postfix(&[Some(cond), Some(then), Some(otherwise)], "?:")
+    }
+
+    fn visit_list(&mut self, _: Span, items: &[Expr]) -> String {
+        let operands: Vec<_> = items.iter().map(Some).collect();
+        self.postfix(&operands, &format!("list/{}", items.len()))
+    }
+
+    fn visit_index(&mut self, object: &Expr, _: Span, index: &Expr) -> String {
+        self.postfix(&[Some(object), Some(index)], "[]")
+    }
+
+    fn visit_index_set(&mut self, object: &Expr, _: Span, index: &Expr, value: &Expr) -> String {
+        self.postfix(&[Some(object), Some(index), Some(value)], "[]=")
+    }
+
+    /// A missing bound is `_`.
+    fn visit_slice(
+        &mut self,
+        object: &Expr,
+        _: Span,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> String {
+        self.postfix(&[Some(object), start, end], "[:]")
+    }
+
+    /// The body is statements, which have no postfix form, so only the
+    /// arity shows.
+    fn visit_lambda(&mut self, _: Span, params: &[Ident], _: &Stmt) -> String {
+        format!("fun/{}", params.len())
+    }
+
+    fn visit_get(&mut self, object: &Expr, name: &Ident) -> String {
+        format!("{} .{}", object.accept(self), name)
+    }
+}
+
+/// `e` in reverse Polish notation, from `RpnPrinter`.
+pub fn rpn(e: &Expr) -> String {
+    e.accept(&mut RpnPrinter)
+}
+
+/// Collects a line per expression directly in a statement, and those of
+/// the statements nested in it, for `rpn_lines`.
+impl StmtVisitor<Vec<String>> for RpnPrinter {
+    fn visit_expr_stmt(&mut self, expr: &Expr) -> Vec<String> {
+        vec![expr.accept(self)]
+    }
+
+    fn visit_print(&mut self, expr: &Expr) -> Vec<String> {
+        vec![expr.accept(self)]
+    }
+
+    fn visit_var(&mut self, _: &Ident, init: Option<&Expr>) -> Vec<String> {
+        init.map(|e| e.accept(self)).into_iter().collect()
+    }
+
+    fn visit_block(&mut self, stmts: &[Stmt]) -> Vec<String> {
+        stmts.iter().flat_map(|s| s.accept(self)).collect()
+    }
+
+    fn visit_if(&mut self, cond: &Expr, then: &Stmt, otherwise: Option<&Stmt>) -> Vec<String> {
+        let mut lines = vec![cond.accept(self)];
+        lines.extend(then.accept(self));
+        if let Some(otherwise) = otherwise {
+            lines.extend(otherwise.accept(self));
+        }
+        lines
+    }
+
+    fn visit_while(&mut self, cond: &Expr, body: &Stmt) -> Vec<String> {
+        let mut lines = vec![cond.accept(self)];
+        lines.extend(body.accept(self));
+        lines
+    }
+
+    fn visit_for_in(&mut self, _: &Ident, iterable: &Expr, body: &Stmt) -> Vec<String> {
+        self.visit_while(iterable, body)
+    }
+
+    fn visit_function(&mut self, _: &Ident, _: &[Ident], body: &Stmt) -> Vec<String> {
+        body.accept(self)
+    }
+
+    fn visit_return(&mut self, _: Span, value: Option<&Expr>) -> Vec<String> {
+        value.map(|e| e.accept(self)).into_iter().collect()
+    }
+
+    fn visit_throw(&mut self, _: Span, value: &Expr) -> Vec<String> {
+        vec![value.accept(self)]
+    }
+
+    fn visit_try(&mut self, body: &Stmt, _: &Ident, handler: &Stmt) -> Vec<String> {
+        let mut lines = body.accept(self);
+        lines.extend(handler.accept(self));
+        lines
+    }
+}
+
+/// The expressions of `stmts` in reverse Polish notation, a line each in
+/// source order, including those nested in blocks and function bodies,
+/// for `--print-rpn`.
+pub fn rpn_lines(stmts: &[Stmt]) -> Vec<String> {
+    RpnPrinter.visit_block(stmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        crate::lox::Lox::default().parse(source).unwrap()
+    }
+
+    #[test]
+    fn test_prefix_form() {
+        let ast = parse("print -123 * (45.67);\nvar s = \"1\" + 1;\nprint f(a)[0:];");
+        let printed: Vec<String> = ast.iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            printed,
+            [
+                "(print (* (- 123) (group 45.67)))",
+                "(var s (+ \"1\" 1))",
+                "(print ([:] (call f a) 0 _))",
+            ]
+        );
+        let ast = parse("fun f(a, b) { if (a or b) return a; else { return; } }");
+        assert_eq!(
+            ast[0].to_string(),
+            "(fun f (a b) (block (if (or a b) (return a) (block (return)))))"
+        );
+    }
+
+    #[test]
+    fn test_tree() {
+        let ast = parse("var a = 1;\nwhile (a < 3)\n  a = a + 1;");
+        let stmts: Vec<&Stmt> = ast.iter().collect();
+        assert_eq!(
+            tree(&stmts, usize::MAX),
+            "Var a [line 1]\n\
+             \x20 Literal 1\n\
+             While [line 2]\n\
+             \x20 Binary <\n\
+             \x20   Variable a\n\
+             \x20   Literal 3\n\
+             \x20 Expression [line 3]\n\
+             \x20   Assign a\n\
+             \x20     Binary +\n\
+             \x20       Variable a\n\
+             \x20       Literal 1\n"
+        );
+        assert_eq!(
+            tree(&stmts, 1),
+            "Var a [line 1]\n\
+             \x20 Literal 1\n\
+             While [line 2]\n\
+             \x20 Binary <\n\
+             \x20 Expression [line 3]\n"
+        );
+    }
+
+    #[test]
+    fn test_rpn() {
+        let ast = parse(
+            "(1 + 2) * (4 - 3);\nvar a = -b or f(1, x[1:]);\nfun g(n) { return [n, \"s\"]; }",
+        );
+        assert_eq!(
+            rpn_lines(&ast),
+            [
+                "1 2 + 4 3 - *",
+                "b neg f 1 x 1 _ [:] call/2 or",
+                "n \"s\" list/2",
+            ]
+        );
+    }
+}
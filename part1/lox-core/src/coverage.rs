@@ -0,0 +1,144 @@
+use crate::observer::Event;
+use crate::parser::{self, Expr, Stmt};
+use crate::scanner::Scanner;
+use std::collections::BTreeMap;
+
+/// Counts how often the statements of a program run, from the events of
+/// `Lox::run_observed`, and reports it by line. A line's count is that of
+/// its busiest statement, so `if (n < 2) return n;` counts how often the
+/// `if` ran rather than adding the `return` to it.
+pub struct Coverage {
+    source: String,
+    /// Runs of each statement, keyed by its line and where it starts, with
+    /// every statement of the program, run or not.
+    statements: BTreeMap<(i32, usize), u64>,
+}
+
+/// Whether `stmt` counts; a block is only run through what it contains.
+fn counted(stmt: &Stmt) -> bool {
+    !matches!(stmt, Stmt::Block(_))
+}
+
+impl Coverage {
+    /// Ready to count the statements of `source`, found by parsing it
+    /// again. A program that doesn't parse has none, and never runs.
+    pub fn new(source: &str) -> Self {
+        let mut statements = BTreeMap::new();
+        if let Ok(program) = Scanner::scan(source).and_then(|tokens| parser::parse(&tokens)) {
+            let mut stmts: Vec<&Stmt> = program.iter().collect();
+            let mut exprs: Vec<&Expr> = vec![];
+            // Walk the statements of function and lambda bodies too.
+            while let Some(stmt) = stmts.pop() {
+                if let Some(span) = stmt.span().filter(|_| counted(stmt)) {
+                    statements.insert((span.line, span.start), 0);
+                }
+                match stmt {
+                    Stmt::Expr(e) | Stmt::Print(e) | Stmt::Throw(_, e) => exprs.push(e),
+                    Stmt::Var(_, init) | Stmt::Return(_, init) => exprs.extend(init),
+                    Stmt::Block(body) => stmts.extend(body),
+                    Stmt::If(c, t, e) => {
+                        exprs.push(c);
+                        stmts.push(t);
+                        stmts.extend(e.as_deref());
+                    }
+                    Stmt::While(c, body) | Stmt::ForIn(_, c, body) => {
+                        exprs.push(c);
+                        stmts.push(body);
+                    }
+                    Stmt::Function(_, _, body) => stmts.push(body),
+                    Stmt::Try(_, body, _, handler) => stmts.extend([&**body, &**handler]),
+                }
+                while let Some(expr) = exprs.pop() {
+                    match expr {
+                        Expr::Lambda(_, _, body) => stmts.push(body),
+                        e => exprs.extend(e.children()),
+                    }
+                }
+            }
+        }
+        Self {
+            source: source.to_owned(),
+            statements,
+        }
+    }
+
+    /// Count the statement an `Event::StatementExecuted` ran.
+    pub fn observe(&mut self, event: &Event) {
+        if let Event::StatementExecuted { stmt, span } = event {
+            if counted(stmt) {
+                *self.statements.entry((span.line, span.start)).or_default() += 1;
+            }
+        }
+    }
+
+    /// Runs of each line with a statement on it.
+    pub fn lines(&self) -> BTreeMap<i32, u64> {
+        let mut lines = BTreeMap::new();
+        for (&(line, _), &runs) in &self.statements {
+            let count = lines.entry(line).or_insert(0);
+            *count = runs.max(*count);
+        }
+        lines
+    }
+
+    /// The source with each line's count in the margin, as gcov lays it
+    /// out: `#####` for a line never run, `-` for one with no statement.
+    pub fn listing(&self) -> String {
+        let lines = self.lines();
+        let mut out = String::new();
+        for (i, text) in self.source.lines().enumerate() {
+            let line = i as i32 + 1;
+            let count = match lines.get(&line) {
+                None => "-".to_owned(),
+                Some(0) => "#####".to_owned(),
+                Some(runs) => runs.to_string(),
+            };
+            out += &format!("{:>9}:{:>5}:{}\n", count, line, text);
+        }
+        out
+    }
+
+    /// An lcov tracefile for the source, naming it `path`.
+    pub fn lcov(&self, path: &str) -> String {
+        let lines = self.lines();
+        let mut out = format!("TN:\nSF:{}\n", path);
+        for (line, runs) in &lines {
+            out += &format!("DA:{},{}\n", line, runs);
+        }
+        let hit = lines.values().filter(|&&runs| runs > 0).count();
+        out += &format!("LF:{}\nLH:{}\nend_of_record\n", lines.len(), hit);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::Lox;
+
+    #[test]
+    fn test_counts_by_line() {
+        let source = "fun fib(n) {\n  if (n < 2) return n;\n  return fib(n - 1) + fib(n - 2);\n}\n\
+                      var f = fun () {\n  print \"never\";\n};\nprint fib(3);\n";
+        let mut coverage = Coverage::new(source);
+        let mut lox = Lox::default();
+        lox.set_output(std::io::sink());
+        lox.run_observed(source.to_owned(), |e| coverage.observe(e))
+            .unwrap();
+        assert_eq!(
+            coverage.listing(),
+            "        1:    1:fun fib(n) {\n\
+             \x20       5:    2:  if (n < 2) return n;\n\
+             \x20       2:    3:  return fib(n - 1) + fib(n - 2);\n\
+             \x20       -:    4:}\n\
+             \x20       1:    5:var f = fun () {\n\
+             \x20   #####:    6:  print \"never\";\n\
+             \x20       -:    7:};\n\
+             \x20       1:    8:print fib(3);\n"
+        );
+        assert_eq!(
+            coverage.lcov("fib.lox"),
+            "TN:\nSF:fib.lox\nDA:1,1\nDA:2,5\nDA:3,2\nDA:5,1\nDA:6,0\nDA:8,1\nLF:6\nLH:5\nend_of_record\n"
+        );
+    }
+}
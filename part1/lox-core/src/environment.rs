@@ -0,0 +1,291 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::default::Default;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::intern::Symbol;
+use crate::interpreter::{Hooks, Object};
+use crate::parser::Slot;
+use anyhow::Result;
+
+/// One level of variables, linked to the scope it is nested in. Scopes are
+/// shared, so a closure can keep the one it was created in alive. Locals
+/// live in slots numbered by the resolver in declaration order; only the
+/// global scope looks names up.
+#[derive(Default)]
+pub struct Scope {
+    slots: Vec<Object>,
+    globals: HashMap<Symbol, Object>,
+    parent: Option<Rc<RefCell<Scope>>>,
+    /// Left with `pop_scope` or `restore`; nothing may be defined in it since.
+    #[cfg(debug_assertions)]
+    exited: bool,
+}
+
+// Just the names: a scope can hold a closure that holds the scope.
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.parent {
+            Some(_) => write!(f, "<{} locals>", self.slots.len()),
+            None => f.debug_set().entries(self.globals.keys()).finish(),
+        }
+    }
+}
+
+impl Scope {
+    fn child(parent: Rc<RefCell<Scope>>) -> Rc<RefCell<Scope>> {
+        Rc::new(RefCell::new(Scope {
+            parent: Some(parent),
+            ..Default::default()
+        }))
+    }
+
+    /// The scope `depth` levels out from `scope`.
+    fn ancestor(scope: &Rc<RefCell<Scope>>, depth: usize) -> Rc<RefCell<Scope>> {
+        let mut scope = scope.clone();
+        for _ in 0..depth {
+            let parent = scope.borrow().parent.clone();
+            scope = parent.expect("slot deeper than the scope chain");
+        }
+        scope
+    }
+}
+
+pub struct Enviornment {
+    globals: Rc<RefCell<Scope>>,
+    current: Rc<RefCell<Scope>>,
+    /// Handlers registered with `onError` and `onExit`, kept for the session.
+    pub hooks: Hooks,
+    /// The script's command-line arguments, as `args()` gives them.
+    pub args: Vec<String>,
+    /// Scopes made current by `push_scope` or `enter`, innermost last, with
+    /// the call that must leave each: `pop_scope` or `restore`.
+    #[cfg(debug_assertions)]
+    entered: Vec<(Rc<RefCell<Scope>>, &'static str)>,
+}
+
+impl Default for Enviornment {
+    fn default() -> Self {
+        let globals = Rc::new(RefCell::new(Scope::default()));
+        Enviornment {
+            current: globals.clone(),
+            globals,
+            hooks: Hooks::default(),
+            args: vec![],
+            #[cfg(debug_assertions)]
+            entered: vec![],
+        }
+    }
+}
+
+impl Enviornment {
+    pub fn new() -> Self {
+        Enviornment {
+            ..Default::default()
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.current = Scope::child(self.current.clone());
+        self.entered("pop_scope");
+    }
+
+    pub fn pop_scope(&mut self) {
+        let parent = self.current.borrow().parent.clone();
+        let parent = parent.expect("popped the global scope");
+        self.exiting("pop_scope");
+        self.current = parent;
+    }
+
+    /// The innermost scope, for a closure to hold on to.
+    pub fn capture(&self) -> Rc<RefCell<Scope>> {
+        self.current.clone()
+    }
+
+    /// Make a new scope inside `parent` current, returning the scope it
+    /// replaces for `restore`.
+    pub fn enter(&mut self, parent: Rc<RefCell<Scope>>) -> Rc<RefCell<Scope>> {
+        let previous = std::mem::replace(&mut self.current, Scope::child(parent));
+        self.entered("restore");
+        previous
+    }
+
+    /// Go back to a scope returned by `enter`.
+    pub fn restore(&mut self, scope: Rc<RefCell<Scope>>) {
+        self.exiting("restore");
+        self.current = scope;
+    }
+
+    #[cfg(debug_assertions)]
+    fn entered(&mut self, exit: &'static str) {
+        self.entered.push((self.current.clone(), exit));
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn entered(&mut self, _exit: &'static str) {}
+
+    /// Check that `how` leaves the scope most recently entered, and the way
+    /// it was entered, and mark it exited.
+    #[cfg(debug_assertions)]
+    fn exiting(&mut self, how: &str) {
+        let innermost = self.entered.pop();
+        assert!(
+            innermost.is_some_and(|(s, exit)| exit == how && Rc::ptr_eq(&s, &self.current)),
+            "{} does not match the innermost push_scope or enter",
+            how
+        );
+        self.current.borrow_mut().exited = true;
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn exiting(&mut self, _how: &str) {}
+
+    /// Define `name` in the current scope: by name at the top level, else in
+    /// the next slot. Redefining a global replaces it; the resolver has
+    /// already rejected a local declared twice in one scope.
+    pub fn define(&mut self, name: Symbol, value: Object) {
+        let mut scope = self.current.borrow_mut();
+        #[cfg(debug_assertions)]
+        assert!(
+            !scope.exited,
+            "defined '{}' in a scope already exited",
+            name
+        );
+        match scope.parent {
+            Some(_) => scope.slots.push(value),
+            None => {
+                scope.globals.insert(name, value);
+            }
+        }
+    }
+
+    /// Assign to a local at `slot`, or the global `name` without one.
+    pub fn assign(&mut self, name: Symbol, slot: Option<Slot>, value: Object) -> Result<()> {
+        let scope = match slot {
+            Some(slot) => Scope::ancestor(&self.current, slot.depth),
+            None => self.globals.clone(),
+        };
+        let mut scope = scope.borrow_mut();
+        let v = match slot {
+            Some(slot) => scope.slots.get_mut(slot.index),
+            None => scope.globals.get_mut(&name),
+        };
+        match v {
+            Some(v) => {
+                *v = value;
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!(format!("Undefined variable '{}'.", name))),
+        }
+    }
+
+    /// Names and values defined in the outermost scope.
+    pub fn globals(&self) -> Vec<(Symbol, Object)> {
+        let globals = self.globals.borrow();
+        globals
+            .globals
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .collect()
+    }
+
+    /// Call `f` with the values in every enclosing scope, innermost first.
+    pub fn for_each_value(&self, mut f: impl FnMut(&Object)) {
+        let mut scope = Some(self.current.clone());
+        while let Some(s) = scope {
+            let s = s.borrow();
+            s.slots.iter().chain(s.globals.values()).for_each(&mut f);
+            scope = s.parent.clone();
+        }
+    }
+
+    /// The local at `slot`, or the global `name` without one.
+    pub fn get(&self, name: Symbol, slot: Option<Slot>) -> Result<Object> {
+        let v = match slot {
+            Some(slot) => Scope::ancestor(&self.current, slot.depth)
+                .borrow()
+                .slots
+                .get(slot.index)
+                .cloned(),
+            None => self.globals.borrow().globals.get(&name).cloned(),
+        };
+        v.ok_or_else(|| anyhow::anyhow!(format!("Undefined variable '{}'.", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(s: &str) -> Symbol {
+        Symbol::intern(s)
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let mut env = Enviornment::new();
+        env.define(name("g"), Object::Double(0.0));
+        for i in 0..10_000 {
+            env.push_scope();
+            env.define(name("x"), Object::Double(i as f64));
+        }
+        let outermost = Slot {
+            depth: 9_999,
+            index: 0,
+        };
+        assert_eq!(
+            env.get(name("x"), Some(outermost)).unwrap(),
+            Object::Double(0.0)
+        );
+        for _ in 0..10_000 {
+            env.pop_scope();
+        }
+        // Back at the top level, so this is a global.
+        env.define(name("h"), Object::Nil);
+        assert_eq!(env.globals().len(), 2);
+    }
+
+    #[test]
+    fn test_closure_scope_outlives_block() {
+        let mut env = Enviornment::new();
+        env.push_scope();
+        env.define(name("a"), Object::Double(1.0));
+        let closure = env.capture();
+        env.pop_scope();
+        let caller = env.enter(closure);
+        let a = Some(Slot { depth: 1, index: 0 });
+        env.assign(name("a"), a, Object::Double(2.0)).unwrap();
+        assert_eq!(env.get(name("a"), a).unwrap(), Object::Double(2.0));
+        env.restore(caller);
+    }
+
+    #[test]
+    #[should_panic(expected = "popped the global scope")]
+    fn test_pop_global_scope() {
+        Enviornment::new().pop_scope();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "restore does not match")]
+    fn test_restore_skips_pop() {
+        let mut env = Enviornment::new();
+        let caller = env.enter(env.capture());
+        env.push_scope();
+        env.restore(caller);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "defined 'late' in a scope already exited")]
+    fn test_define_after_exit() {
+        let mut env = Enviornment::new();
+        env.push_scope();
+        let block = env.capture();
+        env.pop_scope();
+        // Only `enter`, making a child, is a sound way back in.
+        env.current = block;
+        env.define(name("late"), Object::Nil);
+    }
+}
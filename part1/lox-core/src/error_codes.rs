@@ -0,0 +1,446 @@
+use std::fmt;
+
+/// A kind of diagnostic, with a code that stays the same across releases so
+/// it can be searched for and explained. `E0xxx` codes are found before the
+/// program runs (scanning, parsing, resolving), `E1xxx` while it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    UnterminatedString,
+    UnexpectedCharacter,
+    MisplacedDigitSeparator,
+    ExpectExpression,
+    ExpectName,
+    ExpectToken,
+    InvalidAssignmentTarget,
+    TooManyArguments,
+    AlreadyDeclared,
+    ReadInOwnInitializer,
+    TopLevelReturn,
+    OperandNotNumber,
+    OperandsNotNumbers,
+    UndefinedVariable,
+    OperandsNotAddable,
+    Uninitialized,
+    NotCallable,
+    WrongArity,
+    BadIndex,
+    IndexOutOfRange,
+    NotIndexable,
+    UndefinedProperty,
+    NotIterable,
+    OutOfMemory,
+    NonFiniteMath,
+    BadArgument,
+    StepLimit,
+    StackOverflow,
+    LoopLimit,
+    AllocationLimit,
+    TimeLimit,
+    UncaughtError,
+    AssertionFailed,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 33] = [
+        Self::UnterminatedString,
+        Self::UnexpectedCharacter,
+        Self::MisplacedDigitSeparator,
+        Self::ExpectExpression,
+        Self::ExpectName,
+        Self::ExpectToken,
+        Self::InvalidAssignmentTarget,
+        Self::TooManyArguments,
+        Self::AlreadyDeclared,
+        Self::ReadInOwnInitializer,
+        Self::TopLevelReturn,
+        Self::OperandNotNumber,
+        Self::OperandsNotNumbers,
+        Self::UndefinedVariable,
+        Self::OperandsNotAddable,
+        Self::Uninitialized,
+        Self::NotCallable,
+        Self::WrongArity,
+        Self::BadIndex,
+        Self::IndexOutOfRange,
+        Self::NotIndexable,
+        Self::UndefinedProperty,
+        Self::NotIterable,
+        Self::OutOfMemory,
+        Self::NonFiniteMath,
+        Self::BadArgument,
+        Self::StepLimit,
+        Self::StackOverflow,
+        Self::LoopLimit,
+        Self::AllocationLimit,
+        Self::TimeLimit,
+        Self::UncaughtError,
+        Self::AssertionFailed,
+    ];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::UnterminatedString => "E0001",
+            Self::UnexpectedCharacter => "E0002",
+            Self::MisplacedDigitSeparator => "E0003",
+            Self::ExpectExpression => "E0101",
+            Self::ExpectName => "E0102",
+            Self::ExpectToken => "E0103",
+            Self::InvalidAssignmentTarget => "E0104",
+            Self::TooManyArguments => "E0105",
+            Self::AlreadyDeclared => "E0201",
+            Self::ReadInOwnInitializer => "E0202",
+            Self::TopLevelReturn => "E0203",
+            Self::OperandNotNumber => "E1001",
+            Self::OperandsNotNumbers => "E1002",
+            Self::UndefinedVariable => "E1003",
+            Self::OperandsNotAddable => "E1004",
+            Self::Uninitialized => "E1005",
+            Self::NotCallable => "E1006",
+            Self::WrongArity => "E1007",
+            Self::BadIndex => "E1008",
+            Self::IndexOutOfRange => "E1009",
+            Self::NotIndexable => "E1010",
+            Self::UndefinedProperty => "E1011",
+            Self::NotIterable => "E1012",
+            Self::OutOfMemory => "E1013",
+            Self::NonFiniteMath => "E1014",
+            Self::BadArgument => "E1015",
+            Self::StepLimit => "E1016",
+            Self::StackOverflow => "E1017",
+            Self::LoopLimit => "E1018",
+            Self::AllocationLimit => "E1019",
+            Self::TimeLimit => "E1020",
+            Self::UncaughtError => "E1021",
+            Self::AssertionFailed => "E1022",
+        }
+    }
+
+    /// Look up a code such as `E1003`; case doesn't matter.
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.code().eq_ignore_ascii_case(code))
+    }
+
+    /// The kind of a diagnostic or runtime error with this message.
+    pub fn for_message(message: &str) -> Option<Self> {
+        let starts = |prefix: &str| message.starts_with(prefix);
+        Some(match message {
+            _ if starts("Unterminated string") => Self::UnterminatedString,
+            _ if starts("Unexpected character") => Self::UnexpectedCharacter,
+            _ if starts("Misplaced digit separator") => Self::MisplacedDigitSeparator,
+            _ if starts("Expect expression") => Self::ExpectExpression,
+            _ if starts("Expect ") && message.contains(" name") => Self::ExpectName,
+            _ if starts("Expect ") => Self::ExpectToken,
+            _ if starts("Invalid assignment target") => Self::InvalidAssignmentTarget,
+            _ if starts("Can't have more than") => Self::TooManyArguments,
+            _ if starts("Already a variable") => Self::AlreadyDeclared,
+            _ if starts("Can't read local variable") => Self::ReadInOwnInitializer,
+            _ if starts("Can't return from top-level code") => Self::TopLevelReturn,
+            _ if starts("Operand must be a number") => Self::OperandNotNumber,
+            _ if starts("Operands must be numbers") => Self::OperandsNotNumbers,
+            _ if starts("Undefined variable") => Self::UndefinedVariable,
+            _ if starts("Operands must be two numbers or two strings") => Self::OperandsNotAddable,
+            _ if starts("Variable '") && message.contains("not been initialized") => {
+                Self::Uninitialized
+            }
+            _ if starts("Can only call") => Self::NotCallable,
+            _ if starts("Expected ") && message.contains(" arguments but got ") => Self::WrongArity,
+            _ if starts("Index must be") => Self::BadIndex,
+            _ if starts("Index out of range") => Self::IndexOutOfRange,
+            _ if starts("Only lists and strings") || starts("Only list elements") => {
+                Self::NotIndexable
+            }
+            _ if starts("Undefined property") || starts("Only namespaces") => {
+                Self::UndefinedProperty
+            }
+            _ if starts("Can only iterate") => Self::NotIterable,
+            _ if starts("Out of memory") => Self::OutOfMemory,
+            // Before the rest, whatever the thrown value says.
+            _ if starts("Uncaught error") => Self::UncaughtError,
+            _ if starts("Assertion failed") => Self::AssertionFailed,
+            _ if message.contains(" produced ") => Self::NonFiniteMath,
+            _ if starts("Expected argument ") => Self::BadArgument,
+            _ if starts("Step limit exceeded") => Self::StepLimit,
+            _ if starts("Stack overflow") => Self::StackOverflow,
+            _ if starts("Loop limit exceeded") => Self::LoopLimit,
+            _ if starts("Allocation limit exceeded") => Self::AllocationLimit,
+            _ if starts("Time limit exceeded") => Self::TimeLimit,
+            _ => return None,
+        })
+    }
+
+    /// One line saying what went wrong.
+    pub fn summary(self) -> &'static str {
+        match self {
+            Self::UnterminatedString => "a string literal has no closing quote",
+            Self::UnexpectedCharacter => "a character that can't start any token",
+            Self::MisplacedDigitSeparator => "a `_` digit separator in the wrong place",
+            Self::ExpectExpression => "an expression was expected",
+            Self::ExpectName => "a name was expected",
+            Self::ExpectToken => "a required token is missing",
+            Self::InvalidAssignmentTarget => "the left side of `=` can't be assigned to",
+            Self::TooManyArguments => "more than 255 parameters or arguments",
+            Self::AlreadyDeclared => "a local variable is declared twice in one scope",
+            Self::ReadInOwnInitializer => "a local variable is read in its own initializer",
+            Self::TopLevelReturn => "`return` outside of any function",
+            Self::OperandNotNumber => "`-` applied to something other than a number",
+            Self::OperandsNotNumbers => "arithmetic or comparison on non-numbers",
+            Self::UndefinedVariable => "a variable is used without being declared",
+            Self::OperandsNotAddable => "`+` on values that can't be added",
+            Self::Uninitialized => "a variable is read before it is assigned (--strict-init)",
+            Self::NotCallable => "a call on something other than a function",
+            Self::WrongArity => "a function is called with the wrong number of arguments",
+            Self::BadIndex => "an index that isn't a whole, non-negative number",
+            Self::IndexOutOfRange => "an index past the end of a list or string",
+            Self::NotIndexable => "indexing or slicing something that isn't a list or string",
+            Self::UndefinedProperty => "a property that doesn't exist",
+            Self::NotIterable => "`for`-`in` over something that can't be iterated",
+            Self::OutOfMemory => "the program used more memory than --memory-limit allows",
+            Self::NonFiniteMath => "arithmetic on finite numbers gave infinity or NaN",
+            Self::BadArgument => "a builtin function got an argument of the wrong type",
+            Self::StepLimit => "the program ran more statements than --max-steps allows",
+            Self::StackOverflow => "calls nested deeper than --max-call-depth allows",
+            Self::LoopLimit => "loops ran more times than --max-loop-iterations allows",
+            Self::AllocationLimit => "the program made more objects than --max-allocations allows",
+            Self::TimeLimit => "the program ran for longer than --timeout-ms allows",
+            Self::UncaughtError => "a `throw` that no `catch` handled",
+            Self::AssertionFailed => "the condition given to `assert` was false or nil",
+        }
+    }
+
+    /// A longer description, with an example, for `explain`.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Self::UnterminatedString => {
+                "A string starts with `\"` and runs to the next `\"`, across lines if need be.\n\
+                 The file ended before the closing quote:\n\n    \
+                 print \"hello;\n\n\
+                 Close the string: `print \"hello\";`"
+            }
+            Self::UnexpectedCharacter => {
+                "The scanner found a character that no Lox token starts with:\n\n    \
+                 var a = 1 @ 2;\n\n\
+                 Remove it, or put it inside a string."
+            }
+            Self::MisplacedDigitSeparator => {
+                "With --digit-separators, `_` may only sit between two digits:\n\n    \
+                 print 1__000;\n    print 1000_;\n\n\
+                 Write `1_000` instead."
+            }
+            Self::ExpectExpression => {
+                "The parser needed a value here but found something else, often\n\
+                 because an operator has no right-hand side:\n\n    \
+                 print 1 +;\n\n\
+                 Finish the expression: `print 1 + 2;`"
+            }
+            Self::ExpectName => {
+                "A declaration needs a name, and reserved words can't be used as one:\n\n    \
+                 var class = 3;\n    fun while() {}\n\n\
+                 Pick a name that isn't a keyword."
+            }
+            Self::ExpectToken => {
+                "A piece of punctuation the grammar requires is missing, such as the\n\
+                 `;` ending a statement or the `)` closing a call:\n\n    \
+                 var a = 1\n    print a;\n\n\
+                 The error points at the token after the gap; add the missing one there."
+            }
+            Self::InvalidAssignmentTarget => {
+                "Only variables, list elements and indexes can be assigned to:\n\n    \
+                 1 = 2;\n    a + b = 3;\n\n\
+                 Assign to a variable instead: `a = 3;`"
+            }
+            Self::TooManyArguments => {
+                "A function can declare at most 255 parameters and a call can pass at\n\
+                 most 255 arguments. Pass a list instead of many separate values."
+            }
+            Self::AlreadyDeclared => {
+                "Inside a block, each name can be declared once:\n\n    \
+                 {\n      var a = 1;\n      var a = 2;\n    }\n\n\
+                 Assign instead (`a = 2;`) or use a new name. Globals may be redeclared."
+            }
+            Self::ReadInOwnInitializer => {
+                "A local variable isn't usable until its initializer finishes, so this\n\
+                 can't refer to an outer `a`:\n\n    \
+                 var a = 1;\n    {\n      var a = a + 1;\n    }\n\n\
+                 Give the inner variable a different name."
+            }
+            Self::TopLevelReturn => {
+                "`return` leaves a function, so it can only appear inside one:\n\n    \
+                 print 1;\n    return;\n\n\
+                 To stop a script early, call `exit(0)`."
+            }
+            Self::OperandNotNumber => {
+                "Negation only works on numbers:\n\n    \
+                 print -\"abc\";"
+            }
+            Self::OperandsNotNumbers => {
+                "`-`, `*`, `/`, `%` and the comparisons `<`, `<=`, `>`, `>=` only work on\n\
+                 numbers:\n\n    \
+                 print \"a\" < \"b\";"
+            }
+            Self::UndefinedVariable => {
+                "A variable was read or assigned before any `var` declared it:\n\n    \
+                 print count;\n    count = 1;\n\n\
+                 Declare it first: `var count = 1;`. A function body may use a global\n\
+                 declared later, as long as it is declared before the call."
+            }
+            Self::OperandsNotAddable => {
+                "`+` adds two numbers or joins two strings. With --strict-concat, a\n\
+                 string and a number can't be mixed:\n\n    \
+                 print \"total: \" + 3;\n\n\
+                 Convert explicitly, or leave --strict-concat off."
+            }
+            Self::Uninitialized => {
+                "With --strict-init, a variable declared without a value must be\n\
+                 assigned before it is read:\n\n    \
+                 var a;\n    print a;"
+            }
+            Self::NotCallable => {
+                "Only functions can be called:\n\n    \
+                 var a = 1;\n    a();"
+            }
+            Self::WrongArity => {
+                "A function must get exactly as many arguments as it has parameters:\n\n    \
+                 fun add(a, b) { return a + b; }\n    add(1, 2, 3);"
+            }
+            Self::BadIndex => {
+                "List and string indexes are whole numbers counted from 0:\n\n    \
+                 var l = [1, 2];\n    print l[-1];\n    print l[0.5];"
+            }
+            Self::IndexOutOfRange => {
+                "An index must be less than the length of the list or string:\n\n    \
+                 var l = [1, 2];\n    print l[2];"
+            }
+            Self::NotIndexable => {
+                "Only lists and strings can be indexed and sliced, and only list\n\
+                 elements can be assigned:\n\n    \
+                 var n = 12;\n    print n[0];\n    \"ab\"[0] = \"c\";"
+            }
+            Self::UndefinedProperty => {
+                "Properties exist only on namespaces made with `:load <file> as <name>`,\n\
+                 and only for the globals that file defined:\n\n    \
+                 :load lib.lox as lib\n    print lib.missing;"
+            }
+            Self::NotIterable => {
+                "`for (var x in ...)` works over lists, strings and iterators:\n\n    \
+                 for (var x in 3) print x;\n\n\
+                 Use `range(3)` to count."
+            }
+            Self::OutOfMemory => {
+                "The values held in variables took more bytes than --memory-limit\n\
+                 allows. Raise the limit or keep fewer values alive."
+            }
+            Self::NonFiniteMath => {
+                "With --strict-math, arithmetic on finite numbers that gives infinity\n\
+                 or NaN is an error (with --check-math, a warning):\n\n    \
+                 print 1 / 0;"
+            }
+            Self::BadArgument => {
+                "Builtins check their arguments, counted from 1, and name the one\n\
+                 that is wrong:\n\n    \
+                 print sqrt(\"4\");\n\n\
+                 Convert it first: `print sqrt(num(\"4\"));`"
+            }
+            Self::StepLimit => {
+                "With --max-steps, a program may only run that many statements, counting\n\
+                 each pass through a loop body again. Raise the limit, or look for a\n\
+                 loop that never ends:\n\n    \
+                 while (true) print 1;"
+            }
+            Self::StackOverflow => {
+                "Each call waits for the ones it makes to return, and only so many may be\n\
+                 waiting at once (--max-call-depth). Recursion that never reaches its base\n\
+                 case runs out:\n\n    \
+                 fun f(n) { return f(n + 1); }\n    f(0);\n\n\
+                 Check the base case, or rewrite deep recursion as a loop."
+            }
+            Self::LoopLimit => {
+                "With --max-loop-iterations, the bodies of all the program's `while`,\n\
+                 `for` and `for`-`in` loops may only run that many times between them.\n\
+                 Raise the limit, or look for a loop whose condition never turns false:\n\n    \
+                 for (var i = 0; i < 10; i = i - 1) print i;"
+            }
+            Self::AllocationLimit => {
+                "With --max-allocations, a program may only make that many lists,\n\
+                 strings and functions, counting those builtins return, whether or not\n\
+                 they are still in use:\n\n    \
+                 var s = \"\";\n    while (true) s = s + \"x\";"
+            }
+            Self::TimeLimit => {
+                "With --timeout-ms, the program is stopped at the first statement it\n\
+                 starts after that many milliseconds. A single long call to a builtin\n\
+                 is not cut short."
+            }
+            Self::UncaughtError => {
+                "A value thrown with `throw` unwinds calls until a `try` around it\n\
+                 catches it. With none, the program stops:\n\n    \
+                 fun check(n) {\n      if (n < 0) throw \"negative\";\n    }\n    check(-1);\n\n\
+                 Wrap the call: `try { check(-1); } catch (e) { print e; }`. A `catch`\n\
+                 also gets the message of any runtime error, though not of the limits\n\
+                 set by flags such as --max-steps."
+            }
+            Self::AssertionFailed => {
+                "`assert(condition, message)` stops the program when the condition is\n\
+                 false or nil, even inside a function, with the message if given:\n\n    \
+                 var total = 1 + 1;\n    assert(total == 3, \"total is \" + str(total));\n\n\
+                 Fix the code the assertion checks, or the assertion."
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_unique() {
+        for (i, a) in ErrorCode::ALL.iter().enumerate() {
+            assert_eq!(ErrorCode::parse(a.code()), Some(*a));
+            assert!(ErrorCode::ALL[i + 1..].iter().all(|b| a.code() != b.code()));
+        }
+        assert_eq!(
+            ErrorCode::parse("e1003"),
+            Some(ErrorCode::UndefinedVariable)
+        );
+        assert_eq!(ErrorCode::parse("E9999"), None);
+    }
+
+    #[test]
+    fn test_for_message() {
+        let cases = [
+            ("Unterminated string.", "E0001"),
+            ("Expect expression.", "E0101"),
+            ("Expect variable name.", "E0102"),
+            ("Expect ';' after value.", "E0103"),
+            ("Expect ')' after arguments.", "E0103"),
+            ("Already a variable with this name in this scope.", "E0201"),
+            ("Can't return from top-level code.", "E0203"),
+            ("Undefined variable 'a'", "E1003"),
+            ("Expected 2 arguments but got 3", "E1007"),
+            ("1 / 0 produced inf", "E1014"),
+            ("Loop limit exceeded (5 iterations)", "E1018"),
+            ("Uncaught error: 1 / 0 produced inf", "E1021"),
+            ("Assertion failed: sorted", "E1022"),
+            (
+                "Expected argument 1 of sqrt to be a number, got string",
+                "E1015",
+            ),
+        ];
+        for (message, code) in cases {
+            assert_eq!(
+                ErrorCode::for_message(message).map(ErrorCode::code),
+                Some(code),
+                "{}",
+                message
+            );
+        }
+    }
+}
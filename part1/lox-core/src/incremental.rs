@@ -0,0 +1,577 @@
+use crate::lox_error::{Diagnostic, Diagnostics, LoxError};
+use crate::parser::{Expr, Parser, Stmt};
+use crate::resolver::Resolver;
+use crate::scanner::{self, ScanFrom, ScanOptions};
+use crate::tokens::{Span, Token};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// A change to a source: the bytes `range` replaced with `text`.
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+/// What an edit scanned and parsed again, as indices after it: the rest of
+/// the tokens and declarations were only moved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Changed {
+    pub tokens: Range<usize>,
+    pub declarations: Range<usize>,
+}
+
+/// A top-level declaration: the tokens it was parsed from, and what came
+/// of them.
+#[derive(Debug)]
+pub struct Declaration {
+    pub tokens: Range<usize>,
+    /// `None` if it didn't parse.
+    pub stmt: Option<Stmt>,
+    /// From parsing it, and resolving it if that went well.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Source kept scanned, parsed and resolved as it is edited, for editors
+/// that want feedback on every keystroke. An edit is scanned again from the
+/// token it touches until the new tokens line up with the old ones, and
+/// only the top-level declarations with changed tokens are parsed again.
+/// The tokens and declarations after them keep their work, moved to where
+/// the edit left them.
+///
+/// Declarations parse and resolve independently: the resolver gives
+/// globals no slots, and the parser starts each one afresh, so the result
+/// is what parsing the whole text again would give.
+pub struct ParsedSource {
+    text: String,
+    /// Ending with `EOF`; empty if an unterminated string stopped the scan.
+    tokens: Vec<Token>,
+    /// In source order.
+    scan_diagnostics: Vec<Diagnostic>,
+    declarations: Vec<Declaration>,
+}
+
+/// Diagnostics reported to a parser, which can be taken away between
+/// declarations while the parser still holds it.
+#[derive(Clone, Default)]
+struct Reported(Rc<RefCell<Vec<Diagnostic>>>);
+
+impl LoxError for Reported {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.0.borrow_mut().push(diagnostic);
+    }
+
+    fn has_error(&self) -> bool {
+        self.0.borrow().iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Parse and resolve the declarations in `tokens` starting at index `from`,
+/// until the end or `stop` accepts the index of the next one.
+fn parse_declarations(
+    tokens: &[Token],
+    from: usize,
+    mut stop: impl FnMut(usize) -> bool,
+) -> Vec<Declaration> {
+    let reported = Reported::default();
+    let mut sink = reported.clone();
+    let mut rest = tokens[from..].iter().peekable();
+    let mut parser = Parser::new(&mut rest, &mut sink);
+    let mut declarations = vec![];
+    loop {
+        let start = tokens.len() - parser.remaining();
+        if stop(start) {
+            break;
+        }
+        let Some(stmt) = parser.next_declaration() else {
+            break;
+        };
+        let mut stmt = stmt.ok();
+        let mut diagnostics = reported.0.take();
+        if let Some(stmt) = stmt
+            .as_mut()
+            .filter(|_| !diagnostics.iter().any(Diagnostic::is_error))
+        {
+            let mut resolved = Diagnostics::default();
+            Resolver::new(&mut resolved).resolve(std::slice::from_mut(stmt));
+            diagnostics.extend(resolved.0);
+        }
+        declarations.push(Declaration {
+            tokens: start..tokens.len() - parser.remaining(),
+            stmt,
+            diagnostics,
+        });
+    }
+    declarations
+}
+
+/// How far the text after an edit moved.
+#[derive(Debug, Clone, Copy)]
+struct Shift {
+    bytes: isize,
+    lines: i32,
+}
+
+/// A node of a parse tree being moved.
+enum Node<'a> {
+    Stmt(&'a mut Stmt),
+    Expr(&'a mut Expr),
+}
+
+impl Shift {
+    fn offset(self, offset: &mut usize) {
+        *offset = (*offset as isize + self.bytes) as usize;
+    }
+
+    fn span(self, span: &mut Span) {
+        self.offset(&mut span.start);
+        self.offset(&mut span.end);
+        span.line += self.lines;
+    }
+
+    fn range(self, range: &mut Range<usize>) {
+        self.offset(&mut range.start);
+        self.offset(&mut range.end);
+    }
+
+    fn diagnostic(self, d: &mut Diagnostic) {
+        self.range(&mut d.span);
+        d.line += self.lines;
+        for context in &mut d.context {
+            self.range(&mut context.span);
+            context.line += self.lines;
+        }
+    }
+
+    /// Move every span in `stmt`, from a stack rather than by recursion.
+    fn stmt(self, stmt: &mut Stmt) {
+        let mut work = vec![Node::Stmt(stmt)];
+        while let Some(node) = work.pop() {
+            match node {
+                Node::Stmt(stmt) => match stmt {
+                    Stmt::Expr(e) | Stmt::Print(e) => work.push(Node::Expr(e)),
+                    Stmt::Var(name, init) => {
+                        self.span(&mut name.span);
+                        work.extend(init.as_mut().map(Node::Expr));
+                    }
+                    Stmt::Block(stmts) => work.extend(stmts.iter_mut().map(Node::Stmt)),
+                    Stmt::If(c, t, e) => {
+                        work.extend([Node::Expr(c), Node::Stmt(t)]);
+                        work.extend(e.as_deref_mut().map(Node::Stmt));
+                    }
+                    Stmt::While(c, body) => work.extend([Node::Expr(c), Node::Stmt(body)]),
+                    Stmt::ForIn(name, iterable, body) => {
+                        self.span(&mut name.span);
+                        work.extend([Node::Expr(iterable), Node::Stmt(body)]);
+                    }
+                    Stmt::Function(name, params, body) => {
+                        self.span(&mut name.span);
+                        params.iter_mut().for_each(|p| self.span(&mut p.span));
+                        work.push(Node::Stmt(body));
+                    }
+                    Stmt::Return(keyword, v) => {
+                        self.span(keyword);
+                        work.extend(v.as_mut().map(Node::Expr));
+                    }
+                    Stmt::Throw(keyword, v) => {
+                        self.span(keyword);
+                        work.push(Node::Expr(v));
+                    }
+                    Stmt::Try(keyword, body, name, handler) => {
+                        self.span(keyword);
+                        self.span(&mut name.span);
+                        work.extend([Node::Stmt(body), Node::Stmt(handler)]);
+                    }
+                },
+                Node::Expr(Expr::Lambda(keyword, params, body)) => {
+                    self.span(keyword);
+                    params.iter_mut().for_each(|p| self.span(&mut p.span));
+                    work.push(Node::Stmt(body));
+                }
+                Node::Expr(expr) => {
+                    match expr {
+                        Expr::Binary(_, op, _) | Expr::Unary(op, _) | Expr::Logical(_, op, _) => {
+                            self.span(&mut op.span)
+                        }
+                        Expr::Variable(n) | Expr::Assign(n, _) | Expr::Get(_, n) => {
+                            self.span(&mut n.span)
+                        }
+                        Expr::Literal(_, s)
+                        | Expr::List(s, _)
+                        | Expr::Index(_, s, _)
+                        | Expr::IndexSet(_, s, _, _)
+                        | Expr::Slice(_, s, _, _)
+                        | Expr::Call(_, s, _) => self.span(s),
+                        Expr::Grouping(_) | Expr::Ternary(..) | Expr::Lambda(..) => {}
+                    }
+                    work.extend(expr.children_mut().into_iter().map(Node::Expr));
+                }
+            }
+        }
+    }
+}
+
+impl ParsedSource {
+    pub fn new(text: String) -> Self {
+        let mut source = Self {
+            text,
+            tokens: vec![],
+            scan_diagnostics: vec![],
+            declarations: vec![],
+        };
+        source.redo();
+        source
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn declarations(&self) -> &[Declaration] {
+        &self.declarations
+    }
+
+    /// The top-level statements that parsed, in order.
+    pub fn statements(&self) -> impl Iterator<Item = &Stmt> {
+        self.declarations.iter().filter_map(|d| d.stmt.as_ref())
+    }
+
+    /// Scan errors, then what each declaration found, in order.
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.scan_diagnostics
+            .iter()
+            .chain(self.declarations.iter().flat_map(|d| &d.diagnostics))
+    }
+
+    pub fn has_error(&self) -> bool {
+        self.diagnostics().any(Diagnostic::is_error)
+    }
+
+    /// Scan and parse the whole text.
+    fn redo(&mut self) -> Changed {
+        let mut diagnostics = Diagnostics::default();
+        self.tokens = scanner::scan_tokens(&mut diagnostics, &self.text).unwrap_or_default();
+        self.scan_diagnostics = diagnostics.0;
+        self.declarations = parse_declarations(&self.tokens, 0, |_| false);
+        Changed {
+            tokens: 0..self.tokens.len(),
+            declarations: 0..self.declarations.len(),
+        }
+    }
+
+    /// Apply `edit`, whose range must be within the text and on character
+    /// boundaries, and bring the tokens and declarations up to date.
+    pub fn edit(&mut self, edit: Edit) -> Changed {
+        let newlines = |s: &str| s.matches('\n').count() as i32;
+        let shift = Shift {
+            bytes: edit.text.len() as isize - edit.range.len() as isize,
+            lines: newlines(&edit.text) - newlines(&self.text[edit.range.clone()]),
+        };
+        self.text.replace_range(edit.range.clone(), &edit.text);
+        // After an unterminated string, there are no tokens to keep.
+        if self.tokens.is_empty() {
+            return self.redo();
+        }
+        match self.rescan(&edit, shift) {
+            Some((old, new)) => {
+                let declarations = self.reparse(old, new.clone(), shift);
+                Changed {
+                    tokens: new,
+                    declarations,
+                }
+            }
+            None => self.redo(),
+        }
+    }
+
+    /// Scan the text around `edit` again, splicing the new tokens in. Gives
+    /// the range of old tokens replaced and that of the new ones, or `None`
+    /// if the scan was stopped by an unterminated string.
+    fn rescan(&mut self, edit: &Edit, shift: Shift) -> Option<(Range<usize>, Range<usize>)> {
+        let eof = self.tokens.len() - 1;
+        // Tokens ending before the edit can't change; one it touches, like
+        // a name being typed, can. Start after the last unchanged one so a
+        // comment the edit is in is scanned again too.
+        let first = self.tokens[..eof].partition_point(|t| t.span.end < edit.range.start);
+        let from = match first.checked_sub(1).map(|i| &self.tokens[i]) {
+            Some(before) => ScanFrom {
+                pos: before.span.end,
+                line: before.span.line,
+                line_start: self.text[..before.span.end]
+                    .rfind('\n')
+                    .map_or(0, |i| i + 1),
+            },
+            None => ScanFrom {
+                pos: 0,
+                line: 1,
+                line_start: 0,
+            },
+        };
+        // Old tokens can be kept from the first line wholly after the edit,
+        // where their columns are the same.
+        let edit_end = edit.range.start + edit.text.len();
+        let settled = self.text[edit_end..].find('\n').map(|i| edit_end + i + 1);
+        let old_tokens = &self.tokens;
+        let mut resync = None;
+        let mut diagnostics = Diagnostics::default();
+        let (mut scanned, line) = scanner::scan_from(
+            &mut diagnostics,
+            &self.text,
+            &ScanOptions::default(),
+            from,
+            |t| {
+                if settled.is_none_or(|settled| t.span.start < settled) {
+                    return false;
+                }
+                let old_start = (t.span.start as isize - shift.bytes) as usize;
+                let j =
+                    first + old_tokens[first..eof].partition_point(|o| o.span.start < old_start);
+                let same = j < eof
+                    && old_tokens[j].span.start == old_start
+                    && old_tokens[j].token_type == t.token_type;
+                if same {
+                    resync = Some(j);
+                }
+                same
+            },
+        )
+        .ok()?;
+        let (old, resumed) = match resync {
+            Some(j) => {
+                // That token is the old one, moved.
+                scanned.pop();
+                (first..j, self.tokens[j].span.start)
+            }
+            None => {
+                scanned.push(scanner::eof(&self.text, line));
+                (first..self.tokens.len(), usize::MAX)
+            }
+        };
+        let new = first..first + scanned.len();
+        self.tokens.splice(old.clone(), scanned);
+        for t in &mut self.tokens[new.end..] {
+            shift.span(&mut t.span);
+        }
+
+        // Scan errors in the text scanned again are replaced with the new
+        // ones, and those after it moved.
+        let removed = from.pos..resumed;
+        self.scan_diagnostics
+            .retain(|d| !removed.contains(&d.span.start));
+        let at = self
+            .scan_diagnostics
+            .partition_point(|d| d.span.start < removed.start);
+        for d in &mut self.scan_diagnostics[at..] {
+            shift.diagnostic(d);
+        }
+        self.scan_diagnostics.splice(at..at, diagnostics.0);
+        Some((old, new))
+    }
+
+    /// Parse the declarations with tokens in `old` again, now that they are
+    /// `new`. Gives the range of declarations replaced.
+    fn reparse(&mut self, old: Range<usize>, new: Range<usize>, shift: Shift) -> Range<usize> {
+        // The declaration before the first changed token may have looked
+        // at it, as an `if` looks for an `else`, so it is parsed again too.
+        let first = self
+            .declarations
+            .partition_point(|d| d.tokens.end < old.start);
+        let from = self.declarations.get(first).map_or(0, |d| d.tokens.start);
+        let old_declarations = &self.declarations;
+        let mut resync = None;
+        let reparsed = parse_declarations(&self.tokens, from, |start| {
+            // Stop where an old declaration after the changed tokens began.
+            if start < new.end {
+                return false;
+            }
+            let old_start = start - new.end + old.end;
+            let k =
+                first + old_declarations[first..].partition_point(|d| d.tokens.start < old_start);
+            let same = old_declarations
+                .get(k)
+                .is_some_and(|d| d.tokens.start == old_start);
+            if same {
+                resync = Some(k);
+            }
+            same
+        });
+        let end = resync.unwrap_or(self.declarations.len());
+        let changed = first..first + reparsed.len();
+        self.declarations.splice(first..end, reparsed);
+        for d in &mut self.declarations[changed.end..] {
+            d.tokens = d.tokens.start - old.end + new.end..d.tokens.end - old.end + new.end;
+            if let Some(stmt) = &mut d.stmt {
+                shift.stmt(stmt);
+            }
+            for diagnostic in &mut d.diagnostics {
+                shift.diagnostic(diagnostic);
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Everything the source holds, spans included, to compare.
+    fn dump(source: &ParsedSource) -> String {
+        let tokens: Vec<String> = source.tokens().iter().map(|t| format!("{:?}", t)).collect();
+        let declarations: Vec<String> = source
+            .declarations()
+            .iter()
+            .map(|d| format!("{:?}", d))
+            .collect();
+        let diagnostics: Vec<String> = source
+            .scan_diagnostics
+            .iter()
+            .map(|d| format!("{:?}", d))
+            .collect();
+        format!(
+            "{}\n{}\n{}",
+            tokens.join("\n"),
+            declarations.join("\n"),
+            diagnostics.join("\n")
+        )
+    }
+
+    /// Apply the edit replacing the first `old` after `after` with `new`,
+    /// and check the result is what parsing from scratch gives.
+    fn edit(source: &mut ParsedSource, after: &str, old: &str, new: &str) -> Changed {
+        let at = source.text().find(after).unwrap();
+        let start = at + source.text()[at..].find(old).unwrap();
+        let changed = source.edit(Edit {
+            range: start..start + old.len(),
+            text: new.to_owned(),
+        });
+        let fresh = ParsedSource::new(source.text().to_owned());
+        assert_eq!(
+            dump(source),
+            dump(&fresh),
+            "after editing to:\n{}",
+            source.text()
+        );
+        changed
+    }
+
+    #[test]
+    fn test_edits_match_full_parse() {
+        let mut source = ParsedSource::new(
+            "var a = 1;\nfun f(x) {\n  if (x) print x;\n  return a;\n}\n\
+             // f(2);\nprint \"two\nlines\";\nvar b = [1, 2];\n"
+                .to_owned(),
+        );
+        assert!(!source.has_error());
+        edit(&mut source, "var a", " a", " ab");
+        edit(&mut source, "return", "a;", "ab;");
+        // Parsing the `if` again sees the new `else`.
+        edit(&mut source, "print x;", "\n", " else print -x;\n");
+        edit(&mut source, "// f", "// ", "");
+        edit(&mut source, "f(2)", ";", "");
+        assert!(source.has_error());
+        edit(&mut source, "f(2)", "\n", ";\n");
+        // An unterminated string, then mended.
+        edit(&mut source, "\"two", "\"", "");
+        assert!(source.tokens().is_empty());
+        edit(&mut source, "print two", "two", "\"two");
+        edit(&mut source, "var b", "[1, 2]", "[1,\n\n 2]");
+        edit(&mut source, "var b", "\n", "@");
+        edit(&mut source, "fun f", "fun f(x) {\n  if", "if");
+        edit(&mut source, "var ab", "var ab = 1;\n", "");
+        let text = source.text().to_owned();
+        edit(&mut source, "", &text, "");
+        edit(&mut source, "", "", "print 1;");
+    }
+
+    #[test]
+    fn test_random_edits_match_full_parse() {
+        let mut source = ParsedSource::new(
+            "fun fib(n) {\n  if (n < 2) return n; else {\n    return fib(n - 1) + fib(n - 2);\n  }\n}\n\
+             var xs = [1, 2, 3]; // numbers\nfor (var x in xs) print fib(x);\n\
+             print \"a\nb\"; var f = fun (a) { return a * 2; };\nwhile (false) print xs[0:1];\n"
+                .to_owned(),
+        );
+        // Bits of Lox, and of broken Lox, to type in.
+        let snippets = [
+            "",
+            "x",
+            "1",
+            ";",
+            "\n",
+            " ",
+            "{",
+            "}",
+            "(",
+            ")",
+            "\"",
+            "//",
+            "else",
+            "var y = 2;\n",
+            "fun",
+            "print",
+            "@",
+            "return",
+            "[",
+            "]",
+        ];
+        // A fixed linear congruential generator, so failures repeat.
+        let mut seed: u64 = 1;
+        let mut next = |n: usize| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+        for _ in 0..300 {
+            let len = source.text().len();
+            let start = next(len + 1);
+            let end = (start + next(4)).min(len);
+            let text = snippets[next(snippets.len())].to_owned();
+            source.edit(Edit {
+                range: start..end,
+                text,
+            });
+            let fresh = ParsedSource::new(source.text().to_owned());
+            assert_eq!(
+                dump(&source),
+                dump(&fresh),
+                "after editing to:\n{}",
+                source.text()
+            );
+        }
+    }
+
+    #[test]
+    fn test_only_the_edited_declaration_is_parsed() {
+        let mut source = ParsedSource::new("var a = 1;\nvar b = 2;\nvar c = 3;\n".to_owned());
+        // The rest of the line is scanned again, but only `var b` parsed.
+        let changed = edit(&mut source, "var b", "2", "20");
+        assert_eq!(changed.tokens, 8..10);
+        assert_eq!(changed.declarations, 1..2);
+        let changed = edit(&mut source, "var c", "3", "30 + 1");
+        assert_eq!(changed.tokens, 13..18);
+        assert_eq!(changed.declarations, 2..3);
+        // `var a` ends just before the edit, so it is parsed again in case
+        // it looked ahead at what changed.
+        let changed = edit(&mut source, "var b", "", "print 0;\n");
+        assert_eq!(changed.tokens, 5..13);
+        assert_eq!(changed.declarations, 0..3);
+        assert_eq!(
+            source
+                .statements()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>(),
+            crate::parse(&crate::Scanner::scan(source.text()).unwrap())
+                .unwrap()
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,1949 @@
+use crate::environment::{Enviornment, Scope};
+use crate::error_codes::ErrorCode;
+use crate::intern::Symbol;
+use crate::iterator::{iterate, LoxIteratorWrapper};
+use crate::lox_error::{Diagnostic, LoxCompileError, LoxError};
+use crate::observer::{Event, Observer};
+use crate::parser::{self, Expr, Ident, Literal, OpKind, Operator, Stmt, StmtVisitor};
+use crate::profiler::Profiler;
+use crate::recorder::{self, ExecutionRecorder};
+use crate::scanner::Scanner;
+use crate::stdlib;
+use crate::tokens::{json_string, Span};
+use anyhow::Result;
+use anyhow::{anyhow, Context};
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::{Debug, Display};
+use std::io::{self, Write};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use take_until::TakeUntilExt;
+
+#[derive(Debug, Clone)]
+pub enum Object {
+    Nil,
+    /// Value of a variable declared without an initializer; reads see `nil`
+    /// unless the interpreter is strict about initialization.
+    Undefined,
+    Boolean(bool),
+    Double(f64),
+    String(String),
+    Callable(LoxCallableWrapper),
+    Iterator(LoxIteratorWrapper),
+    List(Rc<RefCell<Vec<Object>>>),
+    Namespace(Rc<LoxNamespace>),
+    Return(Box<Object>),
+}
+
+/// A named bundle of values, e.g. the definitions of a file loaded into the
+/// REPL with `:load file as name`. Members are read with `name.member`.
+#[derive(Debug)]
+pub struct LoxNamespace {
+    pub name: String,
+    pub members: HashMap<String, Object>,
+}
+
+// Lists are reference values: equal only to themselves, which also keeps
+// comparison of self-containing lists from recursing forever.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Nil, Self::Nil) => true,
+            (Self::Undefined, Self::Undefined) => true,
+            (Self::Boolean(l), Self::Boolean(r)) => l == r,
+            (Self::Double(l), Self::Double(r)) => l == r,
+            (Self::String(l), Self::String(r)) => l == r,
+            (Self::Callable(l), Self::Callable(r)) => l == r,
+            (Self::Iterator(l), Self::Iterator(r)) => l == r,
+            (Self::List(l), Self::List(r)) => Rc::ptr_eq(l, r),
+            (Self::Namespace(l), Self::Namespace(r)) => Rc::ptr_eq(l, r),
+            (Self::Return(l), Self::Return(r)) => l == r,
+            _ => false,
+        }
+    }
+}
+
+/// Hashable view of an `Object`, for natives that key caches or tables on
+/// Lox values. Callables have no key.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub enum ObjectKey {
+    Nil,
+    Boolean(bool),
+    Double(u64),
+    String(String),
+}
+
+impl Object {
+    /// The kind of value, as named in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Nil | Self::Undefined => "nil",
+            Self::Boolean(_) => "boolean",
+            Self::Double(_) => "number",
+            Self::String(_) => "string",
+            Self::Callable(_) => "function",
+            Self::Iterator(_) => "iterator",
+            Self::List(_) => "list",
+            Self::Namespace(_) => "namespace",
+            Self::Return(o) => o.type_name(),
+        }
+    }
+
+    pub fn hash_key(&self) -> Option<ObjectKey> {
+        match self {
+            Self::Nil | Self::Undefined => Some(ObjectKey::Nil),
+            Self::Boolean(b) => Some(ObjectKey::Boolean(*b)),
+            // Normalize -0.0 so it keys the same as 0.0, matching `==`.
+            Self::Double(d) => Some(ObjectKey::Double((d + 0.0).to_bits())),
+            Self::String(s) => Some(ObjectKey::String(s.clone())),
+            Self::Callable(_)
+            | Self::Iterator(_)
+            | Self::List(_)
+            | Self::Namespace(_)
+            | Self::Return(_) => None,
+        }
+    }
+}
+
+// This wrapper is just here so I can get around being able to derive PartialEq on the enum while ignoring (always false) Callables
+#[derive(Debug, Clone)]
+pub struct LoxCallableWrapper {
+    inner: Rc<dyn LoxCallable>,
+}
+impl LoxCallableWrapper {
+    pub fn new(inner: impl LoxCallable + 'static) -> Self {
+        Self {
+            inner: Rc::new(inner),
+        }
+    }
+}
+impl LoxCallable for LoxCallableWrapper {
+    /// Every call goes through here, whether from a call expression or a
+    /// native such as `sort` calling back, so arity is checked here too.
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        if let Some(arity) = self.inner.arity().filter(|a| *a != args.len()) {
+            return Err(anyhow!(NativeError(format!(
+                "Expected {} arguments but got {}",
+                arity,
+                args.len()
+            ))));
+        }
+        let Some(profiler) = i.profiler.clone() else {
+            return self.inner.call(i, args);
+        };
+        profiler
+            .borrow_mut()
+            .enter(self.inner.name(), self.inner.line());
+        let rv = self.inner.call(i, args);
+        profiler.borrow_mut().exit();
+        rv
+    }
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+    fn arity(&self) -> Option<usize> {
+        self.inner.arity()
+    }
+    fn line(&self) -> Option<i32> {
+        self.inner.line()
+    }
+}
+
+pub trait LoxCallable: Debug {
+    /// Natives fail with a `NativeError`, which the call reports as a
+    /// runtime error at the call site.
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object>;
+    fn name(&self) -> String;
+    /// Number of parameters, `None` for variadic natives.
+    fn arity(&self) -> Option<usize> {
+        None
+    }
+    /// Declaration line; natives have no source location.
+    fn line(&self) -> Option<i32> {
+        None
+    }
+}
+/// Callables are equal only to themselves, as in jlox: copies of a wrapper
+/// share the callable, while each evaluation of a declaration makes a new one.
+impl PartialEq for LoxCallableWrapper {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+#[derive(Debug)]
+struct LoxFunction {
+    /// `None` for lambdas.
+    name: Option<Ident>,
+    /// The name, or a lambda's `fun` keyword.
+    declaration: Span,
+    params: Vec<Ident>,
+    body: Stmt,
+    /// The scope the function was declared in.
+    closure: Rc<RefCell<Scope>>,
+}
+impl LoxCallable for LoxFunction {
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        if i.call_stack.len() >= i.max_call_depth {
+            let span = Span {
+                line: i.line,
+                ..Default::default()
+            };
+            return Err(anyhow!(LoxStackOverflow {}))
+                .context(LoxRuntimeError::new(span, "Stack overflow"));
+        }
+        i.call_stack.push(CallFrame {
+            name: self.name(),
+            line: i.line,
+        });
+        if let Some(observe) = i.observer.as_mut() {
+            observe(&Event::FunctionEntered {
+                name: &self.name(),
+                args: &args,
+                span: self.declaration,
+            });
+        }
+        let res = {
+            let mut scope = EnvGuard::call(i, self.closure.clone());
+            std::iter::zip(&self.params, &args)
+                .for_each(|(p, a)| scope.env.define(p.name, a.clone()));
+            scope.execute(&self.body)
+        };
+        // Made before leaving the frame, so its trace starts at the `throw`.
+        let res = match res {
+            Ok(StmtResult::Thrown(value)) => Err(i.uncaught(value)),
+            res => res,
+        };
+        if let Some(frame) = i.call_stack.pop() {
+            i.line = frame.line;
+        }
+
+        // Errors go on to the caller, as in jlox, gathering the trace.
+        res.map(|r| match r {
+            StmtResult::Return(r) => r,
+            _ => Object::Nil,
+        })
+    }
+    fn name(&self) -> String {
+        match &self.name {
+            Some(name) => name.to_string(),
+            None => "lambda".to_owned(),
+        }
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(self.params.len())
+    }
+    fn line(&self) -> Option<i32> {
+        Some(self.declaration.line)
+    }
+}
+
+/// What `Interpreter::evaluate` has left to do. Steps other than `Eval`
+/// and `Push` take the values of sub-expressions already evaluated from the
+/// top of its value stack.
+enum Work<'e> {
+    /// Evaluate an expression, leaving its value on the stack.
+    Eval(&'e Expr),
+    Push(Object),
+    Binary(&'e Operator),
+    Unary(&'e Operator),
+    Assign(&'e Ident),
+    /// Keep the left operand of `and` or `or` if it settles the result, or
+    /// else evaluate the right one.
+    Logical(&'e Operator, &'e Expr),
+    /// Evaluate one branch or the other, given the condition.
+    Ternary(&'e Expr, &'e Expr),
+    /// Call the callee under this many arguments.
+    Call(Span, usize),
+    List(Span, usize),
+    /// Check the object can be indexed before evaluating the index.
+    IndexTarget(Span, &'e Expr),
+    Index(Span),
+    /// Check the object is a list, then the index, before evaluating the
+    /// value to store.
+    SetTarget(Span, &'e Expr, &'e Expr),
+    SetIndex(Span, &'e Expr),
+    Set(usize),
+    /// Check the object can be sliced, then each bound as it is evaluated,
+    /// clamping it to the length.
+    SliceTarget(Span, Option<&'e Expr>, Option<&'e Expr>),
+    Bound(Span, usize),
+    Slice(Span),
+    Get(&'e Ident),
+}
+
+#[derive(Debug, Clone)]
+enum StmtResult {
+    Noop,
+    Return(Object),
+    /// A `throw` leaving the statements of a function or `try` block.
+    Thrown(Object),
+}
+
+/// A native function's complaint about how it was called, such as "Expected
+/// argument 1 of sqrt to be a number, got string". The call expression turns
+/// it into a `LoxRuntimeError` at its parenthesis.
+#[derive(Debug)]
+pub struct NativeError(pub String);
+impl Display for NativeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.", self.0)
+    }
+}
+
+/// Standard output was closed under the program, as when it is piped into
+/// `head`, so nothing more it prints can be seen.
+#[derive(Debug)]
+pub struct LoxOutputClosed {}
+impl Display for LoxOutputClosed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Output closed.")
+    }
+}
+
+/// The program called `exit(status)`.
+#[derive(Debug)]
+pub struct LoxExit(pub i32);
+impl Display for LoxExit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Exited with status {}.", self.0)
+    }
+}
+
+/// The `--entry` function is missing or can't be called with the script's
+/// arguments.
+#[derive(Debug)]
+pub struct LoxEntryError(pub String);
+impl Display for LoxEntryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.", self.0)
+    }
+}
+
+/// Lets another thread, such as a Ctrl-C handler, stop a running program.
+/// The interpreter checks it before each statement. It stays cancelled, so
+/// that natives which swallow errors can't carry on, until `reset`.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Let the next program run.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+/// A call went deeper than the interpreter's call depth limit.
+#[derive(Debug)]
+pub struct LoxStackOverflow {}
+impl Display for LoxStackOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Stack overflow.")
+    }
+}
+
+/// Execution was stopped from outside with a `CancelToken`. Like `LoxExit`,
+/// it unwinds every frame.
+#[derive(Debug)]
+pub struct LoxInterrupted {}
+impl Display for LoxInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Interrupted.")
+    }
+}
+
+/// An `assert` failed, as told apart from other runtime errors.
+#[derive(Debug)]
+pub struct LoxAssertionFailed {}
+impl Display for LoxAssertionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Assertion failed.")
+    }
+}
+
+/// A `throw` that left a function without being caught. The interpreter
+/// keeps the thrown value, which can't be sent with it.
+#[derive(Debug)]
+pub struct LoxThrown {}
+impl Display for LoxThrown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Uncaught error.")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoxRuntimeError {
+    span: Span,
+    message: String,
+    /// The calls in progress, innermost first, as `[line N] in name`.
+    /// Filled in by the statement the error stops.
+    trace: Vec<String>,
+}
+impl LoxRuntimeError {
+    /// The error for what went wrong at `span`, with the book's message
+    /// less its final period, as in "Undefined variable 'a'".
+    fn new(span: Span, message: impl Into<String>) -> Self {
+        LoxRuntimeError {
+            span,
+            message: message.into(),
+            trace: vec![],
+        }
+    }
+
+    /// `new` as an error to return, whose root cause reads the same.
+    fn at(span: Span, message: impl Into<String>) -> anyhow::Error {
+        let e = Self::new(span, message);
+        anyhow!("{}", e.message()).context(e)
+    }
+
+    /// What went wrong, as reported: "Undefined variable 'a'."
+    pub fn message(&self) -> String {
+        format!("{}.", self.message)
+    }
+
+    /// Where it went wrong: the operator, name or call that failed.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Where each call in progress was, innermost first and ending `in
+    /// script`, as jlox prints them.
+    pub fn trace(&self) -> &[String] {
+        &self.trace
+    }
+
+    pub fn code(&self) -> Option<ErrorCode> {
+        ErrorCode::for_message(&self.message)
+    }
+
+    /// One JSON object, for tools reading `--diagnostics-json`.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"kind\":\"runtime\",\"code\":{},\"line\":{},\"column\":{},\"start\":{},\"end\":{},\"message\":{}}}",
+            self.code().map_or("null".to_owned(), |c| json_string(c.code())),
+            self.span.line,
+            self.span.column,
+            self.span.start,
+            self.span.end,
+            json_string(&format!("{}.", self.message))
+        )
+    }
+}
+impl Display for LoxRuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.", self.message)?;
+        if self.trace.is_empty() {
+            return write!(f, "\n[line {}]", self.span.line);
+        }
+        // Runaway recursion would otherwise print a frame per call.
+        let mut frames = self.trace.iter().peekable();
+        while let Some(frame) = frames.next() {
+            write!(f, "\n{}", frame)?;
+            let mut repeats = 0;
+            while frames.next_if_eq(&frame).is_some() {
+                repeats += 1;
+            }
+            match repeats {
+                0 => {}
+                1 | 2 => (0..repeats).try_for_each(|_| write!(f, "\n{}", frame))?,
+                _ => write!(f, "\n[previous line repeated {} more times]", repeats)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Boolean(b) => write!(f, "{}", b),
+            Self::Double(d) => write!(f, "{}", format_number(*d)),
+            Self::String(s) => write!(f, "{}", s),
+            Self::Callable(c) if c.line().is_some() => write!(f, "<fn {}>", c.name()),
+            Self::Callable(_) => write!(f, "<native fn>"),
+            Self::Iterator(_) => write!(f, "<iterator>"),
+            Self::Namespace(ns) => write!(f, "<namespace {}>", ns.name),
+            Self::List(l) => fmt_list(f, l, &mut vec![]),
+            Self::Return(o) => write!(f, "...returning {}...", o),
+            Self::Nil | Self::Undefined => write!(f, "nil"),
+        }
+    }
+}
+
+/// Format a number the way jlox does: Java's `Double.toString`, with a
+/// trailing `.0` dropped so integers print bare.
+pub fn format_number(d: f64) -> String {
+    if d.is_nan() {
+        return "NaN".to_owned();
+    }
+    if d.is_infinite() {
+        return if d > 0.0 { "Infinity" } else { "-Infinity" }.to_owned();
+    }
+    let text = if d == 0.0 || (1e-3..1e7).contains(&d.abs()) {
+        format!("{:?}", d)
+    } else {
+        // Java switches to `1.5E-5` / `1.0E21` outside [1e-3, 1e7).
+        let sci = format!("{:e}", d);
+        let (mantissa, exp) = sci.split_once('e').unwrap();
+        if mantissa.contains('.') {
+            format!("{}E{}", mantissa, exp)
+        } else {
+            format!("{}.0E{}", mantissa, exp)
+        }
+    };
+    match text.strip_suffix(".0") {
+        Some(int) => int.to_owned(),
+        None => text,
+    }
+}
+
+/// Write `[a, b, c]`, printing lists that contain themselves as `[...]`.
+fn fmt_list(
+    f: &mut fmt::Formatter<'_>,
+    list: &Rc<RefCell<Vec<Object>>>,
+    seen: &mut Vec<*const RefCell<Vec<Object>>>,
+) -> fmt::Result {
+    if seen.contains(&Rc::as_ptr(list)) {
+        return write!(f, "[...]");
+    }
+    seen.push(Rc::as_ptr(list));
+    write!(f, "[")?;
+    for (idx, item) in list.borrow().iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
+        }
+        match item {
+            Object::List(l) => fmt_list(f, l, seen)?,
+            o => write!(f, "{}", o)?,
+        }
+    }
+    seen.pop();
+    write!(f, "]")
+}
+
+pub fn truthy(o: &Object) -> bool {
+    match o {
+        Object::Nil | Object::Undefined => false,
+        Object::Boolean(x) => *x,
+        _ => true,
+    }
+}
+
+/// Rough heap bytes owned by `o`. Lists in `seen` were already counted.
+fn heap_size(o: &Object, seen: &mut HashSet<*const RefCell<Vec<Object>>>) -> usize {
+    match o {
+        Object::String(s) => s.capacity(),
+        Object::List(l) if !seen.insert(Rc::as_ptr(l)) => 0,
+        Object::List(l) => {
+            let l = l.borrow();
+            l.capacity() * mem::size_of::<Object>()
+                + l.iter().map(|o| heap_size(o, seen)).sum::<usize>()
+        }
+        Object::Namespace(ns) => ns
+            .members
+            .iter()
+            .map(|(k, v)| k.capacity() + mem::size_of::<Object>() + heap_size(v, seen))
+            .sum(),
+        Object::Return(o) => heap_size(o, seen),
+        _ => 0,
+    }
+}
+
+/// Total order over all values, used by `compare` and `sort`. Values of
+/// different kinds order as nil < booleans < numbers < strings < lists <
+/// functions < iterators < namespaces. Within a kind: false < true, numbers
+/// by value with NaN last, strings by character, lists element-wise. Two
+/// functions, iterators or namespaces always compare equal.
+pub fn compare(a: &Object, b: &Object) -> cmp::Ordering {
+    compare_inner(a, b, &mut vec![])
+}
+
+fn compare_inner(a: &Object, b: &Object, seen: &mut Vec<(*const (), *const ())>) -> cmp::Ordering {
+    fn rank(o: &Object) -> u8 {
+        match o {
+            Object::Nil | Object::Undefined => 0,
+            Object::Boolean(_) => 1,
+            Object::Double(_) => 2,
+            Object::String(_) => 3,
+            Object::List(_) => 4,
+            Object::Callable(_) => 5,
+            Object::Iterator(_) => 6,
+            Object::Namespace(_) => 7,
+            Object::Return(o) => rank(o),
+        }
+    }
+    match (a, b) {
+        (Object::Return(a), b) => compare_inner(a, b, seen),
+        (a, Object::Return(b)) => compare_inner(a, b, seen),
+        (Object::Boolean(l), Object::Boolean(r)) => l.cmp(r),
+        (Object::Double(l), Object::Double(r)) => match (l.is_nan(), r.is_nan()) {
+            (false, false) => l.partial_cmp(r).unwrap_or(cmp::Ordering::Equal),
+            (l, r) => l.cmp(&r),
+        },
+        (Object::String(l), Object::String(r)) => l.cmp(r),
+        (Object::List(l), Object::List(r)) => {
+            // Lists reached again while comparing them are cycles; call
+            // those equal rather than recursing forever.
+            let pair = (Rc::as_ptr(l) as *const (), Rc::as_ptr(r) as *const ());
+            if Rc::ptr_eq(l, r) || seen.contains(&pair) {
+                return cmp::Ordering::Equal;
+            }
+            seen.push(pair);
+            let (l, r) = (l.borrow(), r.borrow());
+            let ord = std::iter::zip(l.iter(), r.iter())
+                .map(|(a, b)| compare_inner(a, b, seen))
+                .find(|o| o.is_ne())
+                .unwrap_or_else(|| l.len().cmp(&r.len()));
+            seen.pop();
+            ord
+        }
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// What to do when arithmetic on finite numbers gives infinity or NaN.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MathCheck {
+    Off,
+    Warn,
+    Error,
+}
+
+/// An active Lox function call; `line` is the line it was called from.
+#[derive(Debug, Clone)]
+pub struct CallFrame {
+    pub name: String,
+    pub line: i32,
+}
+
+/// Lox functions a script registered to be told about runtime events.
+#[derive(Debug, Default)]
+pub struct Hooks {
+    /// Called with the message and line of an error that stopped the program.
+    pub on_error: Vec<LoxCallableWrapper>,
+    /// Called with the exit status once the program is done.
+    pub on_exit: Vec<LoxCallableWrapper>,
+}
+
+/// A scope made current for as long as the guard lives. Dropping it makes
+/// the scope that was current before current again, however the code using
+/// it returns. Derefs to the interpreter, which it borrows meanwhile.
+struct EnvGuard<'i, 'a> {
+    interpreter: &'i mut Interpreter<'a>,
+    /// For a call, the caller's scope; `None` for a block.
+    caller: Option<Rc<RefCell<Scope>>>,
+}
+
+impl<'i, 'a> EnvGuard<'i, 'a> {
+    /// A block's scope, nested in the current one.
+    fn block(interpreter: &'i mut Interpreter<'a>) -> Self {
+        interpreter.env.push_scope();
+        Self {
+            interpreter,
+            caller: None,
+        }
+    }
+
+    /// A call's scope, nested in the scope the function was declared in.
+    fn call(interpreter: &'i mut Interpreter<'a>, closure: Rc<RefCell<Scope>>) -> Self {
+        let caller = Some(interpreter.env.enter(closure));
+        Self {
+            interpreter,
+            caller,
+        }
+    }
+}
+
+impl<'a> Deref for EnvGuard<'_, 'a> {
+    type Target = Interpreter<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        self.interpreter
+    }
+}
+
+impl DerefMut for EnvGuard<'_, '_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.interpreter
+    }
+}
+
+impl Drop for EnvGuard<'_, '_> {
+    fn drop(&mut self) {
+        match self.caller.take() {
+            Some(caller) => self.interpreter.env.restore(caller),
+            None => self.interpreter.env.pop_scope(),
+        }
+    }
+}
+
+pub struct Interpreter<'a> {
+    pub env: &'a mut Enviornment,
+    pub call_stack: Vec<CallFrame>,
+    /// Line of the most recently evaluated call expression.
+    line: i32,
+    recorder: Option<ExecutionRecorder>,
+    observer: Option<Observer<'a>>,
+    profiler: Option<Rc<RefCell<Profiler>>>,
+    cancel: Option<CancelToken>,
+    /// Where `print` writes; stdout when `None`.
+    output: Option<Rc<RefCell<dyn Write>>>,
+    /// Where warnings such as `--check-math`'s go; stderr if unset.
+    warnings: Option<Rc<RefCell<dyn LoxError>>>,
+    /// Set by the first `print` to find stdout closed. Some natives, such as
+    /// `onError` handlers, drop errors, so every later statement fails too.
+    output_closed: bool,
+    /// Set by `exit()`, and sticky for the same reason.
+    exit_status: Option<i32>,
+    /// Statements executed so far, and how many may be.
+    steps: u64,
+    step_limit: Option<u64>,
+    /// Passes through loop bodies so far, and how many may be made.
+    iterations: u64,
+    loop_limit: Option<u64>,
+    /// Lists, strings and functions made so far, and how many may be.
+    allocations: u64,
+    allocation_limit: Option<u64>,
+    /// When the program must have finished by, and the limit it came from.
+    deadline: Option<(Instant, Duration)>,
+    strict_concat: bool,
+    strict_init: bool,
+    math_check: MathCheck,
+    memory_limit: Option<usize>,
+    max_call_depth: usize,
+    /// Where the last `throw` was, and the value of one on its way out of
+    /// calls as a `LoxThrown` error.
+    thrown_at: Span,
+    thrown: Option<Object>,
+}
+
+/// How many Lox calls may be in progress at once unless
+/// `set_max_call_depth` says otherwise. Each one takes several Rust frames,
+/// so this stays well inside a debug build's 2 MiB thread stack.
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 200;
+
+impl<'a> Interpreter<'a> {
+    // pub fn new() -> Self {
+    //     Interpreter {
+    //         env: &mut Enviornment::new(),
+    //     }
+    // }
+    pub fn new_with_env(env: &'a mut Enviornment) -> Self {
+        stdlib::install(env);
+        Interpreter {
+            env,
+            call_stack: vec![],
+            line: 0,
+            recorder: None,
+            observer: None,
+            profiler: None,
+            cancel: None,
+            output: None,
+            warnings: None,
+            output_closed: false,
+            exit_status: None,
+            steps: 0,
+            step_limit: None,
+            iterations: 0,
+            loop_limit: None,
+            allocations: 0,
+            allocation_limit: None,
+            deadline: None,
+            strict_concat: false,
+            strict_init: false,
+            math_check: MathCheck::Off,
+            memory_limit: None,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            thrown_at: Span::default(),
+            thrown: None,
+        }
+    }
+
+    /// Reject `"str" + 1` instead of stringifying the number.
+    pub fn set_strict_concat(&mut self, strict: bool) {
+        self.strict_concat = strict;
+    }
+
+    /// Make reading a variable declared without an initializer an error
+    /// instead of giving `nil`.
+    pub fn set_strict_init(&mut self, strict: bool) {
+        self.strict_init = strict;
+    }
+
+    pub fn set_math_check(&mut self, check: MathCheck) {
+        self.math_check = check;
+    }
+
+    /// Fail with an out-of-memory runtime error once the values held in the
+    /// environment take more than roughly `limit` bytes.
+    pub fn set_memory_limit(&mut self, limit: Option<usize>) {
+        self.memory_limit = limit;
+    }
+
+    /// Fail with a runtime error once more than `limit` statements have run,
+    /// so a program that never finishes can still be checked.
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.step_limit = limit;
+    }
+
+    /// Fail with a runtime error once loop bodies have run more than `limit`
+    /// times in all.
+    pub fn set_loop_limit(&mut self, limit: Option<u64>) {
+        self.loop_limit = limit;
+    }
+
+    /// Fail with a runtime error once the program has made more than `limit`
+    /// lists, strings and functions, counting those builtins return.
+    pub fn set_allocation_limit(&mut self, limit: Option<u64>) {
+        self.allocation_limit = limit;
+    }
+
+    /// Fail with a runtime error at the first statement run more than
+    /// `limit` after this is called.
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.deadline = limit.map(|limit| (Instant::now() + limit, limit));
+    }
+
+    /// Fail with a "Stack overflow." runtime error when a call would make
+    /// more than `depth` in progress. Set too high, deep recursion overflows
+    /// the Rust stack instead.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// Send what `print` writes to `output` instead of stdout.
+    pub fn set_output(&mut self, output: Rc<RefCell<dyn Write>>) {
+        self.output = Some(output);
+    }
+
+    /// Report warnings to `sink` instead of printing them to stderr.
+    pub fn set_warnings(&mut self, sink: Rc<RefCell<dyn LoxError>>) {
+        self.warnings = Some(sink);
+    }
+
+    /// Approximate heap bytes held by variables in every scope.
+    pub fn heap_size(&self) -> usize {
+        let mut seen = HashSet::new();
+        let mut size = 0;
+        self.env.for_each_value(|o| {
+            size += mem::size_of::<Symbol>() + mem::size_of::<Object>() + heap_size(o, &mut seen)
+        });
+        size
+    }
+
+    fn check_memory(&self, line: i32) -> Result<()> {
+        match self.memory_limit {
+            Some(limit) if self.heap_size() > limit => Err(Self::limit_error(
+                line,
+                format!("Out of memory (limit {} bytes)", limit),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The runtime error for running past one of the limits set on the
+    /// interpreter.
+    fn limit_error(line: i32, message: String) -> anyhow::Error {
+        let span = Span {
+            line,
+            ..Default::default()
+        };
+        LoxRuntimeError::at(span, message)
+    }
+
+    /// Count a pass through a loop body against the loop limit.
+    fn count_iteration(&mut self, line: i32) -> Result<()> {
+        self.iterations += 1;
+        match self.loop_limit.filter(|limit| self.iterations > *limit) {
+            Some(limit) => Err(Self::limit_error(
+                line,
+                format!("Loop limit exceeded ({} iterations)", limit),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Count `value` against the allocation limit if it is a new list,
+    /// string or function, and give it back.
+    fn allocated(&mut self, value: Object, line: i32) -> Result<Object> {
+        if !matches!(
+            value,
+            Object::String(_) | Object::List(_) | Object::Callable(_) | Object::Iterator(_)
+        ) {
+            return Ok(value);
+        }
+        self.allocations += 1;
+        match self
+            .allocation_limit
+            .filter(|limit| self.allocations > *limit)
+        {
+            Some(limit) => Err(Self::limit_error(
+                line,
+                format!("Allocation limit exceeded ({} objects)", limit),
+            )),
+            None => Ok(value),
+        }
+    }
+
+    /// Stop with a `LoxInterrupted` error at the next statement once
+    /// `token` is cancelled.
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Log every executed statement, with the variables it touched, to `recorder`.
+    pub fn set_recorder(&mut self, recorder: ExecutionRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    /// Count and time every call in `profiler`.
+    pub fn set_profiler(&mut self, profiler: Rc<RefCell<Profiler>>) {
+        self.profiler = Some(profiler);
+    }
+
+    /// Tell `observer` about each statement executed, function entered and
+    /// variable defined.
+    pub fn set_observer(&mut self, observer: Observer<'a>) {
+        self.observer = Some(observer);
+    }
+
+    /// Stop the program with exit `status`. Every statement from here on
+    /// fails with `LoxExit` too, for natives that don't pass on the errors
+    /// of the functions they call.
+    pub fn exit_with(&mut self, status: i32) -> Result<Object> {
+        self.exit_status = Some(status);
+        Err(anyhow!(LoxExit(status)))
+    }
+
+    /// Fail an `assert` with `message`, as a runtime error at the line of
+    /// the call.
+    pub fn assertion_failed(&self, message: String) -> Result<Object> {
+        let span = Span {
+            line: self.line,
+            ..Default::default()
+        };
+        Err(anyhow!(LoxAssertionFailed {})).context(LoxRuntimeError::new(span, message))
+    }
+
+    /// Frames from innermost to outermost, formatted as `[line N] in name`.
+    pub fn stack_trace(&self) -> Vec<String> {
+        self.trace_at(self.line)
+    }
+
+    /// The stack trace with the innermost frame at `line`.
+    fn trace_at(&self, line: i32) -> Vec<String> {
+        let lines = std::iter::once(line).chain(self.call_stack.iter().rev().map(|f| f.line));
+        let names = self
+            .call_stack
+            .iter()
+            .rev()
+            .map(|f| f.name.as_str())
+            .chain(std::iter::once("script"));
+        std::iter::zip(lines, names)
+            .map(|(line, name)| format!("[line {}] in {}", line, name))
+            .collect()
+    }
+    fn unary(op: &Operator, right: Object) -> Result<Object> {
+        match (op.kind, right) {
+            (OpKind::Negate, Object::Double(x)) => Ok(Object::Double(-x)),
+            (OpKind::Not, o) => Ok(Object::Boolean(!truthy(&o))),
+            _ => Err(LoxRuntimeError::at(op.span, "Operand must be a number")),
+        }
+    }
+
+    /// Wrap the result `v` of arithmetic on `l` and `r`, first reporting it
+    /// per `math_check` if finite operands gave infinity or NaN.
+    fn checked_math(&self, op: &Operator, l: f64, r: f64, v: f64) -> Result<Object> {
+        if self.math_check == MathCheck::Off || v.is_finite() || !l.is_finite() || !r.is_finite() {
+            return Ok(Object::Double(v));
+        }
+        let message = format!(
+            "{} {} {} produced {}",
+            format_number(l),
+            op.kind,
+            format_number(r),
+            format_number(v)
+        );
+        if self.math_check == MathCheck::Error {
+            return Err(LoxRuntimeError::at(op.span, message));
+        }
+        let span = op.span;
+        let warning = Diagnostic::new(
+            span.line,
+            span.column,
+            span.start..span.end,
+            &format!("{}.", message),
+        )
+        .into_warning();
+        match &self.warnings {
+            Some(sink) => sink.borrow_mut().report(warning),
+            None => eprintln!("{}", warning),
+        }
+        Ok(Object::Double(v))
+    }
+
+    fn binary(&self, left: Object, t: &Operator, right: Object) -> Result<Object> {
+        match (left, t.kind, right) {
+            (Object::String(l), OpKind::Add, Object::String(r)) => {
+                Ok(Object::String(format!("{}{}", l, r)))
+            }
+            (Object::String(l), OpKind::Add, r @ Object::Double(_)) if !self.strict_concat => {
+                Ok(Object::String(format!("{}{}", l, r)))
+            }
+            (l @ Object::Double(_), OpKind::Add, Object::String(r)) if !self.strict_concat => {
+                Ok(Object::String(format!("{}{}", l, r)))
+            }
+            (Object::Double(l), OpKind::Add, Object::Double(r)) => {
+                self.checked_math(t, l, r, l + r)
+            }
+            (Object::Double(l), OpKind::Subtract, Object::Double(r)) => {
+                self.checked_math(t, l, r, l - r)
+            }
+            (Object::Double(l), OpKind::Multiply, Object::Double(r)) => {
+                self.checked_math(t, l, r, l * r)
+            }
+            (Object::Double(l), OpKind::Divide, Object::Double(r)) => {
+                self.checked_math(t, l, r, l / r)
+            }
+            (Object::Double(l), OpKind::Remainder, Object::Double(r)) => {
+                self.checked_math(t, l, r, l % r)
+            }
+            // Truncated like `%`, so `a ~/ b * b + a % b` is `a`.
+            (Object::Double(l), OpKind::IntDivide, Object::Double(r)) => {
+                self.checked_math(t, l, r, (l / r).trunc())
+            }
+
+            (Object::Double(l), OpKind::Less, Object::Double(r)) => Ok(Object::Boolean(l < r)),
+            (Object::Double(l), OpKind::LessEqual, Object::Double(r)) => {
+                Ok(Object::Boolean(l <= r))
+            }
+            (Object::Double(l), OpKind::Greater, Object::Double(r)) => Ok(Object::Boolean(l > r)),
+            (Object::Double(l), OpKind::GreaterEqual, Object::Double(r)) => {
+                Ok(Object::Boolean(l >= r))
+            }
+
+            (_, OpKind::Comma, r) => Ok(r),
+
+            (l, OpKind::Equal, r) => Ok(Object::Boolean(l == r)),
+            (l, OpKind::NotEqual, r) => Ok(Object::Boolean(l != r)),
+
+            (_, tt, _) => {
+                let message = match tt {
+                    OpKind::Add => "Operands must be two numbers or two strings",
+                    _ => "Operands must be numbers",
+                };
+                Err(LoxRuntimeError::at(t.span, message))
+            }
+        }
+    }
+
+    fn literal(l: &Literal) -> Object {
+        match l {
+            Literal::Nil => Object::Nil,
+            Literal::Boolean(b) => Object::Boolean(*b),
+            Literal::Number(n) => Object::Double(*n),
+            Literal::String(s) => Object::String(s.to_string()),
+        }
+    }
+
+    fn variable(&mut self, n: &Ident) -> Result<Object> {
+        let name = n.name;
+        // FIXME: handle unseen symbol WRT unwarp
+        let v = self
+            .env
+            .get(name, n.slot)
+            .map_err(|_| LoxRuntimeError::at(n.span, format!("Undefined variable '{}'", name)))?;
+        let v = match v {
+            Object::Undefined if self.strict_init => {
+                return Err(LoxRuntimeError::at(
+                    n.span,
+                    format!("Variable '{}' has not been initialized", name),
+                ));
+            }
+            Object::Undefined => Object::Nil,
+            v => v,
+        };
+        if let Some(rec) = self.recorder.as_mut() {
+            rec.read(name.as_str(), &v);
+        }
+        Ok(v)
+    }
+
+    fn assign(&mut self, n: &Ident, val: Object) -> Result<Object> {
+        let name = n.name;
+        self.env
+            .assign(name, n.slot, val)
+            .map_err(|_| LoxRuntimeError::at(n.span, format!("Undefined variable '{}'", name)))?;
+        let v = self.env.get(name, n.slot)?;
+        if let Some(rec) = self.recorder.as_mut() {
+            rec.write(name.as_str(), &v);
+        }
+        Ok(v)
+    }
+
+    fn call(&mut self, callee: Object, paren: Span, arguments: Vec<Object>) -> Result<Object> {
+        self.line = paren.line;
+
+        let c = match callee {
+            Object::Callable(c) => c,
+            _ => {
+                return Err(Self::call_error(
+                    &paren,
+                    "Can only call functions and classes",
+                ))
+            }
+        };
+        let value = c
+            .call(self, arguments)
+            .map_err(|e| match e.downcast::<NativeError>() {
+                Ok(NativeError(message)) => Self::call_error(&paren, &message),
+                Err(e) => e,
+            })?;
+        // Lox functions return what they made already; natives are
+        // counted here.
+        match c.line() {
+            None => self.allocated(value, paren.line),
+            Some(_) => Ok(value),
+        }
+    }
+
+    fn lambda(&mut self, fun: Span, params: &[Ident], body: &Stmt) -> Object {
+        Object::Callable(LoxCallableWrapper::new(LoxFunction {
+            name: None,
+            declaration: fun,
+            params: params.to_vec(),
+            body: body.clone(),
+            closure: self.env.capture(),
+        }))
+    }
+
+    fn get(object: Object, name: &Ident) -> Result<Object> {
+        let member = name.name.as_str();
+        match object {
+            Object::Namespace(ns) => match ns.members.get(member) {
+                Some(Object::Undefined) => Ok(Object::Nil),
+                Some(v) => Ok(v.clone()),
+                None => Err(Self::index_error(
+                    &name.span,
+                    &format!("Undefined property '{}'", member),
+                )),
+            },
+            _ => Err(Self::index_error(
+                &name.span,
+                "Only namespaces have properties",
+            )),
+        }
+    }
+
+    fn index_error(bracket: &Span, message: &str) -> anyhow::Error {
+        LoxRuntimeError::at(*bracket, message)
+    }
+
+    fn call_error(paren: &Span, message: &str) -> anyhow::Error {
+        LoxRuntimeError::at(*paren, message)
+    }
+
+    /// Check an index is a whole number below `len`.
+    fn index_value(index: Object, bracket: &Span, len: usize) -> Result<usize> {
+        match index {
+            Object::Double(d) if d >= 0.0 && d.fract() == 0.0 => {
+                if (d as usize) < len {
+                    Ok(d as usize)
+                } else {
+                    Err(Self::index_error(bracket, "Index out of range"))
+                }
+            }
+            _ => Err(Self::index_error(
+                bracket,
+                "Index must be a non-negative integer",
+            )),
+        }
+    }
+
+    /// Index a list or string, which `Work::IndexTarget` has checked it is.
+    fn index(object: Object, bracket: &Span, index: Object) -> Result<Object> {
+        match object {
+            Object::List(l) => {
+                let len = l.borrow().len();
+                let idx = Self::index_value(index, bracket, len)?;
+                let v = l.borrow()[idx].clone();
+                Ok(v)
+            }
+            Object::String(s) => {
+                let idx = Self::index_value(index, bracket, s.chars().count())?;
+                Ok(Object::String(s.chars().skip(idx).take(1).collect()))
+            }
+            _ => unreachable!("checked by IndexTarget"),
+        }
+    }
+
+    /// The length of a list or string to slice.
+    fn slice_len(object: &Object, bracket: &Span) -> Result<usize> {
+        match object {
+            Object::List(l) => Ok(l.borrow().len()),
+            Object::String(s) => Ok(s.chars().count()),
+            _ => Err(Self::index_error(
+                bracket,
+                "Only lists and strings can be sliced",
+            )),
+        }
+    }
+
+    /// Slice bound: a whole number, clamped to `len`.
+    fn slice_bound(bound: Object, bracket: &Span, len: usize) -> Result<usize> {
+        match bound {
+            Object::Double(d) if d >= 0.0 && d.fract() == 0.0 => Ok((d as usize).min(len)),
+            _ => Err(Self::index_error(
+                bracket,
+                "Slice bounds must be non-negative integers",
+            )),
+        }
+    }
+
+    fn slice(object: Object, start: usize, end: usize) -> Object {
+        let end = end.max(start);
+        match object {
+            Object::List(l) => {
+                let items = l.borrow()[start..end].to_vec();
+                Object::List(Rc::new(RefCell::new(items)))
+            }
+            Object::String(s) => Object::String(s.chars().skip(start).take(end - start).collect()),
+            _ => unreachable!("checked by slice_len"),
+        }
+    }
+
+    /// Start on `expr`: give its value if it has no sub-expressions, or else
+    /// queue them on `work`, along with what to do with their values.
+    fn expand<'e>(&mut self, expr: &'e Expr, work: &mut Vec<Work<'e>>) -> Result<Option<Object>> {
+        // The work stack runs last pushed first.
+        match expr {
+            Expr::Literal(l, _) => return Ok(Some(Self::literal(l))),
+            Expr::Variable(n) => return self.variable(n).map(Some),
+            Expr::Lambda(fun, params, body) => {
+                let lambda = self.lambda(*fun, params, body);
+                return self.allocated(lambda, fun.line).map(Some);
+            }
+            Expr::Binary(l, op, r) => work.extend([Work::Binary(op), Work::Eval(r), Work::Eval(l)]),
+            Expr::Unary(op, e) => work.extend([Work::Unary(op), Work::Eval(e)]),
+            Expr::Grouping(e) => work.push(Work::Eval(e)),
+            Expr::Assign(n, value) => work.extend([Work::Assign(n), Work::Eval(value)]),
+            Expr::Logical(l, op, r) => work.extend([Work::Logical(op, r), Work::Eval(l)]),
+            Expr::Ternary(c, t, e) => work.extend([Work::Ternary(t, e), Work::Eval(c)]),
+            Expr::Call(callee, paren, args) => {
+                work.push(Work::Call(*paren, args.len()));
+                work.extend(args.iter().rev().map(Work::Eval));
+                work.push(Work::Eval(callee));
+            }
+            Expr::List(bracket, items) => {
+                work.push(Work::List(*bracket, items.len()));
+                work.extend(items.iter().rev().map(Work::Eval));
+            }
+            Expr::Index(object, bracket, index) => {
+                work.extend([Work::IndexTarget(*bracket, index), Work::Eval(object)])
+            }
+            Expr::IndexSet(object, bracket, index, value) => {
+                work.extend([Work::SetTarget(*bracket, index, value), Work::Eval(object)])
+            }
+            Expr::Slice(object, bracket, start, end) => work.extend([
+                Work::SliceTarget(*bracket, start.as_deref(), end.as_deref()),
+                Work::Eval(object),
+            ]),
+            Expr::Get(object, name) => work.extend([Work::Get(name), Work::Eval(object)]),
+        }
+        Ok(None)
+    }
+
+    /// Evaluate `expr` from a stack of pending work rather than by
+    /// recursion, so that nesting as deep as a 100,000-term `+` chain can't
+    /// overflow the Rust stack. Only calls to Lox functions recurse, as far
+    /// as `set_max_call_depth` allows.
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Object> {
+        let mut work = vec![];
+        // A variable or literal is done without allocating either stack.
+        if let Some(value) = self.expand(expr, &mut work)? {
+            return Ok(value);
+        }
+        let mut values: Vec<Object> = vec![];
+        let pop = |values: &mut Vec<Object>| values.pop().expect("operand evaluated");
+        while let Some(step) = work.pop() {
+            let value = match step {
+                Work::Eval(expr) => match self.expand(expr, &mut work)? {
+                    Some(value) => value,
+                    None => continue,
+                },
+                Work::Push(value) => value,
+                Work::Binary(op) => {
+                    let right = pop(&mut values);
+                    let left = pop(&mut values);
+                    match self.binary(left, op, right)? {
+                        value if op.kind == OpKind::Add => self.allocated(value, op.span.line)?,
+                        value => value,
+                    }
+                }
+                Work::Unary(op) => Self::unary(op, pop(&mut values))?,
+                Work::Assign(n) => {
+                    let value = pop(&mut values);
+                    self.assign(n, value)?
+                }
+                Work::Logical(op, right) => {
+                    let left = pop(&mut values);
+                    // `or` is settled by a truthy left operand, `and` by a falsey one.
+                    if truthy(&left) == (op.kind == OpKind::Or) {
+                        left
+                    } else {
+                        work.push(Work::Eval(right));
+                        continue;
+                    }
+                }
+                Work::Ternary(t, e) => {
+                    let branch = if truthy(&pop(&mut values)) { t } else { e };
+                    work.push(Work::Eval(branch));
+                    continue;
+                }
+                Work::Call(paren, argc) => {
+                    let args = values.split_off(values.len() - argc);
+                    let callee = pop(&mut values);
+                    self.call(callee, paren, args)?
+                }
+                Work::List(bracket, len) => {
+                    let items = values.split_off(values.len() - len);
+                    self.allocated(Object::List(Rc::new(RefCell::new(items))), bracket.line)?
+                }
+                Work::IndexTarget(bracket, index) => {
+                    if !matches!(values.last(), Some(Object::List(_) | Object::String(_))) {
+                        return Err(Self::index_error(
+                            &bracket,
+                            "Only lists and strings can be indexed",
+                        ));
+                    }
+                    work.extend([Work::Index(bracket), Work::Eval(index)]);
+                    continue;
+                }
+                Work::Index(bracket) => {
+                    let index = pop(&mut values);
+                    let value = Self::index(pop(&mut values), &bracket, index)?;
+                    // Only a string's character is new; a list's element isn't.
+                    match value {
+                        Object::String(_) => self.allocated(value, bracket.line)?,
+                        value => value,
+                    }
+                }
+                Work::SetTarget(bracket, index, value) => {
+                    if !matches!(values.last(), Some(Object::List(_))) {
+                        return Err(Self::index_error(
+                            &bracket,
+                            "Only list elements can be assigned",
+                        ));
+                    }
+                    work.extend([Work::SetIndex(bracket, value), Work::Eval(index)]);
+                    continue;
+                }
+                Work::SetIndex(bracket, value) => {
+                    let index = pop(&mut values);
+                    let Some(Object::List(l)) = values.last() else {
+                        unreachable!("checked by SetTarget");
+                    };
+                    let len = l.borrow().len();
+                    let idx = Self::index_value(index, &bracket, len)?;
+                    work.extend([Work::Set(idx), Work::Eval(value)]);
+                    continue;
+                }
+                Work::Set(idx) => {
+                    let value = pop(&mut values);
+                    let Object::List(l) = pop(&mut values) else {
+                        unreachable!("checked by SetTarget");
+                    };
+                    l.borrow_mut()[idx] = value.clone();
+                    value
+                }
+                Work::SliceTarget(bracket, start, end) => {
+                    let len = Self::slice_len(values.last().expect("operand evaluated"), &bracket)?;
+                    work.push(Work::Slice(bracket));
+                    for (bound, default) in [(end, len), (start, 0)] {
+                        match bound {
+                            Some(bound) => {
+                                work.extend([Work::Bound(bracket, len), Work::Eval(bound)])
+                            }
+                            None => work.push(Work::Push(Object::Double(default as f64))),
+                        }
+                    }
+                    continue;
+                }
+                Work::Bound(bracket, len) => {
+                    let bound = Self::slice_bound(pop(&mut values), &bracket, len)?;
+                    Object::Double(bound as f64)
+                }
+                Work::Slice(bracket) => {
+                    let bounds = (pop(&mut values), pop(&mut values));
+                    let (Object::Double(end), Object::Double(start)) = bounds else {
+                        unreachable!("checked by Bound");
+                    };
+                    let slice = Self::slice(pop(&mut values), start as usize, end as usize);
+                    self.allocated(slice, bracket.line)?
+                }
+                Work::Get(name) => Self::get(pop(&mut values), name)?,
+            };
+            values.push(value);
+        }
+        Ok(values.pop().expect("expression evaluated"))
+    }
+
+    /// Run `ast`, giving a runtime error from it the stack trace of where
+    /// it happened.
+    fn execute(&mut self, ast: &Stmt) -> Result<StmtResult> {
+        self.execute_untraced(ast).map_err(|mut err| {
+            if let Some(e) = err.downcast_mut::<LoxRuntimeError>() {
+                if e.trace.is_empty() {
+                    e.trace = self.trace_at(e.span.line);
+                }
+            }
+            err
+        })
+    }
+
+    fn execute_untraced(&mut self, ast: &Stmt) -> Result<StmtResult> {
+        if self.cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+            return Err(anyhow!(LoxInterrupted {}));
+        }
+        if self.output_closed {
+            return Err(anyhow!(LoxOutputClosed {}));
+        }
+        if let Some(status) = self.exit_status {
+            return Err(anyhow!(LoxExit(status)));
+        }
+        let line = ast.line().unwrap_or(self.line);
+        self.steps += 1;
+        if let Some(limit) = self.step_limit.filter(|limit| self.steps > *limit) {
+            let message = format!("Step limit exceeded ({} statements)", limit);
+            return Err(Self::limit_error(line, message));
+        }
+        if let Some((_, limit)) = self.deadline.filter(|(at, _)| Instant::now() > *at) {
+            let message = format!("Time limit exceeded ({} ms)", limit.as_millis());
+            return Err(Self::limit_error(line, message));
+        }
+        if let (Some(observe), Some(span)) = (self.observer.as_mut(), ast.span()) {
+            observe(&Event::StatementExecuted { stmt: ast, span });
+        }
+        let recorded = recorder::is_recorded(ast);
+        if let Some(rec) = self.recorder.as_mut().filter(|_| recorded) {
+            rec.begin();
+        }
+        let r = self.execute_stmt(ast);
+        if let Some(rec) = self.recorder.as_mut().filter(|_| recorded) {
+            rec.end(ast);
+        }
+        self.check_memory(line)?;
+        r
+    }
+
+    fn execute_stmt(&mut self, ast: &Stmt) -> Result<StmtResult> {
+        ast.accept(self)
+    }
+
+    /// Tell the script's `onError` handlers about `err`, if it is a Lox
+    /// runtime error.
+    pub fn run_error_hooks(&mut self, err: &anyhow::Error) {
+        let e = match err.downcast_ref::<LoxRuntimeError>() {
+            Some(e) => e,
+            None => return,
+        };
+        let args = vec![
+            Object::String(format!("{}.", e.message)),
+            Object::Double(e.span.line as f64),
+        ];
+        // A failing handler must not call itself again.
+        let hooks = mem::take(&mut self.env.hooks.on_error);
+        for hook in &hooks {
+            // There is nowhere left to report a failing handler's error.
+            let _ = hook.call(self, args.clone());
+        }
+        let added = mem::replace(&mut self.env.hooks.on_error, hooks);
+        self.env.hooks.on_error.extend(added);
+    }
+
+    /// Run the script's `onExit` handlers, once, with the exit `status`.
+    pub fn run_exit_hooks(&mut self, status: i32) {
+        for hook in mem::take(&mut self.env.hooks.on_exit) {
+            let _ = hook.call(self, vec![Object::Double(status as f64)]);
+        }
+    }
+
+    /// Call the global function `name` as a program's entry point, with no
+    /// arguments or, if it takes one, with `args` as a list of strings.
+    pub fn call_entry(&mut self, name: &str, args: &[String]) -> Result<Object> {
+        let f = match self.env.get(Symbol::intern(name), None) {
+            Ok(Object::Callable(f)) => f,
+            Ok(_) => {
+                let message = format!("Entry point '{}' is not a function", name);
+                return Err(anyhow!(LoxEntryError(message)));
+            }
+            Err(_) => {
+                let message = format!("No function '{}' to use as the entry point", name);
+                return Err(anyhow!(LoxEntryError(message)));
+            }
+        };
+        let args = match f.arity() {
+            Some(0) => vec![],
+            Some(1) => {
+                let args = args.iter().map(|a| Object::String(a.clone())).collect();
+                vec![Object::List(Rc::new(RefCell::new(args)))]
+            }
+            _ => {
+                return Err(anyhow!(LoxEntryError(format!(
+                    "Entry point '{}' must take no arguments or a list of arguments",
+                    name
+                ))))
+            }
+        };
+        // There is no call site, so traces end at the declaration instead.
+        if let Some(line) = f.line() {
+            self.line = line;
+        }
+        f.call(self, args)
+    }
+
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<()> {
+        statements
+            .iter()
+            .try_for_each(|statement| match self.execute(statement)? {
+                StmtResult::Thrown(value) => Err(self.uncaught(value)),
+                _ => Ok(()),
+            })
+    }
+
+    /// The error for a thrown `value` that no `catch` has taken, keeping
+    /// the value for one further out.
+    fn uncaught(&mut self, value: Object) -> anyhow::Error {
+        let mut e = LoxRuntimeError::new(self.thrown_at, format!("Uncaught error: {}", value));
+        e.trace = self.trace_at(self.thrown_at.line);
+        self.thrown = Some(value);
+        anyhow!(LoxThrown {}).context(e)
+    }
+
+    /// Run `source` in this interpreter's environment and give the value of
+    /// its last statement, if that is an expression such as `1 + 2`, or
+    /// `nil`. Nothing is printed but what the program prints: scan and parse
+    /// errors come back as a `LoxCompileError` and runtime ones as a
+    /// `LoxRuntimeError`.
+    pub fn eval_str(&mut self, source: &str) -> Result<Object> {
+        let ast = Scanner::scan(source)
+            .and_then(|tokens| parser::parse_program(&tokens, true))
+            .map_err(|diagnostics| anyhow!(LoxCompileError { diagnostics }))?;
+        match ast.split_last() {
+            Some((Stmt::Expr(last), init)) => {
+                self.interpret(init)?;
+                self.evaluate(last)
+            }
+            _ => self.interpret(&ast).map(|_| Object::Nil),
+        }
+    }
+}
+
+impl StmtVisitor<Result<StmtResult>> for Interpreter<'_> {
+    fn visit_expr_stmt(&mut self, e: &Expr) -> Result<StmtResult> {
+        self.evaluate(e)?;
+        Ok(StmtResult::Noop)
+    }
+
+    fn visit_print(&mut self, e: &Expr) -> Result<StmtResult> {
+        let value = self.evaluate(e)?;
+        let written = match &self.output {
+            Some(output) => writeln!(output.borrow_mut(), "{}", value),
+            None => writeln!(io::stdout(), "{}", value),
+        };
+        match written {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                self.output_closed = true;
+                Err(anyhow!(LoxOutputClosed {}))
+            }
+            rv => rv.context("writing output").map(|_| StmtResult::Noop),
+        }
+    }
+
+    fn visit_var(&mut self, name: &Ident, init: Option<&Expr>) -> Result<StmtResult> {
+        let o = match init {
+            Some(expr) => self.evaluate(expr)?,
+            None => Object::Undefined,
+        };
+        if let Some(rec) = self.recorder.as_mut() {
+            rec.write(name.name.as_str(), &o);
+        }
+        if let Some(observe) = self.observer.as_mut() {
+            let value = match &o {
+                Object::Undefined => &Object::Nil,
+                o => o,
+            };
+            observe(&Event::VariableDefined {
+                name: name.name.as_str(),
+                value,
+                span: name.span,
+            });
+        }
+        self.env.define(name.name, o);
+        Ok(StmtResult::Noop)
+    }
+
+    fn visit_block(&mut self, stmts: &[Stmt]) -> Result<StmtResult> {
+        let mut scope = EnvGuard::block(self);
+        let mut result: Vec<Result<StmtResult>> = stmts
+            .iter()
+            .map(|s| -> Result<StmtResult> { scope.execute(s) })
+            .take_until(|r| !matches!(r, Ok(StmtResult::Noop)))
+            .collect();
+        // println!("jt: {:?}", result);
+        result.pop().unwrap_or(Ok(StmtResult::Noop))
+    }
+
+    fn visit_if(&mut self, c: &Expr, t: &Stmt, e: Option<&Stmt>) -> Result<StmtResult> {
+        if truthy(&self.evaluate(c)?) {
+            self.execute(t)
+        } else if let Some(e) = e {
+            self.execute(e)
+        } else {
+            Ok(StmtResult::Noop)
+        }
+    }
+
+    fn visit_while(&mut self, c: &Expr, s: &Stmt) -> Result<StmtResult> {
+        while truthy(&self.evaluate(c)?) {
+            self.count_iteration(c.line())?;
+            let r = self.execute(s);
+            if !matches!(r, Ok(StmtResult::Noop)) {
+                return r;
+            };
+        }
+        Ok(StmtResult::Noop)
+    }
+
+    fn visit_for_in(&mut self, name: &Ident, iterable: &Expr, body: &Stmt) -> Result<StmtResult> {
+        let it = iterate(&self.evaluate(iterable)?).ok_or_else(|| {
+            LoxRuntimeError::at(
+                name.span,
+                "Can only iterate over lists, strings and iterators",
+            )
+        })?;
+        while let Some(v) = it.next(self)? {
+            self.count_iteration(name.span.line)?;
+            if let Some(observe) = self.observer.as_mut() {
+                observe(&Event::VariableDefined {
+                    name: name.name.as_str(),
+                    value: &v,
+                    span: name.span,
+                });
+            }
+            let mut scope = EnvGuard::block(self);
+            scope.env.define(name.name, v);
+            let r = scope.execute(body);
+            drop(scope);
+            if !matches!(r, Ok(StmtResult::Noop)) {
+                return r;
+            }
+        }
+        Ok(StmtResult::Noop)
+    }
+
+    fn visit_function(
+        &mut self,
+        name: &Ident,
+        params: &[Ident],
+        body: &Stmt,
+    ) -> Result<StmtResult> {
+        let function = Object::Callable(LoxCallableWrapper {
+            inner: Rc::new(LoxFunction {
+                name: Some(name.clone()),
+                declaration: name.span,
+                params: params.to_vec(),
+                body: body.clone(),
+                closure: self.env.capture(),
+            }),
+        });
+        let function = self.allocated(function, name.span.line)?;
+        self.env.define(name.name, function);
+        Ok(StmtResult::Noop)
+    }
+
+    fn visit_return(&mut self, _kw: Span, v: Option<&Expr>) -> Result<StmtResult> {
+        // TODO: Find a way to handle the unwind here ... we don't have throw like java, could use the Error short circuting, but that feels ... gross.
+        // FIXME: Maybe something like this guy did: https://github.com/franeklubi/luxya/blob/c38bd0a3e3063241f0e7517778adab6040ddf08a/src/interpreter/types.rs#L144-L149
+        // which has a StmtResult (Continue, Break, Return, Noop) to propagate those statement actions up 🤔
+
+        let rv = if let Some(v) = v {
+            self.evaluate(v)?
+        } else {
+            Object::Nil
+        };
+        Ok(StmtResult::Return(rv))
+    }
+
+    fn visit_throw(&mut self, keyword: Span, value: &Expr) -> Result<StmtResult> {
+        let value = self.evaluate(value)?;
+        self.thrown_at = keyword;
+        Ok(StmtResult::Thrown(value))
+    }
+
+    fn visit_try(&mut self, body: &Stmt, name: &Ident, handler: &Stmt) -> Result<StmtResult> {
+        let r = self.execute(body);
+        // A runtime error is caught as its message.
+        let caught = match r {
+            Ok(StmtResult::Thrown(value)) => value,
+            Err(e) if e.is::<LoxThrown>() => self.thrown.take().unwrap_or(Object::Nil),
+            // The message isn't the program's to count against the allocation
+            // limit, or running past it could never be caught.
+            Err(e) => match e.downcast_ref::<LoxRuntimeError>() {
+                Some(rte) => Object::String(rte.message()),
+                None => return Err(e),
+            },
+            r => return r,
+        };
+        let mut scope = EnvGuard::block(self);
+        scope.env.define(name.name, caught);
+        scope.execute(handler)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(items: Vec<Object>) -> Object {
+        Object::List(Rc::new(RefCell::new(items)))
+    }
+
+    fn samples() -> Vec<Object> {
+        let cyclic = Rc::new(RefCell::new(vec![Object::Double(1.0)]));
+        cyclic.borrow_mut().push(Object::List(cyclic.clone()));
+        vec![
+            Object::Nil,
+            Object::Boolean(false),
+            Object::Boolean(true),
+            Object::Double(-1.5),
+            Object::Double(0.0),
+            Object::Double(-0.0),
+            Object::Double(2.0),
+            Object::Double(f64::INFINITY),
+            Object::Double(f64::NAN),
+            Object::String("".to_owned()),
+            Object::String("a".to_owned()),
+            Object::String("ab".to_owned()),
+            list(vec![]),
+            list(vec![Object::Double(1.0)]),
+            list(vec![Object::Double(1.0), Object::String("x".to_owned())]),
+            list(vec![list(vec![Object::Nil])]),
+            Object::List(cyclic),
+        ]
+    }
+
+    #[test]
+    fn test_compare_antisymmetric() {
+        let samples = samples();
+        for a in &samples {
+            assert_eq!(compare(a, a), cmp::Ordering::Equal, "{}", a);
+            for b in &samples {
+                assert_eq!(compare(a, b), compare(b, a).reverse(), "{} {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compare_transitive() {
+        let samples = samples();
+        for a in &samples {
+            for b in &samples {
+                for c in &samples {
+                    if compare(a, b).is_le() && compare(b, c).is_le() {
+                        assert!(compare(a, c).is_le(), "{} {} {}", a, b, c);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compare_kinds() {
+        let samples = samples();
+        assert!(compare(&samples[0], &samples[1]).is_lt());
+        assert!(compare(&samples[8], &samples[9]).is_lt());
+        assert!(compare(&samples[4], &samples[5]).is_eq());
+        assert!(compare(&samples[7], &samples[8]).is_lt());
+    }
+
+    #[test]
+    fn test_callable_identity() {
+        let mut env = Enviornment::new();
+        stdlib::install(&mut env);
+        let clock = env.get(Symbol::intern("clock"), None).unwrap();
+        let len = env.get(Symbol::intern("len"), None).unwrap();
+        assert_eq!(clock, clock.clone());
+        assert_ne!(clock, len);
+    }
+
+    #[test]
+    fn test_scopes_unwind_after_errors() {
+        use crate::lox::tests::Captured;
+        use crate::lox::Lox;
+        let errors = Captured::default();
+        let mut lox = Lox::default();
+        lox.set_error_sink(errors.clone());
+        let mut env = Enviornment::new();
+        let failing = [
+            "{ { var a = 1; a(); } }",
+            "fun f(x) { { return x(); } } f(1);",
+            "for (var i in [1, 2]) { var j = i; j.k; }",
+        ];
+        for source in failing {
+            assert!(lox.run_with_env(source.to_owned(), &mut env).is_err());
+        }
+        assert_eq!(
+            errors.text(),
+            "Can only call functions and classes.\n[line 1] in script\n\
+             Can only call functions and classes.\n[line 1] in f\n[line 1] in script\n\
+             Only namespaces have properties.\n[line 1] in script\n"
+        );
+        // Each error left the global scope current.
+        lox.run_with_env("var after = 1;".to_owned(), &mut env)
+            .unwrap();
+        assert!(env.globals().iter().any(|(k, _)| k.as_str() == "after"));
+    }
+    #[test]
+    fn test_call_entry() {
+        use crate::lox::Lox;
+        let mut lox = Lox::default();
+        let mut env = Enviornment::new();
+        let source =
+            "var seen; fun main(args) { seen = args; } fun none() { return 1; } fun two(a, b) {}";
+        lox.run_with_env(source.to_owned(), &mut env).unwrap();
+        let mut interpreter = Interpreter::new_with_env(&mut env);
+        let args = ["a".to_owned(), "b".to_owned()];
+        interpreter.call_entry("main", &args).unwrap();
+        assert_eq!(
+            interpreter.call_entry("none", &args).unwrap(),
+            Object::Double(1.0)
+        );
+        for (name, error) in [
+            ("missing", "No function 'missing' to use as the entry point"),
+            ("seen", "Entry point 'seen' is not a function"),
+            (
+                "two",
+                "Entry point 'two' must take no arguments or a list of arguments",
+            ),
+        ] {
+            let e = interpreter.call_entry(name, &args).unwrap_err();
+            assert_eq!(e.downcast_ref::<LoxEntryError>().unwrap().0, error);
+        }
+        let seen = env.get(Symbol::intern("seen"), None).unwrap();
+        assert_eq!(seen.to_string(), "[a, b]");
+    }
+
+    #[test]
+    fn test_eval_str() {
+        let mut env = Enviornment::new();
+        let mut interpreter = Interpreter::new_with_env(&mut env);
+        let value = interpreter.eval_str("var a = 20; fun f(x) { return x + a; } f(1) * 2");
+        assert_eq!(value.unwrap(), Object::Double(42.0));
+        // Globals persist from one call to the next.
+        assert_eq!(interpreter.eval_str("a;").unwrap(), Object::Double(20.0));
+        assert_eq!(interpreter.eval_str("var b = 1;").unwrap(), Object::Nil);
+
+        let e = interpreter.eval_str("print 1 +; var = 2;").unwrap_err();
+        let diagnostics = &e.downcast_ref::<LoxCompileError>().unwrap().diagnostics;
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            e.to_string(),
+            "[line 1] Error: Expect expression.\n[line 1] Error: Expect variable name."
+        );
+
+        let e = interpreter.eval_str("-\"a\"").unwrap_err();
+        let rte = e.downcast_ref::<LoxRuntimeError>().unwrap();
+        assert_eq!(rte.message(), "Operand must be a number.");
+    }
+
+    #[test]
+    fn test_book_error_messages() {
+        let mut env = Enviornment::new();
+        let mut interpreter = Interpreter::new_with_env(&mut env);
+        // As the book's tests expect them, word for word.
+        let cases = [
+            ("-\"a\"", "Operand must be a number."),
+            ("1 < \"a\"", "Operands must be numbers."),
+            ("nil - 1", "Operands must be numbers."),
+            ("true + 1", "Operands must be two numbers or two strings."),
+            ("x", "Undefined variable 'x'."),
+            ("x = 1", "Undefined variable 'x'."),
+            ("\"f\"()", "Can only call functions and classes."),
+            ("fun f(a) {} f()", "Expected 1 arguments but got 0."),
+        ];
+        for (source, message) in cases {
+            let e = interpreter.eval_str(source).unwrap_err();
+            assert_eq!(e.root_cause().to_string(), message, "{}", source);
+            let rte = e.downcast_ref::<LoxRuntimeError>().unwrap();
+            assert_eq!(rte.message(), message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_deep_expressions() {
+        let mut env = Enviornment::new();
+        let mut interpreter = Interpreter::new_with_env(&mut env);
+        // Far deeper than the Rust stack would allow one frame per level. A
+        // line each, as the scanner is slow on long lines.
+        let sum = vec!["1"; 100_000].join(" +\n");
+        let value = interpreter.eval_str(&sum);
+        assert_eq!(value.unwrap(), Object::Double(100_000.0));
+        // Declaring the function copies its body.
+        let source = format!("fun f() {{ return {}; }} f()", sum);
+        assert_eq!(
+            interpreter.eval_str(&source).unwrap(),
+            Object::Double(100_000.0)
+        );
+        let source = format!("false{} or \"last\"", " or\nnil".repeat(100_000));
+        let value = interpreter.eval_str(&source);
+        assert_eq!(value.unwrap(), Object::String("last".to_owned()));
+    }
+
+    #[test]
+    fn test_operands_checked_in_order() {
+        let mut env = Enviornment::new();
+        let mut interpreter = Interpreter::new_with_env(&mut env);
+        interpreter
+            .eval_str("var calls = 0; fun f() { calls = calls + 1; return 0; }")
+            .unwrap();
+        // The object is checked before the index or bound is evaluated,
+        // and a bad index before the value to store.
+        for source in [
+            "nil[f()];",
+            "nil[f()] = 1;",
+            "[1][nil] = f();",
+            "nil[f():];",
+            "[1][nil:f()];",
+        ] {
+            assert!(interpreter.eval_str(source).is_err(), "{}", source);
+        }
+        assert_eq!(interpreter.eval_str("calls").unwrap(), Object::Double(0.0));
+        let value = interpreter.eval_str("var l = [1, 2, 3]; l[f()] = l[1:][f() + 1] + f()");
+        assert_eq!(value.unwrap(), Object::Double(3.0));
+        assert_eq!(
+            interpreter.eval_str("l[0:2]").unwrap().to_string(),
+            "[3, 2]"
+        );
+        assert_eq!(interpreter.eval_str("calls").unwrap(), Object::Double(3.0));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::interpreter::{Interpreter, LoxCallable, LoxCallableWrapper, NativeError, Object};
+use crate::stdlib::{bad_argument, expect_callable, expect_number};
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+/// A lazy sequence of Lox values. `next` may call back into the interpreter
+/// (e.g. the function given to `map`), so it receives it explicitly, and
+/// fails with whatever error that call does.
+pub trait LoxIterator: Debug {
+    fn next(&mut self, i: &mut Interpreter) -> Result<Option<Object>>;
+}
+
+#[derive(Debug)]
+struct IterState {
+    source: Box<dyn LoxIterator>,
+    // `Some(None)` once a peek has seen the end of the sequence.
+    peeked: Option<Option<Object>>,
+}
+
+/// Shared handle to an iterator, the runtime value behind `iter()`, `range()`
+/// and friends. Clones advance the same underlying sequence.
+#[derive(Debug, Clone)]
+pub struct LoxIteratorWrapper {
+    inner: Rc<RefCell<IterState>>,
+}
+
+impl PartialEq for LoxIteratorWrapper {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl LoxIteratorWrapper {
+    pub fn new(source: impl LoxIterator + 'static) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(IterState {
+                source: Box::new(source),
+                peeked: None,
+            })),
+        }
+    }
+
+    pub fn next(&self, i: &mut Interpreter) -> Result<Option<Object>> {
+        // An iterator being advanced from inside its own `map`/`filter`
+        // callback is already borrowed; treat that as exhausted.
+        let Ok(mut state) = self.inner.try_borrow_mut() else {
+            return Ok(None);
+        };
+        match state.peeked.take() {
+            Some(v) => Ok(v),
+            None => state.source.next(i),
+        }
+    }
+
+    pub fn done(&self, i: &mut Interpreter) -> Result<bool> {
+        let Ok(mut state) = self.inner.try_borrow_mut() else {
+            return Ok(true);
+        };
+        if state.peeked.is_none() {
+            let v = state.source.next(i)?;
+            state.peeked = Some(v);
+        }
+        Ok(matches!(state.peeked, Some(None)))
+    }
+}
+
+/// Iterator over the values of `o`, if it is iterable.
+pub fn iterate(o: &Object) -> Option<LoxIteratorWrapper> {
+    match o {
+        Object::Iterator(it) => Some(it.clone()),
+        Object::List(l) => Some(LoxIteratorWrapper::new(ListIter {
+            list: l.clone(),
+            idx: 0,
+        })),
+        Object::String(s) => Some(LoxIteratorWrapper::new(StringIter {
+            chars: s.chars().collect(),
+            idx: 0,
+        })),
+        _ => None,
+    }
+}
+
+/// Walks the live list, so elements pushed during iteration are visited.
+#[derive(Debug)]
+struct ListIter {
+    list: Rc<RefCell<Vec<Object>>>,
+    idx: usize,
+}
+impl LoxIterator for ListIter {
+    fn next(&mut self, _i: &mut Interpreter) -> Result<Option<Object>> {
+        let Some(v) = self.list.borrow().get(self.idx).cloned() else {
+            return Ok(None);
+        };
+        self.idx += 1;
+        Ok(Some(v))
+    }
+}
+
+#[derive(Debug)]
+struct StringIter {
+    chars: Vec<char>,
+    idx: usize,
+}
+impl LoxIterator for StringIter {
+    fn next(&mut self, _i: &mut Interpreter) -> Result<Option<Object>> {
+        let Some(c) = self.chars.get(self.idx) else {
+            return Ok(None);
+        };
+        self.idx += 1;
+        Ok(Some(Object::String(c.to_string())))
+    }
+}
+
+#[derive(Debug)]
+struct RangeIter {
+    cur: f64,
+    end: f64,
+    step: f64,
+}
+impl LoxIterator for RangeIter {
+    fn next(&mut self, _i: &mut Interpreter) -> Result<Option<Object>> {
+        let more = if self.step > 0.0 {
+            self.cur < self.end
+        } else {
+            self.cur > self.end
+        };
+        if !more {
+            return Ok(None);
+        }
+        let v = self.cur;
+        self.cur += self.step;
+        Ok(Some(Object::Double(v)))
+    }
+}
+
+#[derive(Debug)]
+struct MapIter {
+    source: LoxIteratorWrapper,
+    f: LoxCallableWrapper,
+}
+impl LoxIterator for MapIter {
+    fn next(&mut self, i: &mut Interpreter) -> Result<Option<Object>> {
+        match self.source.next(i)? {
+            Some(v) => self.f.call(i, vec![v]).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FilterIter {
+    source: LoxIteratorWrapper,
+    f: LoxCallableWrapper,
+}
+impl LoxIterator for FilterIter {
+    fn next(&mut self, i: &mut Interpreter) -> Result<Option<Object>> {
+        while let Some(v) = self.source.next(i)? {
+            if crate::interpreter::truthy(&self.f.call(i, vec![v.clone()])?) {
+                return Ok(Some(v));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Debug)]
+struct TakeIter {
+    source: LoxIteratorWrapper,
+    remaining: usize,
+}
+impl LoxIterator for TakeIter {
+    fn next(&mut self, i: &mut Interpreter) -> Result<Option<Object>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.source.next(i)
+    }
+}
+
+/// `range(end)`, `range(start, end)` or `range(start, end, step)`.
+fn range(args: &[Object], name: &str) -> Result<Object> {
+    let (start, end) = match args.len() {
+        1 => (0.0, expect_number(args, 0, name)?),
+        2 | 3 => (expect_number(args, 0, name)?, expect_number(args, 1, name)?),
+        n => {
+            return Err(anyhow!(NativeError(format!(
+                "Expected 1 to 3 arguments but got {}",
+                n
+            ))))
+        }
+    };
+    let step = match args.len() {
+        3 => expect_number(args, 2, name)?,
+        _ => 1.0,
+    };
+    if step == 0.0 {
+        return Err(bad_argument(args, 2, name, "a number other than 0"));
+    }
+    Ok(Object::Iterator(LoxIteratorWrapper::new(RangeIter {
+        cur: start,
+        end,
+        step,
+    })))
+}
+
+/// The natives making up the iterator protocol and sequence combinators.
+#[derive(Debug, Clone, Copy)]
+pub enum IterNative {
+    Iter,
+    Next,
+    Done,
+    Range,
+    Map,
+    Filter,
+    Take,
+}
+
+impl IterNative {
+    pub const ALL: [IterNative; 7] = [
+        Self::Iter,
+        Self::Next,
+        Self::Done,
+        Self::Range,
+        Self::Map,
+        Self::Filter,
+        Self::Take,
+    ];
+}
+
+impl LoxCallable for IterNative {
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let name = self.name();
+        if let Self::Range = self {
+            return range(&args, &name);
+        }
+        let source = iterate(&args[0])
+            .ok_or_else(|| bad_argument(&args, 0, &name, "a list, string or iterator"))?;
+        let it = match self {
+            Self::Iter => source,
+            Self::Next => return Ok(source.next(i)?.unwrap_or(Object::Nil)),
+            Self::Done => return Ok(Object::Boolean(source.done(i)?)),
+            Self::Map => {
+                let f = expect_callable(&args, 1, &name)?.clone();
+                LoxIteratorWrapper::new(MapIter { source, f })
+            }
+            Self::Filter => {
+                let f = expect_callable(&args, 1, &name)?.clone();
+                LoxIteratorWrapper::new(FilterIter { source, f })
+            }
+            Self::Take => match expect_number(&args, 1, &name)? {
+                n if n >= 0.0 => LoxIteratorWrapper::new(TakeIter {
+                    source,
+                    remaining: n as usize,
+                }),
+                _ => return Err(bad_argument(&args, 1, &name, "at least 0")),
+            },
+            Self::Range => unreachable!("range makes its own iterator"),
+        };
+        Ok(Object::Iterator(it))
+    }
+    fn name(&self) -> String {
+        match self {
+            Self::Iter => "iter",
+            Self::Next => "next",
+            Self::Done => "done",
+            Self::Range => "range",
+            Self::Map => "map",
+            Self::Filter => "filter",
+            Self::Take => "take",
+        }
+        .to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        match self {
+            Self::Iter | Self::Next | Self::Done => Some(1),
+            Self::Map | Self::Filter | Self::Take => Some(2),
+            Self::Range => None,
+        }
+    }
+}
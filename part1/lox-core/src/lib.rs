@@ -0,0 +1,30 @@
+pub mod ast_dump;
+pub mod ast_printer;
+pub mod coverage;
+pub mod environment;
+pub mod error_codes;
+pub mod incremental;
+pub mod intern;
+pub mod interpreter;
+pub mod iterator;
+pub mod lox;
+pub mod lox_error;
+pub mod observer;
+pub mod parser;
+pub mod paths;
+pub mod profiler;
+pub mod recorder;
+pub mod resolver;
+pub mod scanner;
+pub mod stdlib;
+pub mod tokens;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+// The embedding API: scan, parse and run Lox without going through `Lox`.
+pub use environment::Enviornment;
+pub use interpreter::{Interpreter, LoxRuntimeError, Object};
+pub use lox_error::{Diagnostic, LoxCompileError};
+pub use parser::{parse, Stmt};
+pub use scanner::Scanner;
+pub use tokens::Token;
@@ -0,0 +1,1068 @@
+use crate::ast_dump::{self, DumpLimits};
+use crate::ast_printer;
+use crate::environment::Enviornment;
+use crate::error_codes::ErrorCode;
+use crate::intern::Symbol;
+use crate::interpreter::{Interpreter, LoxNamespace, MathCheck, Object};
+use crate::lox_error::{Diagnostic, LoxCompileError, LoxError};
+use crate::observer::{Event, Observer};
+use crate::parser;
+use crate::parser::{Expr, Stmt};
+use crate::profiler::Profiler;
+use crate::recorder::ExecutionRecorder;
+use crate::resolver::Resolver;
+use crate::scanner;
+use crate::scanner::ScanOptions;
+use crate::stdlib;
+use crate::tokens::{tokens_json, Token};
+use anyhow::anyhow;
+use anyhow::Context;
+use anyhow::Result;
+use derive_more::Display;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::rc::Rc;
+use std::time::Duration;
+
+pub struct Lox {
+    pub has_error: bool,
+    opts: LoxOptions,
+    cancel: Option<CancelToken>,
+    // Globals and parsed expressions kept between `eval_many` batches.
+    eval_env: Enviornment,
+    expr_cache: HashMap<String, Rc<Expr>>,
+    // Text being scanned and parsed, for quoting in diagnostics.
+    source: String,
+    errors: ErrorSink,
+    output: Option<Rc<RefCell<dyn Write>>>,
+    // The script's command-line arguments, for `args()` and the `--entry` function.
+    args: Vec<String>,
+    // Calls timed with `--profile`, across the interpreters of one run.
+    profiler: Rc<RefCell<Profiler>>,
+}
+
+/// What `run_program` runs: text to scan and parse, or a parsed tree.
+enum Program {
+    Source(String),
+    Ast(Vec<Stmt>),
+}
+
+/// Where `Lox` reports scan, parse and runtime errors.
+#[derive(Clone)]
+enum ErrorSink {
+    Stderr,
+    /// Set by an embedder with `Lox::set_error_sink`, and shared with the
+    /// interpreter for its warnings.
+    Writer(Rc<RefCell<dyn Write>>),
+}
+
+impl ErrorSink {
+    /// Only a terminal gets excerpts and notes; tools expect one line per error.
+    fn is_terminal(&self) -> bool {
+        match self {
+            Self::Stderr => io::stderr().is_terminal(),
+            Self::Writer(_) => false,
+        }
+    }
+
+    /// Write `text` after everything printed so far, so an error follows
+    /// the output of the statement that failed.
+    fn write(&self, text: &str) {
+        // Like `eprint!`, reporting an error can't itself fail.
+        let _ = io::stdout().flush();
+        let _ = match self {
+            Self::Stderr => io::stderr().write_all(text.as_bytes()),
+            Self::Writer(w) => {
+                let mut w = w.borrow_mut();
+                w.write_all(text.as_bytes()).and_then(|_| w.flush())
+            }
+        };
+    }
+}
+
+/// Reports the interpreter's warnings, such as `--check-math`'s, to the
+/// same sink and in the same form as `Lox` reports its own.
+struct RuntimeWarnings {
+    errors: ErrorSink,
+    json: bool,
+    hidden: bool,
+}
+
+impl LoxError for RuntimeWarnings {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if self.hidden {
+            return;
+        }
+        if self.json {
+            self.errors.write(&format!("{}\n", diagnostic.to_json()));
+            return;
+        }
+        let mut text = format!("{}\n", diagnostic);
+        if self.errors.is_terminal() {
+            text += &code_note(diagnostic.code);
+        }
+        self.errors.write(&text);
+    }
+
+    fn has_error(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Display)]
+pub struct LoxScanError {}
+#[derive(Debug, Display)]
+pub struct LoxParseError {}
+pub use crate::interpreter::{
+    CancelToken, LoxAssertionFailed, LoxEntryError, LoxExit, LoxInterrupted, LoxOutputClosed,
+    LoxRuntimeError, LoxStackOverflow, LoxThrown,
+};
+
+/// How a program run by `Lox` failed, wherever in the error's chain of
+/// contexts the Lox error sits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoxErrorKind {
+    /// Scanning, parsing or resolving found errors, already reported.
+    Compile,
+    /// A runtime error, already reported.
+    Runtime,
+    /// The program called `exit(status)`.
+    Exit(i32),
+    Interrupted,
+    /// Stdout was closed under the program, so it stopped quietly.
+    OutputClosed,
+    /// The program couldn't be run as asked, such as with an `--entry`
+    /// function it doesn't have, already reported.
+    Usage,
+}
+
+impl LoxErrorKind {
+    /// The kind of `err`, or `None` if it isn't a Lox error, such as a
+    /// script that couldn't be read.
+    pub fn of(err: &anyhow::Error) -> Option<Self> {
+        // A stack overflow is a runtime error too, and exit and closed
+        // output end a program however deep in it they happen.
+        if let Some(LoxExit(status)) = err.downcast_ref::<LoxExit>() {
+            Some(Self::Exit(*status))
+        } else if err.is::<LoxOutputClosed>() {
+            Some(Self::OutputClosed)
+        } else if err.is::<LoxInterrupted>() {
+            Some(Self::Interrupted)
+        } else if err.is::<LoxRuntimeError>() {
+            Some(Self::Runtime)
+        } else if err.is::<LoxEntryError>() {
+            Some(Self::Usage)
+        } else if err.is::<LoxScanError>()
+            || err.is::<LoxParseError>()
+            || err.is::<LoxCompileError>()
+        {
+            Some(Self::Compile)
+        } else {
+            None
+        }
+    }
+
+    /// The status to exit with, as jlox does: 64 for usage errors, 65 for
+    /// compile errors, 70 for runtime ones, and 130 when interrupted, as by
+    /// Ctrl-C.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Compile => 65,
+            Self::Runtime => 70,
+            Self::Exit(status) => status,
+            Self::Interrupted => 130,
+            Self::OutputClosed => 0,
+            Self::Usage => 64,
+        }
+    }
+}
+
+/// How to run programs. The command line flags when built with `clap`.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "clap", derive(clap::Args))]
+pub struct LoxOptions {
+    #[cfg_attr(feature = "clap", clap(short, long))]
+    pub debug_ast: bool,
+    /// With --debug-ast, show this many levels of nesting (default 100), eliding the rest
+    #[cfg_attr(feature = "clap", clap(long, value_name = "N"))]
+    pub debug_ast_depth: Option<usize>,
+    /// With --debug-ast, show this many entries of each node or list
+    #[cfg_attr(feature = "clap", clap(long, value_name = "N"))]
+    pub debug_ast_width: Option<usize>,
+    /// Print the parse tree of just the function or variable declared with this name
+    #[cfg_attr(feature = "clap", clap(long, value_name = "NAME"))]
+    pub debug_ast_filter: Option<String>,
+    /// Print each expression in reverse Polish notation, as in `1 2 + 4 3 - *`
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub print_rpn: bool,
+    /// Print the scanned tokens as JSON instead of running the program
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub tokens_json: bool,
+    /// Print errors as JSON objects, one per line, with their error codes
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub diagnostics_json: bool,
+    /// Don't report warnings, such as unused locals or unreachable code
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub no_warnings: bool,
+    /// Append every executed statement and the variables it read/wrote to this file
+    #[cfg_attr(feature = "clap", clap(long, value_name = "LOG"))]
+    pub record_execution: Option<String>,
+    /// After the top-level code, call this function, passing the script's
+    /// arguments as a list if it takes one
+    #[cfg_attr(feature = "clap", clap(long, value_name = "FUNCTION"))]
+    pub entry: Option<String>,
+    /// Make `"str" + 1` a runtime error instead of concatenating "str1"
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub strict_concat: bool,
+    /// Make reading a variable declared without an initializer a runtime error
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub strict_init: bool,
+    /// Allow `_` digit separators in number literals, as in `1_000_000`
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub digit_separators: bool,
+    /// Fail with a runtime error once program values use more than about this many bytes
+    #[cfg_attr(feature = "clap", clap(long, value_name = "BYTES"))]
+    pub memory_limit: Option<usize>,
+    /// Fail with a runtime error after running this many statements
+    #[cfg_attr(feature = "clap", clap(long, value_name = "N"))]
+    pub max_steps: Option<u64>,
+    /// Fail with a runtime error after loop bodies have run this many times in all
+    #[cfg_attr(feature = "clap", clap(long, value_name = "N"))]
+    pub max_loop_iterations: Option<u64>,
+    /// Fail with a runtime error after making this many lists, strings and functions
+    #[cfg_attr(feature = "clap", clap(long, value_name = "N"))]
+    pub max_allocations: Option<u64>,
+    /// Fail with a runtime error at the first statement after this many milliseconds
+    #[cfg_attr(feature = "clap", clap(long, value_name = "MS"))]
+    pub timeout_ms: Option<u64>,
+    /// Fail with "Stack overflow." once this many calls are in progress [default: 200]
+    #[cfg_attr(feature = "clap", clap(long, value_name = "N"))]
+    pub max_call_depth: Option<usize>,
+    /// On exit, print to stderr how many times each function was called and
+    /// the time spent in it
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub profile: bool,
+    /// Warn when arithmetic on finite numbers gives infinity or NaN
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub check_math: bool,
+    /// Make arithmetic on finite numbers giving infinity or NaN a runtime error
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub strict_math: bool,
+}
+
+/// Points a terminal user at `explain` for the error's catalog entry.
+fn code_note(code: Option<ErrorCode>) -> String {
+    match code {
+        Some(code) => format!(
+            "  = {}: {} (see `interpreter explain {}`)\n",
+            code,
+            code.summary(),
+            code
+        ),
+        None => String::new(),
+    }
+}
+
+impl Lox {
+    fn report_runtime_error(&mut self, err: &anyhow::Error) {
+        let Some(e) = err.downcast_ref::<LoxRuntimeError>() else {
+            return;
+        };
+        if self.opts.diagnostics_json {
+            self.errors.write(&format!("{}\n", e.to_json()));
+            return;
+        }
+        let mut text = format!("{}\n", e);
+        if self.errors.is_terminal() {
+            text += &code_note(e.code());
+        }
+        self.errors.write(&text);
+    }
+
+    pub fn new(opts: LoxOptions) -> Lox {
+        Lox {
+            has_error: false,
+            opts,
+            cancel: None,
+            eval_env: Enviornment::new(),
+            expr_cache: HashMap::new(),
+            source: String::new(),
+            errors: ErrorSink::Stderr,
+            output: None,
+            args: vec![],
+            profiler: Rc::default(),
+        }
+    }
+
+    /// Report errors to `sink` instead of stderr.
+    pub fn set_error_sink(&mut self, sink: impl Write + 'static) {
+        self.errors = ErrorSink::Writer(Rc::new(RefCell::new(sink)));
+    }
+
+    /// Send what programs `print`, and the `--debug-ast` and `--tokens-json`
+    /// dumps, to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Some(Rc::new(RefCell::new(output)));
+    }
+
+    fn write_output(&self, text: &str) -> Result<()> {
+        match &self.output {
+            Some(output) => output.borrow_mut().write_all(text.as_bytes()),
+            None => io::stdout().write_all(text.as_bytes()),
+        }
+        .context("writing output")
+    }
+
+    /// The script's command-line arguments, for `args()` and the entry point.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Cancelling `token` while a program runs aborts it with
+    /// `LoxInterrupted`. Reset it before running the next one.
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = Some(token);
+    }
+
+    fn check_err(&self) -> Result<()> {
+        match self.has_error {
+            false => Ok(()),
+            true => Err(anyhow!("Failure")),
+        }
+    }
+
+    /// Run `source` as a whole program, then its entry point if it has
+    /// one, then its `onExit` handlers.
+    pub fn run(&mut self, source: String) -> Result<()> {
+        self.run_program(Program::Source(source), None)
+    }
+
+    /// Run an already parsed program like `run`, resolving it first, as
+    /// for a tree read back from `--emit-ast`.
+    pub fn run_ast(&mut self, ast: Vec<Stmt>) -> Result<()> {
+        self.run_program(Program::Ast(ast), None)
+    }
+
+    /// Run `source` like `run`, telling `observer` about each statement
+    /// executed, function entered and variable defined along the way.
+    pub fn run_observed(&mut self, source: String, mut observer: impl FnMut(&Event)) -> Result<()> {
+        self.run_program(Program::Source(source), Some(&mut observer))
+    }
+
+    fn run_program(&mut self, program: Program, mut observer: Option<Observer>) -> Result<()> {
+        let mut env = Enviornment::new();
+        env.args = self.args.clone();
+        let rv = self
+            .run_observing(
+                program,
+                &mut env,
+                observer.as_mut().map(|o| &mut **o as Observer),
+            )
+            .and_then(|_| match self.opts.entry.clone() {
+                Some(name) => self.run_entry(&name, &mut env, observer),
+                None => Ok(()),
+            });
+        let status = rv
+            .as_ref()
+            .err()
+            .and_then(LoxErrorKind::of)
+            .map_or(0, LoxErrorKind::exit_code);
+        self.exit(&mut env, status)?;
+        if self.opts.profile {
+            let report = self.profiler.take().report();
+            self.errors.write(&report);
+        }
+        rv
+    }
+
+    /// Run the `onExit` handlers registered in `env` with the exit `status`.
+    pub fn exit(&mut self, env: &mut Enviornment, status: i32) -> Result<()> {
+        self.interpreter(env)?.run_exit_hooks(status);
+        Ok(())
+    }
+
+    /// Scan and parse `source`, failing if either phase reported an error.
+    pub fn parse(&mut self, source: &str) -> Result<Vec<Stmt>> {
+        self.parse_with(source, false)
+    }
+
+    fn parse_with(&mut self, source: &str, bare_expression: bool) -> Result<Vec<Stmt>> {
+        let tokens = self.scan(source);
+        // println!("Tokens: {:#?}", tokens);
+        if self.check_err().is_err() {
+            return Err(anyhow!("failed to scan")).context(LoxScanError {});
+        }
+
+        let tok = tokens?;
+        let mut tok = tok.iter().peekable();
+        let mut parser = parser::Parser::new(&mut tok, self);
+        if bare_expression {
+            parser = parser.allow_bare_expression();
+        }
+
+        let ast = parser.parse();
+        if self.check_err().is_err() {
+            return Err(anyhow!("failed to parse")).context(LoxParseError {});
+        }
+        self.resolve(ast?)
+    }
+
+    /// Resolve a parsed program, then show it as the options ask.
+    fn resolve(&mut self, mut ast: Vec<Stmt>) -> Result<Vec<Stmt>> {
+        Resolver::new(self).resolve(&mut ast);
+        if self.check_err().is_err() {
+            return Err(anyhow!("failed to resolve")).context(LoxParseError {});
+        }
+        if self.opts.debug_ast || self.opts.debug_ast_filter.is_some() {
+            self.print_ast(&ast)?;
+        }
+        if self.opts.print_rpn {
+            for line in ast_printer::rpn_lines(&ast) {
+                self.write_output(&format!("RPN: {}\n", line))?;
+            }
+        }
+        Ok(ast)
+    }
+
+    /// Show `ast` for `--debug-ast`, within its limits and filter.
+    fn print_ast(&self, ast: &[Stmt]) -> Result<()> {
+        let limits = DumpLimits {
+            depth: Some(self.opts.debug_ast_depth.unwrap_or(ast_dump::DEFAULT_DEPTH)),
+            width: self.opts.debug_ast_width,
+        };
+        let stmts = match &self.opts.debug_ast_filter {
+            Some(name) => ast_dump::declarations(ast, name),
+            None => ast.iter().collect(),
+        };
+        match &self.opts.debug_ast_filter {
+            Some(name) if stmts.is_empty() => {
+                self.write_output(&format!("AST: nothing named '{}' declared\n", name))
+            }
+            _ => self.write_output(&format!("AST: {}", ast_dump::dump(&stmts, &limits))),
+        }
+    }
+
+    fn scan(&mut self, source: &str) -> Result<Vec<Token>> {
+        self.source = source.to_owned();
+        let opts = ScanOptions {
+            digit_separators: self.opts.digit_separators,
+            ..Default::default()
+        };
+        scanner::scan_tokens_with_options(self, source, &opts)
+    }
+
+    /// An interpreter over `env` configured from the options.
+    fn interpreter<'e>(&self, env: &'e mut Enviornment) -> Result<Interpreter<'e>> {
+        let mut interpreter = Interpreter::new_with_env(env);
+        interpreter.set_strict_concat(self.opts.strict_concat);
+        interpreter.set_strict_init(self.opts.strict_init);
+        interpreter.set_memory_limit(self.opts.memory_limit);
+        interpreter.set_step_limit(self.opts.max_steps);
+        interpreter.set_loop_limit(self.opts.max_loop_iterations);
+        interpreter.set_allocation_limit(self.opts.max_allocations);
+        interpreter.set_time_limit(self.opts.timeout_ms.map(Duration::from_millis));
+        if let Some(depth) = self.opts.max_call_depth {
+            interpreter.set_max_call_depth(depth);
+        }
+        if self.opts.profile {
+            interpreter.set_profiler(self.profiler.clone());
+        }
+        if let Some(output) = &self.output {
+            interpreter.set_output(output.clone());
+        }
+        interpreter.set_warnings(Rc::new(RefCell::new(RuntimeWarnings {
+            errors: self.errors.clone(),
+            json: self.opts.diagnostics_json,
+            hidden: self.opts.no_warnings,
+        })));
+        interpreter.set_math_check(match (self.opts.strict_math, self.opts.check_math) {
+            (true, _) => MathCheck::Error,
+            (false, true) => MathCheck::Warn,
+            (false, false) => MathCheck::Off,
+        });
+        if let Some(token) = &self.cancel {
+            interpreter = interpreter.with_cancel_token(token.clone());
+        }
+        if let Some(path) = &self.opts.record_execution {
+            interpreter.set_recorder(ExecutionRecorder::append_to(path)?);
+        }
+        Ok(interpreter)
+    }
+
+    /// Scan and parse `source` as a single expression, reusing the result
+    /// if the same text was parsed before.
+    fn parse_expression_cached(&mut self, source: &str) -> Result<Rc<Expr>> {
+        if let Some(expr) = self.expr_cache.get(source) {
+            return Ok(expr.clone());
+        }
+        self.has_error = false;
+        let tokens = self.scan(source);
+        if self.check_err().is_err() {
+            return Err(anyhow!("failed to scan")).context(LoxScanError {});
+        }
+        let tok = tokens?;
+        let mut tok = tok.iter().peekable();
+        let expr = parser::Parser::new(&mut tok, self).parse_expression();
+        if self.check_err().is_err() {
+            return Err(anyhow!("failed to parse")).context(LoxParseError {});
+        }
+        let mut expr = expr?;
+        Resolver::new(self).resolve_expr(&mut expr);
+        if self.check_err().is_err() {
+            return Err(anyhow!("failed to resolve")).context(LoxParseError {});
+        }
+        let expr = Rc::new(expr);
+        self.expr_cache.insert(source.to_owned(), expr.clone());
+        Ok(expr)
+    }
+
+    /// Evaluate each source as an expression, in order, against one global
+    /// environment that persists across calls. Meant for hosts evaluating
+    /// many small formulas, where per-run setup would otherwise dominate.
+    pub fn eval_many(&mut self, sources: &[&str]) -> Vec<Result<Object>> {
+        let exprs: Vec<Result<Rc<Expr>>> = sources
+            .iter()
+            .map(|source| self.parse_expression_cached(source))
+            .collect();
+        let mut env = std::mem::take(&mut self.eval_env);
+        let results = match self.interpreter(&mut env) {
+            Ok(mut interpreter) => exprs
+                .into_iter()
+                .map(|expr| expr.and_then(|expr| interpreter.evaluate(&expr)))
+                .collect(),
+            Err(e) => {
+                let msg = e.to_string();
+                sources.iter().map(|_| Err(anyhow!("{}", msg))).collect()
+            }
+        };
+        self.eval_env = env;
+        results
+    }
+
+    /// Run `source` against `env`. A runtime error is reported to the error
+    /// sink and returned; `downcast_ref::<LoxRuntimeError>()` recovers it.
+    pub fn run_with_env(&mut self, source: String, env: &mut Enviornment) -> Result<()> {
+        self.run_observing(Program::Source(source), env, None)
+    }
+
+    fn run_observing(
+        &mut self,
+        program: Program,
+        env: &mut Enviornment,
+        observer: Option<Observer>,
+    ) -> Result<()> {
+        let ast = match program {
+            Program::Source(source) if self.opts.tokens_json => {
+                let tokens = self.scan(&source);
+                if self.check_err().is_err() {
+                    return Err(anyhow!("failed to scan")).context(LoxScanError {});
+                }
+                return self.write_output(&format!("{}\n", tokens_json(&source, &tokens?)));
+            }
+            Program::Source(source) => self.parse(&source)?,
+            Program::Ast(ast) => {
+                // There is no text to quote in diagnostics.
+                self.source.clear();
+                self.resolve(ast)?
+            }
+        };
+        let mut interpreter = self.interpreter(env)?;
+        if let Some(observer) = observer {
+            interpreter.set_observer(observer);
+        }
+        let rte = interpreter.interpret(&ast);
+        // println!("{:?}", rte);
+        if let Err(err) = &rte {
+            interpreter.run_error_hooks(err);
+            self.report_runtime_error(err);
+            return rte;
+        }
+
+        self.check_err()
+    }
+
+    /// Call the `--entry` function `name`, reporting its errors as
+    /// `run_observing` does the top-level code's.
+    fn run_entry(
+        &mut self,
+        name: &str,
+        env: &mut Enviornment,
+        observer: Option<Observer>,
+    ) -> Result<()> {
+        let mut interpreter = self.interpreter(env)?;
+        if let Some(observer) = observer {
+            interpreter.set_observer(observer);
+        }
+        let rv = interpreter.call_entry(name, &self.args).map(|_| ());
+        if let Err(err) = &rv {
+            match err.downcast_ref::<LoxEntryError>() {
+                Some(e) if self.opts.diagnostics_json => {
+                    let diagnostic = Diagnostic::new(0, 0, 0..0, &e.to_string());
+                    self.errors.write(&format!("{}\n", diagnostic.to_json()));
+                }
+                Some(e) => self.errors.write(&format!("{}\n", e)),
+                None => {
+                    interpreter.run_error_hooks(err);
+                    self.report_runtime_error(err);
+                }
+            }
+        }
+        rv
+    }
+
+    /// REPL entry point: if `source` is a single expression statement,
+    /// evaluate it and return its value, otherwise run it like
+    /// `run_with_env`.
+    pub fn run_expr_or_stmts(
+        &mut self,
+        source: String,
+        env: &mut Enviornment,
+    ) -> Result<Option<Object>> {
+        if self.opts.tokens_json {
+            return self.run_with_env(source, env).map(|_| None);
+        }
+        let ast = self.parse_with(&source, true)?;
+        let mut interpreter = self.interpreter(env)?;
+        let rv = match ast.as_slice() {
+            [Stmt::Expr(expr)] => interpreter.evaluate(expr).map(Some),
+            _ => interpreter.interpret(&ast).map(|_| None),
+        };
+        if let Err(err) = &rv {
+            interpreter.run_error_hooks(err);
+            self.report_runtime_error(err);
+        }
+        rv
+    }
+
+    /// Run `source` in a fresh environment and bundle the globals it defines
+    /// into a namespace object called `name`.
+    pub fn run_as_namespace(&mut self, source: String, name: &str) -> Result<Object> {
+        let mut env = Enviornment::new();
+        stdlib::install(&mut env);
+        let builtins: HashSet<Symbol> = env.globals().into_iter().map(|(k, _)| k).collect();
+        self.run_with_env(source, &mut env)?;
+        let members = env
+            .globals()
+            .into_iter()
+            .filter(|(k, _)| !builtins.contains(k))
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        Ok(Object::Namespace(Rc::new(LoxNamespace {
+            name: name.to_owned(),
+            members,
+        })))
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new(LoxOptions::default())
+    }
+}
+
+impl LoxError for Lox {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        match diagnostic.is_error() {
+            true => self.has_error = true,
+            false if self.opts.no_warnings => return,
+            false => {}
+        }
+        if self.opts.diagnostics_json {
+            self.errors.write(&format!("{}\n", diagnostic.to_json()));
+            return;
+        }
+        let mut text = format!("{}\n", diagnostic);
+        // Keep piped output to the one line per error that tools expect.
+        if self.errors.is_terminal() && !self.source.is_empty() {
+            text += &diagnostic.excerpt(&self.source);
+            text += &code_note(diagnostic.code);
+        }
+        self.errors.write(&text);
+    }
+
+    fn has_error(&self) -> bool {
+        self.has_error
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// A sink the test can read back.
+    #[derive(Clone, Default)]
+    pub(crate) struct Captured(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Captured {
+        pub(crate) fn text(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_run_observed() {
+        let mut lox = Lox::default();
+        lox.set_output(Captured::default());
+        let source = "fun double(x) { return x * 2; }\n\
+                      var i = 0;\n\
+                      while (i < 2) i = i + double(1);\n\
+                      var n;";
+        let (mut lines, mut loops, mut calls, mut vars) = (vec![], 0, vec![], vec![]);
+        lox.run_observed(source.to_owned(), |event| match event {
+            Event::StatementExecuted { stmt, span } => {
+                lines.push(span.line);
+                loops += matches!(stmt, Stmt::While(..)) as usize;
+            }
+            Event::FunctionEntered { name, args, .. } => calls.push((name.to_string(), args.len())),
+            Event::VariableDefined { name, value, .. } => vars.push(format!("{}={}", name, value)),
+        })
+        .unwrap();
+        assert_eq!(lines, vec![1, 2, 3, 3, 1, 1, 4]);
+        assert_eq!(loops, 1);
+        assert_eq!(calls, vec![("double".to_owned(), 1)]);
+        assert_eq!(vars, vec!["i=0", "n=nil"]);
+    }
+
+    #[test]
+    fn test_runtime_error_to_sink() {
+        let errors = Captured::default();
+        let mut lox = Lox::default();
+        lox.set_error_sink(errors.clone());
+        let err = lox
+            .run_with_env("var a = 1;\n-\"a\";".to_owned(), &mut Enviornment::new())
+            .unwrap_err();
+        let rte = err.downcast_ref::<LoxRuntimeError>().unwrap();
+        assert_eq!(rte.message(), "Operand must be a number.");
+        assert_eq!(rte.span().line, 2);
+        assert_eq!(
+            errors.text(),
+            "Operand must be a number.\n[line 2] in script\n"
+        );
+    }
+
+    #[test]
+    fn test_output_and_step_limit() {
+        let output = Captured::default();
+        let errors = Captured::default();
+        let mut lox = Lox::new(LoxOptions {
+            max_steps: Some(10),
+            ..Default::default()
+        });
+        lox.set_output(output.clone());
+        lox.set_error_sink(errors.clone());
+        let err = lox
+            .run("var i = 0;\nwhile (true) {\n  print i;\n  i = i + 1;\n}".to_owned())
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LoxRuntimeError>().unwrap().message(),
+            "Step limit exceeded (10 statements)."
+        );
+        assert_eq!(output.text(), "0\n1\n2\n");
+        assert!(errors.text().starts_with("Step limit exceeded"));
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        let run = |opts: LoxOptions, source: &str| {
+            let output = Captured::default();
+            let mut lox = Lox::new(opts);
+            lox.set_output(output.clone());
+            lox.set_error_sink(Captured::default());
+            let err = lox.run(source.to_owned()).unwrap_err();
+            let message = err.downcast_ref::<LoxRuntimeError>().unwrap().message();
+            (message, output.text())
+        };
+        let (message, output) = run(
+            LoxOptions {
+                max_loop_iterations: Some(5),
+                ..Default::default()
+            },
+            "for (var x in [1, 2, 3]) print x;\nvar i = 0;\nwhile (true) i = i + 1;",
+        );
+        assert_eq!(message, "Loop limit exceeded (5 iterations).");
+        assert_eq!(output, "1\n2\n3\n");
+
+        // The limit stops the program from inside a function too.
+        let (message, output) = run(
+            LoxOptions {
+                max_allocations: Some(3),
+                ..Default::default()
+            },
+            "fun grow() { var s = \"\"; while (true) s = s + \"x\"; }\n\
+             print grow();\nprint 1 + 2;\nprint [];",
+        );
+        assert_eq!(message, "Allocation limit exceeded (3 objects).");
+        assert_eq!(output, "");
+
+        let (message, _) = run(
+            LoxOptions {
+                timeout_ms: Some(50),
+                ..Default::default()
+            },
+            "while (true) {}",
+        );
+        assert_eq!(message, "Time limit exceeded (50 ms).");
+
+        // A catch gets them like any runtime error, though a spent budget
+        // stays spent.
+        let caught = |opts: LoxOptions, source: &str| {
+            let output = Captured::default();
+            let mut lox = Lox::new(opts);
+            lox.set_output(output.clone());
+            lox.run(source.to_owned()).unwrap();
+            output.text()
+        };
+        let output = caught(
+            LoxOptions {
+                max_loop_iterations: Some(5),
+                ..Default::default()
+            },
+            "fun spin() { while (true) {} }\n\
+             try { spin(); } catch (e) { print e; }\nprint \"after\";",
+        );
+        assert_eq!(output, "Loop limit exceeded (5 iterations).\nafter\n");
+        let output = caught(
+            LoxOptions {
+                max_allocations: Some(3),
+                ..Default::default()
+            },
+            "try { var s = \"\"; while (true) s = s + \"x\"; } catch (e) { print e; }",
+        );
+        assert_eq!(output, "Allocation limit exceeded (3 objects).\n");
+        // Leaving the block frees what it held, so the program can go on.
+        let output = caught(
+            LoxOptions {
+                memory_limit: Some(1000),
+                ..Default::default()
+            },
+            "try { var s = \"x\"; while (true) s = s + s; } catch (e) { print e; }\n\
+             print \"after\";",
+        );
+        assert_eq!(output, "Out of memory (limit 1000 bytes).\nafter\n");
+        // Every statement counts, so the handler's first one fails again.
+        let (message, output) = run(
+            LoxOptions {
+                max_steps: Some(10),
+                ..Default::default()
+            },
+            "try { while (true) {} } catch (e) { print e; }",
+        );
+        assert_eq!(message, "Step limit exceeded (10 statements).");
+        assert_eq!(output, "");
+    }
+
+    #[test]
+    fn test_cancel_token() {
+        let output = Captured::default();
+        let token = CancelToken::new();
+        let mut lox = Lox::default();
+        lox.set_output(output.clone());
+        lox.set_cancel_token(token.clone());
+        let canceller = token.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            canceller.cancel();
+        });
+        // Cancelling inside a function stops its callers too.
+        let source = "fun spin() { while (true) {} }\nprint spin();\nprint \"after\";";
+        let err = lox.run(source.to_owned()).unwrap_err();
+        thread.join().unwrap();
+        assert!(err.is::<LoxInterrupted>());
+        assert_eq!(output.text(), "");
+
+        assert!(lox.run("print 1;".to_owned()).is_err());
+        token.reset();
+        lox.run("print 1;".to_owned()).unwrap();
+        assert_eq!(output.text(), "1\n");
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let output = Captured::default();
+        let errors = Captured::default();
+        let mut lox = Lox::new(LoxOptions {
+            max_call_depth: Some(20),
+            ..Default::default()
+        });
+        lox.set_output(output.clone());
+        lox.set_error_sink(errors.clone());
+        let source = "fun f(n) {\n  print n;\n  f(n + 1);\n}\nf(1);\nprint \"after\";";
+        let err = lox.run(source.to_owned()).unwrap_err();
+        assert!(err.is::<LoxStackOverflow>());
+        assert_eq!(
+            err.downcast_ref::<LoxRuntimeError>().unwrap().message(),
+            "Stack overflow."
+        );
+        assert_eq!(output.text().lines().last(), Some("20"));
+        assert_eq!(
+            errors.text(),
+            "Stack overflow.\n[line 3] in f\n[previous line repeated 19 more times]\n\
+             [line 5] in script\n"
+        );
+    }
+
+    #[test]
+    fn test_profile() {
+        let errors = Captured::default();
+        let mut lox = Lox::new(LoxOptions {
+            profile: true,
+            ..Default::default()
+        });
+        lox.set_output(Captured::default());
+        lox.set_error_sink(errors.clone());
+        let source = "fun fib(n) {\n  if (n < 2) return n;\n  return fib(n - 1) + fib(n - 2);\n}\nprint fib(10);\nprint len(\"ab\");";
+        lox.run(source.to_owned()).unwrap();
+        let report = errors.text();
+        let calls = |label: &str| {
+            let line = report.lines().find(|l| l.ends_with(label)).unwrap();
+            line.split_whitespace().next().unwrap().to_owned()
+        };
+        assert_eq!(calls("fib (line 1)"), "177");
+        assert_eq!(calls("len (native)"), "1");
+    }
+
+    #[test]
+    fn test_script_args() {
+        let output = Captured::default();
+        let mut lox = Lox::default();
+        lox.set_output(output.clone());
+        lox.set_args(vec!["a".to_owned(), "b c".to_owned()]);
+        let source = "var a = args();\npush(a, 1);\nprint a;\nprint args();\nprint len(args());";
+        lox.run(source.to_owned()).unwrap();
+        assert_eq!(output.text(), "[a, b c, 1]\n[a, b c]\n2\n");
+    }
+
+    #[test]
+    fn test_exit() {
+        let output = Captured::default();
+        let mut lox = Lox::default();
+        lox.set_output(output.clone());
+        let source = "onExit(fun (s) { print s; });\n\
+                      fun f() { exit(3); print 1; }\n\
+                      print [f()];\n\
+                      print 2;";
+        let err = lox.run(source.to_owned()).unwrap_err();
+        assert_eq!(err.downcast_ref::<LoxExit>().unwrap().0, 3);
+        assert_eq!(output.text(), "3\n");
+
+        // Handlers see the status the process exits with.
+        let output = Captured::default();
+        let mut lox = Lox::default();
+        lox.set_output(output.clone());
+        lox.set_error_sink(Captured::default());
+        let source = "onExit(fun (s) { print s; });\n\
+                      fun f() { print -nil; }\n\
+                      f();";
+        lox.run(source.to_owned()).unwrap_err();
+        assert_eq!(output.text(), "70\n");
+    }
+
+    #[test]
+    fn test_math_warnings_go_to_error_sink() {
+        let output = Captured::default();
+        let errors = Captured::default();
+        let mut lox = Lox::new(LoxOptions {
+            check_math: true,
+            ..Default::default()
+        });
+        lox.set_output(output.clone());
+        lox.set_error_sink(errors.clone());
+        lox.run("print 1 / 0;\nprint 0 / 0;".to_owned()).unwrap();
+        assert_eq!(output.text(), "Infinity\nNaN\n");
+        assert_eq!(
+            errors.text(),
+            "[line 1] Warning: 1 / 0 produced Infinity.\n[line 2] Warning: 0 / 0 produced NaN.\n"
+        );
+
+        let errors = Captured::default();
+        let mut lox = Lox::new(LoxOptions {
+            check_math: true,
+            diagnostics_json: true,
+            ..Default::default()
+        });
+        lox.set_output(Captured::default());
+        lox.set_error_sink(errors.clone());
+        lox.run("print 1 / 0;".to_owned()).unwrap();
+        assert!(errors
+            .text()
+            .contains("\"kind\":\"warning\",\"code\":\"E1014\""));
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let kind = |source: &str| {
+            // Shallow enough for a test thread's stack.
+            let mut lox = Lox::new(LoxOptions {
+                max_call_depth: Some(20),
+                ..Default::default()
+            });
+            lox.set_output(Captured::default());
+            lox.set_error_sink(Captured::default());
+            let err = lox.run(source.to_owned()).unwrap_err();
+            // However many contexts callers add on the way out.
+            let err = err.context("running a script").context("in a test");
+            LoxErrorKind::of(&err).map(LoxErrorKind::exit_code)
+        };
+        assert_eq!(kind("print \"a;"), Some(65));
+        assert_eq!(kind("print ;"), Some(65));
+        assert_eq!(kind("{ var a = 1; var a = 2; }"), Some(65));
+        assert_eq!(kind("print -nil;"), Some(70));
+        assert_eq!(kind("fun f() { print -nil; } f();"), Some(70));
+        assert_eq!(kind("fun f() { f(); } f();"), Some(70));
+        assert_eq!(kind("exit(3);"), Some(3));
+        assert_eq!(LoxErrorKind::of(&anyhow!("Could not read x.lox")), None);
+    }
+
+    #[test]
+    fn test_dumps_to_output() {
+        let output = Captured::default();
+        let mut lox = Lox::new(LoxOptions {
+            debug_ast_filter: Some("missing".to_owned()),
+            ..Default::default()
+        });
+        lox.set_output(output.clone());
+        lox.run("print 1;".to_owned()).unwrap();
+        assert_eq!(output.text(), "AST: nothing named 'missing' declared\n1\n");
+
+        let output = Captured::default();
+        let mut lox = Lox::new(LoxOptions {
+            tokens_json: true,
+            ..Default::default()
+        });
+        lox.set_output(output.clone());
+        lox.run("nil".to_owned()).unwrap();
+        assert!(output.text().starts_with("[\n  {\"type\":\"NIL\""));
+        assert!(output.text().ends_with("}\n]\n"));
+
+        let output = Captured::default();
+        let mut lox = Lox::new(LoxOptions {
+            print_rpn: true,
+            ..Default::default()
+        });
+        lox.set_output(output.clone());
+        lox.run("print (1 + 2) * (4 - 3);".to_owned()).unwrap();
+        assert_eq!(output.text(), "RPN: 1 2 + 4 3 - *\n3\n");
+    }
+
+    #[test]
+    fn test_parse_error_to_sink() {
+        let errors = Captured::default();
+        let mut lox = Lox::default();
+        lox.set_error_sink(errors.clone());
+        assert!(lox.parse("print;").is_err());
+        assert_eq!(errors.text(), "[line 1] Error: Expect expression.\n");
+    }
+}
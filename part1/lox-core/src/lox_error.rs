@@ -0,0 +1,319 @@
+use crate::error_codes::ErrorCode;
+use crate::tokens::{json_string, Token, TokenType};
+use std::fmt;
+use std::ops::Range;
+
+/// How bad a diagnostic is. Only errors stop a program from running.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Severity {
+    #[default]
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "Error"),
+            Self::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+/// A problem found in the source, with enough position to point at it.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: i32,
+    /// 1-based, in characters.
+    pub column: usize,
+    /// Byte range of the offending text.
+    pub span: Range<usize>,
+    /// Where in the token stream, such as " at end"; empty when the line says enough.
+    pub location: String,
+    pub message: String,
+    /// Constructs the parser was inside when it gave up, outermost first.
+    pub context: Vec<Context>,
+    /// The catalog entry for the message, if it has one.
+    pub code: Option<ErrorCode>,
+}
+
+/// Something being parsed around an error, such as "the condition of this
+/// 'if'", and the token that started it.
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub line: i32,
+    pub column: usize,
+    pub span: Range<usize>,
+    pub what: String,
+}
+
+impl Context {
+    pub fn at(token: &Token, what: &str) -> Self {
+        Self {
+            line: token.line(),
+            column: token.span.column,
+            span: token.span.start..token.span.end,
+            what: what.to_owned(),
+        }
+    }
+}
+
+/// Columns from one tab stop to the next in excerpts.
+pub const TAB_WIDTH: usize = 4;
+
+/// `text` with each tab replaced by spaces up to the next tab stop.
+fn expand_tabs(text: &str) -> String {
+    let mut out = String::new();
+    for c in text.chars() {
+        match c {
+            '\t' => out += &" ".repeat(TAB_WIDTH - out.chars().count() % TAB_WIDTH),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The source line containing `span`, with carets under it starting at
+/// `column`.
+fn excerpt(source: &str, span: &Range<usize>, column: usize) -> String {
+    let start = span.start.min(source.len());
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = source[start..]
+        .find('\n')
+        .map_or(source.len(), |i| start + i);
+    // The `\r` of a CRLF would send the terminal back over the line.
+    let line_end = match source[line_start..line_end].ends_with('\r') {
+        true => line_end - 1,
+        false => line_end,
+    };
+    let text = &source[line_start..line_end];
+    // Underline at least one column, and no further than the line's end.
+    let end = span.end.min(line_end).max(start);
+    // Count columns as shown, where a tab is wider than one.
+    let before: String = text.chars().take(column.saturating_sub(1)).collect();
+    let indent = expand_tabs(&before).chars().count();
+    let width = expand_tabs(&(before + &source[start..end]))
+        .chars()
+        .count()
+        .saturating_sub(indent)
+        .max(1);
+    // Numbered from the source: a string's token line is where it ends.
+    let gutter = (source[..line_start].matches('\n').count() + 1).to_string();
+    format!(
+        "{gutter} | {text}\n{pad} | {spaces}{carets}\n",
+        gutter = gutter,
+        text = expand_tabs(text),
+        pad = " ".repeat(gutter.len()),
+        spaces = " ".repeat(indent),
+        carets = "^".repeat(width),
+    )
+}
+
+impl Diagnostic {
+    pub fn new(line: i32, column: usize, span: Range<usize>, message: &str) -> Self {
+        Self {
+            severity: Severity::Error,
+            line,
+            column,
+            span,
+            location: String::new(),
+            message: message.to_owned(),
+            context: vec![],
+            code: ErrorCode::for_message(message),
+        }
+    }
+
+    /// This diagnostic as a warning, such as a lint finding, which is shown
+    /// but lets the program run.
+    pub fn into_warning(self) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..self
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// A diagnostic pointing at `token`.
+    pub fn at(token: &Token, message: &str) -> Self {
+        let mut diagnostic = Self::new(
+            token.line(),
+            token.span.column,
+            token.span.start..token.span.end,
+            message,
+        );
+        if token.token_type == TokenType::EOF {
+            diagnostic.location = " at end".to_owned();
+        }
+        diagnostic
+    }
+
+    /// The source line containing the span, with carets under the span,
+    /// followed by where each enclosing construct started, innermost first.
+    pub fn excerpt(&self, source: &str) -> String {
+        let mut out = excerpt(source, &self.span, self.column);
+        for context in self.context.iter().rev() {
+            out += &format!(
+                "note: while parsing {} (line {})\n",
+                context.what, context.line
+            );
+            out += &excerpt(source, &context.span, context.column);
+        }
+        out
+    }
+}
+
+impl Diagnostic {
+    /// One JSON object, for tools reading `--diagnostics-json`.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"kind\":{},\"code\":{},\"line\":{},\"column\":{},\"start\":{},\"end\":{},\"location\":{},\"message\":{}}}",
+            json_string(&self.severity.to_string().to_lowercase()),
+            self.code.map_or("null".to_owned(), |c| json_string(c.code())),
+            self.line,
+            self.column,
+            self.span.start,
+            self.span.end,
+            json_string(self.location.trim_start()),
+            json_string(&self.message)
+        )
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[line {}] {}{}: {}",
+            self.line, self.severity, self.location, self.message
+        )
+    }
+}
+
+pub trait LoxError {
+    fn report(&mut self, diagnostic: Diagnostic);
+
+    fn has_error(&self) -> bool;
+}
+
+/// Keeps what is reported, for callers that want the diagnostics rather
+/// than errors printed as they are found. Only errors count as failure.
+#[derive(Debug, Default)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    /// `result`, unless an error was reported while producing it. Warnings
+    /// are dropped.
+    pub fn check<T>(self, result: anyhow::Result<T>) -> Result<T, Vec<Diagnostic>> {
+        match (!self.has_error(), result) {
+            (true, Ok(v)) => Ok(v),
+            (true, Err(e)) => Err(vec![Diagnostic::new(0, 0, 0..0, &e.to_string())]),
+            (false, _) => Err(self.0),
+        }
+    }
+}
+
+impl LoxError for Diagnostics {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic);
+    }
+
+    fn has_error(&self) -> bool {
+        self.0.iter().any(Diagnostic::is_error)
+    }
+}
+
+/// Source that could not be scanned or parsed, with every error found in
+/// it, one per line when shown.
+#[derive(Debug)]
+pub struct LoxCompileError {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for LoxCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = self.diagnostics.iter().map(|d| d.to_string()).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_excerpt() {
+        let source = "var a = 1;\nprint a +* 2;\n";
+        let d = Diagnostic::new(2, 10, 20..21, "Expect expression.");
+        assert_eq!(d.to_string(), "[line 2] Error: Expect expression.");
+        assert_eq!(d.excerpt(source), "2 | print a +* 2;\n  |          ^\n");
+    }
+
+    #[test]
+    fn test_excerpt_crlf() {
+        let source = "var a = 1;\r\nprint a +* 2;\r\n";
+        let d = Diagnostic::new(2, 10, 21..22, "Expect expression.");
+        assert_eq!(d.excerpt(source), "2 | print a +* 2;\n  |          ^\n");
+    }
+
+    #[test]
+    fn test_excerpt_tabs() {
+        let source = "\tprint\ta +* 2;";
+        let d = Diagnostic::new(1, 11, 10..11, "Expect expression.");
+        assert_eq!(
+            d.excerpt(source),
+            "1 |     print   a +* 2;\n  |                ^\n"
+        );
+        // A tab under the carets widens them.
+        let d = Diagnostic::new(1, 2, 1..7, "Expect expression.");
+        assert_eq!(
+            d.excerpt(source),
+            "1 |     print   a +* 2;\n  |     ^^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let mut d = Diagnostic::new(2, 10, 20..21, "Expect ';' after \"x\".");
+        d.location = " at end".to_owned();
+        assert_eq!(
+            d.to_json(),
+            r#"{"kind":"error","code":"E0103","line":2,"column":10,"start":20,"end":21,"location":"at end","message":"Expect ';' after \"x\"."}"#
+        );
+    }
+
+    #[test]
+    fn test_at_end() {
+        let source = "print 1 +\n\n";
+        let tokens = crate::scanner::scan_tokens(&mut crate::lox::Lox::default(), source).unwrap();
+        let d = Diagnostic::at(tokens.last().unwrap(), "Expect expression.");
+        assert_eq!(d.to_string(), "[line 1] Error at end: Expect expression.");
+        assert_eq!(d.excerpt(source), "1 | print 1 +\n  |          ^\n");
+    }
+
+    #[test]
+    fn test_context_notes() {
+        let source = "{\n  if (a print a;\n";
+        let tokens = crate::scanner::scan_tokens(&mut crate::lox::Lox::default(), source).unwrap();
+        let mut d = Diagnostic::at(&tokens[4], "Expect ')' after if condition.");
+        d.context = vec![
+            Context::at(&tokens[0], "this block"),
+            Context::at(&tokens[1], "the condition of this 'if'"),
+        ];
+        assert_eq!(
+            d.to_string(),
+            "[line 2] Error: Expect ')' after if condition."
+        );
+        assert_eq!(
+            d.excerpt(source),
+            "2 |   if (a print a;\n  |         ^^^^^\n\
+             note: while parsing the condition of this 'if' (line 2)\n\
+             2 |   if (a print a;\n  |   ^^\n\
+             note: while parsing this block (line 1)\n\
+             1 | {\n  | ^\n"
+        );
+    }
+}
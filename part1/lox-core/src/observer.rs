@@ -0,0 +1,30 @@
+use crate::interpreter::Object;
+use crate::parser::Stmt;
+use crate::tokens::Span;
+
+/// Something a running program did, as told to the observer given to
+/// `Lox::run_observed`. Tools such as coverage reports, tracers or graders
+/// build on these rather than on the interpreter's internals.
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// Execution reached `stmt`, at `span`. Compound statements such as
+    /// loops are reported before the statements in them.
+    StatementExecuted { stmt: &'a Stmt, span: Span },
+    /// A Lox function (not a native) was called with `args`. `span` is its
+    /// name in the declaration, or a lambda's `fun` keyword.
+    FunctionEntered {
+        name: &'a str,
+        args: &'a [Object],
+        span: Span,
+    },
+    /// `var` or a `for ... in` loop gave `name` its first value, `nil` for
+    /// a declaration without an initializer.
+    VariableDefined {
+        name: &'a str,
+        value: &'a Object,
+        span: Span,
+    },
+}
+
+/// Receives the events of a run, in the order they happen.
+pub type Observer<'a> = &'a mut dyn FnMut(&Event);
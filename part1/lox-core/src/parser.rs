@@ -0,0 +1,1422 @@
+use crate::ast_printer;
+use crate::intern::Symbol;
+use crate::lox_error::{Context, Diagnostic, Diagnostics, LoxError};
+use crate::resolver::Resolver;
+use crate::tokens::{reserved_word, Span, Token, TokenType};
+use anyhow::anyhow;
+use anyhow::Result;
+use std::fmt;
+use std::iter::Iterator;
+use std::iter::Peekable;
+use std::mem;
+use std::rc::Rc;
+use std::slice::Iter;
+
+/// A name as written in the source. Every use of the same name in a parse
+/// shares one allocation.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ident {
+    pub name: Symbol,
+    pub span: Span,
+    /// Where a use of a local variable finds it, filled in by the resolver.
+    /// `None` for globals and declarations.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub slot: Option<Slot>,
+}
+
+/// A local variable's place: `depth` scopes out from the current one, at
+/// `index` among that scope's slots.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Slot {
+    pub depth: usize,
+    pub index: usize,
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpKind {
+    Comma,
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    IntDivide,
+    Negate,
+    Not,
+}
+
+impl OpKind {
+    fn from_token(token_type: &TokenType, unary: bool) -> Self {
+        match token_type {
+            TokenType::COMMA => Self::Comma,
+            TokenType::OR => Self::Or,
+            TokenType::AND => Self::And,
+            TokenType::EQUAL_EQUAL => Self::Equal,
+            TokenType::BANG_EQUAL => Self::NotEqual,
+            TokenType::LESS => Self::Less,
+            TokenType::LESS_EQUAL => Self::LessEqual,
+            TokenType::GREATER => Self::Greater,
+            TokenType::GREATER_EQUAL => Self::GreaterEqual,
+            TokenType::PLUS => Self::Add,
+            TokenType::MINUS if unary => Self::Negate,
+            TokenType::MINUS => Self::Subtract,
+            TokenType::STAR => Self::Multiply,
+            TokenType::SLASH => Self::Divide,
+            TokenType::PERCENT => Self::Remainder,
+            TokenType::TILDE_SLASH => Self::IntDivide,
+            TokenType::BANG => Self::Not,
+            tt => unreachable!("{:?} is not an operator", tt),
+        }
+    }
+}
+
+impl fmt::Display for OpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Self::Comma => ",",
+            Self::Or => "||",
+            Self::And => "&&",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+            Self::Add => "+",
+            Self::Subtract | Self::Negate => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Remainder => "%",
+            Self::IntDivide => "~/",
+            Self::Not => "!",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Operator {
+    pub kind: OpKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Literal {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(Rc<str>),
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Nil => write!(f, "nil"),
+            Self::Boolean(b) => write!(f, "{}", b),
+            Self::Number(n) => write!(f, "{}", n),
+            Self::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    Binary(Box<Expr>, Operator, Box<Expr>),
+    Unary(Operator, Box<Expr>),
+    Literal(Literal, Span),
+    Grouping(Box<Expr>),
+    Variable(Ident),
+    Assign(Ident, Box<Expr>),
+    Logical(Box<Expr>, Operator, Box<Expr>),
+    /// The span is the closing `)`'s, where jlox reports call errors.
+    Call(Box<Expr>, Span, Vec<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+    /// Spans of `[`, for lists, indexing and slicing, point at the bracket.
+    List(Span, Vec<Expr>),
+    Index(Box<Expr>, Span, Box<Expr>),
+    IndexSet(Box<Expr>, Span, Box<Expr>, Box<Expr>),
+    Slice(Box<Expr>, Span, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// The span is the `fun` keyword's.
+    Lambda(Span, Vec<Ident>, Box<Stmt>),
+    Get(Box<Expr>, Ident),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stmt {
+    Expr(Expr),
+    Print(Expr),
+    Var(Ident, Option<Expr>),
+    Block(Vec<Stmt>),
+    If(Expr, Box<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Box<Stmt>),
+    ForIn(Ident, Expr, Box<Stmt>),
+    Function(Ident, Vec<Ident>, Box<Stmt>),
+    /// The span is the `return` keyword's.
+    Return(Span, Option<Expr>),
+    /// The span is the `throw` keyword's.
+    Throw(Span, Expr),
+    /// The `try` keyword's span, the block tried, and the variable and
+    /// block of the `catch`.
+    Try(Span, Box<Stmt>, Ident, Box<Stmt>),
+}
+
+impl Expr {
+    /// Where the expression's operator or first token sits, for diagnostics.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Binary(_, op, _) | Self::Unary(op, _) | Self::Logical(_, op, _) => op.span,
+            Self::Variable(n) | Self::Assign(n, _) | Self::Get(_, n) => n.span,
+            Self::Literal(_, s)
+            | Self::List(s, _)
+            | Self::Index(_, s, _)
+            | Self::IndexSet(_, s, _, _)
+            | Self::Slice(_, s, _, _)
+            | Self::Lambda(s, _, _)
+            | Self::Call(_, s, _) => *s,
+            Self::Grouping(e) | Self::Ternary(e, _, _) => e.span(),
+        }
+    }
+
+    /// Best-effort source line for runtime diagnostics.
+    pub fn line(&self) -> i32 {
+        self.span().line
+    }
+
+    /// The sub-expressions, in evaluation order.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Self::Binary(l, _, r) | Self::Logical(l, _, r) | Self::Index(l, _, r) => vec![l, r],
+            Self::Unary(_, e) | Self::Grouping(e) | Self::Assign(_, e) | Self::Get(e, _) => vec![e],
+            Self::Ternary(a, b, c) | Self::IndexSet(a, _, b, c) => vec![a, b, c],
+            Self::Call(callee, _, args) => std::iter::once(&**callee).chain(args).collect(),
+            Self::List(_, items) => items.iter().collect(),
+            Self::Slice(object, _, start, end) => std::iter::once(object)
+                .chain(start.iter().chain(end))
+                .map(|e| &**e)
+                .collect(),
+            Self::Literal(..) | Self::Variable(_) | Self::Lambda(..) => vec![],
+        }
+    }
+
+    /// The sub-expressions, to change in place.
+    pub(crate) fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Self::Binary(l, _, r) | Self::Logical(l, _, r) | Self::Index(l, _, r) => vec![l, r],
+            Self::Unary(_, e) | Self::Grouping(e) | Self::Assign(_, e) | Self::Get(e, _) => vec![e],
+            Self::Ternary(a, b, c) | Self::IndexSet(a, _, b, c) => vec![a, b, c],
+            Self::Call(callee, _, args) => std::iter::once(&mut **callee).chain(args).collect(),
+            Self::List(_, items) => items.iter_mut().collect(),
+            Self::Slice(object, _, start, end) => std::iter::once(object)
+                .chain(start.iter_mut().chain(end))
+                .map(|e| &mut **e)
+                .collect(),
+            Self::Literal(..) | Self::Variable(_) | Self::Lambda(..) => vec![],
+        }
+    }
+
+    /// Move the expression out, leaving a `nil` in its place.
+    fn take(&mut self) -> Expr {
+        mem::replace(self, Expr::Literal(Literal::Nil, Span::default()))
+    }
+
+    /// Move the sub-expressions out onto `into`.
+    fn take_children(&mut self, into: &mut Vec<Expr>) {
+        match self {
+            Self::Binary(l, _, r) | Self::Logical(l, _, r) | Self::Index(l, _, r) => {
+                into.extend([l.take(), r.take()])
+            }
+            Self::Unary(_, e) | Self::Grouping(e) | Self::Assign(_, e) | Self::Get(e, _) => {
+                into.push(e.take())
+            }
+            Self::Ternary(a, b, c) | Self::IndexSet(a, _, b, c) => {
+                into.extend([a.take(), b.take(), c.take()])
+            }
+            Self::Call(callee, _, args) => {
+                into.push(callee.take());
+                into.append(args);
+            }
+            Self::List(_, items) => into.append(items),
+            Self::Slice(object, _, start, end) => {
+                into.push(object.take());
+                into.extend(start.iter_mut().chain(end).map(|e| e.take()));
+            }
+            Self::Literal(..) | Self::Variable(_) | Self::Lambda(..) => {}
+        }
+    }
+}
+
+/// Copies from a loop rather than recursively, like `Drop`, as declaring a
+/// function copies its body.
+impl Clone for Expr {
+    fn clone(&self) -> Self {
+        enum Step<'e> {
+            Copy(&'e Expr),
+            /// Rebuild the expression from copies of its sub-expressions.
+            Build(&'e Expr),
+        }
+        let mut work = vec![Step::Copy(self)];
+        let mut copied: Vec<Expr> = vec![];
+        while let Some(step) = work.pop() {
+            match step {
+                Step::Copy(e) => {
+                    work.push(Step::Build(e));
+                    // The last is copied first, so `Build` pops the first first.
+                    work.extend(e.children().into_iter().map(Step::Copy));
+                }
+                Step::Build(e) => {
+                    let mut next = || Box::new(copied.pop().expect("sub-expression copied"));
+                    let expr = match e {
+                        Self::Binary(_, op, _) => Self::Binary(next(), *op, next()),
+                        Self::Unary(op, _) => Self::Unary(*op, next()),
+                        Self::Grouping(_) => Self::Grouping(next()),
+                        Self::Assign(n, _) => Self::Assign(n.clone(), next()),
+                        Self::Logical(_, op, _) => Self::Logical(next(), *op, next()),
+                        Self::Call(_, paren, args) => {
+                            Self::Call(next(), *paren, args.iter().map(|_| *next()).collect())
+                        }
+                        Self::Ternary(..) => Self::Ternary(next(), next(), next()),
+                        Self::List(bracket, items) => {
+                            Self::List(*bracket, items.iter().map(|_| *next()).collect())
+                        }
+                        Self::Index(_, bracket, _) => Self::Index(next(), *bracket, next()),
+                        Self::IndexSet(_, bracket, ..) => {
+                            Self::IndexSet(next(), *bracket, next(), next())
+                        }
+                        Self::Slice(_, bracket, start, end) => Self::Slice(
+                            next(),
+                            *bracket,
+                            start.as_ref().map(|_| next()),
+                            end.as_ref().map(|_| next()),
+                        ),
+                        Self::Get(_, name) => Self::Get(next(), name.clone()),
+                        Self::Literal(l, span) => Self::Literal(l.clone(), *span),
+                        Self::Variable(n) => Self::Variable(n.clone()),
+                        Self::Lambda(fun, params, body) => {
+                            Self::Lambda(*fun, params.clone(), body.clone())
+                        }
+                    };
+                    copied.push(expr);
+                }
+            }
+        }
+        copied.pop().expect("expression copied")
+    }
+}
+
+/// Frees sub-expressions from a loop rather than recursively, so dropping a
+/// tree as deep as a 100,000-term `+` chain can't overflow the stack.
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut nested = vec![];
+        self.take_children(&mut nested);
+        while let Some(mut expr) = nested.pop() {
+            // Dropped at the end of the iteration with nothing left under it.
+            expr.take_children(&mut nested);
+        }
+    }
+}
+
+/// The book's prefix form, from `ast_printer::expr`.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", ast_printer::expr(self))
+    }
+}
+
+/// A pass over expressions, with a method per kind of `Expr` so each pass
+/// doesn't repeat the match. `Expr::accept` calls the one for its kind.
+pub trait ExprVisitor<R> {
+    fn visit_binary(&mut self, left: &Expr, op: &Operator, right: &Expr) -> R;
+    fn visit_unary(&mut self, op: &Operator, operand: &Expr) -> R;
+    fn visit_literal(&mut self, value: &Literal, span: Span) -> R;
+    fn visit_grouping(&mut self, inner: &Expr) -> R;
+    fn visit_variable(&mut self, name: &Ident) -> R;
+    fn visit_assign(&mut self, name: &Ident, value: &Expr) -> R;
+    fn visit_logical(&mut self, left: &Expr, op: &Operator, right: &Expr) -> R;
+    fn visit_call(&mut self, callee: &Expr, paren: Span, args: &[Expr]) -> R;
+    fn visit_ternary(&mut self, cond: &Expr, then: &Expr, otherwise: &Expr) -> R;
+    fn visit_list(&mut self, bracket: Span, items: &[Expr]) -> R;
+    fn visit_index(&mut self, object: &Expr, bracket: Span, index: &Expr) -> R;
+    fn visit_index_set(&mut self, object: &Expr, bracket: Span, index: &Expr, value: &Expr) -> R;
+    fn visit_slice(
+        &mut self,
+        object: &Expr,
+        bracket: Span,
+        start: Option<&Expr>,
+        end: Option<&Expr>,
+    ) -> R;
+    fn visit_lambda(&mut self, fun: Span, params: &[Ident], body: &Stmt) -> R;
+    fn visit_get(&mut self, object: &Expr, name: &Ident) -> R;
+}
+
+impl Expr {
+    /// Hand this expression to the method of `visitor` for its kind.
+    pub fn accept<R>(&self, visitor: &mut impl ExprVisitor<R>) -> R {
+        match self {
+            Self::Binary(l, op, r) => visitor.visit_binary(l, op, r),
+            Self::Unary(op, e) => visitor.visit_unary(op, e),
+            Self::Literal(l, s) => visitor.visit_literal(l, *s),
+            Self::Grouping(e) => visitor.visit_grouping(e),
+            Self::Variable(n) => visitor.visit_variable(n),
+            Self::Assign(n, v) => visitor.visit_assign(n, v),
+            Self::Logical(l, op, r) => visitor.visit_logical(l, op, r),
+            Self::Call(callee, s, args) => visitor.visit_call(callee, *s, args),
+            Self::Ternary(c, t, e) => visitor.visit_ternary(c, t, e),
+            Self::List(s, items) => visitor.visit_list(*s, items),
+            Self::Index(o, s, i) => visitor.visit_index(o, *s, i),
+            Self::IndexSet(o, s, i, v) => visitor.visit_index_set(o, *s, i, v),
+            Self::Slice(o, s, a, b) => visitor.visit_slice(o, *s, a.as_deref(), b.as_deref()),
+            Self::Lambda(s, params, body) => visitor.visit_lambda(*s, params, body),
+            Self::Get(o, n) => visitor.visit_get(o, n),
+        }
+    }
+}
+
+/// `ExprVisitor`'s counterpart for statements. `Stmt::accept` calls the
+/// method for the statement's kind.
+pub trait StmtVisitor<R> {
+    fn visit_expr_stmt(&mut self, expr: &Expr) -> R;
+    fn visit_print(&mut self, expr: &Expr) -> R;
+    fn visit_var(&mut self, name: &Ident, init: Option<&Expr>) -> R;
+    fn visit_block(&mut self, stmts: &[Stmt]) -> R;
+    fn visit_if(&mut self, cond: &Expr, then: &Stmt, otherwise: Option<&Stmt>) -> R;
+    fn visit_while(&mut self, cond: &Expr, body: &Stmt) -> R;
+    fn visit_for_in(&mut self, name: &Ident, iterable: &Expr, body: &Stmt) -> R;
+    fn visit_function(&mut self, name: &Ident, params: &[Ident], body: &Stmt) -> R;
+    fn visit_return(&mut self, keyword: Span, value: Option<&Expr>) -> R;
+    fn visit_throw(&mut self, keyword: Span, value: &Expr) -> R;
+    fn visit_try(&mut self, body: &Stmt, name: &Ident, handler: &Stmt) -> R;
+}
+
+impl Stmt {
+    /// Hand this statement to the method of `visitor` for its kind.
+    pub fn accept<R>(&self, visitor: &mut impl StmtVisitor<R>) -> R {
+        match self {
+            Self::Expr(e) => visitor.visit_expr_stmt(e),
+            Self::Print(e) => visitor.visit_print(e),
+            Self::Var(n, init) => visitor.visit_var(n, init.as_ref()),
+            Self::Block(stmts) => visitor.visit_block(stmts),
+            Self::If(c, t, e) => visitor.visit_if(c, t, e.as_deref()),
+            Self::While(c, body) => visitor.visit_while(c, body),
+            Self::ForIn(n, iterable, body) => visitor.visit_for_in(n, iterable, body),
+            Self::Function(n, params, body) => visitor.visit_function(n, params, body),
+            Self::Return(s, v) => visitor.visit_return(*s, v.as_ref()),
+            Self::Throw(s, v) => visitor.visit_throw(*s, v),
+            Self::Try(_, body, n, handler) => visitor.visit_try(body, n, handler),
+        }
+    }
+
+    /// Best-effort source location, `None` for empty blocks.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Expr(e) | Self::Print(e) | Self::If(e, _, _) | Self::While(e, _) => {
+                Some(e.span())
+            }
+            Self::Var(n, _) | Self::ForIn(n, _, _) | Self::Function(n, _, _) => Some(n.span),
+            Self::Return(s, _) | Self::Throw(s, _) | Self::Try(s, _, _, _) => Some(*s),
+            Self::Block(stmts) => stmts.first().and_then(Stmt::span),
+        }
+    }
+
+    /// Best-effort source line, `None` for empty blocks.
+    pub fn line(&self) -> Option<i32> {
+        self.span().map(|s| s.line)
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", ast_printer::stmt(self))
+    }
+}
+
+/// What a `function()` parse is for; selects the wording of its errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FunctionKind {
+    Function,
+    Lambda,
+}
+
+impl fmt::Display for FunctionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Function => write!(f, "function"),
+            Self::Lambda => write!(f, "lambda"),
+        }
+    }
+}
+
+/// Most parameters a function can declare, or arguments a call can pass.
+const MAX_ARGS: usize = 255;
+
+/// Deepest statements and expressions can nest, so parsing them (and
+/// everything that walks the tree after) can't overflow the stack.
+const MAX_NESTING: usize = 256;
+
+/// Parse and resolve `tokens`, as from `Scanner::scan`, into a program, or
+/// give every error found in it.
+pub fn parse(tokens: &[Token]) -> std::result::Result<Vec<Stmt>, Vec<Diagnostic>> {
+    parse_program(tokens, false)
+}
+
+/// `parse`, accepting a final expression without its `;` if
+/// `bare_expression` is set.
+pub(crate) fn parse_program(
+    tokens: &[Token],
+    bare_expression: bool,
+) -> std::result::Result<Vec<Stmt>, Vec<Diagnostic>> {
+    let mut diagnostics = Diagnostics::default();
+    let mut tokens = tokens.iter().peekable();
+    let mut parser = Parser::new(&mut tokens, &mut diagnostics);
+    if bare_expression {
+        parser = parser.allow_bare_expression();
+    }
+    let ast = parser.parse();
+    let mut ast = diagnostics.check(ast)?;
+    let mut diagnostics = Diagnostics::default();
+    Resolver::new(&mut diagnostics).resolve(&mut ast);
+    diagnostics.check(Ok(ast))
+}
+
+type Tokenz<'a> = &'a mut Peekable<Iter<'a, Token>>;
+pub struct Parser<'a> {
+    tokens: Tokenz<'a>,
+    lox: &'a mut dyn LoxError,
+    previous: Option<&'a Token>,
+    // Accept a final expression statement without its ';', as typed at a prompt.
+    bare_expression: bool,
+    // What we're in the middle of parsing, to explain errors in nested code.
+    context: Vec<Context>,
+    // How many statements and expressions deep we are, up to `MAX_NESTING`.
+    depth: usize,
+    // Set once code nests too deeply, after which the errors left are only
+    // confusion from cutting it short.
+    too_deep: bool,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(tokens: Tokenz<'a>, lox: &'a mut dyn LoxError) -> Self {
+        Self {
+            tokens,
+            lox,
+            previous: None,
+            bare_expression: false,
+            context: vec![],
+            depth: 0,
+            too_deep: false,
+        }
+    }
+
+    /// Let the input end in an expression with no ';', so a REPL can take `1 + 2`.
+    pub fn allow_bare_expression(mut self) -> Self {
+        self.bare_expression = true;
+        self
+    }
+
+    pub fn parse(&mut self) -> Result<Vec<Stmt>> {
+        let mut statements = vec![];
+        let mut error = None;
+        // Keep going after an error to report any others in the file too.
+        while let Some(stmt) = self.next_declaration() {
+            match stmt {
+                Ok(stmt) => statements.push(stmt),
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(statements),
+        }
+    }
+
+    /// Parse one top-level declaration, or give `None` at the end. After an
+    /// error, the tokens up to where the next declaration may start are
+    /// skipped.
+    pub fn next_declaration(&mut self) -> Option<Result<Stmt>> {
+        match self.tokens.peek() {
+            None
+            | Some(Token {
+                token_type: TokenType::EOF,
+                ..
+            }) => None,
+            _ => Some(self.declaration()),
+        }
+    }
+
+    /// How many tokens are left to parse.
+    pub(crate) fn remaining(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Parse the tokens as one expression, such as a formula, with nothing
+    /// after it.
+    pub fn parse_expression(&mut self) -> Result<Expr> {
+        let expr = self.expression()?;
+        match self.tokens.peek().copied() {
+            Some(t) if t.token_type != TokenType::EOF => {
+                let msg = "Expect end of expression.";
+                self.report(Diagnostic::at(t, msg));
+                Err(anyhow!("{}", msg))
+            }
+            _ => Ok(expr),
+        }
+    }
+
+    fn report(&mut self, mut diagnostic: Diagnostic) {
+        if self.too_deep {
+            return;
+        }
+        diagnostic.context = self.context.clone();
+        self.lox.report(diagnostic);
+    }
+
+    /// Run `parse` with `what`, started by `token`, noted on any error it reports.
+    fn within<T>(
+        &mut self,
+        token: &Token,
+        what: &str,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        self.context.push(Context::at(token, what));
+        let result = parse(self);
+        self.context.pop();
+        result
+    }
+
+    /// Run `parse` a level deeper, or report code nested too deeply to parse.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == MAX_NESTING {
+            let t = *self.tokens.peek().unwrap();
+            let msg = format!("Can't nest more than {} levels deep.", MAX_NESTING);
+            self.report(Diagnostic::at(t, &msg));
+            self.too_deep = true;
+            return Err(anyhow!(msg));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn advance(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.next();
+        self.previous = token.or(self.previous);
+        token
+    }
+
+    fn operator(token: &Token, unary: bool) -> Operator {
+        Operator {
+            kind: OpKind::from_token(&token.token_type, unary),
+            span: token.span,
+        }
+    }
+
+    fn token_match(&mut self, t: &[TokenType]) -> Option<&'a Token> {
+        let cur_token = self.tokens.peek()?;
+        if t.contains(&cur_token.token_type) {
+            self.advance()
+        } else {
+            None
+        }
+    }
+
+    fn consume(&mut self, t: TokenType, msg: &str) -> Result<&'a Token> {
+        self.token_match(&[t]).ok_or_else(|| {
+            // A stream cut short of its EOF token ends at the last one we saw.
+            if let Some(t) = self.tokens.peek().copied().or(self.previous) {
+                self.report(Diagnostic::at(t, msg));
+            }
+            anyhow!("{}", msg)
+        })
+    }
+    fn consume_identifier(&mut self, msg: &str) -> Result<Ident> {
+        let cur_token = *self.tokens.peek().unwrap();
+        if let TokenType::IDENTIFIER(name) = &cur_token.token_type {
+            self.advance();
+            Ok(Ident {
+                name: *name,
+                span: cur_token.span,
+                slot: None,
+            })
+        } else {
+            let mut diagnostic = Diagnostic::at(cur_token, msg);
+            // Name the keyword, since `var class` looks fine at a glance.
+            if let Some(word) = reserved_word(&cur_token.token_type) {
+                diagnostic.location = format!(" at '{}'", word);
+            }
+            self.report(diagnostic);
+            Err(anyhow!("{}", msg))
+        }
+    }
+
+    /// A declaration or statement. On a syntax error, skips ahead to the
+    /// next statement before returning the error.
+    fn declaration(&mut self) -> Result<Stmt> {
+        let stmt = self.declaration_or_statement();
+        if stmt.is_err() {
+            self.synchronize();
+        }
+        stmt
+    }
+
+    fn declaration_or_statement(&mut self) -> Result<Stmt> {
+        let mut lookahead = self.tokens.clone().map(|t| &t.token_type);
+        match (lookahead.next().unwrap(), lookahead.next()) {
+            (TokenType::VAR, _) => self.var_declaration(),
+            // `fun (` starts a lambda expression statement, not a declaration.
+            (TokenType::FUN, Some(TokenType::LEFT_PAREN)) => self.statement(),
+            (TokenType::FUN, _) => self.fun_declaration(),
+            _ => self.statement(),
+        }
+    }
+
+    fn fun_declaration(&mut self) -> Result<Stmt> {
+        self.advance(); // skip FUN
+        let name = self.consume_identifier(&format!("Expect {} name.", FunctionKind::Function))?;
+        let (parameters, body) = self.function(FunctionKind::Function)?;
+
+        Ok(Stmt::Function(name, parameters, Box::new(body)))
+    }
+
+    /// Parameters and body of a function, after its name (or `fun` keyword
+    /// for lambdas) has been consumed.
+    fn function(&mut self, kind: FunctionKind) -> Result<(Vec<Ident>, Stmt)> {
+        let after = match kind {
+            FunctionKind::Function => "function name",
+            FunctionKind::Lambda => "'fun'",
+        };
+        let paren = self.consume(
+            TokenType::LEFT_PAREN,
+            &format!("Expect '(' after {}.", after),
+        )?;
+        let what = format!("the parameters of this {}", kind);
+        let parameters = self.within(paren, &what, |p| {
+            let mut parameters = vec![];
+            if p.token_match(&[TokenType::RIGHT_PAREN]).is_none() {
+                loop {
+                    if parameters.len() >= MAX_ARGS {
+                        // Reported, but the parser isn't confused, so keep going.
+                        let t = *p.tokens.peek().unwrap();
+                        p.report(Diagnostic::at(
+                            t,
+                            &format!("Can't have more than {} parameters.", MAX_ARGS),
+                        ));
+                    }
+                    parameters.push(p.consume_identifier("Expect parameter name.")?);
+                    if p.token_match(&[TokenType::COMMA]).is_none() {
+                        break;
+                    }
+                }
+                p.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.")?;
+            }
+            Ok(parameters)
+        })?;
+        let msg = format!("Expect '{{' before {} body.", kind);
+        let body = self.nested(|p| p.braced_block(&msg))?;
+
+        Ok((parameters, body))
+    }
+
+    /// A block where only a block will do, as a function's body, or else
+    /// `msg` as the error.
+    fn braced_block(&mut self, msg: &str) -> Result<Stmt> {
+        let cur_token = *self.tokens.peek().unwrap();
+        if cur_token.token_type != TokenType::LEFT_BRACE {
+            self.report(Diagnostic::at(cur_token, msg));
+            return Err(anyhow!("{}", msg));
+        }
+        self.block()
+    }
+
+    fn var_declaration(&mut self) -> Result<Stmt> {
+        self.token_match(&[TokenType::VAR]); // consume VAR
+        let name = self.consume_identifier("Expect variable name.")?;
+
+        let mut initializer: Option<Expr> = None;
+        if self.token_match(&[TokenType::EQUAL]).is_some() {
+            initializer = Some(self.expression()?);
+        }
+
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expect ';' after variable declaration.",
+        )?;
+
+        Ok(Stmt::Var(name, initializer))
+    }
+
+    fn statement(&mut self) -> Result<Stmt> {
+        let cur_token = *self.tokens.peek().unwrap();
+        self.nested(|p| match cur_token.token_type {
+            TokenType::PRINT => p.print_statement(),
+            TokenType::WHILE => p.while_statement(),
+            TokenType::FOR => p.for_statement(),
+            TokenType::IF => p.if_statement(),
+            TokenType::LEFT_BRACE => p.block(),
+            TokenType::RETURN => p.return_statement(),
+            TokenType::THROW => p.throw_statement(),
+            TokenType::TRY => p.try_statement(),
+            _ => p.expression_statement(),
+        })
+    }
+
+    fn throw_statement(&mut self) -> Result<Stmt> {
+        let kw = self.advance().unwrap(); // consume THROW
+        let value = self.expression()?;
+        self.consume(TokenType::SEMICOLON, "Expect ';' after thrown value.")?;
+
+        Ok(Stmt::Throw(kw.span, value))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt> {
+        let kw = self.advance().unwrap(); // consume TRY
+        let body = self.braced_block("Expect '{' after 'try'.")?;
+        self.consume(TokenType::CATCH, "Expect 'catch' after try block.")?;
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'catch'.")?;
+        let name = self.consume_identifier("Expect error variable name.")?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after error variable.")?;
+        let handler = self.braced_block("Expect '{' before catch body.")?;
+
+        Ok(Stmt::Try(kw.span, Box::new(body), name, Box::new(handler)))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt> {
+        let kw = self.advance().unwrap(); // skip RETURN token
+
+        let value = if self.token_match(&[TokenType::SEMICOLON]).is_some() {
+            None
+        } else {
+            let e = Some(self.expression()?);
+            self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
+            e
+        };
+
+        Ok(Stmt::Return(kw.span, value))
+    }
+
+    fn for_statement(&mut self) -> Result<Stmt> {
+        let kw = self.advance().unwrap(); // consume FOR
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.")?;
+        let mut lookahead = self.tokens.clone().map(|t| &t.token_type);
+        if let (Some(TokenType::VAR), Some(TokenType::IDENTIFIER(_)), Some(TokenType::IN)) =
+            (lookahead.next(), lookahead.next(), lookahead.next())
+        {
+            return self.for_in_statement(kw);
+        }
+        let (initilizer, condition, increment) =
+            self.within(kw, "the clauses of this 'for'", Self::for_clauses)?;
+
+        let mut body = self.statement()?;
+
+        if let Some(increment) = increment {
+            body = Stmt::Block(vec![body, Stmt::Expr(increment)])
+        }
+        if let Some(condition) = condition {
+            body = Stmt::While(condition, Box::new(body));
+        }
+        if let Some(initilizer) = initilizer {
+            body = Stmt::Block(vec![initilizer, body]);
+        }
+
+        Ok(body)
+    }
+
+    /// The initializer, condition and increment of a C-style `for`, up to
+    /// and including its `)`.
+    fn for_clauses(&mut self) -> Result<(Option<Stmt>, Option<Expr>, Option<Expr>)> {
+        let cur_token = self.tokens.peek().unwrap();
+        let initilizer = if cur_token.token_type == TokenType::SEMICOLON {
+            self.advance();
+            None
+        } else if cur_token.token_type == TokenType::VAR {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let cur_token = self.tokens.peek().unwrap();
+
+        let condition = if cur_token.token_type == TokenType::SEMICOLON {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.")?;
+
+        let cur_token = self.tokens.peek().unwrap();
+        let increment = if cur_token.token_type == TokenType::RIGHT_PAREN {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.")?;
+        Ok((initilizer, condition, increment))
+    }
+
+    fn for_in_statement(&mut self, kw: &Token) -> Result<Stmt> {
+        let (name, iterable) = self.within(kw, "the clauses of this 'for'", |p| {
+            p.advance(); // consume VAR
+            let name = p.consume_identifier("Expect variable name.")?;
+            p.advance(); // consume IN
+            let iterable = p.expression()?;
+            p.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.")?;
+            Ok((name, iterable))
+        })?;
+        let body = self.statement()?;
+
+        Ok(Stmt::ForIn(name, iterable, Box::new(body)))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt> {
+        let kw = self.advance().unwrap(); // consume WHILE
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'while'.")?;
+        let condition = self.within(kw, "the condition of this 'while'", |p| {
+            let condition = p.expression()?;
+            p.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition.")?;
+            Ok(condition)
+        })?;
+        let body = self.statement()?;
+
+        Ok(Stmt::While(condition, Box::new(body)))
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt> {
+        let kw = self.advance().unwrap(); // consume IF
+
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.")?;
+        let condition = self.within(kw, "the condition of this 'if'", |p| {
+            let condition = p.expression()?;
+            p.consume(TokenType::RIGHT_PAREN, "Expect ')' after if condition.")?;
+            Ok(condition)
+        })?;
+
+        let then_branch = self.statement()?;
+        let else_branch = if self.token_match(&[TokenType::ELSE]).is_some() {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        Ok(Stmt::If(condition, Box::new(then_branch), else_branch))
+    }
+
+    fn block(&mut self) -> Result<Stmt> {
+        let brace = self.advance().unwrap(); // consume LEFT_BRACE
+        self.within(brace, "this block", |p| {
+            let mut statements: Vec<Stmt> = vec![];
+            loop {
+                let cur_token = p.tokens.peek().unwrap();
+                if let TokenType::RIGHT_BRACE | TokenType::EOF = cur_token.token_type {
+                    break;
+                }
+                // Errors are already reported and recovered from; drop the
+                // statement and carry on with the rest of the block.
+                if let Ok(stmt) = p.declaration() {
+                    statements.push(stmt);
+                }
+            }
+
+            p.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.")?;
+            Ok(Stmt::Block(statements))
+        })
+    }
+
+    fn expression_statement(&mut self) -> Result<Stmt> {
+        let expr = self.expression()?;
+        let at_end = matches!(
+            self.tokens.peek(),
+            None | Some(Token {
+                token_type: TokenType::EOF,
+                ..
+            })
+        );
+        if !(self.bare_expression && at_end) {
+            self.consume(TokenType::SEMICOLON, "Expect ';' after expression.")?;
+        }
+
+        Ok(Stmt::Expr(expr))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt> {
+        self.token_match(&[TokenType::PRINT]);
+        let value = self.expression()?;
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
+        Ok(Stmt::Print(value))
+    }
+
+    fn expression(&mut self) -> Result<Expr> {
+        self.comma()
+    }
+
+    fn comma(&mut self) -> Result<Expr> {
+        let mut expr = self.assignment()?;
+
+        while let Some(operator) = self.token_match(&[TokenType::COMMA]) {
+            let right = self.assignment()?;
+            expr = Expr::Binary(
+                Box::new(expr),
+                Self::operator(operator, false),
+                Box::new(right),
+            );
+        }
+        Ok(expr)
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.and_expr()?;
+
+        while let Some(operator) = self.token_match(&[TokenType::OR]) {
+            let right = self.and_expr()?;
+            expr = Expr::Logical(
+                Box::new(expr),
+                Self::operator(operator, false),
+                Box::new(right),
+            );
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut expr = self.equality()?;
+
+        while let Some(operator) = self.token_match(&[TokenType::AND]) {
+            let right = self.equality()?;
+            expr = Expr::Logical(
+                Box::new(expr),
+                Self::operator(operator, false),
+                Box::new(right),
+            );
+        }
+        Ok(expr)
+    }
+
+    fn assignment(&mut self) -> Result<Expr> {
+        let mut expr = self.conditional()?;
+
+        if let Some(equals) = self.token_match(&[TokenType::EQUAL]) {
+            let value = self.nested(|p| p.assignment())?;
+
+            match &mut expr {
+                Expr::Variable(name) => return Ok(Expr::Assign(name.clone(), Box::new(value))),
+                Expr::Index(object, bracket, index) => {
+                    return Ok(Expr::IndexSet(
+                        Box::new(object.take()),
+                        *bracket,
+                        Box::new(index.take()),
+                        Box::new(value),
+                    ))
+                }
+                _ => {}
+            }
+
+            // Reported, but the parser isn't confused, so no need to synchronize.
+            self.lox
+                .report(Diagnostic::at(equals, "Invalid assignment target."));
+        }
+        Ok(expr)
+    }
+
+    fn conditional(&mut self) -> Result<Expr> {
+        let expr = self.or_expr()?;
+
+        if self.token_match(&[TokenType::QUESTION]).is_some() {
+            let then_branch = self.expression()?;
+            self.consume(
+                TokenType::COLON,
+                "Expect ':' after then branch of conditional expression.",
+            )?;
+            let else_branch = self.nested(|p| p.conditional())?;
+            return Ok(Expr::Ternary(
+                Box::new(expr),
+                Box::new(then_branch),
+                Box::new(else_branch),
+            ));
+        }
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr> {
+        let mut expr: Expr = self.comparison()?;
+        while let Some(operator) =
+            self.token_match(&[TokenType::BANG_EQUAL, TokenType::EQUAL_EQUAL])
+        {
+            let right = self.comparison()?;
+            expr = Expr::Binary(
+                Box::new(expr),
+                Self::operator(operator, false),
+                Box::new(right),
+            );
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let mut expr: Expr = self.term()?;
+        while let Some(operator) = self.token_match(&[
+            TokenType::GREATER,
+            TokenType::GREATER_EQUAL,
+            TokenType::LESS,
+            TokenType::LESS_EQUAL,
+        ]) {
+            let right = self.term()?;
+            expr = Expr::Binary(
+                Box::new(expr),
+                Self::operator(operator, false),
+                Box::new(right),
+            );
+        }
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut expr: Expr = self.factor()?;
+        while let Some(operator) = self.token_match(&[TokenType::PLUS, TokenType::MINUS]) {
+            let right = self.factor()?;
+            expr = Expr::Binary(
+                Box::new(expr),
+                Self::operator(operator, false),
+                Box::new(right),
+            );
+        }
+        Ok(expr)
+    }
+
+    fn factor(&mut self) -> Result<Expr> {
+        let mut expr: Expr = self.unary()?;
+        while let Some(operator) = self.token_match(&[
+            TokenType::STAR,
+            TokenType::SLASH,
+            TokenType::PERCENT,
+            TokenType::TILDE_SLASH,
+        ]) {
+            let right = self.unary()?;
+            expr = Expr::Binary(
+                Box::new(expr),
+                Self::operator(operator, false),
+                Box::new(right),
+            );
+        }
+        Ok(expr)
+    }
+
+    /// Every operand nests a level deeper, as parentheses and lists nest
+    /// whole expressions within one.
+    fn unary(&mut self) -> Result<Expr> {
+        self.nested(|p| {
+            if let Some(operator) = p.token_match(&[TokenType::BANG, TokenType::MINUS]) {
+                let right = p.unary()?;
+                Ok(Expr::Unary(Self::operator(operator, true), Box::new(right)))
+            } else {
+                p.call()
+            }
+        })
+    }
+
+    fn call(&mut self) -> Result<Expr> {
+        let mut expr = self.primary()?;
+        while let Some(operator) = self.token_match(&[
+            TokenType::LEFT_PAREN,
+            TokenType::LEFT_BRACKET,
+            TokenType::DOT,
+        ]) {
+            expr = match operator.token_type {
+                TokenType::LEFT_PAREN => self.finish_call(expr, operator)?,
+                TokenType::LEFT_BRACKET => self.finish_index(expr, operator)?,
+                _ => {
+                    let name = self.consume_identifier("Expect property name after '.'.")?;
+                    Expr::Get(Box::new(expr), name)
+                }
+            };
+        }
+        Ok(expr)
+    }
+    fn finish_index(&mut self, object: Expr, bracket: &Token) -> Result<Expr> {
+        let start = match self.tokens.peek().map(|t| &t.token_type) {
+            Some(TokenType::COLON) => None,
+            _ => Some(Box::new(self.expression()?)),
+        };
+        let expr = match (start, self.token_match(&[TokenType::COLON])) {
+            (Some(index), None) => Expr::Index(Box::new(object), bracket.span, index),
+            (start, _) => {
+                let end = match self.tokens.peek().map(|t| &t.token_type) {
+                    Some(TokenType::RIGHT_BRACKET) => None,
+                    _ => Some(Box::new(self.expression()?)),
+                };
+                Expr::Slice(Box::new(object), bracket.span, start, end)
+            }
+        };
+        self.consume(TokenType::RIGHT_BRACKET, "Expect ']' after index.")?;
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr, paren: &Token) -> Result<Expr> {
+        let (arguments, paren) = self.within(paren, "the arguments of this call", |p| {
+            let mut arguments: Vec<Expr> = vec![];
+            if let Some(paren) = p.token_match(&[TokenType::RIGHT_PAREN]) {
+                return Ok((arguments, paren.span));
+            }
+            loop {
+                if arguments.len() >= MAX_ARGS {
+                    let t = *p.tokens.peek().unwrap();
+                    p.report(Diagnostic::at(
+                        t,
+                        &format!("Can't have more than {} arguments.", MAX_ARGS),
+                    ));
+                }
+                // Arguments are separated by commas, so skip the comma operator.
+                arguments.push(p.assignment()?);
+                if p.token_match(&[TokenType::COMMA]).is_none() {
+                    break;
+                }
+            }
+            let paren = p.consume(TokenType::RIGHT_PAREN, "Expect ')' after arguments.")?;
+            Ok((arguments, paren.span))
+        })?;
+
+        Ok(Expr::Call(Box::new(callee), paren, arguments))
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        // Leave a token we can't start an expression with for synchronize().
+        let cur_token = *self.tokens.peek().unwrap();
+        let expr = match &cur_token.token_type {
+            TokenType::FALSE => Expr::Literal(Literal::Boolean(false), cur_token.span),
+            TokenType::TRUE => Expr::Literal(Literal::Boolean(true), cur_token.span),
+            TokenType::NIL => Expr::Literal(Literal::Nil, cur_token.span),
+            TokenType::STRING(s) => {
+                Expr::Literal(Literal::String(Rc::from(s.as_str())), cur_token.span)
+            }
+            TokenType::NUMBER(n) => Expr::Literal(Literal::Number(*n), cur_token.span),
+            TokenType::IDENTIFIER(_name) => {
+                return Ok(Expr::Variable(
+                    self.consume_identifier("Expect expression.")?,
+                ))
+            }
+            TokenType::LEFT_PAREN | TokenType::FUN | TokenType::LEFT_BRACKET => {
+                return self.compound_primary()
+            }
+            _ => {
+                let msg = "Expect expression.";
+                self.report(Diagnostic::at(cur_token, msg));
+                return Err(anyhow!(msg));
+            }
+        };
+        self.advance();
+        Ok(expr)
+    }
+
+    /// Primaries that span several tokens, starting at their first one.
+    fn compound_primary(&mut self) -> Result<Expr> {
+        let cur_token = self.advance().unwrap();
+        match &cur_token.token_type {
+            TokenType::LEFT_PAREN => self.within(cur_token, "these parentheses", |p| {
+                let expr: Expr = p.expression()?;
+                p.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.")?;
+                Ok(Expr::Grouping(Box::new(expr)))
+            }),
+
+            TokenType::FUN => {
+                let (params, body) = self.function(FunctionKind::Lambda)?;
+                Ok(Expr::Lambda(cur_token.span, params, Box::new(body)))
+            }
+
+            TokenType::LEFT_BRACKET => self.within(cur_token, "this list", |p| {
+                let mut items = vec![];
+                if p.token_match(&[TokenType::RIGHT_BRACKET]).is_none() {
+                    loop {
+                        // Elements are separated by commas, so skip the comma operator.
+                        items.push(p.assignment()?);
+                        if p.token_match(&[TokenType::COMMA]).is_none() {
+                            break;
+                        }
+                    }
+                    p.consume(TokenType::RIGHT_BRACKET, "Expect ']' after list elements.")?;
+                }
+                Ok(Expr::List(cur_token.span, items))
+            }),
+
+            _ => unreachable!(),
+        }
+    }
+
+    /// Discard tokens until a likely statement boundary, so parsing can
+    /// resume after an error without reporting the same mistake again.
+    fn synchronize(&mut self) {
+        // Never step past the end, which callers look for to stop.
+        if let None
+        | Some(Token {
+            token_type: TokenType::EOF,
+            ..
+        }) = self.tokens.peek()
+        {
+            return;
+        }
+        self.advance();
+        while let Some(cur_token) = self.tokens.peek() {
+            if let Some(Token {
+                token_type: TokenType::SEMICOLON,
+                ..
+            }) = self.previous
+            {
+                return;
+            }
+            match cur_token.token_type {
+                TokenType::CLASS
+                | TokenType::FUN
+                | TokenType::VAR
+                | TokenType::FOR
+                | TokenType::IF
+                | TokenType::WHILE
+                | TokenType::PRINT
+                | TokenType::RETURN
+                | TokenType::THROW
+                | TokenType::TRY
+                | TokenType::EOF => return,
+
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestLox {
+        pub has_error: bool,
+    }
+
+    impl LoxError for TestLox {
+        fn report(&mut self, _diagnostic: Diagnostic) {
+            self.has_error = true;
+        }
+
+        fn has_error(&self) -> bool {
+            self.has_error
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut lox = TestLox { has_error: false };
+        let tokens = [];
+        let tokz = &mut tokens.iter().peekable();
+        let mut parser = Parser::new(tokz, &mut lox);
+        let ast = parser.parse();
+        println!("{:#?}", ast);
+    }
+    #[test]
+    fn test_simple() {
+        let mut lox = TestLox { has_error: false };
+        let tokens = [
+            Token {
+                token_type: TokenType::LEFT_PAREN,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::NUMBER(42.0),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::RIGHT_PAREN,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokz = &mut tokens.iter().peekable();
+
+        let mut parser = Parser::new(tokz, &mut lox);
+        let ast = parser.parse();
+        println!("{:#?}", ast);
+    }
+    #[test]
+    fn test_parse_expression_trailing() {
+        let mut lox = TestLox { has_error: false };
+        let tokens = [
+            Token {
+                token_type: TokenType::NUMBER(1.0),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::NUMBER(2.0),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokz = &mut tokens.iter().peekable();
+
+        let mut parser = Parser::new(tokz, &mut lox);
+        assert!(parser.parse_expression().is_err());
+        assert!(lox.has_error);
+    }
+
+    #[test]
+    fn test_error_context() {
+        let source = "while (x) {\n  print f(1, 2;\n}\nprint 1;";
+        let tokens = crate::scanner::scan_tokens(&mut crate::lox::Lox::default(), source).unwrap();
+        let mut lox = Diagnostics(vec![]);
+        let tokz = &mut tokens.iter().peekable();
+        // The block recovers, so the error only shows up as a report.
+        let _ = Parser::new(tokz, &mut lox).parse();
+
+        let [diagnostic] = &lox.0[..] else {
+            panic!("expected one error, got {:?}", lox.0);
+        };
+        assert_eq!(diagnostic.message, "Expect ')' after arguments.");
+        let context: Vec<_> = diagnostic
+            .context
+            .iter()
+            .map(|c| (c.line, c.what.as_str()))
+            .collect();
+        assert_eq!(
+            context,
+            [(1, "this block"), (2, "the arguments of this call")]
+        );
+    }
+}
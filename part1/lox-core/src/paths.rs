@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR_STR};
+
+/// A path as given by a user or test file, with either `/` or `\` between
+/// components, as a path for this platform.
+pub fn normalize(path: &str) -> PathBuf {
+    PathBuf::from(path.replace(['/', '\\'], MAIN_SEPARATOR_STR))
+}
+
+/// `path` for messages and comparisons: relative to the working directory
+/// when it is inside it, with `/` between components on every platform.
+pub fn display(path: &Path) -> String {
+    let relative = env::current_dir()
+        .ok()
+        .and_then(|cwd| fs::canonicalize(cwd).ok())
+        .zip(fs::canonicalize(path).ok())
+        .and_then(|(cwd, path)| path.strip_prefix(cwd).ok().map(Path::to_path_buf));
+    let path = relative.as_deref().unwrap_or(path);
+    let mut out = String::new();
+    for c in path.components() {
+        match c {
+            Component::Prefix(p) => out += &verbatim_free(&p.as_os_str().to_string_lossy()),
+            Component::RootDir => out.push('/'),
+            Component::CurDir => {}
+            c => {
+                if !(out.is_empty() || out.ends_with('/')) {
+                    out.push('/');
+                }
+                out += &c.as_os_str().to_string_lossy();
+            }
+        }
+    }
+    out
+}
+
+/// Drop the `\\?\` that canonicalizing adds to Windows paths.
+fn verbatim_free(prefix: &str) -> String {
+    prefix.trim_start_matches(r"\\?\").to_owned()
+}
+
+/// Read a Lox source file named as in `normalize`.
+pub fn read_source(path: &str) -> Result<String> {
+    let path = normalize(path);
+    fs::read_to_string(&path).with_context(|| format!("Could not read {}", display(&path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_separators() {
+        let path = normalize(r"test_lox_files\nested/dir\0001.lox");
+        let parts: Vec<_> = path
+            .components()
+            .map(|c| c.as_os_str().to_owned())
+            .collect();
+        assert_eq!(parts, ["test_lox_files", "nested", "dir", "0001.lox"]);
+        assert_eq!(display(&path), "test_lox_files/nested/dir/0001.lox");
+    }
+
+    #[test]
+    fn test_display_relative_to_working_directory() {
+        let inside = env::current_dir().unwrap().join(normalize("src/paths.rs"));
+        assert_eq!(display(&inside), "src/paths.rs");
+        assert_eq!(display(&normalize(r".\src\paths.rs")), "src/paths.rs");
+    }
+}
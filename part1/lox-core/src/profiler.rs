@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Calls to one function and the time spent in them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FunctionProfile {
+    pub name: String,
+    /// Declaration line; `None` for natives.
+    pub line: Option<i32>,
+    pub calls: u64,
+    /// From entering each outermost call to leaving it, so the time of
+    /// recursive calls isn't counted twice.
+    pub total: Duration,
+    /// Time not spent in the functions it called.
+    pub own: Duration,
+    /// Calls in progress, to tell when the outermost one ends.
+    active: usize,
+}
+
+impl FunctionProfile {
+    /// How the report names the function: `fib (line 1)`, or `clock
+    /// (native)`.
+    pub fn label(&self) -> String {
+        match self.line {
+            Some(line) => format!("{} (line {})", self.name, line),
+            None => format!("{} (native)", self.name),
+        }
+    }
+}
+
+/// A call in progress.
+struct Frame {
+    key: (String, Option<i32>),
+    start: Instant,
+    /// Time spent in the calls it has made so far.
+    callees: Duration,
+}
+
+/// Counts the calls to each function and native, and times them, for
+/// `--profile`. Functions are told apart by name and declaration line, so
+/// two closures from one declaration share an entry.
+#[derive(Default)]
+pub struct Profiler {
+    functions: HashMap<(String, Option<i32>), FunctionProfile>,
+    stack: Vec<Frame>,
+}
+
+impl Profiler {
+    pub fn enter(&mut self, name: String, line: Option<i32>) {
+        let key = (name, line);
+        let function = self
+            .functions
+            .entry(key.clone())
+            .or_insert_with(|| FunctionProfile {
+                name: key.0.clone(),
+                line,
+                ..Default::default()
+            });
+        function.active += 1;
+        self.stack.push(Frame {
+            key,
+            start: Instant::now(),
+            callees: Duration::ZERO,
+        });
+    }
+
+    /// Leave the innermost call, whether it returned or failed.
+    pub fn exit(&mut self) {
+        let Some(frame) = self.stack.pop() else {
+            return;
+        };
+        let elapsed = frame.start.elapsed();
+        if let Some(caller) = self.stack.last_mut() {
+            caller.callees += elapsed;
+        }
+        let Some(function) = self.functions.get_mut(&frame.key) else {
+            return;
+        };
+        function.calls += 1;
+        function.own += elapsed.saturating_sub(frame.callees);
+        function.active -= 1;
+        if function.active == 0 {
+            function.total += elapsed;
+        }
+    }
+
+    /// Every function called, the one with the most time of its own first.
+    pub fn functions(&self) -> Vec<&FunctionProfile> {
+        let mut functions: Vec<&FunctionProfile> = self.functions.values().collect();
+        functions.sort_by(|a, b| {
+            b.own
+                .cmp(&a.own)
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| a.line.cmp(&b.line))
+        });
+        functions
+    }
+
+    /// A table of the functions called, as `functions` orders them, with
+    /// times in milliseconds.
+    pub fn report(&self) -> String {
+        let ms = |d: Duration| format!("{:.3}", d.as_secs_f64() * 1000.0);
+        let mut out = format!(
+            "{:>10} {:>12} {:>12}  {}\n",
+            "calls", "total ms", "self ms", "function"
+        );
+        for f in self.functions() {
+            out += &format!(
+                "{:>10} {:>12} {:>12}  {}\n",
+                f.calls,
+                ms(f.total),
+                ms(f.own),
+                f.label()
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recursion_counted_once() {
+        let mut p = Profiler::default();
+        p.enter("f".to_owned(), Some(1));
+        p.enter("f".to_owned(), Some(1));
+        p.enter("clock".to_owned(), None);
+        std::thread::sleep(Duration::from_millis(2));
+        p.exit();
+        p.exit();
+        p.exit();
+        let functions = p.functions();
+        let [clock, f] = functions.as_slice() else {
+            panic!("expected two functions, got {:?}", functions);
+        };
+        assert_eq!((clock.label().as_str(), clock.calls), ("clock (native)", 1));
+        assert_eq!((f.label().as_str(), f.calls), ("f (line 1)", 2));
+        // The outer call's time includes the inner one's, but only once.
+        assert!(f.total >= clock.total && f.total < clock.total * 2);
+        assert!(f.own < clock.own);
+        let report = p.report();
+        assert!(report.starts_with("     calls     total ms      self ms  function\n"));
+        assert!(report.contains("  clock (native)\n"), "{}", report);
+    }
+}
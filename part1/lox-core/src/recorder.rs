@@ -0,0 +1,185 @@
+use crate::interpreter::Object;
+use crate::parser::Stmt;
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+/// Reads and writes of one simple statement, collected while it runs.
+#[derive(Default)]
+struct RecordFrame {
+    reads: Vec<String>,
+    writes: Vec<String>,
+}
+
+/// Appends one line per executed simple statement to a log:
+/// `line <TAB> statement <TAB> reads <TAB> writes`.
+pub struct ExecutionRecorder {
+    out: Box<dyn Write>,
+    frames: Vec<RecordFrame>,
+}
+
+/// Statements recorded as a single step; compound statements are only
+/// recorded through the statements they contain.
+pub fn is_recorded(stmt: &Stmt) -> bool {
+    matches!(
+        stmt,
+        Stmt::Expr(_) | Stmt::Print(_) | Stmt::Var(_, _) | Stmt::Return(_, _) | Stmt::Throw(_, _)
+    )
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+impl ExecutionRecorder {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Self {
+            out,
+            frames: vec![],
+        }
+    }
+
+    pub fn append_to(path: &str) -> Result<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+        Ok(Self::new(Box::new(file)))
+    }
+
+    pub fn begin(&mut self) {
+        self.frames.push(RecordFrame::default());
+    }
+
+    pub fn read(&mut self, name: &str, value: &Object) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.reads.push(format!("{}={}", name, value));
+        }
+    }
+
+    pub fn write(&mut self, name: &str, value: &Object) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.writes.push(format!("{}={}", name, value));
+        }
+    }
+
+    pub fn end(&mut self, stmt: &Stmt) {
+        let frame = self.frames.pop().unwrap_or_default();
+        // Recording is best effort; a full disk shouldn't stop the program.
+        let _ = writeln!(
+            self.out,
+            "{}\t{}\t{}\t{}",
+            stmt.line().unwrap_or(0),
+            escape(&stmt.to_string()),
+            escape(&frame.reads.join(", ")),
+            escape(&frame.writes.join(", "))
+        );
+    }
+}
+
+/// One recorded step, as read back from a log.
+#[derive(Debug, PartialEq)]
+pub struct Step {
+    pub line: i32,
+    pub statement: String,
+    pub reads: String,
+    pub writes: String,
+}
+
+pub fn parse_log(log: impl BufRead) -> Result<Vec<Step>> {
+    log.lines()
+        .enumerate()
+        .map(|(idx, line)| {
+            let line = line?;
+            let mut fields = line.splitn(4, '\t');
+            let mut next = || fields.next().unwrap_or("").to_owned();
+            let (lineno, statement, reads, writes) = (next(), next(), next(), next());
+            Ok(Step {
+                line: lineno
+                    .parse()
+                    .map_err(|_| anyhow!("Malformed record on line {} of log.", idx + 1))?,
+                statement,
+                reads,
+                writes,
+            })
+        })
+        .collect()
+}
+
+/// Step through a recorded log. Reads commands from `input`: `n`/enter for
+/// the next step, `p` for the previous one, a number to jump, `q` to quit.
+pub fn replay(path: &str, input: impl BufRead, mut out: impl Write) -> Result<()> {
+    let steps = parse_log(BufReader::new(File::open(path)?))?;
+    if steps.is_empty() {
+        writeln!(out, "No steps recorded in {}.", path)?;
+        return Ok(());
+    }
+    let mut cur = 0;
+    let mut lines = input.lines();
+    loop {
+        let step = &steps[cur];
+        writeln!(
+            out,
+            "[step {}/{}] line {}: {}",
+            cur + 1,
+            steps.len(),
+            step.line,
+            step.statement
+        )?;
+        if !step.reads.is_empty() {
+            writeln!(out, "  read:  {}", step.reads)?;
+        }
+        if !step.writes.is_empty() {
+            writeln!(out, "  wrote: {}", step.writes)?;
+        }
+        write!(out, "(n)ext, (p)rev, <step>, (q)uit> ")?;
+        out.flush()?;
+        let cmd = match lines.next() {
+            Some(cmd) => cmd?,
+            None => break,
+        };
+        match cmd.trim() {
+            "" | "n" => cur = (cur + 1).min(steps.len() - 1),
+            "p" => cur = cur.saturating_sub(1),
+            "q" => break,
+            n => match n.parse::<usize>() {
+                Ok(n) if (1..=steps.len()).contains(&n) => cur = n - 1,
+                _ => writeln!(out, "Unknown command '{}'.", n)?,
+            },
+        }
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_log() {
+        let log = "3\tprint a\ta=1\t\n4\tb = \"x\\ty\"\t\tb=x\\ty\n";
+        let steps = parse_log(log.as_bytes()).unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step {
+                    line: 3,
+                    statement: "print a".to_owned(),
+                    reads: "a=1".to_owned(),
+                    writes: "".to_owned(),
+                },
+                Step {
+                    line: 4,
+                    statement: "b = \"x\\ty\"".to_owned(),
+                    reads: "".to_owned(),
+                    writes: "b=x\\ty".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_log_malformed() {
+        assert!(parse_log("nope\tprint a\t\t\n".as_bytes()).is_err());
+    }
+}
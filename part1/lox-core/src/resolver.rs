@@ -0,0 +1,373 @@
+use crate::intern::Symbol;
+use crate::lox_error::{Diagnostic, LoxError};
+use crate::parser::{Expr, Ident, Literal, Slot, Stmt};
+use crate::tokens::Span;
+use std::collections::HashMap;
+
+/// A name declared in a scope being resolved.
+struct Local {
+    slot: usize,
+    /// Its initializer has finished.
+    defined: bool,
+    /// Something reads it.
+    used: bool,
+    /// Where a `var` declared it, to warn if it is never read. Parameters,
+    /// functions and loop variables aren't warned about.
+    var: Option<Ident>,
+}
+
+/// A scope being resolved: each name declared in it.
+type ScopeNames = HashMap<Symbol, Local>;
+
+/// Whether `e` is a literal, perhaps in parentheses, and so always truthy
+/// or always falsy.
+fn constant_truth(e: &Expr) -> Option<bool> {
+    match e {
+        Expr::Literal(Literal::Nil | Literal::Boolean(false), _) => Some(false),
+        Expr::Literal(..) => Some(true),
+        Expr::Grouping(e) => constant_truth(e),
+        _ => None,
+    }
+}
+
+/// Numbers the local variables of every scope and points each use of one at
+/// its slot, so the interpreter can skip looking them up by name. Walks the
+/// tree the same way the interpreter does: a scope for each block, for each
+/// function's parameters and for each `for`-`in` loop variable. Anything
+/// not found in an enclosing scope is a global.
+///
+/// Along the way it lints, reporting warnings that don't stop the program:
+/// locals never read, code after a `return`, assigning a variable to
+/// itself, and `if`, `while` and `?:` conditions that are a constant.
+pub struct Resolver<'a> {
+    errors: &'a mut dyn LoxError,
+    scopes: Vec<ScopeNames>,
+    /// How many function bodies the walk is inside, to catch a `return`
+    /// outside of any.
+    functions: usize,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(errors: &'a mut dyn LoxError) -> Self {
+        Self {
+            errors,
+            scopes: vec![],
+            functions: 0,
+        }
+    }
+
+    pub fn resolve(&mut self, stmts: &mut [Stmt]) {
+        stmts.iter_mut().for_each(|s| self.stmt(s));
+    }
+
+    pub fn resolve_expr(&mut self, expr: &mut Expr) {
+        self.expr(expr);
+    }
+
+    fn error(&mut self, ident: &Ident, msg: &str) {
+        self.error_at(ident.span, ident.name.as_str(), msg);
+    }
+
+    /// Report `msg` about the token `lexeme` at `span`.
+    fn error_at(&mut self, span: Span, lexeme: &str, msg: &str) {
+        let mut diagnostic = Diagnostic::new(span.line, span.column, span.start..span.end, msg);
+        diagnostic.location = format!(" at '{}'", lexeme);
+        self.errors.report(diagnostic);
+    }
+
+    fn warn(&mut self, span: Span, msg: &str) {
+        let diagnostic = Diagnostic::new(span.line, span.column, span.start..span.end, msg);
+        self.errors.report(diagnostic.into_warning());
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(ScopeNames::new());
+        f(self);
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let mut unused: Vec<&Ident> = scope
+            .values()
+            .filter(|local| !local.used)
+            .filter_map(|local| local.var.as_ref())
+            .filter(|var| !var.name.as_str().starts_with('_'))
+            .collect();
+        unused.sort_by_key(|var| var.span.start);
+        for var in unused {
+            self.warn(
+                var.span,
+                &format!("Local variable '{}' is never used.", var.name),
+            );
+        }
+    }
+
+    fn condition(&mut self, c: &Expr) {
+        if let Some(truth) = constant_truth(c) {
+            self.warn(c.span(), &format!("Condition is always {}.", truth));
+        }
+    }
+
+    /// Warn about the first statement after a `return` or `throw` in `stmts`.
+    fn unreachable(&mut self, stmts: &[Stmt]) {
+        let mut rest = stmts
+            .iter()
+            .skip_while(|s| !matches!(s, Stmt::Return(..) | Stmt::Throw(..)));
+        let keyword = match rest.next() {
+            Some(Stmt::Throw(..)) => "throw",
+            _ => "return",
+        };
+        if let Some(span) = rest.next().and_then(Stmt::span) {
+            self.warn(span, &format!("Unreachable code after '{}'.", keyword));
+        }
+    }
+
+    fn declare(&mut self, ident: &Ident) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&ident.name) {
+            self.error(ident, "Already a variable with this name in this scope.");
+            return;
+        }
+        let local = Local {
+            slot: scope.len(),
+            defined: false,
+            used: false,
+            var: None,
+        };
+        scope.insert(ident.name, local);
+    }
+
+    fn define(&mut self, ident: &Ident) {
+        if let Some(local) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.get_mut(&ident.name))
+        {
+            local.defined = true;
+        }
+    }
+
+    /// Point `ident` at its slot, noting a use if `read`.
+    fn local(&mut self, ident: &mut Ident, read: bool) {
+        if let Some(Local { defined: false, .. }) =
+            self.scopes.last().and_then(|s| s.get(&ident.name))
+        {
+            self.error(ident, "Can't read local variable in its own initializer.");
+        }
+        ident.slot = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| {
+                scope.get_mut(&ident.name).map(|local| {
+                    local.used |= read;
+                    Slot {
+                        depth,
+                        index: local.slot,
+                    }
+                })
+            });
+    }
+
+    fn function(&mut self, params: &[Ident], body: &mut Stmt) {
+        self.functions += 1;
+        self.scoped(|r| {
+            params.iter().for_each(|p| {
+                r.declare(p);
+                r.define(p);
+            });
+            r.stmt(body);
+        });
+        self.functions -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt) {
+        match stmt {
+            Stmt::Expr(e) | Stmt::Print(e) => self.expr(e),
+            Stmt::Var(name, init) => {
+                self.declare(name);
+                // Not a redeclaration, already reported as an error.
+                if let Some(local) = self
+                    .scopes
+                    .last_mut()
+                    .and_then(|s| s.get_mut(&name.name))
+                    .filter(|local| !local.defined)
+                {
+                    local.var = Some(name.clone());
+                }
+                if let Some(init) = init {
+                    self.expr(init);
+                }
+                self.define(name);
+            }
+            Stmt::Block(stmts) => self.scoped(|r| {
+                r.resolve(stmts);
+                r.unreachable(stmts);
+            }),
+            Stmt::If(c, t, e) => {
+                self.condition(c);
+                self.expr(c);
+                self.stmt(t);
+                if let Some(e) = e {
+                    self.stmt(e);
+                }
+            }
+            Stmt::While(c, body) => {
+                // `while (true)` is how Lox spells a loop left by `return`.
+                if !matches!(c, Expr::Literal(Literal::Boolean(true), _)) {
+                    self.condition(c);
+                }
+                self.expr(c);
+                self.stmt(body);
+            }
+            Stmt::ForIn(name, iterable, body) => {
+                self.expr(iterable);
+                self.scoped(|r| {
+                    r.declare(name);
+                    r.define(name);
+                    r.stmt(body);
+                });
+            }
+            Stmt::Function(name, params, body) => {
+                // Defined before its body so it can call itself.
+                self.declare(name);
+                self.define(name);
+                self.function(params, body);
+            }
+            Stmt::Return(keyword, v) => {
+                if self.functions == 0 {
+                    self.error_at(*keyword, "return", "Can't return from top-level code.");
+                }
+                if let Some(v) = v {
+                    self.expr(v);
+                }
+            }
+            Stmt::Throw(_, v) => self.expr(v),
+            Stmt::Try(_, body, name, handler) => {
+                self.stmt(body);
+                self.scoped(|r| {
+                    r.declare(name);
+                    r.define(name);
+                    r.stmt(handler);
+                });
+            }
+        }
+    }
+
+    /// Resolve `expr` from a stack of pending work rather than by recursion,
+    /// like `Interpreter::evaluate`, so a deeply nested expression can't
+    /// overflow the Rust stack.
+    fn expr(&mut self, expr: &mut Expr) {
+        let mut work = vec![Pending::Expr(expr)];
+        while let Some(pending) = work.pop() {
+            let expr = match pending {
+                Pending::Expr(expr) => expr,
+                Pending::Assigned(name) => {
+                    self.local(name, false);
+                    continue;
+                }
+            };
+            // The stack runs last pushed first.
+            match expr {
+                Expr::Variable(name) => self.local(name, true),
+                Expr::Assign(name, value) => {
+                    if matches!(&**value, Expr::Variable(v) if v.name == name.name) {
+                        let msg = format!("Assignment of '{}' to itself.", name.name);
+                        self.warn(name.span, &msg);
+                    }
+                    work.extend([Pending::Assigned(name), Pending::Expr(value)]);
+                }
+                Expr::Binary(l, _, r) | Expr::Logical(l, _, r) | Expr::Index(l, _, r) => {
+                    work.extend([Pending::Expr(r), Pending::Expr(l)]);
+                }
+                Expr::Unary(_, e) | Expr::Grouping(e) | Expr::Get(e, _) => {
+                    work.push(Pending::Expr(e))
+                }
+                Expr::Literal(..) => {}
+                Expr::Call(callee, _, args) => {
+                    work.extend(args.iter_mut().rev().map(Pending::Expr));
+                    work.push(Pending::Expr(callee));
+                }
+                Expr::Ternary(c, t, e) => {
+                    self.condition(c);
+                    work.extend([Pending::Expr(e), Pending::Expr(t), Pending::Expr(c)]);
+                }
+                Expr::IndexSet(c, _, t, e) => {
+                    work.extend([Pending::Expr(e), Pending::Expr(t), Pending::Expr(c)]);
+                }
+                Expr::List(_, items) => work.extend(items.iter_mut().rev().map(Pending::Expr)),
+                Expr::Slice(o, _, a, b) => {
+                    work.extend(b.as_deref_mut().map(Pending::Expr));
+                    work.extend(a.as_deref_mut().map(Pending::Expr));
+                    work.push(Pending::Expr(o));
+                }
+                Expr::Lambda(_, params, body) => self.function(params, body),
+            }
+        }
+    }
+}
+
+/// What `Resolver::expr` has left to do.
+enum Pending<'e> {
+    Expr(&'e mut Expr),
+    /// Point an assignment at its variable, once the value is resolved.
+    Assigned(&'e mut Ident),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lox::Lox;
+    use crate::lox_error::Diagnostics;
+
+    fn slots(source: &str) -> Vec<(String, Option<Slot>)> {
+        // Parsing runs the resolver.
+        let ast = Lox::default().parse(source).unwrap();
+        let mut found = vec![];
+        fn walk(stmt: &Stmt, found: &mut Vec<(String, Option<Slot>)>) {
+            match stmt {
+                Stmt::Print(Expr::Variable(n)) => found.push((n.to_string(), n.slot)),
+                Stmt::Block(stmts) => stmts.iter().for_each(|s| walk(s, found)),
+                Stmt::Function(_, _, body) => walk(body, found),
+                _ => {}
+            }
+        }
+        ast.iter().for_each(|s| walk(s, &mut found));
+        found
+    }
+
+    #[test]
+    fn test_slots() {
+        let found =
+            slots("var g; { var a; var b; print b; fun f(x) { print x; print a; print g; } }");
+        let slot = |depth, index| Some(Slot { depth, index });
+        assert_eq!(
+            found,
+            [
+                ("b".to_owned(), slot(0, 1)),
+                ("x".to_owned(), slot(1, 0)),
+                ("a".to_owned(), slot(2, 0)),
+                ("g".to_owned(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_warnings() {
+        let tokens = crate::Scanner::scan("{ var a = 1; a = a; }\n{ var b; }").unwrap();
+        let mut ast = crate::parse(&tokens).unwrap();
+        let mut diagnostics = Diagnostics::default();
+        Resolver::new(&mut diagnostics).resolve(&mut ast);
+        assert!(!diagnostics.has_error());
+        let messages: Vec<String> = diagnostics.0.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "[line 1] Warning: Assignment of 'a' to itself.",
+                "[line 2] Warning: Local variable 'b' is never used.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,1129 @@
+use crate::intern::Symbol;
+use crate::lox_error::{Diagnostic, Diagnostics, LoxError};
+use crate::tokens::{keywords, Span, Token, TokenType};
+use anyhow::Result;
+use itertools::{peek_nth, PeekNth};
+use std::str::Chars;
+
+/// Source characters plus the byte offset consumed so far, for token spans.
+struct Cursor<'a> {
+    chars: PeekNth<Chars<'a>>,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if let Some(c) = c {
+            self.pos += c.len_utf8();
+        }
+        c
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Option<&char> {
+        self.chars.peek_nth(n)
+    }
+}
+
+/// Optional lexical extensions.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Accept `_` between digits of a number literal, as in `1_000_000`.
+    pub digit_separators: bool,
+    /// Keep `//` comments as `COMMENT` tokens, for tools such as the
+    /// formatter that put them back. The parser does not accept them.
+    pub comments: bool,
+    /// Keep each run of white space, line breaks included, as a
+    /// `WHITESPACE` token. With `comments` too, the tokens' spans cover the
+    /// whole source, so joining their text gives it back exactly.
+    pub trivia: bool,
+}
+
+/// Scanning for embedders: tokens, or every error found, without a
+/// `LoxError` to report to.
+pub struct Scanner;
+
+impl Scanner {
+    /// The tokens of `source`, ending with `EOF`, or every error in it.
+    pub fn scan(source: &str) -> std::result::Result<Vec<Token>, Vec<Diagnostic>> {
+        Self::scan_with_options(source, &ScanOptions::default())
+    }
+
+    pub fn scan_with_options(
+        source: &str,
+        opts: &ScanOptions,
+    ) -> std::result::Result<Vec<Token>, Vec<Diagnostic>> {
+        let mut diagnostics = Diagnostics::default();
+        let tokens = scan_tokens_with_options(&mut diagnostics, source, opts);
+        diagnostics.check(tokens)
+    }
+}
+
+pub fn scan_tokens(lox: &mut dyn LoxError, source: &str) -> Result<Vec<Token>> {
+    scan_tokens_with_options(lox, source, &ScanOptions::default())
+}
+
+/// Collects whether an error was reported, without printing it.
+struct SilentErrors(bool);
+
+impl LoxError for SilentErrors {
+    fn report(&mut self, _diagnostic: Diagnostic) {
+        self.0 = true;
+    }
+
+    fn has_error(&self) -> bool {
+        self.0
+    }
+}
+
+/// Whether `source` is cut off: an unterminated string, or more opening
+/// brackets than closing ones.
+pub fn is_incomplete(source: &str) -> bool {
+    let tokens = match scan_tokens(&mut SilentErrors(false), source) {
+        Ok(tokens) => tokens,
+        // Only an unterminated string stops the scan.
+        Err(_) => return true,
+    };
+    let depth: i32 = tokens
+        .iter()
+        .map(|t| match t.token_type {
+            TokenType::LEFT_PAREN | TokenType::LEFT_BRACE | TokenType::LEFT_BRACKET => 1,
+            TokenType::RIGHT_PAREN | TokenType::RIGHT_BRACE | TokenType::RIGHT_BRACKET => -1,
+            _ => 0,
+        })
+        .sum();
+    depth > 0
+}
+
+pub fn scan_tokens_with_options(
+    lox: &mut dyn LoxError,
+    source: &str,
+    opts: &ScanOptions,
+) -> Result<Vec<Token>> {
+    let from = ScanFrom {
+        pos: 0,
+        line: 1,
+        line_start: 0,
+    };
+    let (mut tokens, line) = scan_from(lox, source, opts, from, |_| false)?;
+    tokens.push(eof(source, line));
+    Ok(tokens)
+}
+
+/// The `EOF` token of `source`, which has `last_line` lines.
+pub(crate) fn eof(source: &str, last_line: i32) -> Token {
+    // End of input sits just after the last non-blank text, so errors there
+    // name a line the reader can see.
+    let end = source.trim_end().len();
+    let last_line_start = source[..end].rfind('\n').map_or(0, |i| i + 1);
+    Token {
+        token_type: TokenType::EOF,
+        span: Span {
+            start: end,
+            end,
+            line: last_line - source[end..].matches('\n').count() as i32,
+            column: source[last_line_start..end].chars().count() + 1,
+        },
+    }
+}
+
+/// Where `scan_from` starts: a byte offset outside any token, the line
+/// there, and the offset that line starts at.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScanFrom {
+    pub pos: usize,
+    pub line: i32,
+    pub line_start: usize,
+}
+
+/// Scan `source` from `from` to its end, or until `stop` accepts a token,
+/// which is the last one given. Gives the tokens, without an `EOF`, and the
+/// line the scan ended on.
+pub(crate) fn scan_from(
+    lox: &mut dyn LoxError,
+    source: &str,
+    opts: &ScanOptions,
+    from: ScanFrom,
+    mut stop: impl FnMut(&Token) -> bool,
+) -> Result<(Vec<Token>, i32)> {
+    let is_digit = |c: Option<&char>| match c {
+        Some(c) => c.is_ascii_digit() || (opts.digit_separators && *c == '_'),
+        None => false,
+    };
+    let mut tokens = Vec::new();
+    let mut line = from.line;
+    let mut line_start = from.line_start;
+    let mut chars = Cursor {
+        chars: peek_nth(source[from.pos..].chars()),
+        pos: from.pos,
+    };
+
+    while let Some(c) = chars.next() {
+        let start = chars.pos - c.len_utf8();
+        let column = source[line_start..start].chars().count() + 1;
+        let scanned = tokens.len();
+        let error = |lox: &mut dyn LoxError, line: i32, end: usize, message: &str| {
+            lox.report(Diagnostic::new(line, column, start..end, message))
+        };
+
+        match c {
+            // White space, dropped unless kept as one token per run.
+            ' ' | '\t' | '\r' | '\n' => {
+                let mut c = c;
+                loop {
+                    if c == '\n' {
+                        line += 1;
+                        line_start = chars.pos;
+                    }
+                    match chars.peek() {
+                        Some(&next @ (' ' | '\t' | '\r' | '\n')) if opts.trivia => {
+                            chars.next();
+                            c = next;
+                        }
+                        _ => break,
+                    }
+                }
+                if opts.trivia {
+                    tokens.push(Token {
+                        token_type: TokenType::WHITESPACE,
+                        ..Default::default()
+                    });
+                }
+            }
+            // Single-character tokens.
+            '(' => tokens.push(Token {
+                token_type: TokenType::LEFT_PAREN,
+                ..Default::default()
+            }),
+            ')' => tokens.push(Token {
+                token_type: TokenType::RIGHT_PAREN,
+                ..Default::default()
+            }),
+            '{' => tokens.push(Token {
+                token_type: TokenType::LEFT_BRACE,
+                ..Default::default()
+            }),
+            '}' => tokens.push(Token {
+                token_type: TokenType::RIGHT_BRACE,
+                ..Default::default()
+            }),
+            '[' => tokens.push(Token {
+                token_type: TokenType::LEFT_BRACKET,
+                ..Default::default()
+            }),
+            ']' => tokens.push(Token {
+                token_type: TokenType::RIGHT_BRACKET,
+                ..Default::default()
+            }),
+            ',' => tokens.push(Token {
+                token_type: TokenType::COMMA,
+                ..Default::default()
+            }),
+            '.' => tokens.push(Token {
+                token_type: TokenType::DOT,
+                ..Default::default()
+            }),
+            '-' => tokens.push(Token {
+                token_type: TokenType::MINUS,
+                ..Default::default()
+            }),
+            '+' => tokens.push(Token {
+                token_type: TokenType::PLUS,
+                ..Default::default()
+            }),
+            ';' => tokens.push(Token {
+                token_type: TokenType::SEMICOLON,
+                ..Default::default()
+            }),
+            '*' => tokens.push(Token {
+                token_type: TokenType::STAR,
+                ..Default::default()
+            }),
+            '%' => tokens.push(Token {
+                token_type: TokenType::PERCENT,
+                ..Default::default()
+            }),
+            '?' => tokens.push(Token {
+                token_type: TokenType::QUESTION,
+                ..Default::default()
+            }),
+            ':' => tokens.push(Token {
+                token_type: TokenType::COLON,
+                ..Default::default()
+            }),
+            // One or two character tokens.
+            '!' => tokens.push(Token {
+                token_type: if chars.peek() == Some(&'=') {
+                    chars.next();
+                    TokenType::BANG_EQUAL
+                } else {
+                    TokenType::BANG
+                },
+                ..Default::default()
+            }),
+            '=' => tokens.push(Token {
+                token_type: if chars.peek() == Some(&'=') {
+                    chars.next();
+                    TokenType::EQUAL_EQUAL
+                } else {
+                    TokenType::EQUAL
+                },
+                ..Default::default()
+            }),
+            '<' => tokens.push(Token {
+                token_type: if chars.peek() == Some(&'=') {
+                    chars.next();
+                    TokenType::LESS_EQUAL
+                } else {
+                    TokenType::LESS
+                },
+                ..Default::default()
+            }),
+            '>' => tokens.push(Token {
+                token_type: if chars.peek() == Some(&'=') {
+                    chars.next();
+                    TokenType::GREATER_EQUAL
+                } else {
+                    TokenType::GREATER
+                },
+                ..Default::default()
+            }),
+            '~' if chars.peek() == Some(&'/') => {
+                chars.next();
+                tokens.push(Token {
+                    token_type: TokenType::TILDE_SLASH,
+                    ..Default::default()
+                })
+            }
+            // SLASH or comment
+            '/' => {
+                if chars.peek() == Some(&'/') {
+                    let mut text = String::new();
+                    while chars.peek() != Some(&'\n') && chars.peek().is_some() {
+                        text.push(chars.next().unwrap());
+                    }
+                    if opts.comments {
+                        tokens.push(Token {
+                            token_type: TokenType::COMMENT(text[1..].trim_end().to_owned()),
+                            ..Default::default()
+                        });
+                    }
+                } else {
+                    tokens.push(Token {
+                        token_type: TokenType::SLASH,
+                        ..Default::default()
+                    });
+                }
+            }
+            // String Literal
+            '"' => {
+                let mut value = Vec::new();
+                while chars.peek().is_some() && chars.peek() != Some(&'"') {
+                    let x = chars.next();
+                    value.push(x.unwrap());
+                    if x == Some('\n') {
+                        line += 1;
+                        line_start = chars.pos;
+                    }
+                }
+                let x = chars.next();
+                if x.is_none() {
+                    error(lox, line, chars.pos, "Unterminated string.");
+                    return Err(anyhow::anyhow!("Unterminated string."));
+                }
+                // The same string whatever line endings the file was saved with.
+                let value = value.into_iter().collect::<String>().replace("\r\n", "\n");
+                tokens.push(Token {
+                    token_type: TokenType::STRING(value),
+                    ..Default::default()
+                });
+            }
+            // Number literal
+            '0'..='9' => {
+                let mut value = Vec::new();
+                value.push(c);
+                while is_digit(chars.peek()) {
+                    let x = chars.next().unwrap();
+                    value.push(x);
+                }
+                if chars.peek() == Some(&'.') && is_digit(chars.peek_nth(1)) {
+                    let x = chars.next().unwrap();
+                    value.push(x);
+                    while is_digit(chars.peek()) {
+                        let x = chars.next().unwrap();
+                        value.push(x);
+                    }
+                }
+                // A separator must sit between two digits.
+                let misplaced = value.iter().enumerate().any(|(i, c)| {
+                    *c == '_'
+                        && !(value[i - 1].is_ascii_digit()
+                            && value.get(i + 1).is_some_and(char::is_ascii_digit))
+                });
+                if misplaced {
+                    error(lox, line, chars.pos, "Misplaced digit separator in number.");
+                }
+                // Rust parses floats with '.' as the decimal point whatever
+                // the system locale, so this matches the source text exactly.
+                let string_value: String = value.into_iter().filter(|c| *c != '_').collect();
+                let value: f64 = string_value.parse::<f64>().unwrap();
+                tokens.push(Token {
+                    token_type: TokenType::NUMBER(value),
+                    ..Default::default()
+                });
+            }
+            // Idnetifier
+            'a'..='z' | 'A'..='Z' | '_' => {
+                let mut value = Vec::new();
+                value.push(c);
+                while chars.peek().is_some()
+                    && (chars.peek().unwrap().is_ascii_alphanumeric() || chars.peek() == Some(&'_'))
+                {
+                    value.push(chars.next().unwrap());
+                }
+                let value: String = value.into_iter().collect();
+                let kw = keywords();
+                if let Some(token_type) = kw.get(&value) {
+                    tokens.push(Token {
+                        token_type: (*token_type).clone(),
+                        ..Default::default()
+                    });
+                } else {
+                    tokens.push(Token {
+                        token_type: TokenType::IDENTIFIER(Symbol::intern(&value)),
+                        ..Default::default()
+                    });
+                }
+            }
+            _c => {
+                // lox.error(line, &format!("Unexpected character {:?}.", c));
+                error(lox, line, chars.pos, "Unexpected character.");
+            }
+        }
+        for t in &mut tokens[scanned..] {
+            t.span = Span {
+                start,
+                end: chars.pos,
+                line,
+                column,
+            };
+        }
+        if tokens[scanned..].iter().any(&mut stop) {
+            break;
+        }
+    }
+    Ok((tokens, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    struct TestLox {
+        pub has_error: bool,
+    }
+
+    impl LoxError for TestLox {
+        fn report(&mut self, _diagnostic: Diagnostic) {
+            self.has_error = true;
+        }
+
+        fn has_error(&self) -> bool {
+            self.has_error
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut lox = TestLox { has_error: false };
+        let input = "";
+        let expected = vec![Token {
+            token_type: TokenType::EOF,
+            span: Span {
+                line: 1,
+                ..Default::default()
+            },
+        }];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_identifier() {
+        let mut lox = TestLox { has_error: false };
+        let input = "asdf";
+        let expected = vec![
+            Token {
+                token_type: TokenType::IDENTIFIER(Symbol::intern("asdf")),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_digit() {
+        let mut lox = TestLox { has_error: false };
+        let input = "1";
+        let expected = vec![
+            Token {
+                token_type: TokenType::NUMBER(1.0),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_number() {
+        let mut lox = TestLox { has_error: false };
+        let input = "123.123 321";
+        let expected = vec![
+            Token {
+                token_type: TokenType::NUMBER(123.123),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::NUMBER(321.0),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_simple_string() {
+        let mut lox = TestLox { has_error: false };
+        let input = "\"asdf\"";
+        let expected = vec![
+            Token {
+                token_type: TokenType::STRING(input[1..input.len() - 1].to_string()),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_string() {
+        let mut lox = TestLox { has_error: false };
+        let input = "\" asdf\n\t\"";
+        let expected = vec![
+            Token {
+                token_type: TokenType::STRING(input[1..input.len() - 1].to_string()),
+                span: Span {
+                    line: 2, // FIXME: Is this what we expect?
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 2,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_punct() {
+        let mut lox = TestLox { has_error: false };
+        let input = "(){}[],.-+;/*%?:~/";
+        let expected = vec![
+            Token {
+                token_type: TokenType::LEFT_PAREN,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::RIGHT_PAREN,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::LEFT_BRACE,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::RIGHT_BRACE,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::LEFT_BRACKET,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::RIGHT_BRACKET,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::COMMA,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::DOT,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::MINUS,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::PLUS,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::SEMICOLON,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::SLASH,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::STAR,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::PERCENT,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::QUESTION,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::COLON,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::TILDE_SLASH,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_punct2() {
+        let mut lox = TestLox { has_error: false };
+        let input = "! != = == > >= < <= ";
+        let expected = vec![
+            Token {
+                token_type: TokenType::BANG,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::BANG_EQUAL,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EQUAL,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EQUAL_EQUAL,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::GREATER,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::GREATER_EQUAL,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::LESS,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::LESS_EQUAL,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_keywords() {
+        let mut lox = TestLox { has_error: false };
+        let input =
+            "and catch class else false fun for if in nil or print return super this throw \
+                     true try var while";
+        let expected = vec![
+            Token {
+                token_type: TokenType::AND,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::CATCH,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::CLASS,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::ELSE,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::FALSE,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::FUN,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::FOR,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::IF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::IN,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::NIL,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::OR,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::PRINT,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::RETURN,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::SUPER,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::THIS,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::THROW,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::TRUE,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::TRY,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::VAR,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::WHILE,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_comment() {
+        let mut lox = TestLox { has_error: false };
+        let input = "something // comment";
+        let expected = vec![
+            Token {
+                token_type: TokenType::IDENTIFIER(Symbol::intern("something")),
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+            Token {
+                token_type: TokenType::EOF,
+                span: Span {
+                    line: 1,
+                    ..Default::default()
+                },
+            },
+        ];
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens, expected);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_unexp_chr() {
+        let mut lox = TestLox { has_error: false };
+        let input = "@#";
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        let expected = vec![Token {
+            token_type: TokenType::EOF,
+            span: Span {
+                line: 1,
+                ..Default::default()
+            },
+        }];
+        // FIXME: SHould this be an error return?
+        assert_eq!(&tokens, &expected);
+        assert!(lox.has_error());
+    }
+
+    #[test]
+    fn test_unterm_string() {
+        let mut lox = TestLox { has_error: false };
+        let input = "\"asdfa";
+        let tokens = scan_tokens(&mut lox, input);
+        assert!(tokens.is_err());
+        assert!(lox.has_error());
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut lox = TestLox { has_error: false };
+        let input = "a >=\n \"\u{e9}\"";
+        let spans: Vec<Span> = scan_tokens(&mut lox, input)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.span)
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                Span {
+                    start: 0,
+                    end: 1,
+                    line: 1,
+                    column: 1
+                },
+                Span {
+                    start: 2,
+                    end: 4,
+                    line: 1,
+                    column: 3
+                },
+                Span {
+                    start: 6,
+                    end: 10,
+                    line: 2,
+                    column: 2
+                },
+                Span {
+                    start: 10,
+                    end: 10,
+                    line: 2,
+                    column: 5
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crlf() {
+        let mut lox = TestLox { has_error: false };
+        let input = "var a;\r\n\"x\r\ny\"\r\n\tb";
+        let tokens = scan_tokens(&mut lox, input).unwrap();
+        assert_eq!(tokens[3].token_type, TokenType::STRING("x\ny".to_owned()));
+        let lines: Vec<(i32, usize)> = tokens
+            .iter()
+            .map(|t| (t.span.line, t.span.column))
+            .collect();
+        assert_eq!(lines, [(1, 1), (1, 5), (1, 6), (3, 1), (4, 2), (4, 3)]);
+        assert!(!lox.has_error());
+    }
+
+    #[test]
+    fn test_is_incomplete() {
+        assert!(is_incomplete("fun f() {"));
+        assert!(is_incomplete("print \"abc"));
+        assert!(is_incomplete("f(1,"));
+        assert!(!is_incomplete("fun f() { return 1; }"));
+        assert!(!is_incomplete("print 1;"));
+    }
+
+    #[test]
+    fn test_digit_separators() {
+        let mut lox = TestLox { has_error: false };
+        let opts = ScanOptions {
+            digit_separators: true,
+            ..Default::default()
+        };
+        let tokens = scan_tokens_with_options(&mut lox, "1_000_000 2.718_5", &opts).unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::NUMBER(1_000_000.0));
+        assert_eq!(tokens[1].token_type, TokenType::NUMBER(2.7185));
+        assert!(!lox.has_error());
+        for bad in ["1_", "1__0", "1_.5", "1._5"] {
+            let mut lox = TestLox { has_error: false };
+            let _ = scan_tokens_with_options(&mut lox, bad, &opts);
+            assert!(lox.has_error(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut lox = TestLox { has_error: false };
+        let source = "// lead\r\nprint 1; //trail  \n";
+        let tokens = scan_tokens(&mut lox, source).unwrap();
+        assert_eq!(tokens.len(), 4);
+        let opts = ScanOptions {
+            comments: true,
+            ..Default::default()
+        };
+        let tokens = scan_tokens_with_options(&mut lox, source, &opts).unwrap();
+        let types: Vec<_> = tokens.iter().map(|t| &t.token_type).collect();
+        assert_eq!(types[0], &TokenType::COMMENT(" lead".to_owned()));
+        assert_eq!(types[4], &TokenType::COMMENT("trail".to_owned()));
+        assert_eq!(tokens[4].span.line, 2);
+    }
+
+    #[test]
+    fn test_trivia_round_trip() {
+        let mut lox = TestLox { has_error: false };
+        let source = "  // lead\r\nvar s = \"two\nlines\";\t// trail\n\n\nprint s ;  \n";
+        let opts = ScanOptions {
+            comments: true,
+            trivia: true,
+            ..Default::default()
+        };
+        let tokens = scan_tokens_with_options(&mut lox, source, &opts).unwrap();
+        let text: String = tokens
+            .iter()
+            .map(|t| &source[t.span.start..t.span.end])
+            .collect();
+        assert_eq!(text, source);
+        assert_eq!(tokens[0].token_type, TokenType::WHITESPACE);
+        let breaks = tokens
+            .iter()
+            .find(|t| source[t.span.start..t.span.end] == *"\n\n\n");
+        assert_eq!(breaks.map(|t| t.span.line), Some(6));
+        assert!(!lox.has_error());
+    }
+}
@@ -0,0 +1,635 @@
+use crate::environment::Enviornment;
+use crate::intern::Symbol;
+use crate::interpreter::{
+    compare, truthy, Interpreter, LoxCallable, LoxCallableWrapper, NativeError, Object, ObjectKey,
+};
+use crate::iterator::IterNative;
+use anyhow::{anyhow, Result};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::rc::Rc;
+
+/// Define every builtin function in the global scope of `env`.
+pub fn install(env: &mut Enviornment) {
+    define_native(env, LoxBuiltinClock {});
+    define_native(env, LoxBuiltinInspect {});
+    define_native(env, LoxBuiltinStackTrace {});
+    define_native(env, LoxBuiltinMemoize {});
+    for info in [FnInfo::Name, FnInfo::Arity, FnInfo::Location] {
+        define_native(env, LoxBuiltinFnInfo { info });
+    }
+    for native in IterNative::ALL {
+        define_native(env, native);
+    }
+    define_native(env, LoxBuiltinReadLine {});
+    define_native(env, LoxBuiltinArgs {});
+    define_native(env, LoxBuiltinExit {});
+    define_native(env, LoxBuiltinAssert {});
+    define_native(env, LoxBuiltinStr {});
+    define_native(env, LoxBuiltinNum {});
+    define_native(env, LoxBuiltinLen {});
+    define_native(env, LoxBuiltinPush {});
+    define_native(env, LoxBuiltinPop {});
+    for op in [MathOp::Abs, MathOp::Floor, MathOp::Ceil, MathOp::Sqrt] {
+        define_native(env, LoxBuiltinMath { op });
+    }
+    define_native(env, LoxBuiltinRandom {});
+    define_native(env, LoxBuiltinCompare {});
+    define_native(env, LoxBuiltinSort {});
+    for event in [HookEvent::Error, HookEvent::Exit] {
+        define_native(env, LoxBuiltinHook { event });
+    }
+}
+
+fn define_native(env: &mut Enviornment, native: impl LoxCallable + 'static) {
+    env.define(
+        Symbol::intern(&native.name()),
+        Object::Callable(LoxCallableWrapper::new(native)),
+    );
+}
+
+/// The `NativeError` for the native `name` getting something other than
+/// `expected` as `args[idx]`, counting arguments from 1.
+pub(crate) fn bad_argument(
+    args: &[Object],
+    idx: usize,
+    name: &str,
+    expected: &str,
+) -> anyhow::Error {
+    anyhow!(NativeError(format!(
+        "Expected argument {} of {} to be {}, got {}",
+        idx + 1,
+        name,
+        expected,
+        args.get(idx).map_or("nothing", Object::type_name)
+    )))
+}
+
+/// The number at `args[idx]`, or a `NativeError` for the native `name`
+/// saying what it got instead, counting arguments from 1.
+pub fn expect_number(args: &[Object], idx: usize, name: &str) -> Result<f64> {
+    match args.get(idx) {
+        Some(Object::Double(d)) => Ok(*d),
+        _ => Err(bad_argument(args, idx, name, "a number")),
+    }
+}
+
+/// The list at `args[idx]`, like `expect_number`.
+pub fn expect_list<'o>(
+    args: &'o [Object],
+    idx: usize,
+    name: &str,
+) -> Result<&'o Rc<RefCell<Vec<Object>>>> {
+    match args.get(idx) {
+        Some(Object::List(l)) => Ok(l),
+        _ => Err(bad_argument(args, idx, name, "a list")),
+    }
+}
+
+/// The string at `args[idx]`, like `expect_number`.
+pub fn expect_string<'o>(args: &'o [Object], idx: usize, name: &str) -> Result<&'o str> {
+    match args.get(idx) {
+        Some(Object::String(s)) => Ok(s),
+        _ => Err(bad_argument(args, idx, name, "a string")),
+    }
+}
+
+/// The function at `args[idx]`, like `expect_number`.
+pub fn expect_callable<'o>(
+    args: &'o [Object],
+    idx: usize,
+    name: &str,
+) -> Result<&'o LoxCallableWrapper> {
+    match args.get(idx) {
+        Some(Object::Callable(f)) => Ok(f),
+        _ => Err(bad_argument(args, idx, name, "a function")),
+    }
+}
+
+/// Time since the Unix epoch. `SystemTime` panics in the browser, so there
+/// it comes from JavaScript.
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+fn since_epoch() -> std::time::Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+fn since_epoch() -> std::time::Duration {
+    std::time::Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+}
+
+#[derive(Debug)]
+struct LoxBuiltinClock {}
+impl LoxCallable for LoxBuiltinClock {
+    fn call(&self, _i: &mut Interpreter, _args: Vec<Object>) -> Result<Object> {
+        Ok(Object::Double(since_epoch().as_secs_f64()))
+    }
+    fn name(&self) -> String {
+        "clock".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinInspect {}
+impl LoxCallable for LoxBuiltinInspect {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let depth = match args.get(1) {
+            Some(_) => expect_number(&args, 1, &self.name())?.max(0.0) as usize,
+            None => INSPECT_DEFAULT_DEPTH,
+        };
+        Ok(Object::String(inspect(
+            args.first().unwrap_or(&Object::Nil),
+            depth,
+        )))
+    }
+    fn name(&self) -> String {
+        "inspect".to_owned()
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinStackTrace {}
+impl LoxCallable for LoxBuiltinStackTrace {
+    fn call(&self, i: &mut Interpreter, _args: Vec<Object>) -> Result<Object> {
+        let frames = i.stack_trace().into_iter().map(Object::String).collect();
+        Ok(Object::List(Rc::new(RefCell::new(frames))))
+    }
+    fn name(&self) -> String {
+        "stackTrace".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinMemoize {}
+impl LoxCallable for LoxBuiltinMemoize {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let inner = expect_callable(&args, 0, &self.name())?.clone();
+        Ok(Object::Callable(LoxCallableWrapper::new(LoxMemoized {
+            inner,
+            cache: RefCell::new(HashMap::new()),
+        })))
+    }
+    fn name(&self) -> String {
+        "memoize".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// A callable returned by `memoize`, caching results by argument values.
+/// Calls with unhashable arguments go straight through to `inner`.
+#[derive(Debug)]
+struct LoxMemoized {
+    inner: LoxCallableWrapper,
+    cache: RefCell<HashMap<Vec<ObjectKey>, Object>>,
+}
+impl LoxCallable for LoxMemoized {
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let key: Option<Vec<ObjectKey>> = args.iter().map(Object::hash_key).collect();
+        let key = match key {
+            Some(key) => key,
+            None => return self.inner.call(i, args),
+        };
+        if let Some(v) = self.cache.borrow().get(&key) {
+            return Ok(v.clone());
+        }
+        let v = self.inner.call(i, args)?;
+        self.cache.borrow_mut().insert(key, v.clone());
+        Ok(v)
+    }
+    fn name(&self) -> String {
+        self.inner.name()
+    }
+    fn arity(&self) -> Option<usize> {
+        self.inner.arity()
+    }
+    fn line(&self) -> Option<i32> {
+        self.inner.line()
+    }
+}
+
+/// Which piece of function metadata a `LoxBuiltinFnInfo` native reports.
+#[derive(Debug, Clone, Copy)]
+enum FnInfo {
+    Name,
+    Arity,
+    Location,
+}
+
+#[derive(Debug)]
+struct LoxBuiltinFnInfo {
+    info: FnInfo,
+}
+impl LoxCallable for LoxBuiltinFnInfo {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let f = expect_callable(&args, 0, &self.name())?;
+        Ok(match self.info {
+            FnInfo::Name => Object::String(f.name()),
+            FnInfo::Arity => f.arity().map_or(Object::Nil, |a| Object::Double(a as f64)),
+            FnInfo::Location => f
+                .line()
+                .map_or(Object::Nil, |l| Object::String(format!("line {}", l))),
+        })
+    }
+    fn name(&self) -> String {
+        match self.info {
+            FnInfo::Name => "fnName",
+            FnInfo::Arity => "fnArity",
+            FnInfo::Location => "fnLocation",
+        }
+        .to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+pub const INSPECT_DEFAULT_DEPTH: usize = 2;
+
+/// Debug representation of a value: unlike `Display`, strings are quoted and
+/// nested values deeper than `depth` are elided as `...`.
+pub fn inspect(o: &Object, depth: usize) -> String {
+    inspect_inner(o, depth, &mut vec![])
+}
+
+fn inspect_inner(o: &Object, depth: usize, seen: &mut Vec<*const RefCell<Vec<Object>>>) -> String {
+    match o {
+        Object::String(s) => format!("{:?}", s),
+        Object::List(l) if seen.contains(&Rc::as_ptr(l)) => "[<cycle>]".to_owned(),
+        Object::List(l) if l.borrow().is_empty() => "[]".to_owned(),
+        Object::List(_) if depth == 0 => "[...]".to_owned(),
+        Object::List(l) => {
+            seen.push(Rc::as_ptr(l));
+            let items: Vec<String> = l
+                .borrow()
+                .iter()
+                .map(|item| inspect_inner(item, depth - 1, seen))
+                .collect();
+            seen.pop();
+            format!("[{}]", items.join(", "))
+        }
+        Object::Return(o) if depth > 0 => inspect_inner(o, depth - 1, seen),
+        Object::Return(_) => "...".to_owned(),
+        o => o.to_string(),
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinReadLine {}
+impl LoxCallable for LoxBuiltinReadLine {
+    fn call(&self, _i: &mut Interpreter, _args: Vec<Object>) -> Result<Object> {
+        let mut line = String::new();
+        Ok(match std::io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => Object::Nil,
+            Ok(_) => Object::String(line.trim_end_matches(&['\r', '\n'][..]).to_owned()),
+        })
+    }
+    fn name(&self) -> String {
+        "readLine".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// The arguments after the script on the command line, as a new list of
+/// strings each call.
+#[derive(Debug)]
+struct LoxBuiltinArgs {}
+impl LoxCallable for LoxBuiltinArgs {
+    fn call(&self, i: &mut Interpreter, _args: Vec<Object>) -> Result<Object> {
+        let args = i.env.args.iter().cloned().map(Object::String).collect();
+        Ok(Object::List(Rc::new(RefCell::new(args))))
+    }
+    fn name(&self) -> String {
+        "args".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+/// `exit(status)`: stop the program, running `onExit` handlers, and have
+/// the process exit with `status`.
+#[derive(Debug)]
+struct LoxBuiltinExit {}
+impl LoxCallable for LoxBuiltinExit {
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let status = expect_number(&args, 0, &self.name())?;
+        i.exit_with(status as i32)
+    }
+    fn name(&self) -> String {
+        "exit".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// `assert(condition)`, or `assert(condition, message)` to say what was
+/// expected. A false or nil condition stops the program.
+#[derive(Debug)]
+struct LoxBuiltinAssert {}
+impl LoxCallable for LoxBuiltinAssert {
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        if args.first().is_some_and(truthy) {
+            return Ok(Object::Nil);
+        }
+        match args.get(1) {
+            Some(message) => i.assertion_failed(format!("Assertion failed: {}", message)),
+            None => i.assertion_failed("Assertion failed".to_owned()),
+        }
+    }
+    fn name(&self) -> String {
+        "assert".to_owned()
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinStr {}
+impl LoxCallable for LoxBuiltinStr {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        Ok(Object::String(
+            args.first().unwrap_or(&Object::Nil).to_string(),
+        ))
+    }
+    fn name(&self) -> String {
+        "str".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinNum {}
+impl LoxCallable for LoxBuiltinNum {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        if let Some(Object::Double(d)) = args.first() {
+            return Ok(Object::Double(*d));
+        }
+        let s = expect_string(&args, 0, &self.name())?;
+        Ok(s.trim().parse::<f64>().map_or(Object::Nil, Object::Double))
+    }
+    fn name(&self) -> String {
+        "num".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinLen {}
+impl LoxCallable for LoxBuiltinLen {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        Ok(match args.first() {
+            Some(Object::String(s)) => Object::Double(s.chars().count() as f64),
+            Some(Object::List(l)) => Object::Double(l.borrow().len() as f64),
+            _ => return Err(bad_argument(&args, 0, &self.name(), "a list or string")),
+        })
+    }
+    fn name(&self) -> String {
+        "len".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinPush {}
+impl LoxCallable for LoxBuiltinPush {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let l = expect_list(&args, 0, &self.name())?;
+        l.borrow_mut().push(args[1].clone());
+        Ok(Object::Nil)
+    }
+    fn name(&self) -> String {
+        "push".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(2)
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinPop {}
+impl LoxCallable for LoxBuiltinPop {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let l = expect_list(&args, 0, &self.name())?;
+        let popped = l.borrow_mut().pop();
+        Ok(popped.unwrap_or(Object::Nil))
+    }
+    fn name(&self) -> String {
+        "pop".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MathOp {
+    Abs,
+    Floor,
+    Ceil,
+    Sqrt,
+}
+
+#[derive(Debug)]
+struct LoxBuiltinMath {
+    op: MathOp,
+}
+impl LoxCallable for LoxBuiltinMath {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let x = expect_number(&args, 0, &self.name())?;
+        Ok(Object::Double(match self.op {
+            MathOp::Abs => x.abs(),
+            MathOp::Floor => x.floor(),
+            MathOp::Ceil => x.ceil(),
+            MathOp::Sqrt => x.sqrt(),
+        }))
+    }
+    fn name(&self) -> String {
+        match self.op {
+            MathOp::Abs => "abs",
+            MathOp::Floor => "floor",
+            MathOp::Ceil => "ceil",
+            MathOp::Sqrt => "sqrt",
+        }
+        .to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+thread_local! {
+    static RANDOM_STATE: Cell<u64> = Cell::new(since_epoch().as_nanos() as u64 | 1);
+}
+
+/// Uniform number in [0, 1) from a xorshift64* generator. Not suitable for
+/// anything security related.
+#[derive(Debug)]
+struct LoxBuiltinRandom {}
+impl LoxCallable for LoxBuiltinRandom {
+    fn call(&self, _i: &mut Interpreter, _args: Vec<Object>) -> Result<Object> {
+        let x = RANDOM_STATE.with(|state| {
+            let mut x = state.get();
+            x ^= x >> 12;
+            x ^= x << 25;
+            x ^= x >> 27;
+            state.set(x);
+            x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        });
+        Ok(Object::Double((x >> 11) as f64 / (1u64 << 53) as f64))
+    }
+    fn name(&self) -> String {
+        "random".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(0)
+    }
+}
+
+#[derive(Debug)]
+struct LoxBuiltinCompare {}
+impl LoxCallable for LoxBuiltinCompare {
+    fn call(&self, _i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let nil = Object::Nil;
+        let (a, b) = (args.first().unwrap_or(&nil), args.get(1).unwrap_or(&nil));
+        Ok(Object::Double(compare(a, b) as i8 as f64))
+    }
+    fn name(&self) -> String {
+        "compare".to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(2)
+    }
+}
+
+/// Stable merge sort. Unlike `slice::sort_by` it tolerates comparators that
+/// aren't a consistent order, which user-supplied Lox functions may not be.
+fn merge_sort(
+    items: Vec<Object>,
+    cmp: &mut dyn FnMut(&Object, &Object) -> Ordering,
+) -> Vec<Object> {
+    if items.len() <= 1 {
+        return items;
+    }
+    let mut left = items;
+    let right = left.split_off(left.len() / 2);
+    let (left, right) = (merge_sort(left, cmp), merge_sort(right, cmp));
+    let mut out = Vec::with_capacity(left.len() + right.len());
+    let (mut left, mut right) = (left.into_iter().peekable(), right.into_iter().peekable());
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if cmp(r, l) == Ordering::Less {
+            out.extend(right.next());
+        } else {
+            out.extend(left.next());
+        }
+    }
+    out.extend(left);
+    out.extend(right);
+    out
+}
+
+/// `sort(list)` or `sort(list, fn)`: sorts the list in place and returns it.
+/// Without `fn` values are ordered by `compare`; `fn(a, b)` returns a number
+/// below, at or above zero.
+#[derive(Debug)]
+struct LoxBuiltinSort {}
+impl LoxCallable for LoxBuiltinSort {
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let list = expect_list(&args, 0, &self.name())?.clone();
+        // Work on a copy so a comparator touching the list can't conflict
+        // with the borrow.
+        let items = list.borrow().clone();
+        let comparator = match args.get(1) {
+            Some(_) => Some(expect_callable(&args, 1, &self.name())?),
+            None => None,
+        };
+        let sorted = match comparator {
+            Some(f) => {
+                // The first error stops the comparisons that matter; it is
+                // returned once the sort is done, leaving the list as it was.
+                let mut failed = None;
+                let sorted = merge_sort(items, &mut |a, b| {
+                    if failed.is_some() {
+                        return Ordering::Equal;
+                    }
+                    match f.call(i, vec![a.clone(), b.clone()]) {
+                        Ok(Object::Double(d)) => d.partial_cmp(&0.0).unwrap_or(Ordering::Equal),
+                        Ok(got) => {
+                            failed = Some(anyhow!(NativeError(format!(
+                                "Expected argument 2 of sort to return a number, got {}",
+                                got.type_name()
+                            ))));
+                            Ordering::Equal
+                        }
+                        Err(e) => {
+                            failed = Some(e);
+                            Ordering::Equal
+                        }
+                    }
+                });
+                if let Some(e) = failed {
+                    return Err(e);
+                }
+                sorted
+            }
+            None => merge_sort(items, &mut compare),
+        };
+        *list.borrow_mut() = sorted;
+        Ok(Object::List(list))
+    }
+    fn name(&self) -> String {
+        "sort".to_owned()
+    }
+}
+
+/// Which runtime event a `LoxBuiltinHook` native registers a handler for.
+#[derive(Debug, Clone, Copy)]
+enum HookEvent {
+    Error,
+    Exit,
+}
+
+/// `onError(handler)` and `onExit(handler)`: have the interpreter call
+/// `handler` when a runtime error stops the program, or when it exits.
+#[derive(Debug)]
+struct LoxBuiltinHook {
+    event: HookEvent,
+}
+impl LoxCallable for LoxBuiltinHook {
+    fn call(&self, i: &mut Interpreter, args: Vec<Object>) -> Result<Object> {
+        let handler = expect_callable(&args, 0, &self.name())?.clone();
+        match self.event {
+            HookEvent::Error => i.env.hooks.on_error.push(handler),
+            HookEvent::Exit => i.env.hooks.on_exit.push(handler),
+        }
+        Ok(Object::Nil)
+    }
+    fn name(&self) -> String {
+        match self.event {
+            HookEvent::Error => "onError",
+            HookEvent::Exit => "onExit",
+        }
+        .to_owned()
+    }
+    fn arity(&self) -> Option<usize> {
+        Some(1)
+    }
+}
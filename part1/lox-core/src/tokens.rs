@@ -0,0 +1,264 @@
+use crate::intern::Symbol;
+use maplit::hashmap;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Token {
+    pub token_type: TokenType,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn line(&self) -> i32 {
+        self.span.line
+    }
+}
+
+// Tokens are equal by kind and line; where exactly they sit is only used
+// for pointing at them in diagnostics.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.token_type == other.token_type && self.line() == other.line()
+    }
+}
+
+/// Where a token or syntax node sits in its source: a byte range, `end`
+/// exclusive, the line it ends on, and the 1-based column (in characters)
+/// of its first character.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: i32,
+    pub column: usize,
+}
+
+/// `s` as a quoted JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Render a scanned token stream as a JSON array, one object per token with
+/// its type, lexeme, literal value and position.
+pub fn tokens_json(source: &str, tokens: &[Token]) -> String {
+    let entries: Vec<String> = tokens
+        .iter()
+        .map(|t| {
+            let span = t.span;
+            let kind = format!("{:?}", t.token_type);
+            let kind = kind.split('(').next().unwrap_or_default();
+            let lexeme = source.get(span.start..span.end).unwrap_or_default();
+            let literal = match &t.token_type {
+                TokenType::STRING(s) => json_string(s),
+                TokenType::NUMBER(n) => n.to_string(),
+                _ => "null".to_owned(),
+            };
+            format!(
+                "{{\"type\":{},\"lexeme\":{},\"literal\":{},\"line\":{},\"column\":{},\"start\":{},\"end\":{}}}",
+                json_string(kind),
+                json_string(lexeme),
+                literal,
+                span.line,
+                span.column,
+                span.start,
+                span.end
+            )
+        })
+        .collect();
+    format!("[\n  {}\n]", entries.join(",\n  "))
+}
+
+impl Default for Token {
+    fn default() -> Self {
+        Self {
+            token_type: TokenType::UNKNOWN_TOKEN,
+            span: Span {
+                line: -1,
+                ..Span::default()
+            },
+        }
+    }
+}
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on line {}", &self.token_type, self.line())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(non_camel_case_types)]
+pub enum TokenType {
+    // Single-character tokens.
+    LEFT_PAREN,
+    RIGHT_PAREN,
+    LEFT_BRACE,
+    RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
+    COMMA,
+    DOT,
+    MINUS,
+    PLUS,
+    SEMICOLON,
+    SLASH,
+    STAR,
+    PERCENT,
+    QUESTION,
+    COLON,
+
+    // One or two character tokens.
+    BANG,
+    BANG_EQUAL,
+    EQUAL,
+    EQUAL_EQUAL,
+    GREATER,
+    GREATER_EQUAL,
+    LESS,
+    LESS_EQUAL,
+    /// `~/`, integer division: `//` already starts a comment.
+    TILDE_SLASH,
+
+    // Literals.
+    IDENTIFIER(Symbol),
+    STRING(String),
+    NUMBER(f64),
+    /// The text after a comment's `//`, only scanned when asked for.
+    COMMENT(String),
+    /// A run of spaces, tabs and line breaks, only scanned when asked for.
+    WHITESPACE,
+
+    // Keywords.
+    AND,
+    CATCH,
+    CLASS,
+    ELSE,
+    FALSE,
+    FUN,
+    FOR,
+    IF,
+    IN,
+    NIL,
+    OR,
+
+    PRINT,
+    RETURN,
+    SUPER,
+    THIS,
+    THROW,
+    TRUE,
+    TRY,
+    VAR,
+    WHILE,
+
+    EOF,
+    UNKNOWN_TOKEN,
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LEFT_PAREN => write!(f, "(",),
+            Self::RIGHT_PAREN => write!(f, ")",),
+            Self::LEFT_BRACE => write!(f, "{{",),
+            Self::RIGHT_BRACE => write!(f, "}}",),
+            Self::LEFT_BRACKET => write!(f, "[",),
+            Self::RIGHT_BRACKET => write!(f, "]",),
+            Self::COMMA => write!(f, ",",),
+            Self::DOT => write!(f, ".",),
+            Self::MINUS => write!(f, "-",),
+            Self::PLUS => write!(f, "+",),
+            Self::SEMICOLON => write!(f, ";",),
+            Self::SLASH => write!(f, "/",),
+            Self::STAR => write!(f, "*",),
+            Self::PERCENT => write!(f, "%",),
+            Self::TILDE_SLASH => write!(f, "~/",),
+            Self::QUESTION => write!(f, "?",),
+            Self::COLON => write!(f, ":",),
+            Self::BANG => write!(f, "!",),
+            Self::BANG_EQUAL => write!(f, "!=",),
+            Self::EQUAL => write!(f, "=",),
+            Self::EQUAL_EQUAL => write!(f, "==",),
+            Self::GREATER => write!(f, ">",),
+            Self::GREATER_EQUAL => write!(f, ">=",),
+            Self::LESS => write!(f, "<",),
+            Self::LESS_EQUAL => write!(f, "<=",),
+            Self::IDENTIFIER(name) => write!(f, "{}", name),
+            Self::STRING(val) => write!(f, "{}", val),
+            Self::NUMBER(val) => write!(f, "{}", val),
+            Self::COMMENT(text) => write!(f, "//{}", text),
+            Self::WHITESPACE => write!(f, " "),
+            Self::AND => write!(f, "&&",),
+            Self::CATCH => write!(f, "catch",),
+            Self::CLASS => write!(f, "class",),
+            Self::ELSE => write!(f, "else",),
+            Self::FALSE => write!(f, "false",),
+            Self::FUN => write!(f, "fun",),
+            Self::FOR => write!(f, "for",),
+            Self::IF => write!(f, "if",),
+            Self::IN => write!(f, "in",),
+            Self::NIL => write!(f, "nil",),
+            Self::OR => write!(f, "||",),
+            Self::PRINT => write!(f, "print",),
+            Self::RETURN => write!(f, "return",),
+            Self::SUPER => write!(f, "super",),
+            Self::THIS => write!(f, "this",),
+            Self::THROW => write!(f, "throw",),
+            Self::TRUE => write!(f, "true",),
+            Self::TRY => write!(f, "try",),
+            Self::VAR => write!(f, "var",),
+            Self::WHILE => write!(f, "while",),
+            Self::EOF => write!(f, "<EOF>",),
+            Self::UNKNOWN_TOKEN => write!(f, "#######UNKNOWN#######",),
+        }
+    }
+}
+
+/// The source spelling of `token_type` if it is a reserved word.
+pub fn reserved_word(token_type: &TokenType) -> Option<String> {
+    keywords()
+        .into_iter()
+        .find_map(|(word, t)| (t == *token_type).then_some(word))
+}
+
+pub fn keywords() -> HashMap<String, TokenType> {
+    hashmap! {
+        "and".to_owned() => TokenType::AND,
+        "catch".to_owned() => TokenType::CATCH,
+        "class".to_owned() => TokenType::CLASS,
+        "else".to_owned() => TokenType::ELSE,
+        "false".to_owned() => TokenType::FALSE,
+        "fun".to_owned() => TokenType::FUN,
+        "for".to_owned() => TokenType::FOR,
+        "if".to_owned() => TokenType::IF,
+        "in".to_owned() => TokenType::IN,
+        "nil".to_owned() => TokenType::NIL,
+        "or".to_owned() => TokenType::OR,
+
+        "print".to_owned() => TokenType::PRINT,
+        "return".to_owned() => TokenType::RETURN,
+        "super".to_owned() => TokenType::SUPER,
+        "this".to_owned() => TokenType::THIS,
+        "throw".to_owned() => TokenType::THROW,
+        "true".to_owned() => TokenType::TRUE,
+        "try".to_owned() => TokenType::TRY,
+        "var".to_owned() => TokenType::VAR,
+        "while".to_owned() => TokenType::WHILE,
+    }
+}
@@ -0,0 +1,71 @@
+use crate::lox::{Lox, LoxOptions};
+use crate::tokens::json_string;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+/// Bytes written by the program, kept for the page to show.
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+/// Run `source` as a program and describe the run as a JSON object:
+/// `{"ok": bool, "output": string, "diagnostics": [...]}`, each diagnostic
+/// as `--diagnostics-json` prints it.
+pub fn run_to_json(source: &str) -> String {
+    let output = Captured::default();
+    let errors = Captured::default();
+    let mut lox = Lox::new(LoxOptions {
+        diagnostics_json: true,
+        ..Default::default()
+    });
+    lox.set_output(output.clone());
+    lox.set_error_sink(errors.clone());
+    let ok = lox.run(source.to_owned()).is_ok();
+    let diagnostics: Vec<String> = errors.text().lines().map(str::to_owned).collect();
+    format!(
+        "{{\"ok\":{},\"output\":{},\"diagnostics\":[{}]}}",
+        ok,
+        json_string(&output.text()),
+        diagnostics.join(",")
+    )
+}
+
+/// The playground's entry point: `run_to_json` as a JavaScript object.
+#[wasm_bindgen]
+pub fn run_lox(source: &str) -> JsValue {
+    let json = run_to_json(source);
+    js_sys::JSON::parse(&json).unwrap_or_else(|_| JsValue::from_str(&json))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_to_json() {
+        assert_eq!(
+            run_to_json("print \"hi\";\nprint 1 + 2;"),
+            r#"{"ok":true,"output":"hi\n3\n","diagnostics":[]}"#
+        );
+        let json = run_to_json("print 1;\nprint -nil;");
+        assert!(json.starts_with(r#"{"ok":false,"output":"1\n","diagnostics":[{"#));
+        assert!(json.contains(r#""message":"Operand must be a number.""#));
+    }
+}
@@ -0,0 +1,240 @@
+use lox_core::incremental::{Edit, ParsedSource};
+use lox_core::lox_error::Severity;
+use lox_tools::symbols::{DefinitionKind, Symbols};
+use lsp_types::{
+    DiagnosticSeverity, DocumentSymbol, NumberOrString, Position, Range, SymbolKind,
+    TextDocumentContentChangeEvent,
+};
+use std::collections::HashMap;
+
+/// An open file: its text as the editor has it, and what was found in it.
+pub struct Document {
+    /// Scanned and parsed again only around each edit.
+    pub source: ParsedSource,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    /// From the declarations that parse, so names elsewhere in the file
+    /// still work while a line is half typed.
+    pub symbols: Symbols,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let mut document = Self {
+            source: ParsedSource::new(text),
+            line_starts: vec![],
+            symbols: Symbols::default(),
+        };
+        document.update();
+        document
+    }
+
+    pub fn text(&self) -> &str {
+        self.source.text()
+    }
+
+    /// Apply the changes of a `didChange`, in order.
+    pub fn edit(&mut self, changes: Vec<TextDocumentContentChangeEvent>) {
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let range = self.offset(range.start)..self.offset(range.end);
+                    self.source.edit(Edit {
+                        range,
+                        text: change.text,
+                    });
+                }
+                None => self.source = ParsedSource::new(change.text),
+            }
+            // Each change's range is in the text the ones before it left.
+            self.update();
+        }
+    }
+
+    fn update(&mut self) {
+        self.line_starts = std::iter::once(0)
+            .chain(self.text().match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.symbols = Symbols::new(self.source.statements());
+    }
+
+    /// The byte offset of `position`, whose character counts UTF-16 code
+    /// units as LSP does. Past the end of a line is its end.
+    pub fn offset(&self, position: Position) -> usize {
+        let Some(&start) = self.line_starts.get(position.line as usize) else {
+            return self.text().len();
+        };
+        let line = self.text()[start..].split('\n').next().unwrap_or_default();
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let mut units = 0;
+        for (i, c) in line.char_indices() {
+            if units >= position.character as usize {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        start + line.len()
+    }
+
+    /// Where byte `offset` is, as LSP counts.
+    pub fn position(&self, offset: usize) -> Position {
+        let offset = offset.min(self.text().len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let character = self.text()[self.line_starts[line]..offset]
+            .encode_utf16()
+            .count();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn range(&self, span: std::ops::Range<usize>) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+
+    /// The diagnostics as LSP has them.
+    pub fn lsp_diagnostics(&self) -> Vec<lsp_types::Diagnostic> {
+        self.source
+            .diagnostics()
+            .map(|d| lsp_types::Diagnostic {
+                range: self.range(d.span.clone()),
+                severity: Some(match d.severity {
+                    Severity::Error => DiagnosticSeverity::ERROR,
+                    Severity::Warning => DiagnosticSeverity::WARNING,
+                }),
+                code: d.code.map(|c| NumberOrString::String(c.code().to_owned())),
+                source: Some("lox".to_owned()),
+                message: d.message.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Functions and variables, each under the function declaring it.
+    pub fn outline(&self) -> Vec<DocumentSymbol> {
+        let mut declared: HashMap<Option<usize>, Vec<usize>> = HashMap::new();
+        for (i, d) in self.symbols.definitions.iter().enumerate() {
+            if d.kind != DefinitionKind::Parameter {
+                declared.entry(d.function).or_default().push(i);
+            }
+        }
+        self.outline_of(None, &declared)
+    }
+
+    fn outline_of(
+        &self,
+        function: Option<usize>,
+        declared: &HashMap<Option<usize>, Vec<usize>>,
+    ) -> Vec<DocumentSymbol> {
+        let Some(definitions) = declared.get(&function) else {
+            return vec![];
+        };
+        definitions
+            .iter()
+            .map(|&i| {
+                let d = &self.symbols.definitions[i];
+                let range = self.range(d.span.start..d.span.end);
+                let (kind, children) = match d.kind {
+                    DefinitionKind::Function => (
+                        SymbolKind::FUNCTION,
+                        Some(self.outline_of(Some(i), declared)),
+                    ),
+                    _ => (SymbolKind::VARIABLE, None),
+                };
+                #[allow(deprecated)]
+                DocumentSymbol {
+                    name: d.name.to_string(),
+                    detail: None,
+                    kind,
+                    tags: None,
+                    deprecated: None,
+                    range,
+                    selection_range: range,
+                    children,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: text.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_positions() {
+        // `é` is one UTF-16 unit and two bytes, `😀` two units and four.
+        let doc = Document::new("print \"é😀\";\r\nprint 1;\n".to_owned());
+        assert_eq!(doc.position(9), Position::new(0, 8));
+        assert_eq!(doc.offset(Position::new(0, 8)), 9);
+        assert_eq!(doc.offset(Position::new(0, 99)), 15);
+        assert_eq!(doc.position(17), Position::new(1, 0));
+        assert_eq!(doc.offset(Position::new(1, 6)), 23);
+        assert_eq!(doc.offset(Position::new(9, 0)), doc.text().len());
+    }
+
+    #[test]
+    fn test_edits() {
+        let mut doc = Document::new("var a = 1;\nprint a;\n".to_owned());
+        assert_eq!(doc.source.diagnostics().count(), 0);
+        // Each range is in the text the change before it left.
+        doc.edit(vec![
+            change((1, 6), (1, 7), "b +"),
+            change((0, 4), (0, 5), "b"),
+        ]);
+        assert_eq!(doc.text(), "var b = 1;\nprint b +;\n");
+        let messages: Vec<String> = doc.source.diagnostics().map(|d| d.to_string()).collect();
+        assert_eq!(messages, ["[line 2] Error: Expect expression."]);
+        // The declaration that still parses still has its symbols.
+        assert_eq!(doc.symbols.definitions[0].name.as_str(), "b");
+
+        doc.edit(vec![
+            change((1, 9), (1, 9), " b"),
+            change((0, 0), (0, 0), "{ "),
+        ]);
+        doc.edit(vec![change((1, 12), (1, 12), " }")]);
+        assert_eq!(doc.text(), "{ var b = 1;\nprint b + b; }\n");
+        assert_eq!(doc.source.diagnostics().count(), 0);
+        assert_eq!(doc.symbols.references.len(), 3);
+    }
+
+    #[test]
+    fn test_warnings_and_outline() {
+        let doc = Document::new("fun f(a) {\n  var unused;\n  fun g() {}\n}\nvar x;\n".to_owned());
+        let diagnostics = doc.lsp_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].message,
+            "Local variable 'unused' is never used."
+        );
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 6), Position::new(1, 12))
+        );
+        let outline: Vec<(String, Vec<String>)> = doc
+            .outline()
+            .into_iter()
+            .map(|s| {
+                let children = s.children.unwrap_or_default();
+                (s.name, children.into_iter().map(|c| c.name).collect())
+            })
+            .collect();
+        assert_eq!(
+            outline,
+            [
+                ("f".to_owned(), vec!["unused".to_owned(), "g".to_owned()]),
+                ("x".to_owned(), vec![]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use document::Document;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationType, PublishDiagnostics,
+};
+use lsp_types::request::{
+    DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as RequestType,
+};
+use lsp_types::{
+    DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverContents, HoverParams, HoverProviderCapability, Location, MarkedString, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    Url,
+};
+use std::collections::HashMap;
+
+mod document;
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        // Editors send only what changed, and each document is parsed again.
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+/// The open documents, by URI.
+#[derive(Default)]
+struct Server {
+    documents: HashMap<Url, Document>,
+}
+
+impl Server {
+    fn run(&mut self, connection: &Connection) -> Result<()> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    connection.sender.send(self.request(request).into())?;
+                }
+                Message::Notification(notification) => {
+                    if let Some(published) = self.notification(notification) {
+                        connection.sender.send(published.into())?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&self, request: Request) -> Response {
+        match request.method.as_str() {
+            GotoDefinition::METHOD => self.handle::<GotoDefinition>(request, Self::definition),
+            HoverRequest::METHOD => self.handle::<HoverRequest>(request, Self::hover),
+            DocumentSymbolRequest::METHOD => {
+                self.handle::<DocumentSymbolRequest>(request, Self::document_symbols)
+            }
+            method => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("Unsupported request {}", method),
+            ),
+        }
+    }
+
+    /// Answer `request` with `answer`, once its parameters are read.
+    fn handle<R: RequestType>(
+        &self,
+        request: Request,
+        answer: impl FnOnce(&Self, R::Params) -> R::Result,
+    ) -> Response {
+        match serde_json::from_value(request.params) {
+            Ok(params) => Response::new_ok(request.id, answer(self, params)),
+            Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let at = params.text_document_position_params;
+        let doc = self.documents.get(&at.text_document.uri)?;
+        let d = doc.symbols.definition_at(doc.offset(at.position))?;
+        let location = Location::new(at.text_document.uri, doc.range(d.span.start..d.span.end));
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let at = params.text_document_position_params;
+        let doc = self.documents.get(&at.text_document.uri)?;
+        let offset = doc.offset(at.position);
+        let reference = doc.symbols.reference_at(offset)?;
+        Some(Hover {
+            contents: HoverContents::Scalar(MarkedString::String(doc.symbols.describe_at(offset)?)),
+            range: Some(doc.range(reference.span.start..reference.span.end)),
+        })
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let doc = self.documents.get(&params.text_document.uri)?;
+        Some(DocumentSymbolResponse::Nested(doc.outline()))
+    }
+
+    /// Follow a document being opened, edited or closed, giving the
+    /// diagnostics to publish for it.
+    fn notification(&mut self, notification: Notification) -> Option<Notification> {
+        let (uri, version) = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidOpenTextDocumentParams>(DidOpenTextDocument::METHOD)
+                    .ok()?;
+                let doc = params.text_document;
+                self.documents
+                    .insert(doc.uri.clone(), Document::new(doc.text));
+                (doc.uri, Some(doc.version))
+            }
+            DidChangeTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidChangeTextDocumentParams>(
+                        DidChangeTextDocument::METHOD,
+                    )
+                    .ok()?;
+                let doc = params.text_document;
+                self.documents
+                    .get_mut(&doc.uri)?
+                    .edit(params.content_changes);
+                (doc.uri, Some(doc.version))
+            }
+            DidCloseTextDocument::METHOD => {
+                let params = notification
+                    .extract::<lsp_types::DidCloseTextDocumentParams>(DidCloseTextDocument::METHOD)
+                    .ok()?;
+                // Clear what was shown for it.
+                self.documents.remove(&params.text_document.uri);
+                (params.text_document.uri, None)
+            }
+            _ => return None,
+        };
+        let diagnostics = self
+            .documents
+            .get(&uri)
+            .map_or(vec![], Document::lsp_diagnostics);
+        Some(Notification::new(
+            PublishDiagnostics::METHOD.to_owned(),
+            PublishDiagnosticsParams::new(uri, diagnostics, version),
+        ))
+    }
+}
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    Server::default().run(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
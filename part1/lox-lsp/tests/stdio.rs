@@ -0,0 +1,112 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{ChildStdout, Command, Stdio};
+
+fn send(stdin: &mut impl Write, message: Value) {
+    let body = message.to_string();
+    write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    stdin.flush().unwrap();
+}
+
+fn receive(stdout: &mut BufReader<ChildStdout>) -> Value {
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        stdout.read_line(&mut header).unwrap();
+        match header.trim_end() {
+            "" => break,
+            h => {
+                if let Some(n) = h.strip_prefix("Content-Length: ") {
+                    length = n.parse().unwrap();
+                }
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    stdout.read_exact(&mut body).unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[test]
+fn test_session() {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_lox-lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = server.stdin.take().unwrap();
+    let mut stdout = BufReader::new(server.stdout.take().unwrap());
+    let uri = "file:///tmp/main.lox";
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"capabilities": {}}}),
+    );
+    let initialized = receive(&mut stdout);
+    assert_eq!(initialized["result"]["capabilities"]["textDocumentSync"], 2);
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+    );
+
+    let text = "fun f(n) {\n  return n;\n}\nprint f(1) +;\n";
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+        "textDocument": {"uri": uri, "languageId": "lox", "version": 1, "text": text}}}),
+    );
+    let published = receive(&mut stdout);
+    assert_eq!(published["method"], "textDocument/publishDiagnostics");
+    assert_eq!(
+        published["params"]["diagnostics"][0]["message"],
+        "Expect expression."
+    );
+    assert_eq!(
+        published["params"]["diagnostics"][0]["range"]["start"],
+        json!({"line": 3, "character": 12})
+    );
+
+    // Delete the stray `+`.
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+        "textDocument": {"uri": uri, "version": 2},
+        "contentChanges": [{"range": {"start": {"line": 3, "character": 10}, "end": {"line": 3, "character": 12}}, "text": ""}]}}),
+    );
+    let published = receive(&mut stdout);
+    assert_eq!(published["params"]["diagnostics"], json!([]));
+    assert_eq!(published["params"]["version"], 2);
+
+    let at = |id: i32, method: &str, line: i32, character: i32| {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": {
+        "textDocument": {"uri": uri}, "position": {"line": line, "character": character}}})
+    };
+    send(&mut stdin, at(2, "textDocument/definition", 1, 9));
+    assert_eq!(
+        receive(&mut stdout)["result"],
+        json!({"uri": uri,
+        "range": {"start": {"line": 0, "character": 6}, "end": {"line": 0, "character": 7}}})
+    );
+    send(&mut stdin, at(3, "textDocument/hover", 3, 6));
+    assert_eq!(
+        receive(&mut stdout)["result"]["contents"],
+        "global function 'f', declared on line 1"
+    );
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 4, "method": "textDocument/documentSymbol", "params": {
+        "textDocument": {"uri": uri}}}),
+    );
+    let symbols = receive(&mut stdout);
+    assert_eq!(symbols["result"][0]["name"], "f");
+    assert_eq!(symbols["result"][0]["children"], json!([]));
+
+    send(
+        &mut stdin,
+        json!({"jsonrpc": "2.0", "id": 5, "method": "shutdown"}),
+    );
+    assert_eq!(receive(&mut stdout)["id"], 5);
+    send(&mut stdin, json!({"jsonrpc": "2.0", "method": "exit"}));
+    assert!(server.wait().unwrap().success());
+}
@@ -0,0 +1,261 @@
+use lox_core::interpreter::format_number;
+use lox_core::parser::{Expr, Ident, Literal, Stmt};
+
+/// Builds a Graphviz DOT rendering of a parse tree, one node per AST node.
+/// Children are emitted in source order and `ordering=out` keeps them so.
+struct DotGraph {
+    out: String,
+    nodes: usize,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn literal_label(l: &Literal) -> String {
+    match l {
+        Literal::String(s) => format!("\"{}\"", s),
+        Literal::Number(n) => format_number(*n),
+        l => l.to_string(),
+    }
+}
+
+impl DotGraph {
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.nodes;
+        self.nodes += 1;
+        self.out
+            .push_str(&format!("  n{} [label=\"{}\"];\n", id, escape(label)));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: &str) {
+        if label.is_empty() {
+            self.out.push_str(&format!("  n{} -> n{};\n", from, to));
+        } else {
+            self.out.push_str(&format!(
+                "  n{} -> n{} [label=\"{}\"];\n",
+                from,
+                to,
+                escape(label)
+            ));
+        }
+    }
+
+    fn child_expr(&mut self, parent: usize, e: &Expr, label: &str) {
+        let id = self.expr(e);
+        self.edge(parent, id, label);
+    }
+
+    fn child_stmt(&mut self, parent: usize, s: &Stmt, label: &str) {
+        let id = self.stmt(s);
+        self.edge(parent, id, label);
+    }
+
+    fn params(&mut self, parent: usize, params: &[Ident]) {
+        for p in params {
+            let id = self.node(p.name.as_str());
+            self.edge(parent, id, "param");
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) -> usize {
+        match e {
+            Expr::Binary(l, op, r) | Expr::Logical(l, op, r) => {
+                let id = self.node(&op.kind.to_string());
+                self.child_expr(id, l, "");
+                self.child_expr(id, r, "");
+                id
+            }
+            Expr::Unary(op, e) => {
+                let id = self.node(&op.kind.to_string());
+                self.child_expr(id, e, "");
+                id
+            }
+            Expr::Literal(l, _) => self.node(&literal_label(l)),
+            Expr::Grouping(e) => {
+                let id = self.node("group");
+                self.child_expr(id, e, "");
+                id
+            }
+            Expr::Variable(n) => self.node(n.name.as_str()),
+            Expr::Assign(n, v) => {
+                let id = self.node(&format!("{} =", n));
+                self.child_expr(id, v, "");
+                id
+            }
+            Expr::Call(callee, _, args) => {
+                let id = self.node("call");
+                self.child_expr(id, callee, "callee");
+                for a in args {
+                    self.child_expr(id, a, "arg");
+                }
+                id
+            }
+            Expr::Ternary(c, t, e) => {
+                let id = self.node("?:");
+                self.child_expr(id, c, "cond");
+                self.child_expr(id, t, "then");
+                self.child_expr(id, e, "else");
+                id
+            }
+            Expr::List(_, items) => {
+                let id = self.node("list");
+                for item in items {
+                    self.child_expr(id, item, "");
+                }
+                id
+            }
+            Expr::Index(o, _, i) => {
+                let id = self.node("[]");
+                self.child_expr(id, o, "");
+                self.child_expr(id, i, "index");
+                id
+            }
+            Expr::IndexSet(o, _, i, v) => {
+                let id = self.node("[]=");
+                self.child_expr(id, o, "");
+                self.child_expr(id, i, "index");
+                self.child_expr(id, v, "value");
+                id
+            }
+            Expr::Slice(o, _, a, b) => {
+                let id = self.node("[:]");
+                self.child_expr(id, o, "");
+                if let Some(a) = a {
+                    self.child_expr(id, a, "start");
+                }
+                if let Some(b) = b {
+                    self.child_expr(id, b, "end");
+                }
+                id
+            }
+            Expr::Lambda(_, params, body) => {
+                let id = self.node("fun");
+                self.params(id, params);
+                self.child_stmt(id, body, "body");
+                id
+            }
+            Expr::Get(o, n) => {
+                let id = self.node(&format!(".{}", n));
+                self.child_expr(id, o, "");
+                id
+            }
+        }
+    }
+
+    fn stmt(&mut self, s: &Stmt) -> usize {
+        match s {
+            Stmt::Expr(e) => {
+                let id = self.node("expr");
+                self.child_expr(id, e, "");
+                id
+            }
+            Stmt::Print(e) => {
+                let id = self.node("print");
+                self.child_expr(id, e, "");
+                id
+            }
+            Stmt::Var(n, init) => {
+                let id = self.node(&format!("var {}", n));
+                if let Some(init) = init {
+                    self.child_expr(id, init, "");
+                }
+                id
+            }
+            Stmt::Block(stmts) => {
+                let id = self.node("block");
+                for s in stmts {
+                    self.child_stmt(id, s, "");
+                }
+                id
+            }
+            Stmt::If(c, t, e) => {
+                let id = self.node("if");
+                self.child_expr(id, c, "cond");
+                self.child_stmt(id, t, "then");
+                if let Some(e) = e {
+                    self.child_stmt(id, e, "else");
+                }
+                id
+            }
+            Stmt::While(c, body) => {
+                let id = self.node("while");
+                self.child_expr(id, c, "cond");
+                self.child_stmt(id, body, "body");
+                id
+            }
+            Stmt::ForIn(n, e, body) => {
+                let id = self.node(&format!("for {} in", n));
+                self.child_expr(id, e, "");
+                self.child_stmt(id, body, "body");
+                id
+            }
+            Stmt::Function(n, params, body) => {
+                let id = self.node(&format!("fun {}", n));
+                self.params(id, params);
+                self.child_stmt(id, body, "body");
+                id
+            }
+            Stmt::Return(_, v) => {
+                let id = self.node("return");
+                if let Some(v) = v {
+                    self.child_expr(id, v, "");
+                }
+                id
+            }
+            Stmt::Throw(_, v) => {
+                let id = self.node("throw");
+                self.child_expr(id, v, "");
+                id
+            }
+            Stmt::Try(_, body, n, handler) => {
+                let id = self.node(&format!("try, catch {}", n));
+                self.child_stmt(id, body, "body");
+                self.child_stmt(id, handler, "catch");
+                id
+            }
+        }
+    }
+}
+
+/// Render `stmts` as a DOT digraph rooted at a `program` node.
+pub fn to_dot(stmts: &[Stmt]) -> String {
+    let mut g = DotGraph {
+        out: String::new(),
+        nodes: 0,
+    };
+    let root = g.node("program");
+    for s in stmts {
+        g.child_stmt(root, s, "");
+    }
+    format!(
+        "digraph ast {{\n  ordering=out;\n  node [shape=box];\n{}}}\n",
+        g.out
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use lox_core::parser::{OpKind, Operator};
+    use lox_core::tokens::Span;
+
+    #[test]
+    fn test_binary_children_in_order() {
+        let span = Span::default();
+        let ast = [Stmt::Print(Expr::Binary(
+            Box::new(Expr::Literal(Literal::Number(1.0), span)),
+            Operator {
+                kind: OpKind::Subtract,
+                span,
+            },
+            Box::new(Expr::Literal(Literal::String("a\"b".into()), span)),
+        ))];
+        assert_eq!(
+            to_dot(&ast),
+            "digraph ast {\n  ordering=out;\n  node [shape=box];\n  n0 [label=\"program\"];\n  n1 [label=\"print\"];\n  n2 [label=\"-\"];\n  n3 [label=\"1\"];\n  n2 -> n3;\n  n4 [label=\"\\\"a\\\"b\\\"\"];\n  n2 -> n4;\n  n1 -> n2;\n  n0 -> n1;\n}\n"
+        );
+    }
+}
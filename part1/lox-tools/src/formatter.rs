@@ -0,0 +1,284 @@
+use lox_core::lox_error::Diagnostic;
+use lox_core::parser;
+use lox_core::scanner::{ScanOptions, Scanner};
+use lox_core::tokens::{Token, TokenType};
+
+/// One level of indentation.
+const INDENT: &str = "  ";
+
+/// A bracket that has been opened and not yet closed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Open {
+    Paren,
+    /// The parentheses of a C-style `for`, where `;` doesn't end the line.
+    ForClauses,
+    /// `[`, counting the `?`s inside still waiting for their `:`, so a
+    /// slice's `:` can be told from a ternary's.
+    Bracket {
+        ternaries: usize,
+    },
+    Brace,
+}
+
+/// Whether a token with `t` before it ends an operand, so that a `(` or `[`
+/// after it is a call or index rather than the start of a new expression,
+/// and a `-` after it subtracts rather than negates.
+fn ends_operand(t: &TokenType) -> bool {
+    matches!(
+        t,
+        TokenType::IDENTIFIER(_)
+            | TokenType::STRING(_)
+            | TokenType::NUMBER(_)
+            | TokenType::RIGHT_PAREN
+            | TokenType::RIGHT_BRACKET
+            | TokenType::TRUE
+            | TokenType::FALSE
+            | TokenType::NIL
+            | TokenType::THIS
+            | TokenType::SUPER
+    )
+}
+
+/// Lays the tokens of a program back out, comments included, deciding all
+/// the whitespace itself. It works on tokens rather than the parse tree so
+/// comments and `for` loops, which the parser desugars, come back as written.
+struct Formatter<'s> {
+    source: &'s str,
+    out: String,
+    indent: usize,
+    open: Vec<Open>,
+    prev: Option<&'s Token>,
+    /// The previous token ended a line; the break is written before the
+    /// next token, so a comment on the same line can go first.
+    line_break: bool,
+    /// The previous token binds to the next with no space: a unary
+    /// operator or a slice's `:`.
+    glue: bool,
+}
+
+impl<'s> Formatter<'s> {
+    fn lexeme(&self, t: &Token) -> &'s str {
+        &self.source[t.span.start..t.span.end]
+    }
+
+    /// The line `t` starts on; a token's span gives the line it ends on.
+    fn start_line(&self, t: &Token) -> i32 {
+        t.span.line - self.lexeme(t).matches('\n').count() as i32
+    }
+
+    fn space_before(&self, prev: &TokenType, t: &TokenType) -> bool {
+        match (prev, t) {
+            _ if self.glue => false,
+            (_, TokenType::RIGHT_PAREN)
+            | (_, TokenType::RIGHT_BRACKET)
+            | (_, TokenType::COMMA)
+            | (_, TokenType::SEMICOLON)
+            | (_, TokenType::DOT)
+            | (TokenType::LEFT_PAREN, _)
+            | (TokenType::LEFT_BRACKET, _)
+            | (TokenType::DOT, _)
+            | (TokenType::LEFT_BRACE, TokenType::RIGHT_BRACE) => false,
+            (_, TokenType::COLON) => {
+                !matches!(self.open.last(), Some(Open::Bracket { ternaries: 0 }))
+            }
+            (prev, TokenType::LEFT_PAREN | TokenType::LEFT_BRACKET) => !ends_operand(prev),
+            _ => true,
+        }
+    }
+
+    fn new_line(&mut self, blank: bool, depth: usize) {
+        self.out.push('\n');
+        if blank {
+            self.out.push('\n');
+        }
+        self.out.push_str(&INDENT.repeat(depth));
+    }
+
+    fn token(&mut self, t: &'s Token, next: Option<&Token>) {
+        let lexeme = self.lexeme(t);
+        let start_line = self.start_line(t);
+        let prev = self.prev;
+        match &t.token_type {
+            // A comment after code on the same line stays there.
+            TokenType::COMMENT(_) if prev.is_some_and(|p| p.span.line == start_line) => {
+                self.out.push(' ');
+                self.out.push_str(lexeme.trim_end());
+                self.line_break = true;
+                self.glue = false;
+                self.prev = Some(t);
+                return;
+            }
+            TokenType::COMMENT(_) => self.line_break = prev.is_some(),
+            TokenType::RIGHT_BRACE => {
+                self.open.pop();
+                self.indent = self.indent.saturating_sub(1);
+                self.line_break =
+                    !matches!(prev.map(|p| &p.token_type), Some(TokenType::LEFT_BRACE));
+            }
+            _ => {}
+        }
+
+        if let (true, Some(prev)) = (self.line_break, prev) {
+            let closing = t.token_type == TokenType::RIGHT_BRACE;
+            let blank = start_line > prev.span.line + 1
+                && prev.token_type != TokenType::LEFT_BRACE
+                && !closing;
+            // Lines continuing an expression, after a comment inside it,
+            // are indented a level further.
+            let continued = !closing && !matches!(self.open.last(), None | Some(Open::Brace));
+            self.new_line(blank, self.indent + continued as usize);
+        } else if let Some(prev) = prev {
+            if self.space_before(&prev.token_type, &t.token_type) {
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str(lexeme.trim_end());
+
+        self.line_break = false;
+        self.glue = false;
+        match &t.token_type {
+            TokenType::LEFT_BRACE => {
+                self.open.push(Open::Brace);
+                self.indent += 1;
+                self.line_break = true;
+            }
+            TokenType::LEFT_PAREN => self.open.push(match prev.map(|p| &p.token_type) {
+                Some(TokenType::FOR) => Open::ForClauses,
+                _ => Open::Paren,
+            }),
+            TokenType::LEFT_BRACKET => self.open.push(Open::Bracket { ternaries: 0 }),
+            TokenType::RIGHT_PAREN | TokenType::RIGHT_BRACKET => {
+                self.open.pop();
+            }
+            TokenType::QUESTION => {
+                if let Some(Open::Bracket { ternaries }) = self.open.last_mut() {
+                    *ternaries += 1;
+                }
+            }
+            TokenType::COLON => match self.open.last_mut() {
+                Some(Open::Bracket { ternaries: 0 }) => self.glue = true,
+                Some(Open::Bracket { ternaries }) => *ternaries -= 1,
+                _ => {}
+            },
+            TokenType::SEMICOLON => self.line_break = self.open.last() != Some(&Open::ForClauses),
+            // A block ends its line unless it's a lambda's body inside an
+            // expression, or followed by its `else`.
+            TokenType::RIGHT_BRACE => {
+                self.line_break = !matches!(
+                    next.map(|n| &n.token_type),
+                    Some(
+                        TokenType::ELSE
+                            | TokenType::SEMICOLON
+                            | TokenType::COMMA
+                            | TokenType::RIGHT_PAREN
+                            | TokenType::RIGHT_BRACKET
+                            | TokenType::LEFT_PAREN
+                            | TokenType::DOT
+                    )
+                )
+            }
+            TokenType::COMMENT(_) => self.line_break = true,
+            TokenType::BANG => self.glue = true,
+            TokenType::MINUS => self.glue = !prev.is_some_and(|p| ends_operand(&p.token_type)),
+            _ => {}
+        }
+        self.prev = Some(t);
+    }
+}
+
+/// `source` laid out in the canonical style: a statement per line,
+/// two-space indents, single spaces around binary operators, at most one
+/// blank line in a row, and comments kept where they were. Fails with the
+/// program's syntax errors, if any, rather than guess at its structure.
+pub fn format(source: &str) -> Result<String, Vec<Diagnostic>> {
+    parser::parse(&Scanner::scan(source)?)?;
+    let opts = ScanOptions {
+        comments: true,
+        ..Default::default()
+    };
+    let tokens = Scanner::scan_with_options(source, &opts)?;
+    let mut f = Formatter {
+        source,
+        out: String::new(),
+        indent: 0,
+        open: vec![],
+        prev: None,
+        line_break: false,
+        glue: false,
+    };
+    let tokens: Vec<&Token> = tokens
+        .iter()
+        .filter(|t| t.token_type != TokenType::EOF)
+        .collect();
+    for (i, t) in tokens.iter().enumerate() {
+        f.token(t, tokens.get(i + 1).copied());
+    }
+    if !f.out.is_empty() {
+        f.out.push('\n');
+    }
+    Ok(f.out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// The token kinds of `source`, comments included, without positions.
+    fn kinds(source: &str) -> Vec<TokenType> {
+        let opts = ScanOptions {
+            comments: true,
+            ..Default::default()
+        };
+        let tokens = Scanner::scan_with_options(source, &opts).unwrap();
+        tokens.into_iter().map(|t| t.token_type).collect()
+    }
+
+    #[test]
+    fn test_layout() {
+        let source = "// Sums.\nfun sum(xs){var t=0;// running total\n\n\n  for(var i=0;i<len(xs);i=i+1)t=t+xs[i];\nreturn t;}\nprint -sum([1,2])*2;if(!true){}else{print f(fun(a){return a;}) ? x[1:] : nil;}";
+        assert_eq!(
+            format(source).unwrap(),
+            "// Sums.\n\
+             fun sum(xs) {\n\
+             \x20 var t = 0; // running total\n\
+             \n\
+             \x20 for (var i = 0; i < len(xs); i = i + 1) t = t + xs[i];\n\
+             \x20 return t;\n\
+             }\n\
+             print -sum([1, 2]) * 2;\n\
+             if (!true) {} else {\n\
+             \x20 print f(fun (a) {\n\
+             \x20   return a;\n\
+             \x20 }) ? x[1:] : nil;\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let errors = format("print (1;\n").unwrap_err();
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_fixtures_stable() {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(Path::new("../test_lox_files")).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|e| e == "lox") {
+                files.push(path);
+            }
+        }
+        assert!(!files.is_empty());
+        for file in files {
+            let source = std::fs::read_to_string(&file).unwrap();
+            let formatted = match format(&source) {
+                Ok(formatted) => formatted,
+                Err(_) => continue,
+            };
+            assert_eq!(kinds(&source), kinds(&formatted), "{}", file.display());
+            assert_eq!(format(&formatted).unwrap(), formatted, "{}", file.display());
+        }
+    }
+}
@@ -0,0 +1,5 @@
+pub mod ast_graph;
+pub mod formatter;
+pub mod printer;
+pub mod symbols;
+pub mod transpile;
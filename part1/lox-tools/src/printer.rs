@@ -0,0 +1,303 @@
+use lox_core::parser::{Expr, Ident, Literal, OpKind, Stmt};
+
+/// Prints a parse tree back out as Lox source in one canonical layout: one
+/// statement per line, two-space indents, single spaces around binary
+/// operators. Comments and the original spacing are gone, but the output
+/// parses to the same tree, parentheses included since they are `Grouping`
+/// nodes. Desugared loops come back as the blocks and `while`s they became.
+struct SourcePrinter {
+    out: String,
+    indent: usize,
+}
+
+impl SourcePrinter {
+    fn line_start(&mut self) {
+        self.out.push_str(&"  ".repeat(self.indent));
+    }
+
+    fn params(&mut self, params: &[Ident]) {
+        let names: Vec<&str> = params.iter().map(|p| p.name.as_str()).collect();
+        self.out.push('(');
+        self.out.push_str(&names.join(", "));
+        self.out.push_str(") ");
+    }
+
+    fn list(&mut self, items: &[Expr]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(item);
+        }
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Binary(l, op, r) | Expr::Logical(l, op, r) => {
+                self.expr(l);
+                match op.kind {
+                    OpKind::Comma => self.out.push_str(", "),
+                    OpKind::Or => self.out.push_str(" or "),
+                    OpKind::And => self.out.push_str(" and "),
+                    kind => self.out.push_str(&format!(" {} ", kind)),
+                }
+                self.expr(r);
+            }
+            Expr::Unary(op, e) => {
+                self.out.push_str(&op.kind.to_string());
+                self.expr(e);
+            }
+            Expr::Literal(Literal::String(s), _) => self.out.push_str(&format!("\"{}\"", s)),
+            Expr::Literal(l, _) => self.out.push_str(&l.to_string()),
+            Expr::Grouping(e) => {
+                self.out.push('(');
+                self.expr(e);
+                self.out.push(')');
+            }
+            Expr::Variable(n) => self.out.push_str(n.name.as_str()),
+            Expr::Assign(n, v) => {
+                self.out.push_str(&format!("{} = ", n));
+                self.expr(v);
+            }
+            Expr::Call(callee, _, args) => {
+                self.expr(callee);
+                self.out.push('(');
+                self.list(args);
+                self.out.push(')');
+            }
+            Expr::Ternary(c, t, e) => {
+                self.expr(c);
+                self.out.push_str(" ? ");
+                self.expr(t);
+                self.out.push_str(" : ");
+                self.expr(e);
+            }
+            Expr::List(_, items) => {
+                self.out.push('[');
+                self.list(items);
+                self.out.push(']');
+            }
+            Expr::Index(o, _, i) => {
+                self.expr(o);
+                self.out.push('[');
+                self.expr(i);
+                self.out.push(']');
+            }
+            Expr::IndexSet(o, _, i, v) => {
+                self.expr(o);
+                self.out.push('[');
+                self.expr(i);
+                self.out.push_str("] = ");
+                self.expr(v);
+            }
+            Expr::Slice(o, _, a, b) => {
+                self.expr(o);
+                self.out.push('[');
+                if let Some(a) = a {
+                    self.expr(a);
+                }
+                self.out.push(':');
+                if let Some(b) = b {
+                    self.expr(b);
+                }
+                self.out.push(']');
+            }
+            Expr::Lambda(_, params, body) => {
+                self.out.push_str("fun ");
+                self.params(params);
+                self.body(body);
+            }
+            Expr::Get(o, n) => {
+                self.expr(o);
+                self.out.push_str(&format!(".{}", n));
+            }
+        }
+    }
+
+    /// A statement that continues the current line, as after `if (...) `.
+    fn body(&mut self, s: &Stmt) {
+        match s {
+            Stmt::Block(stmts) if stmts.is_empty() => self.out.push_str("{}"),
+            Stmt::Block(stmts) => {
+                self.out.push_str("{\n");
+                self.indent += 1;
+                for s in stmts {
+                    self.stmt(s);
+                }
+                self.indent -= 1;
+                self.line_start();
+                self.out.push('}');
+            }
+            Stmt::Expr(e) => {
+                self.expr(e);
+                self.out.push(';');
+            }
+            Stmt::Print(e) => {
+                self.out.push_str("print ");
+                self.expr(e);
+                self.out.push(';');
+            }
+            Stmt::Var(n, init) => {
+                self.out.push_str(&format!("var {}", n));
+                if let Some(init) = init {
+                    self.out.push_str(" = ");
+                    self.expr(init);
+                }
+                self.out.push(';');
+            }
+            Stmt::If(c, t, e) => {
+                self.out.push_str("if (");
+                self.expr(c);
+                self.out.push_str(") ");
+                self.body(t);
+                if let Some(e) = e {
+                    self.out.push_str(" else ");
+                    self.body(e);
+                }
+            }
+            Stmt::While(c, body) => {
+                self.out.push_str("while (");
+                self.expr(c);
+                self.out.push_str(") ");
+                self.body(body);
+            }
+            Stmt::ForIn(n, e, body) => {
+                self.out.push_str(&format!("for (var {} in ", n));
+                self.expr(e);
+                self.out.push_str(") ");
+                self.body(body);
+            }
+            Stmt::Function(n, params, body) => {
+                self.out.push_str(&format!("fun {}", n));
+                self.params(params);
+                self.body(body);
+            }
+            Stmt::Return(_, v) => {
+                self.out.push_str("return");
+                if let Some(v) = v {
+                    self.out.push(' ');
+                    self.expr(v);
+                }
+                self.out.push(';');
+            }
+            Stmt::Throw(_, v) => {
+                self.out.push_str("throw ");
+                self.expr(v);
+                self.out.push(';');
+            }
+            Stmt::Try(_, body, n, handler) => {
+                self.out.push_str("try ");
+                self.body(body);
+                self.out.push_str(&format!(" catch ({}) ", n));
+                self.body(handler);
+            }
+        }
+    }
+
+    fn stmt(&mut self, s: &Stmt) {
+        self.line_start();
+        self.body(s);
+        self.out.push('\n');
+    }
+}
+
+/// Render `stmts` as canonical Lox source.
+pub fn to_source(stmts: &[Stmt]) -> String {
+    let mut p = SourcePrinter {
+        out: String::new(),
+        indent: 0,
+    };
+    for s in stmts {
+        p.stmt(s);
+    }
+    p.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use lox_core::lox_error::{Diagnostic, LoxError};
+    use lox_core::parser::Parser;
+    use lox_core::scanner;
+    use std::path::Path;
+
+    /// Records that something went wrong without printing it: the files
+    /// written to fail are skipped.
+    struct Quiet(bool);
+
+    impl LoxError for Quiet {
+        fn report(&mut self, _diagnostic: Diagnostic) {
+            self.0 = true;
+        }
+
+        fn has_error(&self) -> bool {
+            self.0
+        }
+    }
+
+    fn parse(source: &str) -> Option<Vec<Stmt>> {
+        let mut errors = Quiet(false);
+        let tokens = scanner::scan_tokens(&mut errors, source).ok()?;
+        let mut tokens = tokens.iter().peekable();
+        let ast = Parser::new(&mut tokens, &mut errors).parse().ok()?;
+        (!errors.0).then_some(ast)
+    }
+
+    /// The tree's debug dump with every `Span { .. }` blanked, since
+    /// printing moves tokens around.
+    fn shape(ast: &[Stmt]) -> String {
+        let dump = format!("{:?}", ast);
+        let mut out = String::new();
+        let mut rest = dump.as_str();
+        while let Some(at) = rest.find("Span {") {
+            let end = at + rest[at..].find('}').unwrap() + 1;
+            out.push_str(&rest[..at]);
+            out.push_str("Span");
+            rest = &rest[end..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn lox_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                lox_files(&path, files);
+            } else if path.extension().is_some_and(|e| e == "lox") {
+                files.push(path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_nesting() {
+        let source = "fun f(a, b) {\n  if (a) {} else while (b) b = b - 1;\n  return fun () {\n    print [a, b][0:1];\n  };\n}\n";
+        assert_eq!(to_source(&parse(source).unwrap()), source);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut files = vec![];
+        lox_files(Path::new("../test_lox_files"), &mut files);
+        assert!(!files.is_empty());
+        for file in files {
+            let source = std::fs::read_to_string(&file).unwrap();
+            let ast = match parse(&source) {
+                Some(ast) => ast,
+                None => continue,
+            };
+            let printed = to_source(&ast);
+            let reparsed = parse(&printed).unwrap_or_else(|| {
+                panic!(
+                    "{}: printed source fails to parse:\n{}",
+                    file.display(),
+                    printed
+                )
+            });
+            assert_eq!(shape(&ast), shape(&reparsed), "{}", file.display());
+            assert_eq!(printed, to_source(&reparsed), "{}", file.display());
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use lox_core::environment::Enviornment;
+use lox_core::intern::Symbol;
+use lox_core::parser::{Expr, Ident, Stmt};
+use lox_core::stdlib;
+use lox_core::tokens::Span;
+use std::collections::HashMap;
+use std::fmt;
+
+/// What declared a name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefinitionKind {
+    Variable,
+    Function,
+    Parameter,
+    LoopVariable,
+    /// The variable of a `catch`, holding what was caught.
+    ErrorVariable,
+}
+
+impl fmt::Display for DefinitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Variable => write!(f, "variable"),
+            Self::Function => write!(f, "function"),
+            Self::Parameter => write!(f, "parameter"),
+            Self::LoopVariable => write!(f, "loop variable"),
+            Self::ErrorVariable => write!(f, "error variable"),
+        }
+    }
+}
+
+/// A declaration of a name.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: Symbol,
+    pub kind: DefinitionKind,
+    /// The declared name.
+    pub span: Span,
+    /// Scopes in from the top level, counted as the resolver does; 0 for
+    /// globals.
+    pub depth: usize,
+    /// The named function it is declared in, as an index into
+    /// `Symbols::definitions`. Lambdas don't count.
+    pub function: Option<usize>,
+}
+
+/// A name as written in the source, and the definition it resolves to.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub name: Symbol,
+    pub span: Span,
+    /// `None` for globals the program never declares, such as natives.
+    pub definition: Option<usize>,
+}
+
+/// Where each name in a program is declared, and what each use of one
+/// refers to, for editors to jump between them. Scopes are those of the
+/// resolver: a local resolves to the innermost enclosing declaration of
+/// it, and anything else to the first top-level declaration of the name.
+#[derive(Debug, Default)]
+pub struct Symbols {
+    pub definitions: Vec<Definition>,
+    /// Every name in the program, declarations included, in source order.
+    pub references: Vec<Reference>,
+}
+
+impl Symbols {
+    pub fn new<'a>(program: impl IntoIterator<Item = &'a Stmt>) -> Self {
+        let mut walk = Walk::default();
+        program.into_iter().for_each(|s| walk.stmt(s));
+        for (reference, name) in walk.unresolved {
+            walk.symbols.references[reference].definition = walk.globals.get(&name).copied();
+        }
+        walk.symbols.references.sort_by_key(|r| r.span.start);
+        walk.symbols
+    }
+
+    /// The name at byte `offset`, counting the end of a name as on it so a
+    /// cursor just after one finds it.
+    pub fn reference_at(&self, offset: usize) -> Option<&Reference> {
+        let after = self.references.partition_point(|r| r.span.end < offset);
+        self.references[after..]
+            .first()
+            .filter(|r| r.span.start <= offset)
+    }
+
+    /// The definition the name at byte `offset` resolves to.
+    pub fn definition_at(&self, offset: usize) -> Option<&Definition> {
+        self.reference_at(offset)?
+            .definition
+            .map(|d| &self.definitions[d])
+    }
+
+    /// What the name at byte `offset` is and where it lives, such as
+    /// "local variable 'a' in 'f', declared on line 2".
+    pub fn describe_at(&self, offset: usize) -> Option<String> {
+        let reference = self.reference_at(offset)?;
+        let Some(d) = reference.definition else {
+            let name = reference.name;
+            let builtin = {
+                let mut env = Enviornment::new();
+                stdlib::install(&mut env);
+                env.get(name, None).is_ok()
+            };
+            return Some(match builtin {
+                true => format!("global '{}', a builtin", name),
+                false => format!("global '{}', not declared in this file", name),
+            });
+        };
+        let d = &self.definitions[d];
+        let scope = if d.depth == 0 { "global" } else { "local" };
+        let within = match d.function {
+            Some(f) if d.depth > 0 => format!(" in '{}'", self.definitions[f].name),
+            _ => String::new(),
+        };
+        Some(format!(
+            "{} {} '{}'{}, declared on line {}",
+            scope, d.kind, d.name, within, d.span.line
+        ))
+    }
+}
+
+/// Walks a program as the resolver does, recording names instead of slots.
+#[derive(Default)]
+struct Walk {
+    symbols: Symbols,
+    scopes: Vec<HashMap<Symbol, usize>>,
+    /// The first top-level declaration of each global.
+    globals: HashMap<Symbol, usize>,
+    /// References not found in any enclosing scope, resolved as globals
+    /// once the whole program is seen, since functions may use globals
+    /// declared after them.
+    unresolved: Vec<(usize, Symbol)>,
+    function: Option<usize>,
+}
+
+impl Walk {
+    fn declare(&mut self, ident: &Ident, kind: DefinitionKind) -> usize {
+        let index = self.symbols.definitions.len();
+        self.symbols.definitions.push(Definition {
+            name: ident.name,
+            kind,
+            span: ident.span,
+            depth: self.scopes.len(),
+            function: self.function,
+        });
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(ident.name, index);
+            }
+            None => {
+                self.globals.entry(ident.name).or_insert(index);
+            }
+        }
+        self.symbols.references.push(Reference {
+            name: ident.name,
+            span: ident.span,
+            definition: Some(index),
+        });
+        index
+    }
+
+    fn refer(&mut self, ident: &Ident) {
+        let definition = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&ident.name))
+            .copied();
+        if definition.is_none() {
+            self.unresolved
+                .push((self.symbols.references.len(), ident.name));
+        }
+        self.symbols.references.push(Reference {
+            name: ident.name,
+            span: ident.span,
+            definition,
+        });
+    }
+
+    fn scoped(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(HashMap::new());
+        f(self);
+        self.scopes.pop();
+    }
+
+    /// Walk a function body, inside `function` if it has a name.
+    fn function(&mut self, function: Option<usize>, params: &[Ident], body: &Stmt) {
+        let outer = self.function;
+        self.function = function.or(outer);
+        self.scoped(|w| {
+            for p in params {
+                w.declare(p, DefinitionKind::Parameter);
+            }
+            w.stmt(body);
+        });
+        self.function = outer;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expr(e) | Stmt::Print(e) => self.expr(e),
+            Stmt::Var(name, init) => {
+                self.declare(name, DefinitionKind::Variable);
+                if let Some(init) = init {
+                    self.expr(init);
+                }
+            }
+            Stmt::Block(stmts) => self.scoped(|w| stmts.iter().for_each(|s| w.stmt(s))),
+            Stmt::If(c, t, e) => {
+                self.expr(c);
+                self.stmt(t);
+                if let Some(e) = e {
+                    self.stmt(e);
+                }
+            }
+            Stmt::While(c, body) => {
+                self.expr(c);
+                self.stmt(body);
+            }
+            Stmt::ForIn(name, iterable, body) => {
+                self.expr(iterable);
+                self.scoped(|w| {
+                    w.declare(name, DefinitionKind::LoopVariable);
+                    w.stmt(body);
+                });
+            }
+            Stmt::Function(name, params, body) => {
+                // Declared before its body so it can call itself.
+                let function = self.declare(name, DefinitionKind::Function);
+                self.function(Some(function), params, body);
+            }
+            Stmt::Return(_, v) => {
+                if let Some(v) = v {
+                    self.expr(v);
+                }
+            }
+            Stmt::Throw(_, v) => self.expr(v),
+            Stmt::Try(_, body, name, handler) => {
+                self.stmt(body);
+                self.scoped(|w| {
+                    w.declare(name, DefinitionKind::ErrorVariable);
+                    w.stmt(handler);
+                });
+            }
+        }
+    }
+
+    /// Walk `expr` from a stack, as the resolver does, so deep nesting
+    /// can't overflow.
+    fn expr(&mut self, expr: &Expr) {
+        let mut work = vec![expr];
+        while let Some(expr) = work.pop() {
+            match expr {
+                Expr::Variable(name) => self.refer(name),
+                Expr::Assign(name, _) => self.refer(name),
+                Expr::Lambda(_, params, body) => self.function(None, params, body),
+                _ => {}
+            }
+            work.extend(expr.children().into_iter().rev());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox_core::{parse, Scanner};
+
+    fn symbols(source: &str) -> Symbols {
+        Symbols::new(&parse(&Scanner::scan(source).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_definitions() {
+        let source = "fun f(a) {\n  var b = a;\n  print g + b + clock;\n}\nvar g = 1;\n\
+                      for (var x in [1]) print fun (a) { return a; };\n";
+        let symbols = symbols(source);
+        // The byte offset of `name` in the first place `text` has it.
+        let at = |text: &str, name: &str| source.find(text).unwrap() + text.find(name).unwrap();
+        let line_of = |offset| symbols.definition_at(offset).map(|d| d.span.line);
+        // A local use finds its declaration, a global one the later global.
+        assert_eq!(line_of(at("= a;", "a")), Some(1));
+        assert_eq!(line_of(at("+ b", "b")), Some(2));
+        assert_eq!(line_of(at("g +", "g")), Some(5));
+        assert_eq!(line_of(at("clock", "clock")), None);
+        assert_eq!(line_of(at("[1]", "1")), None);
+        // The lambda's `a` is its own parameter, not `f`'s.
+        assert_eq!(line_of(at("return a", "a")), Some(6));
+        assert_eq!(
+            symbols.describe_at(at("return a", "a")).unwrap(),
+            "local parameter 'a', declared on line 6"
+        );
+        assert_eq!(
+            symbols.describe_at(at("+ b", "b")).unwrap(),
+            "local variable 'b' in 'f', declared on line 2"
+        );
+        assert_eq!(
+            symbols.describe_at(at("f(", "f")).unwrap(),
+            "global function 'f', declared on line 1"
+        );
+        assert_eq!(
+            symbols.describe_at(at("clock", "clock")).unwrap(),
+            "global 'clock', a builtin"
+        );
+    }
+}
@@ -0,0 +1,431 @@
+use lox_core::environment::Enviornment;
+use lox_core::intern::Symbol;
+use lox_core::lox_error::Diagnostic;
+use lox_core::parser::{Expr, Ident, Literal, OpKind, Stmt};
+use lox_core::stdlib;
+use lox_core::tokens::Span;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+/// The dynamic values and operations the generated code is written against.
+const RUNTIME: &str = include_str!("transpile/runtime.rs");
+
+/// The builtins the runtime has.
+const NATIVES: &[&str] = &[
+    "clock", "exit", "str", "num", "len", "push", "pop", "abs", "floor", "ceil", "sqrt",
+];
+
+/// A local variable: the Rust variable holding its cell, and the function
+/// it belongs to, counted in from the top level.
+struct Local {
+    rust: String,
+    function: usize,
+}
+
+/// Lower a parsed and resolved program to a standalone Rust program that
+/// prints what running it would, with the interpreter's default options,
+/// and fails the same way: `rustc -O out.rs` builds it. `name` is put in
+/// its header. Property access, `throw` and `try`, and builtins other than
+/// the `NATIVES`, are refused.
+pub fn to_rust(program: &[Stmt], name: &str) -> Result<String, Diagnostic> {
+    let mut t = Transpiler {
+        declared: program
+            .iter()
+            .filter_map(|s| match s {
+                Stmt::Var(name, _) | Stmt::Function(name, _, _) => Some(name.name),
+                _ => None,
+            })
+            .collect(),
+        ..Default::default()
+    };
+    let mut body = String::new();
+    for s in program {
+        t.stmt(s, 1, &mut body)?;
+    }
+    Ok(format!(
+        "// Transpiled from {} by `interpreter transpile`. Build it with `rustc -O`.\n\
+         #![allow(dead_code, unused_variables, unreachable_code, unused_braces)]\n\n\
+         {}\n\
+         fn run() -> Result<(), Error> {{\n{}    Ok(())\n}}\n",
+        name, RUNTIME, body
+    ))
+}
+
+#[derive(Default)]
+struct Transpiler {
+    /// The names the program declares at the top level.
+    declared: HashSet<Symbol>,
+    scopes: Vec<HashMap<Symbol, Local>>,
+    /// For each function being lowered, innermost last, the outer locals
+    /// its body uses, which its closure must hold a handle to.
+    captures: Vec<BTreeSet<String>>,
+    next_id: usize,
+}
+
+fn unsupported(span: Span, what: &str) -> Diagnostic {
+    Diagnostic::new(
+        span.line,
+        span.column,
+        span.start..span.end,
+        &format!("Can't transpile {}.", what),
+    )
+}
+
+fn indent(depth: usize) -> String {
+    "    ".repeat(depth)
+}
+
+impl Transpiler {
+    /// Declare `ident` in the innermost scope, giving its Rust name.
+    fn declare(&mut self, ident: &Ident) -> String {
+        self.next_id += 1;
+        let rust = format!("{}_{}", ident.name, self.next_id);
+        let local = Local {
+            rust: rust.clone(),
+            function: self.captures.len(),
+        };
+        self.scopes
+            .last_mut()
+            .expect("locals are declared in a scope")
+            .insert(ident.name, local);
+        rust
+    }
+
+    /// The Rust name of the local `ident` uses, noting it as captured by
+    /// each function between here and where it was declared.
+    fn local(&mut self, ident: &Ident) -> String {
+        let local = self
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&ident.name))
+            .expect("the resolver found this local");
+        for captured in &mut self.captures[local.function..] {
+            captured.insert(local.rust.clone());
+        }
+        local.rust.clone()
+    }
+
+    /// A global `ident` uses must be one the program declares or the
+    /// runtime has, unless it is undefined for the interpreter too.
+    fn check_global(&self, ident: &Ident) -> Result<(), Diagnostic> {
+        let name = ident.name;
+        if self.declared.contains(&name) || NATIVES.contains(&name.as_str()) {
+            return Ok(());
+        }
+        let mut builtins = Enviornment::new();
+        stdlib::install(&mut builtins);
+        match builtins.get(name, None) {
+            Ok(_) => Err(unsupported(ident.span, &format!("the builtin '{}'", name))),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, depth: usize, out: &mut String) -> Result<(), Diagnostic> {
+        let pad = indent(depth);
+        match stmt {
+            Stmt::Expr(e) => writeln!(out, "{}let _ = {};", pad, self.expr(e, depth)?).unwrap(),
+            Stmt::Print(e) => writeln!(out, "{}print({});", pad, self.expr(e, depth)?).unwrap(),
+            Stmt::Var(name, init) => {
+                let value = match init {
+                    Some(init) => self.expr(init, depth)?,
+                    None => "Value::Nil".to_owned(),
+                };
+                match self.scopes.is_empty() {
+                    true => writeln!(
+                        out,
+                        "{}define_global({:?}, {});",
+                        pad,
+                        name.name.as_str(),
+                        value
+                    ),
+                    false => writeln!(out, "{}let {} = cell({});", pad, self.declare(name), value),
+                }
+                .unwrap();
+            }
+            Stmt::Block(stmts) => {
+                writeln!(out, "{}{{", pad).unwrap();
+                self.scopes.push(HashMap::new());
+                for s in stmts {
+                    self.stmt(s, depth + 1, out)?;
+                }
+                self.scopes.pop();
+                writeln!(out, "{}}}", pad).unwrap();
+            }
+            Stmt::If(c, t, e) => {
+                writeln!(out, "{}if truthy(&{}) {{", pad, self.expr(c, depth)?).unwrap();
+                self.stmt(t, depth + 1, out)?;
+                if let Some(e) = e {
+                    writeln!(out, "{}}} else {{", pad).unwrap();
+                    self.stmt(e, depth + 1, out)?;
+                }
+                writeln!(out, "{}}}", pad).unwrap();
+            }
+            Stmt::While(c, body) => {
+                writeln!(out, "{}while truthy(&{}) {{", pad, self.expr(c, depth)?).unwrap();
+                self.stmt(body, depth + 1, out)?;
+                writeln!(out, "{}}}", pad).unwrap();
+            }
+            Stmt::ForIn(name, iterable, body) => {
+                let iterable = self.expr(iterable, depth)?;
+                writeln!(
+                    out,
+                    "{pad}{{\n{pad}    let mut iter = Iter::new({}, {})?;",
+                    iterable, name.span.line
+                )
+                .unwrap();
+                writeln!(out, "{}    while let Some(v) = iter.next() {{", pad).unwrap();
+                // Each time round has its own variable, for closures to keep.
+                self.scopes.push(HashMap::new());
+                writeln!(out, "{}        let {} = cell(v);", pad, self.declare(name)).unwrap();
+                self.stmt(body, depth + 2, out)?;
+                self.scopes.pop();
+                writeln!(out, "{pad}    }}\n{pad}}}").unwrap();
+            }
+            Stmt::Function(name, params, body) => {
+                let global = self.scopes.is_empty();
+                let rust = match global {
+                    true => String::new(),
+                    // Declared first, so the body can call it.
+                    false => self.declare(name),
+                };
+                let function = self.function(name.name.as_str(), params, body, depth)?;
+                match global {
+                    true => writeln!(
+                        out,
+                        "{}define_global({:?}, {});",
+                        pad,
+                        name.name.as_str(),
+                        function
+                    ),
+                    false => writeln!(
+                        out,
+                        "{pad}let {rust} = cell(Value::Nil);\n{pad}*{rust}.borrow_mut() = {function};"
+                    ),
+                }
+                .unwrap();
+            }
+            Stmt::Return(_, v) => {
+                let value = match v {
+                    Some(v) => self.expr(v, depth)?,
+                    None => "Value::Nil".to_owned(),
+                };
+                writeln!(out, "{}return Ok({});", pad, value).unwrap();
+            }
+            Stmt::Throw(keyword, _) => return Err(unsupported(*keyword, "'throw'")),
+            Stmt::Try(keyword, ..) => return Err(unsupported(*keyword, "'try'")),
+        }
+        Ok(())
+    }
+
+    /// A function value: a closure over handles to the outer locals it uses.
+    fn function(
+        &mut self,
+        name: &str,
+        params: &[Ident],
+        body: &Stmt,
+        depth: usize,
+    ) -> Result<String, Diagnostic> {
+        let pad = indent(depth + 1);
+        self.captures.push(BTreeSet::new());
+        self.scopes.push(HashMap::new());
+        let mut inner = String::new();
+        if !params.is_empty() {
+            writeln!(inner, "{}    let mut args = args.into_iter();", pad).unwrap();
+        }
+        for p in params {
+            let rust = self.declare(p);
+            writeln!(
+                inner,
+                "{}    let {} = cell(args.next().unwrap());",
+                pad, rust
+            )
+            .unwrap();
+        }
+        self.stmt(body, depth + 2, &mut inner)?;
+        self.scopes.pop();
+        let captured = self.captures.pop().unwrap();
+        let mut out = format!("Value::function({:?}, {}, {{\n", name, params.len());
+        for rust in captured {
+            writeln!(out, "{}let {} = {}.clone();", pad, rust, rust).unwrap();
+        }
+        write!(
+            out,
+            "{pad}move |args: Vec<Value>| -> Result<Value, Error> {{\n{inner}{pad}    Ok(Value::Nil)\n{pad}}}\n{}}})",
+            indent(depth)
+        )
+        .unwrap();
+        Ok(out)
+    }
+
+    fn expr(&mut self, expr: &Expr, depth: usize) -> Result<String, Diagnostic> {
+        let line = expr.span().line;
+        Ok(match expr {
+            Expr::Literal(l, _) => match l {
+                Literal::Nil => "Value::Nil".to_owned(),
+                Literal::Boolean(b) => format!("Value::Bool({})", b),
+                Literal::Number(n) => format!("Value::Number({:?})", n),
+                Literal::String(s) => format!("Value::str({:?})", s),
+            },
+            Expr::Grouping(e) => self.expr(e, depth)?,
+            Expr::Variable(name) => match name.slot {
+                Some(_) => format!("{}.borrow().clone()", self.local(name)),
+                None => {
+                    self.check_global(name)?;
+                    format!("get_global({:?}, {})?", name.name.as_str(), line)
+                }
+            },
+            Expr::Assign(name, value) => {
+                let value = self.expr(value, depth)?;
+                match name.slot {
+                    Some(_) => format!(
+                        "{{ let v = {}; *{}.borrow_mut() = v.clone(); v }}",
+                        value,
+                        self.local(name)
+                    ),
+                    None => {
+                        self.check_global(name)?;
+                        format!("set_global({:?}, {}, {})?", name.name.as_str(), value, line)
+                    }
+                }
+            }
+            Expr::Unary(op, e) => {
+                let e = self.expr(e, depth)?;
+                match op.kind {
+                    OpKind::Not => format!("Value::Bool(!truthy(&{}))", e),
+                    _ => format!("negate({}, {})?", e, line),
+                }
+            }
+            Expr::Binary(l, op, r) => {
+                let (l, r) = (self.expr(l, depth)?, self.expr(r, depth)?);
+                let f = |op: &str| format!("|a, b| a {} b", op);
+                match op.kind {
+                    OpKind::Comma => format!("{{ let _ = {}; {} }}", l, r),
+                    OpKind::Equal => format!("Value::Bool({} == {})", l, r),
+                    OpKind::NotEqual => format!("Value::Bool({} != {})", l, r),
+                    OpKind::Add => format!("add({}, {}, {})?", l, r, line),
+                    OpKind::Subtract => format!("arithmetic({}, {}, {}, {})?", l, r, line, f("-")),
+                    OpKind::Multiply => format!("arithmetic({}, {}, {}, {})?", l, r, line, f("*")),
+                    OpKind::Divide => format!("arithmetic({}, {}, {}, {})?", l, r, line, f("/")),
+                    OpKind::Remainder => format!("arithmetic({}, {}, {}, {})?", l, r, line, f("%")),
+                    OpKind::IntDivide => {
+                        let f = "|a, b| (a / b).trunc()";
+                        format!("arithmetic({}, {}, {}, {})?", l, r, line, f)
+                    }
+                    OpKind::Less => format!("comparison({}, {}, {}, {})?", l, r, line, f("<")),
+                    OpKind::LessEqual => {
+                        format!("comparison({}, {}, {}, {})?", l, r, line, f("<="))
+                    }
+                    OpKind::Greater => format!("comparison({}, {}, {}, {})?", l, r, line, f(">")),
+                    OpKind::GreaterEqual => {
+                        format!("comparison({}, {}, {}, {})?", l, r, line, f(">="))
+                    }
+                    kind => unreachable!("{:?} is not a binary operator", kind),
+                }
+            }
+            Expr::Logical(l, op, r) => {
+                let (l, r) = (self.expr(l, depth)?, self.expr(r, depth)?);
+                match op.kind {
+                    OpKind::Or => format!(
+                        "{{ let l = {}; if truthy(&l) {{ l }} else {{ {} }} }}",
+                        l, r
+                    ),
+                    _ => format!(
+                        "{{ let l = {}; if truthy(&l) {{ {} }} else {{ l }} }}",
+                        l, r
+                    ),
+                }
+            }
+            Expr::Ternary(c, t, e) => format!(
+                "if truthy(&{}) {{ {} }} else {{ {} }}",
+                self.expr(c, depth)?,
+                self.expr(t, depth)?,
+                self.expr(e, depth)?
+            ),
+            Expr::Call(callee, paren, args) => {
+                let callee = self.expr(callee, depth)?;
+                let args = self.exprs(args, depth)?;
+                format!("call({}, vec![{}], {})?", callee, args, paren.line)
+            }
+            Expr::List(_, items) => format!("Value::list(vec![{}])", self.exprs(items, depth)?),
+            Expr::Index(object, bracket, index) => format!(
+                "{{ let o = {}; indexable(&o, {line})?; index(o, {}, {line})? }}",
+                self.expr(object, depth)?,
+                self.expr(index, depth)?,
+                line = bracket.line
+            ),
+            Expr::IndexSet(object, bracket, index, value) => format!(
+                "{{ let o = {}; assignable(&o, {line})?; let i = element(&o, {}, {line})?; \
+                 set_element(o, i, {}) }}",
+                self.expr(object, depth)?,
+                self.expr(index, depth)?,
+                self.expr(value, depth)?,
+                line = bracket.line
+            ),
+            Expr::Slice(object, bracket, start, end) => {
+                let line = bracket.line;
+                let mut bound = |b: &Option<Box<Expr>>, default: &str| match b {
+                    Some(b) => Ok(format!("bound({}, n, {})?", self.expr(b, depth)?, line)),
+                    None => Ok(default.to_owned()),
+                };
+                let (start, end) = (bound(start, "0")?, bound(end, "n")?);
+                format!(
+                    "{{ let o = {}; let n = slice_len(&o, {})?; let s = {}; let e = {}; slice(o, s, e) }}",
+                    self.expr(object, depth)?,
+                    line,
+                    start,
+                    end
+                )
+            }
+            Expr::Lambda(_, params, body) => self.function("lambda", params, body, depth)?,
+            Expr::Get(_, name) => return Err(unsupported(name.span, "property access")),
+        })
+    }
+
+    fn exprs(&mut self, exprs: &[Expr], depth: usize) -> Result<String, Diagnostic> {
+        let exprs = exprs
+            .iter()
+            .map(|e| self.expr(e, depth))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(exprs.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lox_core::{parse, Scanner};
+
+    fn transpile(source: &str) -> Result<String, String> {
+        to_rust(&parse(&Scanner::scan(source).unwrap()).unwrap(), "test.lox")
+            .map_err(|d| d.to_string())
+    }
+
+    #[test]
+    fn test_closures_capture_what_they_use() {
+        let rust = transpile(
+            "{ var a = 1; var b = 2; fun f() { fun g() { return a; } return g; } print f()(); }",
+        )
+        .unwrap();
+        // `f` holds `a` for `g`, but neither needs `b`.
+        assert_eq!(rust.matches("let a_1 = a_1.clone();").count(), 2);
+        assert!(!rust.contains("b_2.clone()"));
+        // A local function can call itself.
+        assert!(rust.contains("let f_3 = cell(Value::Nil);"));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert_eq!(
+            transpile("print sort;").unwrap_err(),
+            "[line 1] Error: Can't transpile the builtin 'sort'."
+        );
+        // Declaring a global of the same name replaces the builtin.
+        assert!(transpile("fun sort() {}\nprint sort;").is_ok());
+        assert!(transpile("print undefined;").is_ok());
+        assert_eq!(
+            transpile("fun f() {\n  try {} catch (e) {}\n}").unwrap_err(),
+            "[line 2] Error: Can't transpile 'try'."
+        );
+    }
+}
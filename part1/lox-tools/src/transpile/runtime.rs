@@ -0,0 +1,468 @@
+// The runtime every transpiled program starts with: Lox's dynamic values
+// and the operations on them, behaving as the tree-walker's do. Not a
+// module of lox-tools; `transpile` pastes it into its output.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+
+#[derive(Clone)]
+enum Value {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(Rc<str>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Fn(Rc<Function>),
+}
+
+type Body = dyn Fn(Vec<Value>) -> Result<Value, Error>;
+
+struct Function {
+    name: &'static str,
+    arity: usize,
+    /// `false` for natives, whose errors reach the caller.
+    lox: bool,
+    body: Box<Body>,
+}
+
+/// Why a program stopped early.
+enum Error {
+    /// The message, the line reached in the call it is leaving, and the
+    /// calls it has left, innermost first, as `[line N] in name`.
+    Runtime(String, i32, Vec<String>),
+    StackOverflow(i32),
+    Exit(i32),
+}
+
+fn cell(v: Value) -> Rc<RefCell<Value>> {
+    Rc::new(RefCell::new(v))
+}
+
+fn fail<T>(message: &str, line: i32) -> Result<T, Error> {
+    Err(Error::Runtime(message.to_owned(), line, vec![]))
+}
+
+// Lists and functions are equal only to themselves.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+            (Value::Fn(a), Value::Fn(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    fn str(s: &str) -> Value {
+        Value::Str(s.into())
+    }
+
+    fn list(items: Vec<Value>) -> Value {
+        Value::List(Rc::new(RefCell::new(items)))
+    }
+
+    fn function(
+        name: &'static str,
+        arity: usize,
+        body: impl Fn(Vec<Value>) -> Result<Value, Error> + 'static,
+    ) -> Value {
+        Value::Fn(Rc::new(Function {
+            name,
+            arity,
+            lox: true,
+            body: Box::new(body),
+        }))
+    }
+
+    fn native(name: &'static str, arity: usize, body: fn(Vec<Value>) -> Result<Value, Error>) -> Value {
+        Value::Fn(Rc::new(Function {
+            name,
+            arity,
+            lox: false,
+            body: Box::new(body),
+        }))
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Str(_) => "string",
+            Value::List(_) => "list",
+            Value::Fn(_) => "function",
+        }
+    }
+}
+
+fn format_number(d: f64) -> String {
+    if d.is_nan() {
+        return "NaN".to_owned();
+    }
+    if d.is_infinite() {
+        return if d > 0.0 { "Infinity" } else { "-Infinity" }.to_owned();
+    }
+    let text = if d == 0.0 || (1e-3..1e7).contains(&d.abs()) {
+        format!("{:?}", d)
+    } else {
+        let sci = format!("{:e}", d);
+        let (mantissa, exp) = sci.split_once('e').unwrap();
+        if mantissa.contains('.') {
+            format!("{}E{}", mantissa, exp)
+        } else {
+            format!("{}.0E{}", mantissa, exp)
+        }
+    };
+    match text.strip_suffix(".0") {
+        Some(int) => int.to_owned(),
+        None => text,
+    }
+}
+
+fn fmt_list(f: &mut fmt::Formatter<'_>, list: &Rc<RefCell<Vec<Value>>>, seen: &mut Vec<*const RefCell<Vec<Value>>>) -> fmt::Result {
+    if seen.contains(&Rc::as_ptr(list)) {
+        return write!(f, "[...]");
+    }
+    seen.push(Rc::as_ptr(list));
+    write!(f, "[")?;
+    for (i, item) in list.borrow().iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        match item {
+            Value::List(l) => fmt_list(f, l, seen)?,
+            v => write!(f, "{}", v)?,
+        }
+    }
+    seen.pop();
+    write!(f, "]")
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::List(l) => fmt_list(f, l, &mut vec![]),
+            Value::Fn(c) if c.lox => write!(f, "<fn {}>", c.name),
+            Value::Fn(_) => write!(f, "<native fn>"),
+        }
+    }
+}
+
+fn truthy(v: &Value) -> bool {
+    !matches!(v, Value::Nil | Value::Bool(false))
+}
+
+fn print(v: Value) {
+    // Whoever closed the pipe, like `head`, has all the output it wanted.
+    if writeln!(std::io::stdout(), "{}", v).is_err() {
+        std::process::exit(0);
+    }
+}
+
+fn add(a: Value, b: Value, line: i32) -> Result<Value, Error> {
+    Ok(match (a, b) {
+        (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+        (Value::Str(a), b @ (Value::Str(_) | Value::Number(_))) => Value::str(&format!("{}{}", a, b)),
+        (a @ Value::Number(_), Value::Str(b)) => Value::str(&format!("{}{}", a, b)),
+        _ => return fail("Operands must be two numbers or two strings", line),
+    })
+}
+
+fn numbers(a: Value, b: Value, line: i32) -> Result<(f64, f64), Error> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => Ok((a, b)),
+        _ => fail("Operands must be numbers", line),
+    }
+}
+
+fn arithmetic(a: Value, b: Value, line: i32, op: fn(f64, f64) -> f64) -> Result<Value, Error> {
+    let (a, b) = numbers(a, b, line)?;
+    Ok(Value::Number(op(a, b)))
+}
+
+fn comparison(a: Value, b: Value, line: i32, op: fn(&f64, &f64) -> bool) -> Result<Value, Error> {
+    let (a, b) = numbers(a, b, line)?;
+    Ok(Value::Bool(op(&a, &b)))
+}
+
+fn negate(v: Value, line: i32) -> Result<Value, Error> {
+    match v {
+        Value::Number(n) => Ok(Value::Number(-n)),
+        _ => fail("Operand must be a number", line),
+    }
+}
+
+thread_local! {
+    static GLOBALS: RefCell<HashMap<&'static str, Value>> = RefCell::new(natives());
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+fn define_global(name: &'static str, v: Value) {
+    GLOBALS.with(|g| g.borrow_mut().insert(name, v));
+}
+
+fn get_global(name: &'static str, line: i32) -> Result<Value, Error> {
+    match GLOBALS.with(|g| g.borrow().get(name).cloned()) {
+        Some(v) => Ok(v),
+        None => fail(&format!("Undefined variable '{}'", name), line),
+    }
+}
+
+fn set_global(name: &'static str, v: Value, line: i32) -> Result<Value, Error> {
+    GLOBALS.with(|g| match g.borrow_mut().get_mut(name) {
+        Some(old) => {
+            *old = v.clone();
+            Ok(v)
+        }
+        None => fail(&format!("Undefined variable '{}'", name), line),
+    })
+}
+
+/// The most calls in progress at once, as the interpreter allows by default.
+const MAX_CALL_DEPTH: usize = 200;
+
+fn call(callee: Value, args: Vec<Value>, line: i32) -> Result<Value, Error> {
+    let Value::Fn(f) = callee else {
+        return fail("Can only call functions and classes", line);
+    };
+    if f.arity != args.len() {
+        let message = format!("Expected {} arguments but got {}", f.arity, args.len());
+        return fail(&message, line);
+    }
+    if !f.lox {
+        return match (f.body)(args) {
+            Err(Error::Runtime(message, ..)) => fail(&message, line),
+            rv => rv,
+        };
+    }
+    let depth = DEPTH.with(|d| d.get());
+    if depth >= MAX_CALL_DEPTH {
+        return Err(Error::StackOverflow(line));
+    }
+    DEPTH.with(|d| d.set(depth + 1));
+    let rv = (f.body)(args);
+    DEPTH.with(|d| d.set(depth));
+    // As in the interpreter, errors go on to the caller, gathering the trace.
+    match rv {
+        Err(Error::Runtime(message, at, mut trace)) => {
+            trace.push(format!("[line {}] in {}", at, f.name));
+            Err(Error::Runtime(message, line, trace))
+        }
+        rv => rv,
+    }
+}
+
+fn index_value(index: Value, len: usize, line: i32) -> Result<usize, Error> {
+    match index {
+        Value::Number(d) if d >= 0.0 && d.fract() == 0.0 => match (d as usize) < len {
+            true => Ok(d as usize),
+            false => fail("Index out of range", line),
+        },
+        _ => fail("Index must be a non-negative integer", line),
+    }
+}
+
+fn indexable(v: &Value, line: i32) -> Result<(), Error> {
+    match v {
+        Value::List(_) | Value::Str(_) => Ok(()),
+        _ => fail("Only lists and strings can be indexed", line),
+    }
+}
+
+fn index(v: Value, index: Value, line: i32) -> Result<Value, Error> {
+    match v {
+        Value::List(l) => {
+            let i = index_value(index, l.borrow().len(), line)?;
+            let item = l.borrow()[i].clone();
+            Ok(item)
+        }
+        Value::Str(s) => {
+            let i = index_value(index, s.chars().count(), line)?;
+            Ok(Value::str(&s.chars().skip(i).take(1).collect::<String>()))
+        }
+        _ => fail("Only lists and strings can be indexed", line),
+    }
+}
+
+fn assignable(v: &Value, line: i32) -> Result<(), Error> {
+    match v {
+        Value::List(_) => Ok(()),
+        _ => fail("Only list elements can be assigned", line),
+    }
+}
+
+fn element(v: &Value, index: Value, line: i32) -> Result<usize, Error> {
+    match v {
+        Value::List(l) => index_value(index, l.borrow().len(), line),
+        _ => fail("Only list elements can be assigned", line),
+    }
+}
+
+fn set_element(v: Value, i: usize, item: Value) -> Value {
+    if let Value::List(l) = v {
+        if let Some(slot) = l.borrow_mut().get_mut(i) {
+            *slot = item.clone();
+        }
+    }
+    item
+}
+
+fn slice_len(v: &Value, line: i32) -> Result<usize, Error> {
+    match v {
+        Value::List(l) => Ok(l.borrow().len()),
+        Value::Str(s) => Ok(s.chars().count()),
+        _ => fail("Only lists and strings can be sliced", line),
+    }
+}
+
+fn bound(v: Value, len: usize, line: i32) -> Result<usize, Error> {
+    match v {
+        Value::Number(d) if d >= 0.0 && d.fract() == 0.0 => Ok((d as usize).min(len)),
+        _ => fail("Slice bounds must be non-negative integers", line),
+    }
+}
+
+fn slice(v: Value, start: usize, end: usize) -> Value {
+    let end = end.max(start);
+    match v {
+        Value::List(l) => {
+            let l = l.borrow();
+            let end = end.min(l.len());
+            Value::list(l[start.min(end)..end].to_vec())
+        }
+        Value::Str(s) => Value::str(&s.chars().skip(start).take(end - start).collect::<String>()),
+        v => v,
+    }
+}
+
+/// What `for (var x in ...)` walks: a list as it is when each element is
+/// reached, or the characters of a string.
+enum Iter {
+    List(Rc<RefCell<Vec<Value>>>, usize),
+    Chars(Vec<char>, usize),
+}
+
+impl Iter {
+    fn new(v: Value, line: i32) -> Result<Iter, Error> {
+        match v {
+            Value::List(l) => Ok(Iter::List(l, 0)),
+            Value::Str(s) => Ok(Iter::Chars(s.chars().collect(), 0)),
+            _ => fail("Can only iterate over lists, strings and iterators", line),
+        }
+    }
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            Iter::List(l, i) => {
+                let v = l.borrow().get(*i).cloned()?;
+                *i += 1;
+                Some(v)
+            }
+            Iter::Chars(chars, i) => {
+                let c = *chars.get(*i)?;
+                *i += 1;
+                Some(Value::str(&c.to_string()))
+            }
+        }
+    }
+}
+
+fn bad_argument<T>(args: &[Value], name: &str, expected: &str) -> Result<T, Error> {
+    let message = format!("Expected argument 1 of {} to be {}, got {}", name, expected, args[0].type_name());
+    fail(&message, 0)
+}
+
+fn expect_number(args: &[Value], name: &str) -> Result<f64, Error> {
+    match &args[0] {
+        Value::Number(n) => Ok(*n),
+        _ => bad_argument(args, name, "a number"),
+    }
+}
+
+fn expect_list<'v>(args: &'v [Value], name: &str) -> Result<&'v Rc<RefCell<Vec<Value>>>, Error> {
+    match &args[0] {
+        Value::List(l) => Ok(l),
+        _ => bad_argument(args, name, "a list"),
+    }
+}
+
+fn natives() -> HashMap<&'static str, Value> {
+    let mut natives = HashMap::new();
+    let mut define = |name: &'static str, arity: usize, body: fn(Vec<Value>) -> Result<Value, Error>| {
+        natives.insert(name, Value::native(name, arity, body));
+    };
+    define("clock", 0, |_| {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+        Ok(Value::Number(now.unwrap_or_default().as_secs_f64()))
+    });
+    define("exit", 1, |args| Err(Error::Exit(expect_number(&args, "exit")? as i32)));
+    define("str", 1, |args| Ok(Value::str(&args[0].to_string())));
+    define("num", 1, |args| {
+        match &args[0] {
+            Value::Number(n) => Ok(Value::Number(*n)),
+            Value::Str(s) => Ok(s.trim().parse::<f64>().map_or(Value::Nil, Value::Number)),
+            _ => bad_argument(&args, "num", "a string"),
+        }
+    });
+    define("len", 1, |args| {
+        match &args[0] {
+            Value::Str(s) => Ok(Value::Number(s.chars().count() as f64)),
+            Value::List(l) => Ok(Value::Number(l.borrow().len() as f64)),
+            _ => bad_argument(&args, "len", "a list or string"),
+        }
+    });
+    define("push", 2, |args| {
+        expect_list(&args, "push")?.borrow_mut().push(args[1].clone());
+        Ok(Value::Nil)
+    });
+    define("pop", 1, |args| {
+        Ok(expect_list(&args, "pop")?.borrow_mut().pop().unwrap_or(Value::Nil))
+    });
+    define("abs", 1, |args| Ok(Value::Number(expect_number(&args, "abs")?.abs())));
+    define("floor", 1, |args| Ok(Value::Number(expect_number(&args, "floor")?.floor())));
+    define("ceil", 1, |args| Ok(Value::Number(expect_number(&args, "ceil")?.ceil())));
+    define("sqrt", 1, |args| Ok(Value::Number(expect_number(&args, "sqrt")?.sqrt())));
+    natives
+}
+
+fn main() {
+    match run() {
+        Ok(()) => {}
+        Err(Error::Exit(status)) => std::process::exit(status),
+        Err(Error::Runtime(message, line, trace)) => {
+            eprintln!("{}.", message);
+            // Runaway recursion would otherwise print a frame per call.
+            let mut frames = trace.iter().peekable();
+            while let Some(frame) = frames.next() {
+                eprintln!("{}", frame);
+                let mut repeats = 0;
+                while frames.next_if_eq(&frame).is_some() {
+                    repeats += 1;
+                }
+                match repeats {
+                    0 => {}
+                    1 | 2 => (0..repeats).for_each(|_| eprintln!("{}", frame)),
+                    _ => eprintln!("[previous line repeated {} more times]", repeats),
+                }
+            }
+            eprintln!("[line {}] in script", line);
+            std::process::exit(70);
+        }
+        Err(Error::StackOverflow(line)) => {
+            eprintln!("Stack overflow.\n[line {}]", line);
+            std::process::exit(70);
+        }
+    }
+}
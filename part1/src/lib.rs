@@ -1,7 +0,0 @@
-pub mod environment;
-pub mod interpreter;
-pub mod lox;
-pub mod lox_error;
-pub mod parser;
-pub mod scanner;
-pub mod tokens;
@@ -0,0 +1,348 @@
+use crate::chunk::{Chunk, OpCode};
+use crate::value::Value;
+use anyhow::{anyhow, bail, Result};
+use std::collections::VecDeque;
+
+/// The first bytes of every compiled file.
+pub const MAGIC: &[u8; 4] = b"LOXC";
+
+/// Bumped whenever the layout or the instruction set changes, since old
+/// files would otherwise run as different instructions.
+pub const VERSION: u16 = 1;
+
+const NIL: u8 = 0;
+const BOOL: u8 = 1;
+const NUMBER: u8 = 2;
+const STRING: u8 = 3;
+
+/// `chunk` as a file: the magic and version, then the constant pool, the
+/// code, and the line of each byte of code as runs of `(line, count)`.
+/// Numbers are big-endian, as jump offsets are in the code.
+pub fn encode(chunk: &Chunk) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+    out.extend(VERSION.to_be_bytes());
+    out.extend((chunk.constants.len() as u32).to_be_bytes());
+    for constant in &chunk.constants {
+        match constant {
+            Value::Nil => out.push(NIL),
+            Value::Bool(b) => out.extend([BOOL, *b as u8]),
+            Value::Number(n) => {
+                out.push(NUMBER);
+                out.extend(n.to_bits().to_be_bytes());
+            }
+            Value::String(s) => {
+                out.push(STRING);
+                out.extend((s.len() as u32).to_be_bytes());
+                out.extend(s.as_bytes());
+            }
+        }
+    }
+    out.extend((chunk.code.len() as u32).to_be_bytes());
+    out.extend(&chunk.code);
+    let mut runs: Vec<(i32, u32)> = vec![];
+    for &line in &chunk.lines {
+        match runs.last_mut() {
+            Some((last, count)) if *last == line => *count += 1,
+            _ => runs.push((line, 1)),
+        }
+    }
+    out.extend((runs.len() as u32).to_be_bytes());
+    for (line, count) in runs {
+        out.extend(line.to_be_bytes());
+        out.extend(count.to_be_bytes());
+    }
+    out
+}
+
+/// Load a chunk written by `encode`, checking that it is safe to run.
+pub fn decode(bytes: &[u8]) -> Result<Chunk> {
+    let mut r = Reader { bytes, pos: 0 };
+    if r.take(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+        bail!("Not a compiled Lox file.");
+    }
+    let version = r.u16()?;
+    if version != VERSION {
+        bail!(
+            "Compiled for bytecode version {}, but this is version {}; compile it again.",
+            version,
+            VERSION
+        );
+    }
+    let mut chunk = Chunk::new();
+    for _ in 0..r.u32()? {
+        chunk.constants.push(match r.u8()? {
+            NIL => Value::Nil,
+            BOOL => Value::Bool(r.u8()? != 0),
+            NUMBER => Value::Number(f64::from_bits(u64::from_be_bytes(
+                r.take(8)?.try_into().unwrap(),
+            ))),
+            STRING => {
+                let len = r.u32()? as usize;
+                let s = std::str::from_utf8(r.take(len)?)
+                    .map_err(|_| anyhow!("Corrupt compiled file: a string is not UTF-8."))?;
+                Value::String(s.into())
+            }
+            tag => bail!("Corrupt compiled file: unknown constant type {}.", tag),
+        });
+    }
+    let len = r.u32()? as usize;
+    chunk.code = r.take(len)?.to_vec();
+    for _ in 0..r.u32()? {
+        let line = r.u32()? as i32;
+        let count = r.u32()? as usize;
+        if chunk.lines.len() + count > chunk.code.len() {
+            bail!("Corrupt compiled file: more lines than code.");
+        }
+        chunk.lines.extend(std::iter::repeat_n(line, count));
+    }
+    if chunk.lines.len() != chunk.code.len() {
+        bail!("Corrupt compiled file: fewer lines than code.");
+    }
+    if r.pos != bytes.len() {
+        bail!("Corrupt compiled file: unexpected bytes at the end.");
+    }
+    verify(&chunk).map_err(|e| anyhow!("Corrupt compiled file: {}", e))?;
+    Ok(chunk)
+}
+
+/// Reads big-endian numbers from the front of a file.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos.saturating_add(n))
+            .ok_or_else(|| anyhow!("Corrupt compiled file: it ends too soon."))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+/// Check that running `chunk` can't go wrong the way only a corrupt file
+/// could: every instruction and operand is whole, constants and jump
+/// targets exist, nothing pops an empty stack or reads a slot past its
+/// top, and every path ends in `Return` with the stack the same height
+/// however an instruction is reached. Runtime errors are left to the VM.
+fn verify(chunk: &Chunk) -> Result<()> {
+    let code = &chunk.code;
+    // The stack height before each instruction, once some path reaches it.
+    let mut heights: Vec<Option<usize>> = vec![None; code.len()];
+    let mut work = VecDeque::from([(0, 0)]);
+    while let Some((ip, height)) = work.pop_front() {
+        let Some(seen) = heights.get_mut(ip) else {
+            bail!("execution runs past the end of the code");
+        };
+        match seen {
+            Some(seen) if *seen == height => continue,
+            Some(_) => bail!("the stack height differs between paths to {}", ip),
+            None => *seen = Some(height),
+        }
+        let op =
+            OpCode::try_from(code[ip]).map_err(|b| anyhow!("unknown opcode {} at {}", b, ip))?;
+        let operand = |i: usize| {
+            code.get(ip + i)
+                .copied()
+                .ok_or_else(|| anyhow!("an instruction at {} is cut short", ip))
+        };
+        let needs = |n: usize| match height >= n {
+            true => Ok(()),
+            false => Err(anyhow!("the instruction at {} pops an empty stack", ip)),
+        };
+        let next = ip + 1;
+        match op {
+            OpCode::Constant => {
+                let constant = operand(1)? as usize;
+                if constant >= chunk.constants.len() {
+                    bail!("the instruction at {} uses a missing constant", ip);
+                }
+                work.push_back((next + 1, height + 1));
+            }
+            OpCode::GetGlobal | OpCode::DefineGlobal | OpCode::SetGlobal => {
+                if !matches!(
+                    chunk.constants.get(operand(1)? as usize),
+                    Some(Value::String(_))
+                ) {
+                    bail!("the instruction at {} names a global with no string", ip);
+                }
+                let height = match op {
+                    OpCode::GetGlobal => height + 1,
+                    OpCode::DefineGlobal => {
+                        needs(1)?;
+                        height - 1
+                    }
+                    _ => {
+                        needs(1)?;
+                        height
+                    }
+                };
+                work.push_back((next + 1, height));
+            }
+            OpCode::GetLocal | OpCode::SetLocal => {
+                if operand(1)? as usize >= height {
+                    bail!("the instruction at {} uses a slot past the stack", ip);
+                }
+                let height = match op {
+                    OpCode::GetLocal => height + 1,
+                    _ => height,
+                };
+                work.push_back((next + 1, height));
+            }
+            OpCode::Nil | OpCode::True | OpCode::False => work.push_back((next, height + 1)),
+            OpCode::Pop | OpCode::Print => {
+                needs(1)?;
+                work.push_back((next, height - 1));
+            }
+            OpCode::Equal
+            | OpCode::Greater
+            | OpCode::Less
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide => {
+                needs(2)?;
+                work.push_back((next, height - 1));
+            }
+            OpCode::Not | OpCode::Negate => {
+                needs(1)?;
+                work.push_back((next, height));
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+                let offset = u16::from_be_bytes([operand(1)?, operand(2)?]) as usize;
+                let after = ip + 3;
+                let target = match op {
+                    OpCode::Loop => after.checked_sub(offset),
+                    _ => Some(after + offset),
+                }
+                .ok_or_else(|| anyhow!("the jump at {} lands before the code", ip))?;
+                if op == OpCode::JumpIfFalse {
+                    needs(1)?;
+                    work.push_back((after, height));
+                }
+                work.push_back((target, height));
+            }
+            OpCode::Return => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler;
+    use part1::lox_error::Diagnostic;
+    use part1::lox_error::LoxError;
+
+    struct NoErrors {}
+    impl LoxError for NoErrors {
+        fn report(&mut self, diagnostic: Diagnostic) {
+            panic!("unexpected error: {}", diagnostic);
+        }
+        fn has_error(&self) -> bool {
+            false
+        }
+    }
+
+    fn compile(source: &str) -> Chunk {
+        compiler::compile(source, &mut NoErrors {}).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let chunk = compile(
+            "var a = \"é\";\n{ var b = a + \"b\"; print b; }\n\
+             for (var i = 0; i < 3; i = i + 1) if (i != 1 and true) print i; else print nil;\n\
+             print -1.5 or !false;",
+        );
+        let decoded = decode(&encode(&chunk)).unwrap();
+        assert_eq!(decoded.code, chunk.code);
+        assert_eq!(decoded.lines, chunk.lines);
+        assert_eq!(decoded.constants, chunk.constants);
+    }
+
+    #[test]
+    fn test_header_checks() {
+        let bytes = encode(&compile("print 1;"));
+        let error = |bytes: &[u8]| decode(bytes).unwrap_err().to_string();
+        assert_eq!(error(b"print 1;"), "Not a compiled Lox file.");
+        let mut newer = bytes.clone();
+        newer[5] += 1;
+        assert_eq!(
+            error(&newer),
+            "Compiled for bytecode version 2, but this is version 1; compile it again."
+        );
+        assert_eq!(
+            error(&bytes[..bytes.len() - 1]),
+            "Corrupt compiled file: it ends too soon."
+        );
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(
+            error(&longer),
+            "Corrupt compiled file: unexpected bytes at the end."
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let chunk = |code: &[u8], constants: Vec<Value>| Chunk {
+            code: code.to_vec(),
+            lines: vec![1; code.len()],
+            constants,
+        };
+        let error = |chunk: Chunk| verify(&chunk).unwrap_err().to_string();
+        let (nil, pop, ret) = (OpCode::Nil as u8, OpCode::Pop as u8, OpCode::Return as u8);
+        assert!(verify(&chunk(&[nil, pop, ret], vec![])).is_ok());
+        assert_eq!(
+            error(chunk(&[pop, ret], vec![])),
+            "the instruction at 0 pops an empty stack"
+        );
+        assert_eq!(
+            error(chunk(&[nil], vec![])),
+            "execution runs past the end of the code"
+        );
+        assert_eq!(
+            error(chunk(&[OpCode::Constant as u8, 0, ret], vec![])),
+            "the instruction at 0 uses a missing constant"
+        );
+        assert_eq!(
+            error(chunk(
+                &[OpCode::GetGlobal as u8, 0, ret],
+                vec![Value::Number(1.0)]
+            )),
+            "the instruction at 0 names a global with no string"
+        );
+        assert_eq!(
+            error(chunk(&[nil, OpCode::GetLocal as u8, 1, ret], vec![])),
+            "the instruction at 1 uses a slot past the stack"
+        );
+        // `if (nil) nil;` without the `Pop` after the jump leaves the
+        // stack one deeper on one path than the other.
+        let jump = OpCode::JumpIfFalse as u8;
+        assert_eq!(
+            error(chunk(&[nil, jump, 0, 1, nil, ret], vec![])),
+            "the stack height differs between paths to 5"
+        );
+        assert_eq!(
+            error(chunk(&[OpCode::Loop as u8, 0, 9, ret], vec![])),
+            "the jump at 0 lands before the code"
+        );
+        assert_eq!(error(chunk(&[0xff], vec![])), "unknown opcode 255 at 0");
+    }
+}
@@ -0,0 +1,204 @@
+use crate::value::Value;
+use std::fmt::Write;
+
+/// One instruction. Operands, if any, follow the opcode byte in the chunk:
+/// a one-byte constant or slot index, or a two-byte big-endian jump offset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    Equal,
+    Greater,
+    Less,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Return,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 24] = [
+        Self::Constant,
+        Self::Nil,
+        Self::True,
+        Self::False,
+        Self::Pop,
+        Self::GetLocal,
+        Self::SetLocal,
+        Self::GetGlobal,
+        Self::DefineGlobal,
+        Self::SetGlobal,
+        Self::Equal,
+        Self::Greater,
+        Self::Less,
+        Self::Add,
+        Self::Subtract,
+        Self::Multiply,
+        Self::Divide,
+        Self::Not,
+        Self::Negate,
+        Self::Print,
+        Self::Jump,
+        Self::JumpIfFalse,
+        Self::Loop,
+        Self::Return,
+    ];
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, u8> {
+        Self::ALL.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+/// A compiled sequence of instructions, with the source line of every byte
+/// and the constants the instructions refer to.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub lines: Vec<i32>,
+    pub constants: Vec<Value>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, byte: u8, line: i32) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    pub fn write_op(&mut self, op: OpCode, line: i32) {
+        self.write(op as u8, line);
+    }
+
+    /// Add `value` to the constant pool, returning its index.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// A listing of every instruction, one per line, headed by `name`.
+    pub fn disassemble(&self, name: &str) -> String {
+        let mut out = format!("== {} ==\n", name);
+        let mut offset = 0;
+        while offset < self.code.len() {
+            offset = self.disassemble_instruction(&mut out, offset);
+        }
+        out
+    }
+
+    /// Append the instruction at `offset` to `out`, returning the offset of
+    /// the next one.
+    pub fn disassemble_instruction(&self, out: &mut String, offset: usize) -> usize {
+        write!(out, "{:04} ", offset).unwrap();
+        if offset > 0 && self.lines[offset] == self.lines[offset - 1] {
+            out.push_str("   | ");
+        } else {
+            write!(out, "{:4} ", self.lines[offset]).unwrap();
+        }
+        let op = match OpCode::try_from(self.code[offset]) {
+            Ok(op) => op,
+            Err(byte) => {
+                writeln!(out, "Unknown opcode {}", byte).unwrap();
+                return offset + 1;
+            }
+        };
+        let name = format!("OP_{}", op_name(op));
+        match op {
+            OpCode::Constant | OpCode::GetGlobal | OpCode::DefineGlobal | OpCode::SetGlobal => {
+                let constant = self.code[offset + 1];
+                writeln!(
+                    out,
+                    "{:<16} {:4} '{}'",
+                    name, constant, self.constants[constant as usize]
+                )
+                .unwrap();
+                offset + 2
+            }
+            OpCode::GetLocal | OpCode::SetLocal => {
+                writeln!(out, "{:<16} {:4}", name, self.code[offset + 1]).unwrap();
+                offset + 2
+            }
+            OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+                let jump = u16::from_be_bytes([self.code[offset + 1], self.code[offset + 2]]);
+                let target = match op {
+                    OpCode::Loop => offset + 3 - jump as usize,
+                    _ => offset + 3 + jump as usize,
+                };
+                writeln!(out, "{:<16} {:4} -> {}", name, offset, target).unwrap();
+                offset + 3
+            }
+            _ => {
+                writeln!(out, "{}", name).unwrap();
+                offset + 1
+            }
+        }
+    }
+}
+
+/// `JumpIfFalse` as `JUMP_IF_FALSE`, as clox spells it.
+fn op_name(op: OpCode) -> String {
+    let mut name = String::new();
+    for c in format!("{:?}", op).chars() {
+        if c.is_uppercase() && !name.is_empty() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_round_trip() {
+        for op in OpCode::ALL {
+            assert_eq!(OpCode::try_from(op as u8), Ok(op));
+        }
+        assert_eq!(OpCode::try_from(OpCode::ALL.len() as u8), Err(24));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut chunk = Chunk::new();
+        let constant = chunk.add_constant(Value::Number(1.2));
+        chunk.write_op(OpCode::Constant, 123);
+        chunk.write(constant as u8, 123);
+        chunk.write_op(OpCode::Negate, 123);
+        chunk.write_op(OpCode::JumpIfFalse, 124);
+        chunk.write(0, 124);
+        chunk.write(1, 124);
+        chunk.write_op(OpCode::Return, 125);
+        assert_eq!(
+            chunk.disassemble("test chunk"),
+            "== test chunk ==\n\
+             0000  123 OP_CONSTANT         0 '1.2'\n\
+             0002    | OP_NEGATE\n\
+             0003  124 OP_JUMP_IF_FALSE    3 -> 7\n\
+             0006  125 OP_RETURN\n"
+        );
+    }
+}
@@ -0,0 +1,642 @@
+use crate::chunk::{Chunk, OpCode};
+use crate::value::Value;
+use part1::intern::Symbol;
+use part1::lox_error::{Diagnostic, LoxError};
+use part1::scanner;
+use part1::tokens::{Token, TokenType};
+
+/// Binding power of an infix operator, weakest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    None,
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Term,
+    Factor,
+    Unary,
+    Call,
+    Primary,
+}
+
+impl Precedence {
+    /// The next tighter level, for the right operand of a left-associative
+    /// operator.
+    fn next(self) -> Self {
+        match self {
+            Self::None => Self::Assignment,
+            Self::Assignment => Self::Or,
+            Self::Or => Self::And,
+            Self::And => Self::Equality,
+            Self::Equality => Self::Comparison,
+            Self::Comparison => Self::Term,
+            Self::Term => Self::Factor,
+            Self::Factor => Self::Unary,
+            Self::Unary => Self::Call,
+            Self::Call | Self::Primary => Self::Primary,
+        }
+    }
+}
+
+struct Local {
+    name: Symbol,
+    /// `None` between the declaration and the end of its initializer.
+    depth: Option<usize>,
+}
+
+/// Locals live in one-byte stack slots.
+const MAX_LOCALS: usize = u8::MAX as usize + 1;
+
+type ParseFn<'a> = fn(&mut Compiler<'a>, bool);
+
+/// Single-pass compiler from tokens straight to bytecode: a Pratt parser
+/// for expressions and recursive descent for statements.
+pub struct Compiler<'a> {
+    source: &'a str,
+    tokens: &'a [Token],
+    current: usize,
+    errors: &'a mut dyn LoxError,
+    had_error: bool,
+    // Suppress cascading errors until the next statement boundary.
+    panic_mode: bool,
+    chunk: Chunk,
+    locals: Vec<Local>,
+    scope_depth: usize,
+}
+
+/// Compile `source` to a chunk, or `None` after reporting errors to `errors`.
+pub fn compile(source: &str, errors: &mut dyn LoxError) -> Option<Chunk> {
+    let tokens = scanner::scan_tokens(errors, source).ok()?;
+    if errors.has_error() {
+        return None;
+    }
+    let mut compiler = Compiler::new(source, &tokens, errors);
+    while !compiler.check(&TokenType::EOF) {
+        compiler.declaration();
+    }
+    compiler.emit_op(OpCode::Return);
+    match compiler.had_error {
+        true => None,
+        false => Some(compiler.chunk),
+    }
+}
+
+impl<'a> Compiler<'a> {
+    fn new(source: &'a str, tokens: &'a [Token], errors: &'a mut dyn LoxError) -> Self {
+        Self {
+            source,
+            tokens,
+            current: 0,
+            errors,
+            had_error: false,
+            panic_mode: false,
+            chunk: Chunk::new(),
+            locals: vec![],
+            scope_depth: 0,
+        }
+    }
+
+    fn peek(&self) -> &'a Token {
+        // The scanner always ends the stream with EOF; stay on it.
+        &self.tokens[self.current.min(self.tokens.len() - 1)]
+    }
+
+    fn previous(&self) -> &'a Token {
+        &self.tokens[self.current.saturating_sub(1)]
+    }
+
+    fn advance(&mut self) {
+        if !self.check(&TokenType::EOF) {
+            self.current += 1;
+        }
+    }
+
+    fn check(&self, t: &TokenType) -> bool {
+        &self.peek().token_type == t
+    }
+
+    fn match_token(&mut self, t: &TokenType) -> bool {
+        let matched = self.check(t);
+        if matched {
+            self.advance();
+        }
+        matched
+    }
+
+    fn consume(&mut self, t: TokenType, msg: &str) {
+        if self.check(&t) {
+            self.advance();
+        } else {
+            self.error_at(self.peek(), msg);
+        }
+    }
+
+    fn error_at(&mut self, token: &Token, msg: &str) {
+        if self.panic_mode {
+            return;
+        }
+        self.panic_mode = true;
+        self.had_error = true;
+        let mut diagnostic = Diagnostic::at(token, msg);
+        if token.token_type != TokenType::EOF {
+            // Like clox, always name the offending lexeme.
+            diagnostic.location =
+                format!(" at '{}'", &self.source[token.span.start..token.span.end]);
+        }
+        self.errors.report(diagnostic);
+    }
+
+    fn error(&mut self, msg: &str) {
+        self.error_at(self.previous(), msg);
+    }
+
+    fn emit(&mut self, byte: u8) {
+        let line = self.previous().line();
+        self.chunk.write(byte, line);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit(op as u8);
+    }
+
+    fn emit_op_arg(&mut self, op: OpCode, arg: u8) {
+        self.emit_op(op);
+        self.emit(arg);
+    }
+
+    fn make_constant(&mut self, value: Value) -> u8 {
+        let constant = self.chunk.add_constant(value);
+        match u8::try_from(constant) {
+            Ok(constant) => constant,
+            Err(_) => {
+                self.error("Too many constants in one chunk.");
+                0
+            }
+        }
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let constant = self.make_constant(value);
+        self.emit_op_arg(OpCode::Constant, constant);
+    }
+
+    /// Emit a forward jump with a placeholder offset, returning where the
+    /// offset is for `patch_jump`.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op(op);
+        self.emit(0xff);
+        self.emit(0xff);
+        self.chunk.code.len() - 2
+    }
+
+    /// Point the jump whose offset is at `offset` to the next instruction.
+    fn patch_jump(&mut self, offset: usize) {
+        let jump = self.chunk.code.len() - offset - 2;
+        let jump = u16::try_from(jump).unwrap_or_else(|_| {
+            self.error("Too much code to jump over.");
+            0
+        });
+        self.chunk.code[offset..offset + 2].copy_from_slice(&jump.to_be_bytes());
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        self.emit_op(OpCode::Loop);
+        let jump = self.chunk.code.len() - loop_start + 2;
+        let jump = u16::try_from(jump).unwrap_or_else(|_| {
+            self.error("Loop body too large.");
+            0
+        });
+        jump.to_be_bytes().into_iter().for_each(|b| self.emit(b));
+    }
+
+    fn declaration(&mut self) {
+        if self.match_token(&TokenType::VAR) {
+            self.var_declaration();
+        } else {
+            self.statement();
+        }
+        if self.panic_mode {
+            self.synchronize();
+        }
+    }
+
+    fn var_declaration(&mut self) {
+        let global = self.parse_variable("Expect variable name.");
+        if self.match_token(&TokenType::EQUAL) {
+            self.expression();
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.consume(
+            TokenType::SEMICOLON,
+            "Expect ';' after variable declaration.",
+        );
+        self.define_variable(global);
+    }
+
+    /// Declare the variable named by the next token, returning the constant
+    /// holding its name if it is a global.
+    fn parse_variable(&mut self, msg: &str) -> u8 {
+        let name = match self.peek().token_type {
+            TokenType::IDENTIFIER(name) => name,
+            _ => {
+                self.error_at(self.peek(), msg);
+                return 0;
+            }
+        };
+        self.advance();
+        if self.scope_depth > 0 {
+            self.declare_local(name);
+            return 0;
+        }
+        self.identifier_constant(name)
+    }
+
+    fn identifier_constant(&mut self, name: Symbol) -> u8 {
+        self.make_constant(Value::String(name.as_str().into()))
+    }
+
+    fn declare_local(&mut self, name: Symbol) {
+        let shadows = self
+            .locals
+            .iter()
+            .rev()
+            .take_while(|l| l.depth.is_none_or(|d| d >= self.scope_depth))
+            .any(|l| l.name == name);
+        if shadows {
+            self.error("Already a variable with this name in this scope.");
+        }
+        if self.locals.len() == MAX_LOCALS {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        self.locals.push(Local { name, depth: None });
+    }
+
+    fn define_variable(&mut self, global: u8) {
+        if self.scope_depth > 0 {
+            if let Some(local) = self.locals.last_mut() {
+                local.depth = Some(self.scope_depth);
+            }
+            return;
+        }
+        self.emit_op_arg(OpCode::DefineGlobal, global);
+    }
+
+    fn resolve_local(&mut self, name: Symbol) -> Option<u8> {
+        let (slot, local) = self
+            .locals
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, l)| l.name == name)?;
+        if local.depth.is_none() {
+            self.error("Can't read local variable in its own initializer.");
+        }
+        Some(slot as u8)
+    }
+
+    fn statement(&mut self) {
+        match self.peek().token_type {
+            TokenType::PRINT => {
+                self.advance();
+                self.print_statement();
+            }
+            TokenType::IF => {
+                self.advance();
+                self.if_statement();
+            }
+            TokenType::WHILE => {
+                self.advance();
+                self.while_statement();
+            }
+            TokenType::FOR => {
+                self.advance();
+                self.for_statement();
+            }
+            TokenType::LEFT_BRACE => {
+                self.advance();
+                self.begin_scope();
+                self.block();
+                self.end_scope();
+            }
+            _ => self.expression_statement(),
+        }
+    }
+
+    fn print_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::SEMICOLON, "Expect ';' after value.");
+        self.emit_op(OpCode::Print);
+    }
+
+    fn expression_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::SEMICOLON, "Expect ';' after expression.");
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn if_statement(&mut self) {
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.");
+        self.expression();
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition.");
+
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.statement();
+        let else_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop);
+
+        if self.match_token(&TokenType::ELSE) {
+            self.statement();
+        }
+        self.patch_jump(else_jump);
+    }
+
+    fn while_statement(&mut self) {
+        let loop_start = self.chunk.code.len();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'while'.");
+        self.expression();
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition.");
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.statement();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop);
+    }
+
+    fn for_statement(&mut self) {
+        self.begin_scope();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.");
+        if self.match_token(&TokenType::SEMICOLON) {
+            // No initializer.
+        } else if self.match_token(&TokenType::VAR) {
+            self.var_declaration();
+        } else {
+            self.expression_statement();
+        }
+
+        let mut loop_start = self.chunk.code.len();
+        let mut exit_jump = None;
+        if !self.match_token(&TokenType::SEMICOLON) {
+            self.expression();
+            self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.");
+            exit_jump = Some(self.emit_jump(OpCode::JumpIfFalse));
+            self.emit_op(OpCode::Pop);
+        }
+
+        if !self.match_token(&TokenType::RIGHT_PAREN) {
+            // The increment runs after the body, so jump over it now and
+            // loop back to it from the end of the body.
+            let body_jump = self.emit_jump(OpCode::Jump);
+            let increment_start = self.chunk.code.len();
+            self.expression();
+            self.emit_op(OpCode::Pop);
+            self.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.");
+
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.statement();
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump {
+            self.patch_jump(exit_jump);
+            self.emit_op(OpCode::Pop);
+        }
+        self.end_scope();
+    }
+
+    fn block(&mut self) {
+        while !self.check(&TokenType::RIGHT_BRACE) && !self.check(&TokenType::EOF) {
+            self.declaration();
+        }
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after block.");
+    }
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.scope_depth -= 1;
+        while let Some(local) = self.locals.last() {
+            if local.depth.is_some_and(|d| d <= self.scope_depth) {
+                break;
+            }
+            self.locals.pop();
+            self.emit_op(OpCode::Pop);
+        }
+    }
+
+    fn synchronize(&mut self) {
+        self.panic_mode = false;
+        while !self.check(&TokenType::EOF) {
+            if self.previous().token_type == TokenType::SEMICOLON {
+                return;
+            }
+            match self.peek().token_type {
+                TokenType::CLASS
+                | TokenType::FUN
+                | TokenType::VAR
+                | TokenType::FOR
+                | TokenType::IF
+                | TokenType::WHILE
+                | TokenType::PRINT
+                | TokenType::RETURN => return,
+                _ => self.advance(),
+            }
+        }
+    }
+
+    fn expression(&mut self) {
+        self.parse_precedence(Precedence::Assignment);
+    }
+
+    /// The prefix rule, infix rule and infix precedence for a token.
+    fn rule(t: &TokenType) -> (Option<ParseFn<'a>>, Option<ParseFn<'a>>, Precedence) {
+        match t {
+            TokenType::LEFT_PAREN => (Some(Self::grouping), None, Precedence::None),
+            TokenType::MINUS => (Some(Self::unary), Some(Self::binary), Precedence::Term),
+            TokenType::PLUS => (None, Some(Self::binary), Precedence::Term),
+            TokenType::SLASH | TokenType::STAR => (None, Some(Self::binary), Precedence::Factor),
+            TokenType::BANG => (Some(Self::unary), None, Precedence::None),
+            TokenType::BANG_EQUAL | TokenType::EQUAL_EQUAL => {
+                (None, Some(Self::binary), Precedence::Equality)
+            }
+            TokenType::GREATER
+            | TokenType::GREATER_EQUAL
+            | TokenType::LESS
+            | TokenType::LESS_EQUAL => (None, Some(Self::binary), Precedence::Comparison),
+            TokenType::IDENTIFIER(_) => (Some(Self::variable), None, Precedence::None),
+            TokenType::STRING(_) | TokenType::NUMBER(_) => {
+                (Some(Self::literal), None, Precedence::None)
+            }
+            TokenType::FALSE | TokenType::TRUE | TokenType::NIL => {
+                (Some(Self::literal), None, Precedence::None)
+            }
+            TokenType::AND => (None, Some(Self::and), Precedence::And),
+            TokenType::OR => (None, Some(Self::or), Precedence::Or),
+            _ => (None, None, Precedence::None),
+        }
+    }
+
+    fn parse_precedence(&mut self, precedence: Precedence) {
+        self.advance();
+        let prefix = match Self::rule(&self.previous().token_type).0 {
+            Some(prefix) => prefix,
+            None => {
+                self.error("Expect expression.");
+                return;
+            }
+        };
+        let can_assign = precedence <= Precedence::Assignment;
+        prefix(self, can_assign);
+
+        while precedence <= Self::rule(&self.peek().token_type).2 {
+            self.advance();
+            if let Some(infix) = Self::rule(&self.previous().token_type).1 {
+                infix(self, can_assign);
+            }
+        }
+
+        if can_assign && self.match_token(&TokenType::EQUAL) {
+            self.error("Invalid assignment target.");
+        }
+    }
+
+    fn grouping(&mut self, _can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after expression.");
+    }
+
+    fn literal(&mut self, _can_assign: bool) {
+        match &self.previous().token_type {
+            TokenType::NUMBER(n) => self.emit_constant(Value::Number(*n)),
+            TokenType::STRING(s) => self.emit_constant(Value::String(s.as_str().into())),
+            TokenType::TRUE => self.emit_op(OpCode::True),
+            TokenType::FALSE => self.emit_op(OpCode::False),
+            _ => self.emit_op(OpCode::Nil),
+        }
+    }
+
+    fn variable(&mut self, can_assign: bool) {
+        let name = match self.previous().token_type {
+            TokenType::IDENTIFIER(name) => name,
+            _ => unreachable!(),
+        };
+        let (get, set, arg) = match self.resolve_local(name) {
+            Some(slot) => (OpCode::GetLocal, OpCode::SetLocal, slot),
+            None => (
+                OpCode::GetGlobal,
+                OpCode::SetGlobal,
+                self.identifier_constant(name),
+            ),
+        };
+        if can_assign && self.match_token(&TokenType::EQUAL) {
+            self.expression();
+            self.emit_op_arg(set, arg);
+        } else {
+            self.emit_op_arg(get, arg);
+        }
+    }
+
+    fn unary(&mut self, _can_assign: bool) {
+        let operator = self.previous().token_type.clone();
+        self.parse_precedence(Precedence::Unary);
+        match operator {
+            TokenType::MINUS => self.emit_op(OpCode::Negate),
+            _ => self.emit_op(OpCode::Not),
+        }
+    }
+
+    fn binary(&mut self, _can_assign: bool) {
+        let operator = self.previous().token_type.clone();
+        self.parse_precedence(Self::rule(&operator).2.next());
+        let ops: &[OpCode] = match operator {
+            TokenType::BANG_EQUAL => &[OpCode::Equal, OpCode::Not],
+            TokenType::EQUAL_EQUAL => &[OpCode::Equal],
+            TokenType::GREATER => &[OpCode::Greater],
+            TokenType::GREATER_EQUAL => &[OpCode::Less, OpCode::Not],
+            TokenType::LESS => &[OpCode::Less],
+            TokenType::LESS_EQUAL => &[OpCode::Greater, OpCode::Not],
+            TokenType::PLUS => &[OpCode::Add],
+            TokenType::MINUS => &[OpCode::Subtract],
+            TokenType::STAR => &[OpCode::Multiply],
+            _ => &[OpCode::Divide],
+        };
+        ops.iter().for_each(|op| self.emit_op(*op));
+    }
+
+    fn and(&mut self, _can_assign: bool) {
+        let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        self.parse_precedence(Precedence::And);
+        self.patch_jump(end_jump);
+    }
+
+    fn or(&mut self, _can_assign: bool) {
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        let end_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(else_jump);
+        self.emit_op(OpCode::Pop);
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Errors(Vec<String>);
+    impl LoxError for Errors {
+        fn report(&mut self, diagnostic: Diagnostic) {
+            self.0.push(diagnostic.to_string());
+        }
+        fn has_error(&self) -> bool {
+            !self.0.is_empty()
+        }
+    }
+
+    #[test]
+    fn test_compile_expression() {
+        let chunk = compile("print 1 + 2 * 3;", &mut Errors::default()).unwrap();
+        assert_eq!(
+            chunk.disassemble("script"),
+            "== script ==\n\
+             0000    1 OP_CONSTANT         0 '1'\n\
+             0002    | OP_CONSTANT         1 '2'\n\
+             0004    | OP_CONSTANT         2 '3'\n\
+             0006    | OP_MULTIPLY\n\
+             0007    | OP_ADD\n\
+             0008    | OP_PRINT\n\
+             0009    | OP_RETURN\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let mut errors = Errors::default();
+        let source = "print 1 +;\nvar a = 1\nprint a;\n{ var b = b; }\n1 = 2;";
+        assert!(compile(source, &mut errors).is_none());
+        assert_eq!(
+            errors.0,
+            [
+                "[line 1] Error at ';': Expect expression.",
+                "[line 3] Error at 'print': Expect ';' after variable declaration.",
+                "[line 4] Error at 'b': Can't read local variable in its own initializer.",
+                "[line 5] Error at '=': Invalid assignment target.",
+            ]
+        );
+    }
+}
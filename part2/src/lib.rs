@@ -0,0 +1,5 @@
+pub mod bytecode;
+pub mod chunk;
+pub mod compiler;
+pub mod value;
+pub mod vm;
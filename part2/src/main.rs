@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use clox::bytecode;
+use clox::compiler;
+use clox::vm::{CompileError, RuntimeError, Vm};
+use part1::lox_error::{Diagnostic, LoxError};
+use part1::paths;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+/// Prints compile errors to stderr as they are found.
+#[derive(Default)]
+struct Reporter {
+    had_error: bool,
+}
+
+impl LoxError for Reporter {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        eprintln!("{}", diagnostic);
+        self.had_error = true;
+    }
+
+    fn has_error(&self) -> bool {
+        self.had_error
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Args {
+    /// Script to run
+    #[clap()]
+    script: Option<String>,
+    /// Print the stack and each instruction to stderr as it runs
+    #[clap(long)]
+    trace_execution: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compile a script to a bytecode file, for `run`
+    Compile {
+        #[clap()]
+        script: String,
+        /// Where to write it [default: the script with a .loxc extension]
+        #[clap(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Run a bytecode file written by `compile`
+    Run {
+        #[clap()]
+        file: String,
+        /// Print the stack and each instruction to stderr as it runs
+        #[clap(long)]
+        trace_execution: bool,
+    },
+}
+
+fn run_file(path: &str, mut vm: Vm<io::Stdout>) -> Result<()> {
+    let source = paths::read_source(path)?;
+    vm.interpret(&source, &mut Reporter::default())
+}
+
+fn compile(script: &str, output: Option<String>) -> Result<()> {
+    let source = paths::read_source(script)?;
+    let chunk = compiler::compile(&source, &mut Reporter::default())
+        .ok_or_else(|| anyhow!("failed to compile"))
+        .context(CompileError {})?;
+    let output = output.map_or_else(
+        || paths::normalize(script).with_extension("loxc"),
+        |o| paths::normalize(&o),
+    );
+    fs::write(&output, bytecode::encode(&chunk))
+        .with_context(|| format!("Could not write {}", paths::display(&output)))
+}
+
+fn run_compiled(path: &str, mut vm: Vm<io::Stdout>) -> Result<()> {
+    let path = paths::normalize(path);
+    let bytes =
+        fs::read(&path).with_context(|| format!("Could not read {}", paths::display(&path)))?;
+    let chunk = bytecode::decode(&bytes)
+        .with_context(|| format!("Could not load {}", paths::display(&path)))?;
+    vm.execute(&chunk)
+}
+
+fn run_prompt(mut vm: Vm<io::Stdout>) -> Result<()> {
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+        // Errors have been reported; keep the session going.
+        if let Err(e) = vm.interpret(&line, &mut Reporter::default()) {
+            if let Some(e) = e.downcast_ref::<RuntimeError>() {
+                eprintln!("{}", e);
+            }
+        }
+    }
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut vm = Vm::new();
+    vm.set_trace_execution(args.trace_execution);
+    let rv = match (args.command, &args.script) {
+        (Some(Command::Compile { script, output }), _) => compile(&script, output),
+        (
+            Some(Command::Run {
+                file,
+                trace_execution,
+            }),
+            _,
+        ) => {
+            vm.set_trace_execution(trace_execution);
+            run_compiled(&file, vm)
+        }
+        (None, None) => run_prompt(vm),
+        (None, Some(path)) => run_file(path, vm),
+    };
+    if let Err(e) = &rv {
+        if e.downcast_ref::<CompileError>().is_some() {
+            std::process::exit(65);
+        } else if let Some(e) = e.downcast_ref::<RuntimeError>() {
+            eprintln!("{}", e);
+            std::process::exit(70);
+        }
+    }
+    rv
+}
@@ -0,0 +1,327 @@
+use crate::chunk::{Chunk, OpCode};
+use crate::compiler;
+use crate::value::Value;
+use anyhow::{anyhow, Context, Result};
+use part1::lox_error::LoxError;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// Compilation failed; the errors have been reported.
+#[derive(Debug)]
+pub struct CompileError {}
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Compile error.")
+    }
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub message: String,
+    pub line: i32,
+}
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\n[line {}] in script", self.message, self.line)
+    }
+}
+
+/// A stack machine running compiled chunks. Globals persist from one
+/// `interpret` call to the next.
+pub struct Vm<W: Write> {
+    stack: Vec<Value>,
+    globals: HashMap<Rc<str>, Value>,
+    out: W,
+    trace_execution: bool,
+    /// Instructions each `interpret` may run before failing.
+    step_limit: Option<u64>,
+}
+
+impl Vm<io::Stdout> {
+    pub fn new() -> Self {
+        Self::with_output(io::stdout())
+    }
+}
+
+impl Default for Vm<io::Stdout> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Vm<W> {
+    /// A VM that prints to `out`.
+    pub fn with_output(out: W) -> Self {
+        Self {
+            stack: vec![],
+            globals: HashMap::new(),
+            out,
+            trace_execution: false,
+            step_limit: None,
+        }
+    }
+
+    /// Print the stack and each instruction to stderr as it runs, like
+    /// clox's `DEBUG_TRACE_EXECUTION`.
+    pub fn set_trace_execution(&mut self, trace: bool) {
+        self.trace_execution = trace;
+    }
+
+    /// Fail with a runtime error once a program has run `limit`
+    /// instructions, so one that never finishes can still be checked.
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.step_limit = limit;
+    }
+
+    /// The stack, then the instruction at `ip`, as clox traces them.
+    fn trace(&self, chunk: &Chunk, ip: usize) -> String {
+        let mut out = " ".repeat(10);
+        for v in &self.stack {
+            out += &format!("[ {} ]", v);
+        }
+        out.push('\n');
+        chunk.disassemble_instruction(&mut out, ip);
+        out
+    }
+
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    /// Compile and run `source`, reporting compile errors to `errors`.
+    pub fn interpret(&mut self, source: &str, errors: &mut dyn LoxError) -> Result<()> {
+        let chunk = compiler::compile(source, errors)
+            .ok_or_else(|| anyhow!("failed to compile"))
+            .context(CompileError {})?;
+        self.execute(&chunk)
+    }
+
+    /// Run an already compiled chunk, such as one loaded by
+    /// `bytecode::decode`.
+    pub fn execute(&mut self, chunk: &Chunk) -> Result<()> {
+        let rv = self.run(chunk);
+        self.stack.clear();
+        rv
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    fn run(&mut self, chunk: &Chunk) -> Result<()> {
+        let mut ip = 0;
+        let read_byte = |ip: &mut usize| {
+            *ip += 1;
+            chunk.code[*ip - 1]
+        };
+        let read_short = |ip: &mut usize| {
+            *ip += 2;
+            u16::from_be_bytes([chunk.code[*ip - 2], chunk.code[*ip - 1]]) as usize
+        };
+        let error = |ip: usize, message: &str| {
+            anyhow!("{}", message).context(RuntimeError {
+                message: message.to_owned(),
+                line: chunk.lines[ip - 1],
+            })
+        };
+        let mut steps = 0;
+        loop {
+            steps += 1;
+            if let Some(limit) = self.step_limit.filter(|limit| steps > *limit) {
+                let message = format!("Step limit exceeded ({} instructions).", limit);
+                return Err(error(ip.max(1), &message));
+            }
+            if self.trace_execution {
+                eprint!("{}", self.trace(chunk, ip));
+            }
+            let op = OpCode::try_from(read_byte(&mut ip))
+                .map_err(|byte| anyhow!("Unknown opcode {}", byte))?;
+            match op {
+                OpCode::Constant => {
+                    let constant = read_byte(&mut ip) as usize;
+                    self.stack.push(chunk.constants[constant].clone());
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = read_byte(&mut ip) as usize;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = read_byte(&mut ip) as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal | OpCode::DefineGlobal | OpCode::SetGlobal => {
+                    let name = match &chunk.constants[read_byte(&mut ip) as usize] {
+                        Value::String(name) => name.clone(),
+                        v => unreachable!("global name {:?}", v),
+                    };
+                    match op {
+                        OpCode::GetGlobal => match self.globals.get(&name) {
+                            Some(v) => self.stack.push(v.clone()),
+                            None => {
+                                return Err(error(ip, &format!("Undefined variable '{}'.", name)))
+                            }
+                        },
+                        OpCode::DefineGlobal => {
+                            let value = self.pop();
+                            self.globals.insert(name, value);
+                        }
+                        _ => match self.globals.get_mut(&name) {
+                            // Assignment is an expression; leave the value on the stack.
+                            Some(v) => *v = self.stack.last().unwrap().clone(),
+                            None => {
+                                return Err(error(ip, &format!("Undefined variable '{}'.", name)))
+                            }
+                        },
+                    }
+                }
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.stack.push(Value::Bool(a == b));
+                }
+                OpCode::Greater
+                | OpCode::Less
+                | OpCode::Subtract
+                | OpCode::Multiply
+                | OpCode::Divide => {
+                    let (a, b) = match (self.peek(1), self.peek(0)) {
+                        (Value::Number(a), Value::Number(b)) => (*a, *b),
+                        _ => return Err(error(ip, "Operands must be numbers.")),
+                    };
+                    self.pop();
+                    self.pop();
+                    self.stack.push(match op {
+                        OpCode::Greater => Value::Bool(a > b),
+                        OpCode::Less => Value::Bool(a < b),
+                        OpCode::Subtract => Value::Number(a - b),
+                        OpCode::Multiply => Value::Number(a * b),
+                        _ => Value::Number(a / b),
+                    });
+                }
+                OpCode::Add => {
+                    let sum = match (self.peek(1), self.peek(0)) {
+                        (Value::Number(a), Value::Number(b)) => Value::Number(a + b),
+                        (Value::String(a), Value::String(b)) => {
+                            Value::String(format!("{}{}", a, b).into())
+                        }
+                        _ => return Err(error(ip, "Operands must be two numbers or two strings.")),
+                    };
+                    self.pop();
+                    self.pop();
+                    self.stack.push(sum);
+                }
+                OpCode::Not => {
+                    let v = self.pop();
+                    self.stack.push(Value::Bool(v.is_falsey()));
+                }
+                OpCode::Negate => match self.peek(0) {
+                    Value::Number(n) => {
+                        let n = -n;
+                        self.pop();
+                        self.stack.push(Value::Number(n));
+                    }
+                    _ => return Err(error(ip, "Operand must be a number.")),
+                },
+                OpCode::Print => {
+                    let v = self.pop();
+                    writeln!(self.out, "{}", v).context("writing output")?;
+                }
+                OpCode::Jump => {
+                    let offset = read_short(&mut ip);
+                    ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = read_short(&mut ip);
+                    if self.peek(0).is_falsey() {
+                        ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = read_short(&mut ip);
+                    ip -= offset;
+                }
+                OpCode::Return => return Ok(()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use part1::lox_error::Diagnostic;
+
+    struct NoErrors {}
+    impl LoxError for NoErrors {
+        fn report(&mut self, diagnostic: Diagnostic) {
+            panic!("unexpected error: {}", diagnostic);
+        }
+        fn has_error(&self) -> bool {
+            false
+        }
+    }
+
+    fn run(source: &str) -> (Result<()>, String) {
+        let mut vm = Vm::with_output(vec![]);
+        let rv = vm.interpret(source, &mut NoErrors {});
+        (rv, String::from_utf8(vm.into_output()).unwrap())
+    }
+
+    #[test]
+    fn test_run() {
+        let (rv, out) = run("var a = \"a\";\n\
+             { var b = a + \"b\"; print b; }\n\
+             for (var i = 0; i < 3; i = i + 1) if (i != 1 and true) print i; else print -i;\n\
+             print nil or !false;");
+        assert!(rv.is_ok());
+        assert_eq!(out, "ab\n0\n-1\n2\ntrue\n");
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut vm = Vm::with_output(vec![]);
+        vm.stack = vec![Value::Number(1.0), Value::String("a".into())];
+        let mut chunk = Chunk::new();
+        chunk.write_op(OpCode::Pop, 1);
+        assert_eq!(
+            vm.trace(&chunk, 0),
+            "          [ 1 ][ a ]\n0000    1 OP_POP\n"
+        );
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut vm = Vm::with_output(vec![]);
+        vm.set_step_limit(Some(100));
+        let e = vm
+            .interpret("while (true) print 1;", &mut NoErrors {})
+            .unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<RuntimeError>().unwrap().message,
+            "Step limit exceeded (100 instructions)."
+        );
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let (rv, out) = run("print 1;\nprint -\"x\";");
+        assert_eq!(out, "1\n");
+        let e = rv.unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<RuntimeError>().unwrap().to_string(),
+            "Operand must be a number.\n[line 2] in script"
+        );
+    }
+}
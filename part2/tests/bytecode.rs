@@ -0,0 +1,70 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+fn interpreter(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_interpreter"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Every fixture clox compiles prints the same, and fails with the same
+/// runtime error, when compiled to a file and run from it.
+#[test]
+fn test_fixtures_round_trip() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let mut checked = 0;
+    for fixtures in ["test_lox_files", "../part1/test_lox_files"] {
+        for entry in std::fs::read_dir(root.join(fixtures)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|e| e != "lox") {
+                continue;
+            }
+            let script = path.to_str().unwrap();
+            let compiled = dir.join(path.with_extension("loxc").file_name().unwrap());
+            let compiled = compiled.to_str().unwrap();
+            // Most of part 1's use features clox doesn't have yet.
+            if !interpreter(&["compile", script, "-o", compiled])
+                .status
+                .success()
+            {
+                continue;
+            }
+            let from_source = interpreter(&[script]);
+            let from_file = interpreter(&["run", compiled]);
+            assert_eq!(from_file.stdout, from_source.stdout, "{}", script);
+            assert_eq!(from_file.stderr, from_source.stderr, "{}", script);
+            assert_eq!(
+                from_file.status.code(),
+                from_source.status.code(),
+                "{}",
+                script
+            );
+            checked += 1;
+        }
+    }
+    assert!(checked >= 4);
+}
+
+#[test]
+fn test_default_output_and_errors() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let script = dir.join("default_output.lox");
+    std::fs::write(&script, "print \"compiled\";\n").unwrap();
+    let output = interpreter(&["compile", script.to_str().unwrap()]);
+    assert!(output.status.success());
+    let compiled = dir.join("default_output.loxc");
+    let output = interpreter(&["run", compiled.to_str().unwrap()]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "compiled\n");
+
+    // A script isn't a compiled file, and a compile error writes nothing.
+    let output = interpreter(&["run", script.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Not a compiled Lox file."));
+    let broken = dir.join("broken.lox");
+    std::fs::write(&broken, "print ;\n").unwrap();
+    let output = interpreter(&["compile", broken.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(65));
+    assert!(!dir.join("broken.loxc").exists());
+}
@@ -0,0 +1,520 @@
+//! Generational fuzzing: build random programs that are valid by
+//! construction, with every variable declared before use and of the type its
+//! uses expect, then check that the tree-walker and the bytecode VM both run
+//! them without panicking and print the same thing.
+//!
+//! Opt in with `cargo test --features fuzz --test fuzz`, or use the `fuzz`
+//! profile for release speed with assertions and symbols. Set
+//! `LOX_FUZZ_ITERATIONS` for a longer run and `LOX_FUZZ_SEED` to replay one.
+
+use clox::vm::{CompileError, Vm};
+use part1::lox::{Lox, LoxOptions, LoxRuntimeError};
+use part1::lox_error::{Diagnostic, LoxError};
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+
+/// Statements or instructions either engine may run before the program is
+/// taken to be stuck. Loops are bounded, so this is only a safety net.
+const STEP_LIMIT: u64 = 1_000_000;
+
+/// xorshift64*, so a seed always gives the same program.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Ty {
+    Num,
+    Bool,
+    Str,
+}
+
+const TYPES: [Ty; 3] = [Ty::Num, Ty::Bool, Ty::Str];
+
+#[derive(Debug)]
+enum Expr {
+    Literal(String),
+    Variable(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+    Grouping(Box<Expr>),
+    Assign(String, Box<Expr>),
+}
+
+#[derive(Debug)]
+enum Stmt {
+    Print(Expr),
+    Var(String, Expr),
+    Expr(Expr),
+    Block(Vec<Stmt>),
+    If(Expr, Vec<Stmt>, Option<Vec<Stmt>>),
+    /// A counter from 0 up to the bound, as a `while` or a `for`.
+    Loop {
+        counter: String,
+        bound: usize,
+        body: Vec<Stmt>,
+        desugared: bool,
+    },
+}
+
+struct Var {
+    name: String,
+    ty: Ty,
+    /// Loop counters are read but never written, so every loop ends.
+    assignable: bool,
+}
+
+/// Builds a program, tracking the variables in scope at each point.
+struct Generator {
+    rng: Rng,
+    scopes: Vec<Vec<Var>>,
+    /// The local being declared, which its own initializer can't read.
+    declaring: Option<String>,
+    names: usize,
+    /// Statements left to generate, so programs stay small enough for the
+    /// VM's 256 constants per chunk.
+    budget: usize,
+    loops: usize,
+}
+
+impl Generator {
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            scopes: vec![vec![]],
+            declaring: None,
+            names: 0,
+            budget: 30,
+            loops: 0,
+        }
+    }
+
+    fn fresh_name(&mut self, prefix: &str) -> String {
+        self.names += 1;
+        format!("{}{}", prefix, self.names)
+    }
+
+    /// The variables an expression here can use, innermost first, without
+    /// the ones shadowed by inner declarations.
+    fn visible(&self, ty: Ty, assignable: bool) -> Vec<String> {
+        let mut seen = vec![];
+        let mut found = vec![];
+        for var in self.scopes.iter().rev().flat_map(|s| s.iter().rev()) {
+            if seen.contains(&var.name) {
+                continue;
+            }
+            seen.push(var.name.clone());
+            if var.ty == ty
+                && (var.assignable || !assignable)
+                && self.declaring.as_ref() != Some(&var.name)
+            {
+                found.push(var.name.clone());
+            }
+        }
+        found
+    }
+
+    fn any_type(&mut self) -> Ty {
+        TYPES[self.rng.below(TYPES.len())]
+    }
+
+    fn pick(&mut self, names: Vec<String>) -> Option<String> {
+        match names.len() {
+            0 => None,
+            n => Some(names[self.rng.below(n)].clone()),
+        }
+    }
+
+    fn literal(&mut self, ty: Ty) -> Expr {
+        Expr::Literal(match ty {
+            Ty::Num => match self.rng.below(3) {
+                0 => format!("{}.5", self.rng.below(10)),
+                _ => self.rng.below(20).to_string(),
+            },
+            Ty::Bool => ["true", "false"][self.rng.below(2)].to_owned(),
+            Ty::Str => format!("\"{}\"", ["", "a", "bc", "lox"][self.rng.below(4)]),
+        })
+    }
+
+    fn expr(&mut self, ty: Ty, depth: usize) -> Expr {
+        if depth == 0 || self.rng.one_in(4) {
+            let var = self.visible(ty, false);
+            return match self.rng.one_in(2) {
+                true => self
+                    .pick(var)
+                    .map_or_else(|| self.literal(ty), Expr::Variable),
+                false => self.literal(ty),
+            };
+        }
+        let sub = |g: &mut Self, ty| Box::new(g.expr(ty, depth - 1));
+        if self.rng.one_in(8) {
+            if let Some(name) = self.pick(self.visible(ty, true)) {
+                return Expr::Grouping(Box::new(Expr::Assign(name, sub(self, ty))));
+            }
+        }
+        match ty {
+            Ty::Num => match self.rng.below(4) {
+                0 => Expr::Unary("-", sub(self, Ty::Num)),
+                1 => Expr::Grouping(sub(self, Ty::Num)),
+                _ => {
+                    let op = ["+", "-", "*", "/"][self.rng.below(4)];
+                    Expr::Binary(sub(self, Ty::Num), op, sub(self, Ty::Num))
+                }
+            },
+            Ty::Bool => match self.rng.below(4) {
+                0 => {
+                    let operand = self.any_type();
+                    Expr::Unary("!", sub(self, operand))
+                }
+                1 => {
+                    // Not `<=` or `>=`: clox, like the book's, compiles them to
+                    // `!(a > b)` and `!(a < b)`, which differ for NaN.
+                    let op = ["<", ">"][self.rng.below(2)];
+                    Expr::Binary(sub(self, Ty::Num), op, sub(self, Ty::Num))
+                }
+                2 => {
+                    let operands = self.any_type();
+                    let op = ["==", "!="][self.rng.below(2)];
+                    Expr::Binary(sub(self, operands), op, sub(self, operands))
+                }
+                _ => {
+                    let op = ["and", "or"][self.rng.below(2)];
+                    Expr::Binary(sub(self, Ty::Bool), op, sub(self, Ty::Bool))
+                }
+            },
+            Ty::Str => Expr::Binary(sub(self, Ty::Str), "+", sub(self, Ty::Str)),
+        }
+    }
+
+    fn block(&mut self, vars: Vec<Var>) -> Vec<Stmt> {
+        self.scopes.push(vars);
+        let mut stmts = vec![];
+        for _ in 0..1 + self.rng.below(4) {
+            match self.budget {
+                0 => break,
+                _ => stmts.push(self.stmt()),
+            }
+        }
+        self.scopes.pop();
+        stmts
+    }
+
+    fn stmt(&mut self) -> Stmt {
+        self.budget = self.budget.saturating_sub(1);
+        let ty = self.any_type();
+        let nested = self.scopes.len() < 4;
+        match self.rng.below(9) {
+            0 | 1 => Stmt::Print(self.expr(ty, 3)),
+            2 | 3 => {
+                // Locals sometimes shadow an outer variable of another type.
+                let outer_ty = self.any_type();
+                let outer = self
+                    .pick(self.visible(outer_ty, false))
+                    .filter(|_| self.scopes.len() > 1 && self.rng.one_in(3))
+                    .filter(|name| !self.scopes.last().unwrap().iter().any(|v| &v.name == name));
+                let name = outer.unwrap_or_else(|| self.fresh_name("v"));
+                self.declaring = Some(name.clone()).filter(|_| self.scopes.len() > 1);
+                let init = self.expr(ty, 2);
+                self.declaring = None;
+                self.scopes.last_mut().unwrap().push(Var {
+                    name: name.clone(),
+                    ty,
+                    assignable: true,
+                });
+                Stmt::Var(name, init)
+            }
+            4 => match self.pick(self.visible(ty, true)) {
+                Some(name) => Stmt::Expr(Expr::Assign(name, Box::new(self.expr(ty, 2)))),
+                None => Stmt::Print(self.expr(ty, 2)),
+            },
+            5 if nested => Stmt::Block(self.block(vec![])),
+            6 if nested => {
+                let cond_ty = self.any_type();
+                let cond = self.expr(cond_ty, 2);
+                let then = self.block(vec![]);
+                let otherwise = match self.rng.one_in(2) {
+                    true => Some(self.block(vec![])),
+                    false => None,
+                };
+                Stmt::If(cond, then, otherwise)
+            }
+            7 | 8 if nested && self.loops < 2 => {
+                let counter = self.fresh_name("i");
+                let bound = self.rng.below(4);
+                self.loops += 1;
+                let body = self.block(vec![Var {
+                    name: counter.clone(),
+                    ty: Ty::Num,
+                    assignable: false,
+                }]);
+                self.loops -= 1;
+                Stmt::Loop {
+                    counter,
+                    bound,
+                    body,
+                    desugared: self.rng.one_in(2),
+                }
+            }
+            _ => Stmt::Print(self.expr(ty, 2)),
+        }
+    }
+
+    fn program(&mut self) -> Vec<Stmt> {
+        let mut stmts = vec![];
+        while self.budget > 0 {
+            stmts.push(self.stmt());
+        }
+        stmts
+    }
+}
+
+fn render_expr(e: &Expr, out: &mut String) {
+    match e {
+        Expr::Literal(s) | Expr::Variable(s) => out.push_str(s),
+        Expr::Unary(op, e) => {
+            out.push_str(op);
+            render_expr(e, out);
+        }
+        Expr::Binary(l, op, r) => {
+            out.push('(');
+            render_expr(l, out);
+            out.push_str(&format!(" {} ", op));
+            render_expr(r, out);
+            out.push(')');
+        }
+        Expr::Grouping(e) => {
+            out.push('(');
+            render_expr(e, out);
+            out.push(')');
+        }
+        Expr::Assign(name, e) => {
+            out.push_str(&format!("{} = ", name));
+            render_expr(e, out);
+        }
+    }
+}
+
+fn render_block(stmts: &[Stmt], indent: usize, out: &mut String) {
+    out.push_str("{\n");
+    for s in stmts {
+        render_stmt(s, indent + 1, out);
+    }
+    out.push_str(&"  ".repeat(indent));
+    out.push('}');
+}
+
+fn render_stmt(s: &Stmt, indent: usize, out: &mut String) {
+    out.push_str(&"  ".repeat(indent));
+    match s {
+        Stmt::Print(e) => {
+            out.push_str("print ");
+            render_expr(e, out);
+            out.push(';');
+        }
+        Stmt::Var(name, e) => {
+            out.push_str(&format!("var {} = ", name));
+            render_expr(e, out);
+            out.push(';');
+        }
+        Stmt::Expr(e) => {
+            render_expr(e, out);
+            out.push(';');
+        }
+        Stmt::Block(stmts) => render_block(stmts, indent, out),
+        Stmt::If(cond, then, otherwise) => {
+            out.push_str("if (");
+            render_expr(cond, out);
+            out.push_str(") ");
+            render_block(then, indent, out);
+            if let Some(otherwise) = otherwise {
+                out.push_str(" else ");
+                render_block(otherwise, indent, out);
+            }
+        }
+        Stmt::Loop {
+            counter: i,
+            bound,
+            body,
+            desugared: false,
+        } => {
+            out.push_str(&format!(
+                "for (var {0} = 0; {0} < {1}; {0} = {0} + 1) ",
+                i, bound
+            ));
+            render_block(body, indent, out);
+        }
+        Stmt::Loop {
+            counter: i,
+            bound,
+            body,
+            desugared: true,
+        } => {
+            let pad = "  ".repeat(indent);
+            out.push_str(&format!(
+                "{{\n{0}  var {1} = 0;\n{0}  while ({1} < {2}) {{\n{0}    ",
+                pad, i, bound
+            ));
+            render_block(body, indent + 2, out);
+            out.push_str(&format!("\n{0}    {1} = {1} + 1;\n{0}  }}\n{0}}}", pad, i));
+        }
+    }
+    out.push('\n');
+}
+
+fn render(program: &[Stmt]) -> String {
+    let mut out = String::new();
+    for s in program {
+        render_stmt(s, 0, &mut out);
+    }
+    out
+}
+
+/// A `Write` whose contents can be read back after an engine owns it.
+#[derive(Clone, Default)]
+struct Captured(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+}
+
+/// Keeps the VM's compile errors for the failure message.
+#[derive(Default)]
+struct Diagnostics(Vec<String>);
+
+impl LoxError for Diagnostics {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.0.push(diagnostic.to_string());
+    }
+
+    fn has_error(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
+/// What a run printed, or why it stopped early.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Printed(String),
+    Failed(String),
+    /// The VM ran out of room for constants; the program is fine, just big.
+    TooManyConstants,
+    StepLimit,
+}
+
+fn run_tree_walker(source: &str) -> Outcome {
+    let output = Captured::default();
+    let errors = Captured::default();
+    let mut lox = Lox::new(LoxOptions {
+        max_steps: Some(STEP_LIMIT),
+        ..Default::default()
+    });
+    lox.set_output(output.clone());
+    lox.set_error_sink(errors.clone());
+    match lox.run(source.to_owned()) {
+        Ok(()) => Outcome::Printed(output.text()),
+        Err(e) => match e.downcast_ref::<LoxRuntimeError>() {
+            Some(rte) if rte.message().starts_with("Step limit") => Outcome::StepLimit,
+            _ => Outcome::Failed(format!("{:#}\n{}", e, errors.text())),
+        },
+    }
+}
+
+fn run_vm(source: &str) -> Outcome {
+    let mut vm = Vm::with_output(vec![]);
+    vm.set_step_limit(Some(STEP_LIMIT));
+    let mut errors = Diagnostics::default();
+    let rv = vm.interpret(source, &mut errors);
+    let output = String::from_utf8_lossy(&vm.into_output()).into_owned();
+    match rv {
+        Ok(()) => Outcome::Printed(output),
+        Err(e) if e.downcast_ref::<CompileError>().is_some() => {
+            match errors.0.iter().all(|e| e.contains("Too many constants")) {
+                true => Outcome::TooManyConstants,
+                false => Outcome::Failed(errors.0.join("\n")),
+            }
+        }
+        Err(e) if format!("{:#}", e).contains("Step limit") => Outcome::StepLimit,
+        Err(e) => Outcome::Failed(format!("{:#}", e)),
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok().map(|v| {
+        v.parse()
+            .unwrap_or_else(|_| panic!("{} must be a number", name))
+    })
+}
+
+#[test]
+fn test_engines_agree() {
+    let iterations = env_u64("LOX_FUZZ_ITERATIONS").unwrap_or(500);
+    let first_seed = env_u64("LOX_FUZZ_SEED").unwrap_or_else(|| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    });
+    println!("LOX_FUZZ_SEED={}", first_seed);
+    let mut compared = 0;
+    for seed in first_seed..first_seed + iterations {
+        let source = render(&Generator::new(seed).program());
+        let replay = format!(
+            "LOX_FUZZ_SEED={} LOX_FUZZ_ITERATIONS=1, program:\n{}",
+            seed, source
+        );
+        let run = |engine: fn(&str) -> Outcome| {
+            panic::catch_unwind(AssertUnwindSafe(|| engine(&source)))
+                .unwrap_or_else(|_| panic!("panicked on {}", replay))
+        };
+        let (walked, compiled) = (run(run_tree_walker), run(run_vm));
+        match (&walked, &compiled) {
+            (Outcome::Failed(e), _) | (_, Outcome::Failed(e)) => {
+                panic!("failed with {} on {}", e, replay)
+            }
+            (Outcome::Printed(_), Outcome::Printed(_)) => {
+                assert_eq!(walked, compiled, "engines disagree on {}", replay);
+                compared += 1;
+            }
+            _ => {}
+        }
+    }
+    // Programs too big for the VM are skipped, but not most of them.
+    assert!(
+        compared * 2 > iterations,
+        "only {} of {} programs compared",
+        compared,
+        iterations
+    );
+}